@@ -0,0 +1,289 @@
+//! `install-schedule launchd|task-scheduler`: register the miner to start on its own on
+//! macOS and Windows, with the arguments after `--`. The launchd agent starts at login; the
+//! Task Scheduler task starts at logon and again when each --schedule window opens, and with
+//! --only-when-idle only runs while the machine is idle. Both restart the miner when it
+//! fails but not after a clean exit, and both hand --schedule and --only-when-idle on to the
+//! miner, which pauses outside them itself, so a miner already running keeps to them too.
+//! The miner runs in the directory install-schedule was run from, so it finds the same
+//! config file; launchd writes its output to NAME.log there.
+
+use crate::schedule::Schedule;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Manager {
+    /// A launchd agent in ~/Library/LaunchAgents (macOS)
+    Launchd(Install),
+    /// A Task Scheduler task for the current user (Windows)
+    TaskScheduler(Install),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Install {
+    /// Label of the launchd agent, or name of the task
+    #[arg(long, default_value = "portocripto")]
+    name: String,
+    /// Local-time mining windows, e.g. "22:00-07:00", handed on to the miner
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    schedule: Option<Schedule>,
+    /// Minutes of keyboard and mouse idleness before mining, handed on to the miner
+    #[arg(long, value_name = "MINUTES")]
+    only_when_idle: Option<u64>,
+    /// Print the plist or task XML instead of installing it
+    #[arg(long)]
+    print: bool,
+    /// Unregister what an earlier install-schedule registered under --name
+    #[arg(long, conflicts_with_all = ["schedule", "only_when_idle", "print"])]
+    remove: bool,
+    /// Miner arguments, after `--`
+    #[arg(last = true)]
+    args: Vec<String>,
+}
+
+impl Install {
+    /// The miner's full command line.
+    fn command_line(&self) -> std::io::Result<Vec<String>> {
+        let mut argv = vec![std::env::current_exe()?.display().to_string()];
+        argv.extend(self.args.iter().cloned());
+        if let Some(schedule) = &self.schedule {
+            argv.extend(["--schedule".to_string(), schedule.to_string()]);
+        }
+        if let Some(minutes) = self.only_when_idle {
+            argv.extend(["--only-when-idle".to_string(), minutes.to_string()]);
+        }
+        Ok(argv)
+    }
+}
+
+/// Install, print or remove as `manager` says.
+pub fn run(manager: &Manager) -> Result<(), String> {
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    match manager {
+        Manager::Launchd(install) => {
+            let home = std::env::var_os("HOME").ok_or("HOME is not set")?;
+            let path = PathBuf::from(home).join("Library/LaunchAgents").join(format!("{}.plist", install.name));
+            if install.remove {
+                let _ = run_command("launchctl", &["unload", "-w", &path.display().to_string()]);
+                std::fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                eprintln!("install-schedule: removed launchd agent {}", install.name);
+                return Ok(());
+            }
+            let plist = plist(install, &cwd).map_err(|e| e.to_string())?;
+            if install.print {
+                print!("{}", plist);
+                return Ok(());
+            }
+            std::fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+            std::fs::write(&path, plist).map_err(|e| format!("{}: {}", path.display(), e))?;
+            run_command("launchctl", &["load", "-w", &path.display().to_string()])?;
+            eprintln!("install-schedule: installed launchd agent {} at {}", install.name, path.display());
+            Ok(())
+        }
+        Manager::TaskScheduler(install) => {
+            if install.remove {
+                run_command("schtasks", &["/Delete", "/TN", &install.name, "/F"])?;
+                eprintln!("install-schedule: removed scheduled task {}", install.name);
+                return Ok(());
+            }
+            let xml = task_xml(install, &cwd).map_err(|e| e.to_string())?;
+            if install.print {
+                print!("{}", xml);
+                return Ok(());
+            }
+            // schtasks only takes the file in UTF-16
+            let path = std::env::temp_dir().join(format!("{}.task.xml", install.name));
+            let utf16: Vec<u8> =
+                "\u{feff}".encode_utf16().chain(xml.encode_utf16()).flat_map(u16::to_le_bytes).collect();
+            std::fs::write(&path, utf16).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let created =
+                run_command("schtasks", &["/Create", "/TN", &install.name, "/XML", &path.display().to_string(), "/F"]);
+            let _ = std::fs::remove_file(&path);
+            created?;
+            eprintln!("install-schedule: installed scheduled task {}", install.name);
+            Ok(())
+        }
+    }
+}
+
+fn run_command(program: &str, args: &[&str]) -> Result<(), String> {
+    match Command::new(program).args(args).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{} {} failed ({})", program, args.join(" "), status)),
+        Err(e) => Err(format!("{}: {}", program, e)),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn plist(install: &Install, cwd: &Path) -> std::io::Result<String> {
+    let arguments: String =
+        install.command_line()?.iter().map(|arg| format!("        <string>{}</string>\n", escape(arg))).collect();
+    let log = escape(&cwd.join(format!("{}.log", install.name)).display().to_string());
+    Ok(format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+            "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+            "<plist version=\"1.0\">\n",
+            "<dict>\n",
+            "    <key>Label</key>\n    <string>{}</string>\n",
+            "    <key>ProgramArguments</key>\n    <array>\n{}    </array>\n",
+            "    <key>WorkingDirectory</key>\n    <string>{}</string>\n",
+            "    <key>RunAtLoad</key>\n    <true/>\n",
+            "    <key>KeepAlive</key>\n    <dict>\n        <key>SuccessfulExit</key>\n        <false/>\n    </dict>\n",
+            "    <key>ProcessType</key>\n    <string>Background</string>\n",
+            "    <key>StandardOutPath</key>\n    <string>{}</string>\n",
+            "    <key>StandardErrorPath</key>\n    <string>{}</string>\n",
+            "</dict>\n",
+            "</plist>\n"
+        ),
+        escape(&install.name),
+        arguments,
+        escape(&cwd.display().to_string()),
+        log,
+        log
+    ))
+}
+
+/// One argument of a Windows command line, quoted if it has to be.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\\\""))
+}
+
+fn task_xml(install: &Install, cwd: &Path) -> std::io::Result<String> {
+    let argv = install.command_line()?;
+    let arguments: Vec<String> = argv[1..].iter().map(|arg| quote(arg)).collect();
+    let mut triggers = String::from("    <LogonTrigger>\n      <Enabled>true</Enabled>\n    </LogonTrigger>\n");
+    for start in install.schedule.iter().flat_map(Schedule::starts) {
+        triggers += &format!(
+            concat!(
+                "    <CalendarTrigger>\n",
+                "      <StartBoundary>2000-01-01T{:02}:{:02}:00</StartBoundary>\n",
+                "      <ScheduleByDay>\n        <DaysInterval>1</DaysInterval>\n      </ScheduleByDay>\n",
+                "    </CalendarTrigger>\n"
+            ),
+            start / 60,
+            start % 60
+        );
+    }
+    let idle = match install.only_when_idle {
+        Some(minutes) => format!(
+            concat!(
+                "    <RunOnlyIfIdle>true</RunOnlyIfIdle>\n",
+                "    <IdleSettings>\n      <Duration>PT{}M</Duration>\n      <WaitTimeout>PT0S</WaitTimeout>\n",
+                "      <StopOnIdleEnd>true</StopOnIdleEnd>\n      <RestartOnIdle>true</RestartOnIdle>\n",
+                "    </IdleSettings>\n"
+            ),
+            minutes.max(1)
+        ),
+        None => "    <RunOnlyIfIdle>false</RunOnlyIfIdle>\n".to_string(),
+    };
+    Ok(format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-16\"?>\n",
+            "<Task version=\"1.2\" xmlns=\"http://schemas.microsoft.com/windows/2004/02/mit/task\">\n",
+            "  <Triggers>\n{}  </Triggers>\n",
+            "  <Settings>\n",
+            "    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>\n",
+            "    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>\n",
+            "    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>\n",
+            "    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>\n",
+            "    <Priority>7</Priority>\n",
+            "{}",
+            "    <RestartOnFailure>\n      <Interval>PT1M</Interval>\n      <Count>999</Count>\n",
+            "    </RestartOnFailure>\n",
+            "  </Settings>\n",
+            "  <Actions>\n    <Exec>\n",
+            "      <Command>{}</Command>\n      <Arguments>{}</Arguments>\n",
+            "      <WorkingDirectory>{}</WorkingDirectory>\n",
+            "    </Exec>\n  </Actions>\n",
+            "</Task>\n"
+        ),
+        triggers,
+        idle,
+        escape(&argv[0]),
+        escape(&arguments.join(" ")),
+        escape(&cwd.display().to_string())
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(schedule: Option<&str>, only_when_idle: Option<u64>) -> Install {
+        Install {
+            name: "porto<crypto>".to_string(),
+            schedule: schedule.map(|s| s.parse().unwrap()),
+            only_when_idle,
+            print: true,
+            remove: false,
+            args: vec!["--args-file".to_string(), "C:\\mining\\my args.json".to_string()],
+        }
+    }
+
+    #[test]
+    fn command_lines() {
+        let argv = install(Some("22:00-07:00"), Some(10)).command_line().unwrap();
+        assert_eq!(argv[0], std::env::current_exe().unwrap().display().to_string());
+        assert_eq!(
+            argv[1..],
+            ["--args-file", "C:\\mining\\my args.json", "--schedule", "22:00-07:00", "--only-when-idle", "10"]
+        );
+        assert_eq!(install(None, None).command_line().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn quoting_and_escaping() {
+        assert_eq!(escape("a&b <c> \"d\""), "a&amp;b &lt;c&gt; &quot;d&quot;");
+        assert_eq!(quote("--threads"), "--threads");
+        assert_eq!(quote("my args.json"), "\"my args.json\"");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+
+    #[test]
+    fn launchd_agents() {
+        let plist = plist(&install(None, None), Path::new("/Users/me/mining")).unwrap();
+        assert!(plist.contains("<key>Label</key>\n    <string>porto&lt;crypto&gt;</string>\n"), "{}", plist);
+        assert!(plist.contains("        <string>C:\\mining\\my args.json</string>\n    </array>\n"), "{}", plist);
+        assert!(plist.contains("<key>WorkingDirectory</key>\n    <string>/Users/me/mining</string>"));
+        assert!(plist.contains("<string>/Users/me/mining/porto&lt;crypto&gt;.log</string>"));
+        assert!(plist.contains("<key>SuccessfulExit</key>\n        <false/>"));
+    }
+
+    #[test]
+    fn scheduled_tasks() {
+        let xml = task_xml(&install(Some("22:00-07:00,12:30-13:00"), Some(0)), Path::new("C:\\mining")).unwrap();
+        assert_eq!(xml.matches("<LogonTrigger>").count(), 1);
+        assert!(xml.contains("<StartBoundary>2000-01-01T22:00:00</StartBoundary>"), "{}", xml);
+        assert!(xml.contains("<StartBoundary>2000-01-01T12:30:00</StartBoundary>"), "{}", xml);
+        // Zero idle minutes would never trigger
+        assert!(
+            xml.contains("<RunOnlyIfIdle>true</RunOnlyIfIdle>\n    <IdleSettings>\n      <Duration>PT1M</Duration>")
+        );
+        assert!(xml.contains(
+            "<Arguments>--args-file &quot;C:\\mining\\my args.json&quot; --schedule 22:00-07:00,12:30-13:00 \
+             --only-when-idle 0</Arguments>"
+        ));
+        assert!(xml.contains("<WorkingDirectory>C:\\mining</WorkingDirectory>"));
+        let plain = task_xml(&install(None, None), Path::new("C:\\")).unwrap();
+        assert!(!plain.contains("CalendarTrigger") && plain.contains("<RunOnlyIfIdle>false</RunOnlyIfIdle>"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_commands() {
+        assert_eq!(run_command("true", &[]), Ok(()));
+        assert_eq!(run_command("false", &["x"]).unwrap_err(), "false x failed (exit status: 1)");
+        assert!(run_command("portocripto-no-such-command", &[])
+            .unwrap_err()
+            .starts_with("portocripto-no-such-command: "));
+    }
+}
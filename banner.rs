@@ -0,0 +1,194 @@
+//! The configuration a run resolved to, printed to stderr before mining starts: the values
+//! that come from flags, the config file, the challenge and the CPU all end up here, so a
+//! wrong difficulty reading or preimage shows up before the first hash rather than after
+//! hours without a solution. `--output json` prints it as one JSON line instead.
+
+use crate::{format_count, json_string, printable, Challenge, Endian, Mask, NonceOrder};
+use clap::ValueEnum;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// `config:` lines
+    Text,
+    /// One JSON object on a single line
+    Json,
+}
+
+pub struct Banner<'a> {
+    pub challenge: &'a Challenge,
+    pub backend: &'a str,
+    /// Whether the backend's batch entry point is in use
+    pub batched: bool,
+    pub threads: usize,
+    pub batch_size: u64,
+    /// Whether --warmup may still retune `batch_size`
+    pub tuned: bool,
+    pub difficulty_mask: Mask,
+    pub suffix: &'a str,
+    pub order: &'a NonceOrder,
+    /// "local", or the range server or Redis URL handing out nonces
+    pub coordination: &'a str,
+    /// The index the first worker continues from, if a journal or range log was found
+    pub resumed_at: Option<u64>,
+    /// Unix time at which the `no_pre_mine_hour` window closes, if enforced
+    pub window_closes: Option<i64>,
+    /// The last hashrate measured on this machine and where it was found, for the expected time
+    pub hashrate: Option<(f64, &'a str)>,
+}
+
+impl Banner<'_> {
+    fn expected_hashes(&self) -> f64 {
+        2f64.powi(crate::mask_zero_bits(self.difficulty_mask) as i32)
+    }
+
+    /// Seconds to an expected solution at the last measured hashrate.
+    fn expected_secs(&self) -> Option<f64> {
+        self.hashrate.map(|(rate, _)| self.expected_hashes() / rate)
+    }
+
+    fn difficulty(&self) -> String {
+        let zero_bits = crate::mask_zero_bits(self.difficulty_mask);
+        let kind = if crate::is_leading_zero_mask(self.difficulty_mask) { "leading " } else { "" };
+        let time = match (self.hashrate, self.expected_secs()) {
+            (Some((rate, source)), Some(secs)) => {
+                format!(", ~{} at {:.0} hashes/s ({})", crate::format_duration(secs), rate, source)
+            }
+            _ => String::new(),
+        };
+        format!(
+            "{} = {} {}zero bits, 1 in {} hashes{}",
+            self.challenge.difficulty,
+            zero_bits,
+            kind,
+            format_count(self.expected_hashes()),
+            time
+        )
+    }
+
+    fn prefix(&self) -> String {
+        let c = self.challenge;
+        let endian = if c.prefix_endian == Endian::Little { "little" } else { "big" };
+        format!("{}-endian at byte {} of a {}-byte hash", endian, c.prefix_offset, c.hash_len)
+    }
+
+    /// The preimage with the nonce left as a placeholder.
+    fn template(&self) -> String {
+        format!("<nonce {}>{}", self.challenge.nonce_encoding.name(), printable(self.suffix.as_bytes()))
+    }
+
+    fn batch(&self) -> String {
+        let tuned = if self.tuned { ", retuned by --warmup" } else { "" };
+        let lanes = if self.batched { ", batched" } else { "" };
+        format!("batch size {}{}{}", self.batch_size, tuned, lanes)
+    }
+
+    pub fn print(&self, format: Format) {
+        let hmac = self.challenge.hmac_key.is_some();
+        match format {
+            Format::Text => {
+                let start =
+                    self.resumed_at.map_or("from the start".to_string(), |at| format!("resumed at {:016x}", at));
+                eprintln!("config: backend     {}{}", self.backend, if hmac { " under HMAC" } else { "" });
+                eprintln!("config: threads     {}, {}", self.threads, self.batch());
+                eprintln!("config: difficulty  {}", self.difficulty());
+                eprintln!("config: prefix      {}", self.prefix());
+                eprintln!("config: preimage    {}", self.template());
+                eprintln!("config: nonces      {}, {}, {}", self.order.describe(), self.coordination, start);
+                if let Some(closes) = self.window_closes {
+                    eprintln!(
+                        "config: window      closes in {}",
+                        crate::format_duration((closes - crate::unix_now()) as f64)
+                    );
+                }
+            }
+            Format::Json => eprintln!(
+                concat!(
+                    "{{\"backend\": {}, \"hmac\": {}, \"batched\": {}, \"threads\": {}, \"batch_size\": {}, ",
+                    "\"difficulty\": {}, \"zero_bits\": {}, \"prefix\": {}, \"preimage\": {}, ",
+                    "\"nonce_encoding\": {}, \"nonce_order\": {}, \"coordination\": {}, \"resumed_at\": {}, ",
+                    "\"window_closes_unix\": {}, \"hashrate\": {}, \"expected_secs\": {}}}"
+                ),
+                json_string(self.backend),
+                hmac,
+                self.batched,
+                self.threads,
+                self.batch_size,
+                json_string(&self.challenge.difficulty),
+                crate::mask_zero_bits(self.difficulty_mask),
+                json_string(&self.prefix()),
+                json_string(&self.template()),
+                json_string(self.challenge.nonce_encoding.name()),
+                json_string(&self.order.describe()),
+                json_string(self.coordination),
+                self.resumed_at.map_or("null".to_string(), |at| format!("\"{:016x}\"", at)),
+                self.window_closes.map_or("null".to_string(), |closes| closes.to_string()),
+                self.hashrate.map_or("null".to_string(), |(rate, _)| format!("{:.1}", rate)),
+                self.expected_secs().map_or("null".to_string(), |secs| format!("{:.1}", secs)),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json, parse_mask, NonceStrategy};
+
+    fn banner<'a>(challenge: &'a Challenge, order: &'a NonceOrder) -> Banner<'a> {
+        Banner {
+            challenge,
+            backend: "avx2",
+            batched: false,
+            threads: 8,
+            batch_size: 4096,
+            tuned: false,
+            difficulty_mask: parse_mask(&challenge.difficulty).unwrap(),
+            suffix: "addr\n",
+            order,
+            coordination: "local",
+            resumed_at: None,
+            window_closes: None,
+            hashrate: None,
+        }
+    }
+
+    fn challenge(difficulty: &str) -> Challenge {
+        let parameters = json::parse(&format!(
+            "{{\"address\": \"addr_test1qpzry9x8gf2tvdw0s3jn4syst5\", \"challenge_id\": \"**D07\", \
+             \"difficulty\": \"{}\", \"no_pre_mine\": \"00112233\", \
+             \"latest_submission\": \"2099-12-31T23:59:59.000Z\", \"no_pre_mine_hour\": \"7\", \
+             \"prefix_offset\": 2, \"prefix_endian\": \"little\", \"hash_len\": 64}}",
+            difficulty
+        ))
+        .unwrap();
+        Challenge::from_json(&parameters).unwrap()
+    }
+
+    #[test]
+    fn config_lines() {
+        let order =
+            NonceOrder { strategy: NonceStrategy::Strided, threads: 8, seed: 0, worker_index: 0, worker_count: 1 };
+        let leading = challenge("0000ffff");
+        let banner = banner(&leading, &order);
+        assert_eq!(banner.difficulty(), "0000ffff = 16 leading zero bits, 1 in 65536 hashes");
+        assert_eq!(banner.prefix(), "little-endian at byte 2 of a 64-byte hash");
+        assert_eq!(banner.template(), "<nonce hex16>addr\\x0a");
+        assert_eq!(banner.batch(), "batch size 4096");
+        let tuned = Banner { tuned: true, batched: true, ..banner };
+        assert_eq!(tuned.batch(), "batch size 4096, retuned by --warmup, batched");
+    }
+
+    #[test]
+    fn expected_times() {
+        let order =
+            NonceOrder { strategy: NonceStrategy::Strided, threads: 8, seed: 0, worker_index: 0, worker_count: 1 };
+        let scattered = challenge("f0f0ffff");
+        let banner = Banner { hashrate: Some((64.0, "last run")), ..banner(&scattered, &order) };
+        assert_eq!(banner.expected_secs(), Some(4.0));
+        assert_eq!(banner.difficulty(), "f0f0ffff = 8 zero bits, 1 in 256 hashes, ~4s at 64 hashes/s (last run)");
+        let hard = challenge("00000000000000000000");
+        assert_eq!(banner.expected_hashes(), 256.0);
+        let banner = Banner { hashrate: None, ..self::banner(&hard, &order) };
+        assert_eq!(banner.difficulty(), "00000000000000000000 = 80 leading zero bits, 1 in 1.209e24 hashes");
+    }
+}
@@ -2,39 +2,1428 @@
 use blake2::Blake2bVar;
 use blake2::digest::{Update, VariableOutput};
 //use ashmaize::{blake2, Rom, RomGenerationType};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use rayon::prelude::*;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub use error::PortocriptoError;
+use evaluator::DifficultyEvaluator;
+use params::{Bech32Address, ChallengeId, Field, HexMask, HexString};
+use preimage::{printable, write_preimage, HexCase, NonceEncoding, PreimageBuilder, SaltPosition};
+use profiling::PhaseTimings;
+use schedule::Schedule;
+pub use verifier::{hash_structure_good, meets_target, Mask, PrefixLayout, Target};
+
+mod autostart;
+mod banner;
+mod blake2b;
+mod campaign;
+mod chaos;
+mod coverage;
+mod crash;
+mod daemon;
+mod ed25519;
+mod energy;
+mod environment;
+mod error;
+mod evaluator;
+mod identity;
+mod idle;
+mod json;
+mod kubernetes;
+mod lan;
+mod loadtest;
+mod logfile;
+mod machine;
+mod mdns;
+mod miner;
+mod mqtt;
+mod offline;
+mod otlp;
+mod outbox;
+mod params;
+mod payout;
+mod preempt;
+mod preimage;
+mod profiling;
+mod proxy;
+mod race;
+mod ramp;
+mod range_log;
+mod range_server;
+mod receipt;
+mod redis;
+mod reporter;
+mod retry;
+mod s3;
+mod schedule;
+mod secondary;
+mod shm;
+mod sim;
+mod sink;
+mod solution;
+mod spec;
+mod stats;
+mod suspend;
+mod verifier;
+mod wire;
+mod ws;
 
 const NUM_THREADS: usize = 8;
+const BATCH_SIZE: u64 = 1; // Hashes per worker between checks of the stop flag
+const CHECKS_PER_SECOND: f64 = 100.0; // Stop-flag checks per worker targeted by --warmup
+const CONFIG_FILE: &str = "portocripto.toml";
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+const JOURNAL_INTERVAL: Duration = Duration::from_secs(5);
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_FALSE_POSITIVES: u64 = 3; // Default for candidates the reference rejects before the backend is dropped
+const MAX_WORKER_RESTARTS: u32 = 3; // Per worker and search, before a panic fails the search
+const WINDOW_SECS: i64 = 3600; // How long after its no_pre_mine_hour solutions are accepted
+const FAILBACK_INTERVAL: Duration = Duration::from_secs(60); // Between tries of the first --range-server on a backup
 pub const MB: usize = 1024 * 1024;
 pub const GB: usize = 1024 * MB;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
+struct Cli {
+    /// Configuration file read at startup and written by `autotune`
+    #[arg(long, global = true, default_value = CONFIG_FILE)]
+    config: PathBuf,
+    /// Apply the `[profile.NAME]` section of the config file on top of its top-level keys
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Read the challenge fields from a JSON file: the challenge server's reply or one object
+    /// with snake_case or camelCase keys. Flags given on the command line take precedence
+    #[arg(long, global = true, value_name = "FILE")]
+    args_file: Option<PathBuf>,
+    /// Write stderr to this file instead, rotating it by --log-rotate
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+    /// Start a new --log-file past SIZE and at midnight UTC, deleting old ones after AGE
+    #[arg(long, global = true, value_name = "SIZE,AGE", requires = "log_file")]
+    log_rotate: Option<logfile::Rotation>,
+    /// Replace the hash backend with a fake that solves about one preimage in HASHES, and
+    /// run the clock on the hashes done rather than on real time
+    #[arg(long, global = true, hide = true, value_name = "HASHES")]
+    simulate: Option<u64>,
+    /// Hashes per second of virtual time under --simulate
+    #[arg(long, global = true, hide = true, value_name = "RATE", requires = "simulate")]
+    simulate_hashrate: Option<f64>,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    challenge: Option<Challenge>,
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check every compiled hash backend against built-in test vectors
+    Selftest,
+    /// List the compiled hash backends and what each can do on this machine
+    Backends {
+        /// Print one JSON object instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Benchmark thread counts and batch sizes and save the fastest to the config file
+    Autotune {
+        /// Seconds to measure each combination
+        #[arg(long, default_value_t = 2)]
+        duration: u64,
+        /// Largest thread count to try (default: available parallelism)
+        #[arg(long)]
+        max_threads: Option<usize>,
+    },
+    /// Convert a difficulty between mask, zero-bit, target and expected-hashes forms
+    Difficulty {
+        #[command(flatten)]
+        form: DifficultyForm,
+    },
+    /// Probability of finding a solution within a time window at a given hashrate
+    Chance {
+        #[command(flatten)]
+        form: DifficultyForm,
+        /// Hashes per second, as measured by `compare` or shown in the status line
+        #[arg(long)]
+        hashrate: f64,
+        /// Time available, e.g. 90s, 45m or 1h30m
+        #[arg(long, value_parser = params::parse_duration, required_unless_present = "until_hour")]
+        window: Option<Duration>,
+        /// Use the time left until the next full hour (local time) as the window
+        #[arg(long, conflicts_with = "window")]
+        until_hour: bool,
+    },
+    /// Estimate the electricity cost of an expected solution on this machine
+    Economics {
+        #[command(flatten)]
+        form: DifficultyForm,
+        /// Hashes per second (default: the config's autotune baseline, else measured now)
+        #[arg(long)]
+        hashrate: Option<f64>,
+        /// Power draw while mining, in watts (default: config `power_watts`)
+        #[arg(long)]
+        watts: Option<f64>,
+        /// Electricity price per kWh (default: config `electricity_price`)
+        #[arg(long)]
+        price: Option<f64>,
+        /// Reward per accepted solution, in the same currency (default: config `reward_per_solution`)
+        #[arg(long)]
+        reward: Option<f64>,
+    },
+    /// Hash a challenge for a fixed time and print a histogram of leading zero bits
+    Analyze {
+        #[command(flatten)]
+        challenge: Challenge,
+        /// Seconds to hash
+        #[arg(long, default_value_t = 10)]
+        duration: u64,
+        /// Worker threads (default: CPUs available to the process)
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Benchmark every compiled backend on a challenge and rank them by hashrate
+    Compare {
+        #[command(flatten)]
+        challenge: Challenge,
+        /// Seconds to measure each backend
+        #[arg(long, default_value_t = 5)]
+        duration: u64,
+        /// Worker threads (default: CPUs available to the process)
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Check nonces against a challenge and report which pass
+    Verify {
+        #[command(flatten)]
+        challenge: Challenge,
+        /// Single nonce to check (16 hex digits)
+        #[arg(long, required_unless_present = "batch", conflicts_with = "batch")]
+        nonce: Option<String>,
+        /// File with one hex nonce per line
+        #[arg(long)]
+        batch: Option<PathBuf>,
+    },
+    /// Print a verified solution as a JSON payload ready to paste into a submitter
+    EncodeSolution {
+        #[command(flatten)]
+        challenge: Challenge,
+        /// Solution nonce (16 hex digits)
+        #[arg(long)]
+        nonce: String,
+    },
+    /// Hand out exclusive nonce ranges to miners started with --range-server (one server per challenge)
+    RangeServer {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8420")]
+        listen: String,
+        /// Nonces per lease
+        #[arg(long, default_value_t = 1 << 28)]
+        range_size: u64,
+        /// Seconds a lease stays valid without renewal
+        #[arg(long, default_value_t = 300)]
+        lease_secs: u64,
+        /// Start handing out nonces after this many ranges, so a standby server listed after
+        /// another in miners' --range-server covers a different part of the nonce space
+        #[arg(long, default_value_t = 0)]
+        first_range: u64,
+        /// Answer mDNS queries so miners started with --discover find this server
+        #[arg(long)]
+        announce: bool,
+        /// The challenge's difficulty, for `/fleet` to estimate the time to a solution
+        #[arg(long, value_name = "HEX")]
+        difficulty: Option<String>,
+    },
+    /// Summaries of past runs
+    Stats {
+        #[command(subcommand)]
+        action: StatsCommand,
+    },
+    /// Mine challenges submitted over HTTP one at a time, highest priority first
+    Daemon {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8421")]
+        listen: String,
+        /// Admin bearer token, which controls every namespace (default: config `daemon_token`)
+        #[arg(long)]
+        token: Option<String>,
+        /// Client token for one namespace as NAMESPACE=TOKEN; repeat for several participants
+        /// (default: config `daemon_clients`, comma-separated)
+        #[arg(long = "client", value_name = "NAMESPACE=TOKEN", value_parser = parse_client)]
+        clients: Vec<(String, String)>,
+        /// Worker threads (default: config file, then 8 or the CPUs available to the process if fewer)
+        #[arg(long)]
+        threads: Option<usize>,
+        /// Submit solo solutions with this command, run as --on-solution is
+        #[arg(long, value_parser = parse_hook)]
+        solo_on_solution: Option<String>,
+        /// Submit pool solutions with this command
+        #[arg(long, requires = "pool_address", value_parser = parse_hook)]
+        pool_on_solution: Option<String>,
+        /// Address pool jobs are mined for instead of the one they were submitted with
+        #[arg(long)]
+        pool_address: Option<Bech32Address>,
+        /// Where solutions go (default: auto with both commands, else the one given); the admin
+        /// can change it through POST /payout
+        #[arg(long, value_enum)]
+        payout: Option<payout::Mode>,
+        /// Expected time to a solution, in seconds, under which `--payout auto` mines solo
+        #[arg(long, value_name = "SECS", default_value_t = 600.0)]
+        solo_below: f64,
+        /// Never submit a (challenge_id, nonce) recorded here twice (default: portocripto.submitted)
+        #[arg(long, value_name = "FILE")]
+        submitted: Option<PathBuf>,
+        #[command(flatten)]
+        budget: campaign::Budget,
+    },
+    /// Feed a running daemon synthetic challenges, cancel some, and check every job's outcome
+    Loadtest {
+        /// Daemon to load
+        #[arg(long, default_value = "http://127.0.0.1:8421")]
+        daemon: String,
+        /// Bearer token (default: config `daemon_token`)
+        #[arg(long)]
+        token: Option<String>,
+        /// Challenges to submit
+        #[arg(long, default_value_t = 20)]
+        jobs: u64,
+        /// Challenges submitted per second
+        #[arg(long, default_value_t = 1.0)]
+        rate: f64,
+        /// Difficulty of the synthetic challenges
+        #[arg(long, default_value = "0FFFFFFF")]
+        difficulty: String,
+        /// Share of jobs to cancel at a random point after submitting them
+        #[arg(long, default_value_t = 0.2)]
+        cancel: f64,
+        /// Seed for the challenges, priorities and cancel times (default: random)
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Seconds to wait for the jobs still running once all are submitted
+        #[arg(long, default_value_t = 300)]
+        timeout_secs: u64,
+    },
+    /// Interactively set the address, endpoints, threads and notifications in the config file
+    Init,
+    /// Register the miner, with the arguments after `--`, to start on its own on macOS or Windows
+    InstallSchedule {
+        #[command(subcommand)]
+        manager: autostart::Manager,
+    },
+    /// Re-verify the solution recorded in a --report-json file against its parameters
+    Replay {
+        /// Report written by a previous run
+        report: PathBuf,
+    },
+    /// Merge --range-log files and report coverage, gaps and nonces mined more than once
+    Ranges {
+        /// Logs written by the miners of a farm
+        #[arg(required = true)]
+        logs: Vec<PathBuf>,
+        /// Also draw the merged coverage as a PPM heatmap in this file, a band per challenge
+        #[arg(long, value_name = "FILE")]
+        coverage_map: Option<PathBuf>,
+        /// Search indices per pixel of the --coverage-map (default: about 4096 per band)
+        #[arg(long, value_name = "INDICES", requires = "coverage_map")]
+        coverage_cell: Option<u64>,
+    },
+    /// Summarize a --race-log: how often this miner's solutions won the race to the network
+    Races {
+        /// Log written by earlier runs
+        race_log: PathBuf,
+    },
+    /// Check the signature of a --receipt file and re-verify its solution
+    VerifyReceipt {
+        /// Receipt written by a previous run
+        receipt: PathBuf,
+        /// Fail unless the receipt was signed by this hex public key
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StatsCommand {
+    /// Chart hashrate over time, solutions per day and per-challenge totals from --report-json files
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Html)]
+        format: ExportFormat,
+        /// Where to write the export (default: stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Reports written by previous runs
+        #[arg(required = true)]
+        reports: Vec<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExportFormat {
+    /// One self-contained page with inline SVG charts
+    Html,
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct DifficultyForm {
+    /// Hex bitmask as passed to --difficulty (bits that may be set in the hash prefix)
+    #[arg(long)]
+    mask: Option<String>,
+    /// Number of leading zero bits required
+    #[arg(long)]
+    zero_bits: Option<u32>,
+    /// Full 256-bit target in hex: a hash passes when it is numerically <= the target
+    #[arg(long)]
+    target: Option<String>,
+    /// Average number of hashes per solution
+    #[arg(long)]
+    expected_hashes: Option<f64>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct Challenge {
+    /// Repeat as --address ADDR[:WEIGHT] to split challenge rounds between several addresses
+    /// (config `address` takes them comma-separated)
+    #[arg(long)]
+    address: Bech32Address,
+    #[arg(long)]
+    challenge_id: ChallengeId,
+    #[arg(long)]
+    difficulty: HexMask, // This is a hexadecimal string representing the bitmask for the required zero prefix
+    /// Instead of --difficulty: require N leading zero hex digits, passed on as the
+    /// equivalent mask (3 becomes 000FFFFF)
+    #[arg(long, value_name = "N")]
+    #[allow(dead_code)] // Turned into --difficulty before clap runs, see `expand_difficulty_zeros`
+    difficulty_zeros: Option<u32>,
+    #[arg(long)]
+    no_pre_mine: HexString,
+    #[arg(long)]
+    latest_submission: Field,
+    #[arg(long)]
+    no_pre_mine_hour: Field,
+    /// Keep a `0x` before the hex values (difficulty, no_pre_mine) in the preimage instead of
+    /// stripping it
+    #[arg(long = "keep-0x")]
+    #[allow(dead_code)] // Read before clap runs, see `parse_cli`
+    keep_0x: bool,
+    /// Byte offset in the hash where the difficulty prefix starts
+    #[arg(long, default_value_t = 0)]
+    prefix_offset: usize,
+    /// Byte order in which the difficulty prefix is read from the hash
+    #[arg(long, value_enum, default_value_t = Endian::Big)]
+    prefix_endian: Endian,
+    /// Digest length in bytes (1 to 64); the difficulty prefix must fit inside it
+    #[arg(long, default_value_t = 32, value_parser = params::parse_hash_len)]
+    hash_len: usize,
+    /// Also require the hash to be numerically <= this 256-bit target (up to 64 hex digits,
+    /// over the first 32 hash bytes); the --difficulty mask still goes in the preimage
+    #[arg(long, value_name = "HEX")]
+    target: Option<String>,
+    /// Hex key: every hash becomes HMAC-Blake2b of the preimage under this key
+    #[arg(long, value_name = "HEX")]
+    hmac_key: Option<HexString>,
+    /// Extra field baked into the preimage as-is, for protocol revisions that add a salt
+    #[arg(long)]
+    salt: Option<String>,
+    /// Where the salt goes among the fields that follow the nonce
+    #[arg(long, value_enum, default_value_t = SaltPosition::Append, requires = "salt")]
+    salt_position: SaltPosition,
+    /// Case the hex fields (difficulty, no_pre_mine) are put in the preimage with; the
+    /// server hashes the exact characters, so a wrong case gives hashes it rejects
+    #[arg(long, value_enum, default_value_t = HexCase::AsIs)]
+    hex_case: HexCase,
+    /// How the nonce is written at the start of the preimage
+    #[arg(long, value_enum, default_value_t = NonceEncoding::Hex16)]
+    nonce_encoding: NonceEncoding,
+    /// Protocol revision that fills in the layout flags not given here (default: the
+    /// `spec_version` of the --args-file JSON)
+    #[arg(long, value_name = "N")]
+    #[allow(dead_code)] // Turned into layout flags before clap runs, see `expand_spec_version`
+    spec_version: Option<u32>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl Challenge {
+    /// `hash`, wrapped in HMAC when the challenge has a key.
+    fn keyed(&self, hash: HashFn) -> Result<HashFn, PortocriptoError> {
+        match &self.hmac_key {
+            Some(key) => hmac_backend(hash, key.to_bytes()),
+            None => Ok(hash),
+        }
+    }
+
+    /// From the snake_case keys of a report's parameters or a daemon job; options that are
+    /// missing take their defaults. There is never an HMAC key.
+    fn from_json(parameters: &json::Json) -> Result<Challenge, String> {
+        let field = |name: &str| {
+            parameters
+                .get(name)
+                .and_then(json::Json::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| format!("parameter {} missing", name))
+        };
+        Ok(Challenge {
+            address: field("address")?.parse()?,
+            challenge_id: field("challenge_id")?.parse()?,
+            difficulty: field("difficulty")?.parse()?,
+            difficulty_zeros: None,
+            spec_version: None,
+            no_pre_mine: field("no_pre_mine")?.parse()?,
+            latest_submission: field("latest_submission")?.parse()?,
+            no_pre_mine_hour: field("no_pre_mine_hour")?.parse()?,
+            keep_0x: false,
+            // Reports from before these options existed used the defaults
+            prefix_offset: parameters.get("prefix_offset").and_then(json::Json::as_f64).map_or(0, |v| v as usize),
+            prefix_endian: match parameters.get("prefix_endian").and_then(json::Json::as_str) {
+                Some("little") => Endian::Little,
+                _ => Endian::Big,
+            },
+            hash_len: match parameters.get("hash_len").and_then(json::Json::as_f64) {
+                Some(len) => params::parse_hash_len(&len.to_string())?,
+                None => 32,
+            },
+            hmac_key: None,
+            target: parameters.get("target").and_then(json::Json::as_str).map(str::to_string),
+            salt: parameters.get("salt").and_then(json::Json::as_str).map(str::to_string),
+            salt_position: match parameters.get("salt_position").and_then(json::Json::as_str) {
+                Some("prepend") => SaltPosition::Prepend,
+                _ => SaltPosition::Append,
+            },
+            hex_case: match parameters.get("hex_case").and_then(json::Json::as_str) {
+                Some("lower") => HexCase::Lower,
+                Some("upper") => HexCase::Upper,
+                _ => HexCase::AsIs,
+            },
+            nonce_encoding: match parameters.get("nonce_encoding").and_then(json::Json::as_str) {
+                None => NonceEncoding::Hex16,
+                Some(name) => NonceEncoding::from_str(name, false).map_err(|e| format!("nonce_encoding: {}", e))?,
+            },
+        })
+    }
+
+    fn preimage(&self) -> PreimageBuilder<'_> {
+        PreimageBuilder {
+            address: &self.address,
+            challenge_id: &self.challenge_id,
+            difficulty: &self.difficulty,
+            no_pre_mine: &self.no_pre_mine,
+            latest_submission: &self.latest_submission,
+            no_pre_mine_hour: &self.no_pre_mine_hour,
+            salt: self.salt.as_deref().map(|salt| (salt, self.salt_position)),
+            hex_case: self.hex_case,
+            encoding: self.nonce_encoding,
+        }
+    }
+
+    /// Everything after the nonce in the preimage
+    fn suffix(&self) -> String {
+        self.preimage().suffix()
+    }
+
+    fn difficulty_mask(&self) -> Result<Mask, String> {
+        parse_mask(params::hex_digits(&self.difficulty)).map_err(|e| e.to_string())
+    }
+
+    fn target(&self) -> Result<Option<Target>, String> {
+        self.target.as_deref().map(parse_target).transpose()
+    }
+
+    /// The difficulty and target as one rule.
+    fn evaluator(&self) -> Result<evaluator::Composite, String> {
+        let masked = evaluator::Masked { mask: self.difficulty_mask()?, layout: self.prefix_layout()? };
+        let mut parts: Vec<Box<dyn DifficultyEvaluator>> = vec![Box::new(masked)];
+        if let Some(target) = self.target()? {
+            parts.push(Box::new(evaluator::FullTarget(target)));
+        }
+        Ok(evaluator::Composite(parts))
+    }
+
+    /// Where the difficulty prefix sits in the hash; the prefix is as wide as the mask.
+    fn prefix_layout(&self) -> Result<PrefixLayout, String> {
+        let width = (params::hex_digits(&self.difficulty).len() * 4).max(32).div_ceil(8);
+        if self.prefix_offset + width > self.hash_len {
+            return Err(format!(
+                "a {}-byte prefix at offset {} does not fit in the {}-byte hash",
+                width, self.prefix_offset, self.hash_len
+            ));
+        }
+        Ok(PrefixLayout { offset: self.prefix_offset, width, little_endian: self.prefix_endian == Endian::Little })
+    }
+}
+
+#[derive(clap::Args, Debug)]
 struct Args {
+    /// Print the preimage and hash for nonce 0 and exit without mining
     #[arg(long)]
-    address: String,
+    dry_run: bool,
+    /// Worker threads (default: config file, then 8 or the CPUs available to the process if fewer)
     #[arg(long)]
-    challenge_id: String,
+    threads: Option<usize>,
+    /// Add the workers one at a time over this long, and take them away the same way when
+    /// the search stops, e.g. "30s"
+    #[arg(long, value_name = "DURATION", value_parser = params::parse_duration)]
+    ramp_up: Option<Duration>,
+    /// Hashes each worker computes between checks of the stop flag; larger values cost less
+    /// overhead but react to a stop later. Tuned by --warmup when neither this nor the config sets it
+    #[arg(long, visible_alias = "check-interval", value_name = "N")]
+    batch_size: Option<u64>,
+    /// Periodically POST hashrate, uptime and solution count as JSON to this http:// URL
     #[arg(long)]
-    difficulty: String, // This is a hexadecimal string representing the bitmask for the required zero prefix
+    report_to: Option<String>,
+    /// How often --report-to, --otlp-endpoint, --mqtt-url, --log-progress and --progress-file
+    /// get the hash counters
+    #[arg(long, value_name = "DURATION", value_parser = params::parse_duration, default_value = "60s")]
+    report_interval: Duration,
+    /// Log the hash count and hashrate to stderr every --report-interval
     #[arg(long)]
-    no_pre_mine: String,
+    log_progress: bool,
+    /// Append the hash count and hashrate as a JSON line to this file every --report-interval
+    #[arg(long, value_name = "FILE")]
+    progress_file: Option<PathBuf>,
+    /// Act on a hashrate (sampled every --report-interval) that stays below this floor
+    #[arg(long, value_name = "HASHES_PER_SEC")]
+    min_hashrate: Option<f64>,
+    /// How long the hashrate may stay below --min-hashrate before acting on it
+    #[arg(long, value_name = "DURATION", value_parser = params::parse_duration, default_value = "5m")]
+    min_hashrate_for: Duration,
+    /// What to do once the hashrate has stayed below --min-hashrate for --min-hashrate-for
+    #[arg(long, value_enum, default_value_t = reporter::FloorAction::Warn)]
+    min_hashrate_action: reporter::FloorAction,
+    /// Rig name included in telemetry reports (default: the name in the --identity file)
     #[arg(long)]
-    latest_submission: String,
+    rig_name: Option<String>,
+    /// File keeping this worker's UUID and friendly name across restarts, created if missing
+    /// (default: config `identity`, then portocripto.id in the state directory, see PORTOCRIPTO_STATE_DIR)
+    #[arg(long, value_name = "FILE")]
+    identity: Option<PathBuf>,
+    /// Hash with the backend exported by this shared library instead of blake2b
     #[arg(long)]
-    no_pre_mine_hour: String,
+    plugin: Option<PathBuf>,
+    /// Command run when a solution is found, without a shell; {nonce}, {hash}, {address} and
+    /// {challenge_id} are substituted in its arguments and set as PORTOCRIPTO_NONCE, _HASH,
+    /// _ADDRESS and _CHALLENGE_ID in its environment (use those with `sh -c '...'`)
+    #[arg(long, value_parser = parse_hook)]
+    on_solution: Option<String>,
+    /// Also send the solution to SINK: stdout, ws, file:PATH, webhook:URL or submit:URL; may be
+    /// repeated (default: config `solution_sinks`, comma-separated, then stdout and ws)
+    #[arg(long, value_name = "SINK", value_parser = sink::parse)]
+    solution_sink: Vec<sink::Sink>,
+    /// Retry a failing --on-solution command, then save the solution in this directory and
+    /// keep submitting it in the background of later runs (default: config `outbox`)
+    #[arg(long, value_name = "DIR")]
+    outbox: Option<PathBuf>,
+    /// Record each solution --on-solution accepted here and never submit it again, even
+    /// from the outbox after a restart (default: config `submitted`, then portocripto.submitted)
+    #[arg(long, value_name = "FILE")]
+    submitted: Option<PathBuf>,
+    /// Write an Ed25519-signed, timestamped receipt for each solution into this directory,
+    /// signed by the openssl command (default: config `receipt`)
+    #[arg(long, value_name = "DIR")]
+    receipt: Option<PathBuf>,
+    /// Hex seed signing --receipt files, created if missing (default: config `receipt_key`,
+    /// then portocripto.key)
+    #[arg(long, value_name = "FILE")]
+    receipt_key: Option<PathBuf>,
+    /// Seconds to wait after `latest_submission` (an RFC 3339 timestamp) before mining
+    #[arg(long)]
+    min_submit_interval: Option<u64>,
+    /// Keep mining after the hour named by `no_pre_mine_hour` ends, when solutions are no
+    /// longer accepted
+    #[arg(long)]
+    mine_past_window: bool,
+    /// Journal the job, worker nonces and any unsubmitted solution here to resume after a crash
+    /// (default for a local run: portocripto-CHALLENGE_ID.journal in the state directory)
+    #[arg(long)]
+    journal: Option<PathBuf>,
+    /// Neither read nor write the default journal; every run starts from nonce 0
+    #[arg(long, conflicts_with = "journal")]
+    no_journal: bool,
+    /// Mirror the --journal and --outbox to this bucket and prefix, restoring them on start,
+    /// so a replacement instance resumes the search. The bucket must speak the S3 API over
+    /// plain http:// at AWS_ENDPOINT_URL (MinIO, a local gateway); AWS S3 itself needs TLS,
+    /// which this build does not have. Credentials from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+    #[arg(long, value_name = "s3://BUCKET/PREFIX", conflicts_with = "no_journal")]
+    checkpoint_url: Option<String>,
+    /// Order in which workers walk the nonce space
+    #[arg(long, value_enum, default_value_t = NonceStrategy::Strided)]
+    nonce_strategy: NonceStrategy,
+    /// Seed for `--nonce-strategy permuted`; miners with different seeds cover unrelated orders
+    #[arg(long, default_value_t = 0)]
+    nonce_seed: u64,
+    /// This miner's position among --worker-count miners sharing a challenge (and seed), from
+    /// 0; each mines a disjoint slice of the nonce order (default: the StatefulSet pod ordinal
+    /// when PORTOCRIPTO_REPLICAS is set)
+    #[arg(long, default_value_t = 0, requires = "worker_count")]
+    worker_index: u64,
+    /// Miners splitting the nonce order by --worker-index without a coordinator (default:
+    /// PORTOCRIPTO_REPLICAS in a Kubernetes pod, otherwise 1)
+    #[arg(long, conflicts_with_all = ["range_server", "discover", "coordination"])]
+    worker_count: Option<u64>,
+    /// Use the portable blake2b backend even if the CPU supports a faster one
+    #[arg(long)]
+    force_scalar: bool,
+    /// Inject faults chosen by this seed: kill workers, delay the stop flag, drop submissions
+    #[cfg(debug_assertions)]
+    #[arg(long, hide = true, value_name = "SEED")]
+    chaos: Option<u64>,
+    /// Log nonces whose hash misses the difficulty by at most this many zero bits
+    #[arg(long, value_name = "BITS")]
+    report_near_miss: Option<u32>,
+    /// Also write hashes meeting this easier difficulty to FILE (`-` for stderr) as JSON
+    /// lines, e.g. a pool's share difficulty; may be repeated
+    #[arg(long, value_name = "DIFFICULTY=FILE", value_parser = secondary::parse_spec)]
+    secondary: Vec<secondary::Spec>,
+    /// Write parameters, host info, hash count, hashrate, energy and solution as JSON here at exit
+    #[arg(long)]
+    report_json: Option<PathBuf>,
+    /// Smart plug to read the machine's power draw from over HTTP while mining, for the energy
+    /// in the report (e.g. http://plug/rpc/Switch.GetStatus?id=0 on a Shelly)
+    #[arg(long, value_name = "URL")]
+    energy_plug: Option<String>,
+    /// Dotted path to the number of watts in the plug's JSON answer
+    #[arg(long, value_name = "PATH", default_value = "apower", requires = "energy_plug")]
+    energy_plug_field: String,
+    /// Append the nonce ranges this miner covers to this file as JSON lines, for `ranges`
+    /// to check a farm for overlaps; a local run also resumes after the ranges it lists
+    #[arg(long, value_name = "FILE")]
+    range_log: Option<PathBuf>,
+    /// Once the run stops, draw the search indices it covered as a PPM heatmap in this file
+    #[arg(long, value_name = "FILE")]
+    coverage_map: Option<PathBuf>,
+    /// Search indices per pixel of the --coverage-map (default: about 4096 pixels in all)
+    #[arg(long, value_name = "INDICES", requires = "coverage_map")]
+    coverage_cell: Option<u64>,
+    /// Append when each solution was found and submitted, and when the network accepted the
+    /// round before each run's, to this file, for `races`
+    #[arg(long, value_name = "FILE")]
+    race_log: Option<PathBuf>,
+    /// Start even if another miner on this host holds the lock for the same challenge_id
+    #[arg(long)]
+    allow_duplicate: bool,
+    /// Mine nonce ranges leased from a `range-server` at this http:// URL, or tcp://host:port for
+    /// its binary protocol; a comma-separated list fails over to the next server when one stops
+    /// answering and returns to the first once it is back
+    #[arg(long, conflicts_with_all = ["journal", "nonce_strategy"])]
+    range_server: Option<String>,
+    /// Find a `range-server --announce` on the LAN over mDNS and mine from it
+    #[arg(long, conflicts_with_all = ["journal", "nonce_strategy", "range_server"])]
+    discover: bool,
+    /// Claim nonce chunks from and publish solutions to a Redis server (redis://[:password@]host[:port][/db])
+    #[arg(long, value_name = "URL", conflicts_with_all = ["journal", "nonce_strategy", "range_server", "discover"])]
+    coordination: Option<String>,
+    /// Nonces claimed per Redis chunk
+    #[arg(long, default_value_t = 1 << 28, requires = "coordination")]
+    chunk_size: u64,
+    /// Publish status, job state and solutions to this MQTT broker (mqtt://[user[:password]@]host[:port])
+    #[arg(long)]
+    mqtt_url: Option<String>,
+    /// Topic prefix for --mqtt-url; messages go to <topic>/status, <topic>/state and <topic>/solution
+    #[arg(long, default_value = "portocripto", requires = "mqtt_url")]
+    mqtt_topic: String,
+    /// Send HTTP requests, and CONNECT tunnels for tcp://, redis:// and mqtt://, through this
+    /// proxy, [http://][user:password@]host[:port], except to hosts in NO_PROXY
+    /// (default: config `proxy`, then HTTP_PROXY for HTTP and HTTPS_PROXY or ALL_PROXY for tunnels)
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+    /// Export job spans and hash metrics to this OTLP/HTTP collector (JSON encoding), e.g. http://localhost:4318
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+    /// Opt-in: post panics with the backend, a hash of the parameters and host details to
+    /// this http:// URL or Sentry DSN (http://KEY@host/PROJECT)
+    #[arg(long, value_name = "URL")]
+    crash_report_url: Option<String>,
+    /// Measure the hashrate for this many seconds first and print the projected time to solution
+    #[arg(long, value_name = "SECS")]
+    warmup: Option<u64>,
+    /// Take the challenge fields from this JSON file like --args-file, and restart the search
+    /// with the new values whenever the file changes
+    #[arg(long, value_name = "FILE", conflicts_with = "args_file")]
+    watch: Option<PathBuf>,
+    /// Only mine during these local-time windows, e.g. "22:00-07:00" or "00:00-06:00,13:00-15:00";
+    /// workers pause outside them (UTC on 32-bit targets and off Linux, Android, macOS and the BSDs)
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    schedule: Option<Schedule>,
+    /// Only mine once the keyboard and mouse have been idle this many minutes, and pause as
+    /// soon as they are used again
+    #[arg(long, value_name = "MINUTES")]
+    only_when_idle: Option<u64>,
+    /// Write sampled per-phase timings (preimage, hash, difficulty check) and the run's
+    /// wall-clock phases to this JSON file
+    #[arg(long, value_name = "FILE")]
+    profile_out: Option<PathBuf>,
+    /// Baseline for --warmup in hashes/s (defaults to the `hashrate` saved by autotune); the run
+    /// aborts if the warm-up reaches less than half of it
+    #[arg(long, requires = "warmup")]
+    expected_hashrate: Option<f64>,
+    /// Recompute about one in N hashes with the portable blake2b implementation and report
+    /// any the backend got wrong, such as from an unstable overclock or a faulty SIMD kernel
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    verify_one_in: Option<u64>,
+    /// Candidates the backend may report that the portable blake2b rejects before mining
+    /// carries on with the portable blake2b instead
+    #[arg(long, value_name = "N", default_value_t = MAX_FALSE_POSITIVES)]
+    max_false_positives: u64,
+    /// Broadcast a solution to other miners on the LAN, and stop when one of them broadcasts
+    /// a nonce that solves this preimage
+    #[arg(long)]
+    lan_broadcast: bool,
+    /// On a spot or preemptible AWS, GCP or Azure VM, stop as soon as the metadata service
+    /// announces the instance will be reclaimed, writing the journal and syncing
+    /// --checkpoint-url before it goes
+    #[arg(long)]
+    preemptible: bool,
+    /// With --preemptible, hand the unsearched rest of the range-server lease back for
+    /// reissue instead of leaving it to expire
+    #[arg(long, requires = "preemptible")]
+    release_on_preemption: bool,
+    /// Format of the configuration summary printed to stderr before mining
+    #[arg(long, value_enum, default_value_t = banner::Format::Text)]
+    output: banner::Format,
+    /// Print newline-delimited JSON events (started, progress, solution, exhausted, error)
+    /// on stdout instead of the bare nonce, for programs supervising the miner
+    #[arg(long)]
+    machine: bool,
+    /// Keep live hashrate, hashes and state in this 64-byte memory-mapped file for GUI
+    /// frontends (put it in /dev/shm; the layout is documented in shm.rs)
+    #[arg(long, value_name = "FILE")]
+    stats_shm: Option<PathBuf>,
+    /// Stream the --machine events to WebSocket clients connecting to this address, for
+    /// browser dashboards and stream overlays
+    #[arg(long, value_name = "ADDR")]
+    ws_listen: Option<String>,
+    /// Open no sockets at all: solutions are only printed, and network flags are refused
+    #[arg(long)]
+    offline: bool,
+}
+
+/// The first flag given in `args` that needs the network, for --offline to turn down.
+fn network_flag(args: &Args) -> Option<&'static str> {
+    [
+        ("--report-to", args.report_to.is_some()),
+        ("--on-solution", args.on_solution.is_some()),
+        ("--solution-sink", args.solution_sink.iter().any(sink::Sink::needs_network)),
+        ("--checkpoint-url", args.checkpoint_url.is_some()),
+        ("--range-server", args.range_server.is_some()),
+        ("--discover", args.discover),
+        ("--coordination", args.coordination.is_some()),
+        ("--mqtt-url", args.mqtt_url.is_some()),
+        ("--proxy", args.proxy.is_some()),
+        ("--otlp-endpoint", args.otlp_endpoint.is_some()),
+        ("--crash-report-url", args.crash_report_url.is_some()),
+        ("--lan-broadcast", args.lan_broadcast),
+        ("--preemptible", args.preemptible),
+        ("--ws-listen", args.ws_listen.is_some()),
+        ("--energy-plug", args.energy_plug.is_some()),
+    ]
+    .into_iter()
+    .find(|&(_, given)| given)
+    .map(|(flag, _)| flag)
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonceStrategy {
+    /// Each worker searches its own contiguous block of the nonce space
+    Sequential,
+    /// Workers interleave: worker t hashes t, t + threads, t + 2 * threads, ...
+    Strided,
+    /// Like `permuted`, but with a fresh seed every run
+    Random,
+    /// A pseudorandom permutation of the nonce space chosen by `--nonce-seed`
+    Permuted,
+    /// Strided down from the largest nonce, away from miners that count up from zero
+    Descending,
+    /// Van der Corput order (bit-reversed indices): every prefix of the search is spread
+    /// evenly over the whole nonce space
+    LowDiscrepancy,
+}
+
+/// Maps the interleaved search index of the workers to the nonce actually hashed.
+#[derive(Clone, Copy, Debug)]
+pub struct NonceOrder {
+    pub strategy: NonceStrategy,
+    pub threads: u64,
+    pub seed: u64,
+    /// This miner's slice of the fleet's index space: index `i` here is fleet index
+    /// `i * worker_count + worker_index`, so miners with different indices never overlap
+    pub worker_index: u64,
+    pub worker_count: u64,
+}
+
+impl NonceOrder {
+    pub fn nonce(&self, index: u64) -> u64 {
+        let fleet_index = index.wrapping_mul(self.worker_count).wrapping_add(self.worker_index);
+        match self.strategy {
+            NonceStrategy::Strided => fleet_index,
+            NonceStrategy::Sequential => {
+                // One block per thread of every miner in the fleet
+                let blocks = self.threads * self.worker_count;
+                let block = (u64::MAX / blocks).wrapping_add(1);
+                (self.worker_index * self.threads + index % self.threads) * block + index / self.threads
+            }
+            NonceStrategy::Random | NonceStrategy::Permuted => permute_nonce(fleet_index, self.seed),
+            NonceStrategy::Descending => u64::MAX - fleet_index,
+            NonceStrategy::LowDiscrepancy => fleet_index.reverse_bits(),
+        }
+    }
+
+    /// Identifies the order in the journal; resuming with a different order would skip nonces.
+    pub fn describe(&self) -> String {
+        let order = match self.strategy {
+            NonceStrategy::Strided => "strided".to_string(),
+            NonceStrategy::Sequential => format!("sequential/{}", self.threads),
+            NonceStrategy::Random | NonceStrategy::Permuted => format!("feistel/{}", self.seed),
+            NonceStrategy::Descending => "descending".to_string(),
+            NonceStrategy::LowDiscrepancy => "van-der-corput".to_string(),
+        };
+        match self.worker_count {
+            1 => order,
+            count => format!("{} worker {}/{}", order, self.worker_index, count),
+        }
+    }
+}
+
+const FEISTEL_ROUNDS: u64 = 6;
+
+fn splitmix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Keyed bijection on u64: a Feistel network over the 32-bit halves with round keys drawn
+/// from `seed`, so every seed walks the whole nonce space in its own order.
+pub fn permute_nonce(index: u64, seed: u64) -> u64 {
+    let (mut left, mut right) = ((index >> 32) as u32, index as u32);
+    for round in 0..FEISTEL_ROUNDS {
+        let key = splitmix64(seed.wrapping_add(round.wrapping_mul(0x9e3779b97f4a7c15)));
+        (left, right) = (right, left ^ (splitmix64(u64::from(right) ^ key) >> 32) as u32);
+    }
+    u64::from(left) << 32 | u64::from(right)
+}
+
+#[derive(Debug, Default)]
+struct Config {
+    /// Used when a run is not given --address
+    address: Option<String>,
+    threads: Option<usize>,
+    batch_size: Option<u64>,
+    force_scalar: Option<bool>,
+    min_submit_interval: Option<u64>,
+    report_to: Option<String>,
+    rig_name: Option<String>,
+    identity: Option<PathBuf>,
+    proxy: Option<String>,
+    retry_attempts: Option<u32>,
+    retry_base_delay_ms: Option<u64>,
+    retry_max_delay_ms: Option<u64>,
+    network_timeout_secs: Option<u64>,
+    on_solution: Option<String>,
+    solution_sinks: Vec<sink::Sink>,
+    outbox: Option<PathBuf>,
+    submitted: Option<PathBuf>,
+    receipt: Option<PathBuf>,
+    receipt_key: Option<PathBuf>,
+    mqtt_url: Option<String>,
+    otlp_endpoint: Option<String>,
+    crash_report_url: Option<String>,
+    daemon_token: Option<String>,
+    daemon_clients: Vec<(String, String)>,
+    /// Baseline recorded by `autotune`, checked by `--warmup`
+    hashrate: Option<f64>,
+    /// Inputs to `economics`: draw while mining, price per kWh and what a solution earns
+    power_watts: Option<f64>,
+    electricity_price: Option<f64>,
+    reward_per_solution: Option<f64>,
+}
+
+/// Read `key = value` lines from the config file; a missing file yields the defaults.
+///
+/// Keys before the first `[section]` apply everywhere; keys under `[profile.NAME]`
+/// override them when `profile` is `NAME`. Other sections are skipped. An empty string
+/// leaves a key unset.
+fn load_config(path: &Path, profile: Option<&str>) -> Result<Config, String> {
+    let mut config = Config::default();
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) if profile.is_none() => return Ok(config),
+        Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+    };
+
+    let mut active = true;
+    let mut profile_found = false;
+    for (lineno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            active = profile.is_some() && section.trim().strip_prefix("profile.") == profile;
+            profile_found |= active;
+            continue;
+        }
+        if !active {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            eprintln!("{}:{}: expected `key = value`", path.display(), lineno + 1);
+            continue;
+        };
+        let value = value.trim().trim_matches('"');
+        let parsed = match key.trim() {
+            "threads" => value.parse().map(|v| config.threads = Some(v)).is_ok(),
+            "batch_size" => value.parse().map(|v| config.batch_size = Some(v)).is_ok(),
+            "force_scalar" => value.parse().map(|v| config.force_scalar = Some(v)).is_ok(),
+            "min_submit_interval" => value.parse().map(|v| config.min_submit_interval = Some(v)).is_ok(),
+            "hashrate" => value.parse().map(|v| config.hashrate = Some(v)).is_ok(),
+            "power_watts" => value.parse().map(|v| config.power_watts = Some(v)).is_ok(),
+            "electricity_price" => value.parse().map(|v| config.electricity_price = Some(v)).is_ok(),
+            "reward_per_solution" => value.parse().map(|v| config.reward_per_solution = Some(v)).is_ok(),
+            "address" => {
+                config.address = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "report_to" => {
+                config.report_to = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "rig_name" => {
+                config.rig_name = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "identity" => {
+                config.identity = Some(PathBuf::from(value)).filter(|_| !value.is_empty());
+                true
+            }
+            "proxy" => {
+                config.proxy = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "retry_attempts" => value.parse().map(|v: u32| config.retry_attempts = Some(v.max(1))).is_ok(),
+            "retry_base_delay_ms" => value.parse().map(|v| config.retry_base_delay_ms = Some(v)).is_ok(),
+            "retry_max_delay_ms" => value.parse().map(|v| config.retry_max_delay_ms = Some(v)).is_ok(),
+            "network_timeout_secs" => value.parse().map(|v: u64| config.network_timeout_secs = Some(v.max(1))).is_ok(),
+            "outbox" => {
+                config.outbox = Some(PathBuf::from(value)).filter(|_| !value.is_empty());
+                true
+            }
+            "submitted" => {
+                config.submitted = Some(PathBuf::from(value)).filter(|_| !value.is_empty());
+                true
+            }
+            "receipt" => {
+                config.receipt = Some(PathBuf::from(value)).filter(|_| !value.is_empty());
+                true
+            }
+            "receipt_key" => {
+                config.receipt_key = Some(PathBuf::from(value)).filter(|_| !value.is_empty());
+                true
+            }
+            "on_solution" => value.is_empty() || parse_hook(value).map(|v| config.on_solution = Some(v)).is_ok(),
+            "solution_sinks" => value
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| sink::parse(s.trim()))
+                .collect::<Result<_, _>>()
+                .map(|v| config.solution_sinks = v)
+                .is_ok(),
+            "mqtt_url" => {
+                config.mqtt_url = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "otlp_endpoint" => {
+                config.otlp_endpoint = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "crash_report_url" => {
+                config.crash_report_url = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "daemon_token" => {
+                config.daemon_token = Some(value.to_string()).filter(|v| !v.is_empty());
+                true
+            }
+            "daemon_clients" => value
+                .split(',')
+                .filter(|c| !c.trim().is_empty())
+                .map(|c| parse_client(c.trim()))
+                .collect::<Result<_, _>>()
+                .map(|v| config.daemon_clients = v)
+                .is_ok(),
+            other => {
+                eprintln!("{}:{}: unknown key `{}`", path.display(), lineno + 1, other);
+                true
+            }
+        };
+        if !parsed {
+            eprintln!("{}:{}: invalid value `{}`", path.display(), lineno + 1, value);
+        }
+    }
+    match profile {
+        Some(name) if !profile_found => Err(format!("{}: no [profile.{}] section", path.display(), name)),
+        _ => Ok(config),
+    }
+}
+
+/// `--client` and `daemon_clients`: `NAMESPACE=TOKEN`.
+fn parse_client(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((namespace, token)) if !namespace.is_empty() && !token.is_empty() => {
+            Ok((namespace.to_string(), token.to_string()))
+        }
+        _ => Err(format!("{:?} should look like NAMESPACE=TOKEN", s)),
+    }
+}
+
+/// Set top-level `key = value` entries in the config file, keeping unrelated lines
+/// and profile sections intact.
+fn save_config(path: &Path, entries: &[(&str, String)]) -> std::io::Result<()> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let mut pending: Vec<_> = entries.iter().collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut sections: Vec<String> = Vec::new();
+
+    for line in text.lines() {
+        if !sections.is_empty() || line.trim_start().starts_with('[') {
+            sections.push(line.to_string());
+            continue;
+        }
+        let key = line.split_once('=').map(|(k, _)| k.trim());
+        match pending.iter().position(|(k, _)| Some(*k) == key) {
+            Some(i) => {
+                let (k, v) = pending.remove(i);
+                lines.push(format!("{} = {}", k, v));
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    for (k, v) in pending {
+        lines.push(format!("{} = {}", k, v));
+    }
+    if !sections.is_empty() {
+        lines.push(String::new());
+        lines.extend(sections);
+    }
+
+    std::fs::write(path, lines.join("\n") + "\n")
+}
+
+/// Parse a hex difficulty of up to 32 digits. Up to 8 digits keep the original meaning of a
+/// 32-bit mask over the first four bytes; longer masks cover `4 * digits` bits.
+pub fn parse_mask(hex: &str) -> Result<Mask, PortocriptoError> {
+    if hex.is_empty() || hex.len() > 32 {
+        return Err(PortocriptoError::Parse(format!(
+            "difficulty {:?} has {} hex digits but only the first 16 hash bytes (32 digits) are checked",
+            hex,
+            hex.len()
+        )));
+    }
+    let value = u128::from_str_radix(hex, 16)
+        .map_err(|e| PortocriptoError::Parse(format!("invalid difficulty {:?}: {}", hex, e)))?;
+    let width = (hex.len() as u32 * 4).max(32);
+    Ok(value << (128 - width) | u128::MAX.checked_shr(width).unwrap_or(0))
+}
+
+/// A 32-bit mask over the first four bytes, as the challenge originally defined it.
+pub fn widen_mask(mask: u32) -> Mask {
+    (mask as u128) << 96 | u128::MAX >> 32
+}
+
+/// Hex digits of the mask, dropping trailing don't-care digits but never going below 8.
+pub fn format_mask(mask: Mask) -> String {
+    let digits = (128 - mask.trailing_ones()).div_ceil(4).max(8);
+    format!("{:032X}", mask)[..digits as usize].to_string()
+}
+
+pub fn parse_target(hex: &str) -> Result<Target, String> {
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("target must be 1 to 64 hex digits, got {:?}", hex));
+    }
+    let hex = format!("{:0>64}", hex);
+    let mut target = [0u8; 32];
+    for (byte, digits) in target.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).unwrap(), 16).unwrap();
+    }
+    Ok(target)
+}
+
+/// `difficulty_mask` tightened with the leading zeros every hash within `target` has, so the
+/// cheap prefix check turns away nearly every hash the full comparison would; only for a
+/// prefix read big-endian from the start of the hash, where those zeros are the mask's bits.
+pub fn prefilter_mask(difficulty_mask: Mask, target: &Target, layout: &PrefixLayout) -> Mask {
+    if layout.offset != 0 || layout.little_endian {
+        return difficulty_mask;
+    }
+    difficulty_mask & mask_for_zero_bits(leading_zero_bits(target).min(128))
+}
+
+/// Number of prefix bits the mask requires to be zero.
+pub fn mask_zero_bits(difficulty_mask: Mask) -> u32 {
+    (!difficulty_mask).count_ones()
+}
+
+/// The mask with its `bits` least significant required-zero bits allowed to be set; for a
+/// leading-zero mask this is the difficulty with `bits` fewer leading zeros.
+pub fn relax_mask(difficulty_mask: Mask, bits: u32) -> Mask {
+    let mut mask = difficulty_mask;
+    for _ in 0..bits {
+        let required = !mask;
+        mask |= required & required.wrapping_neg();
+    }
+    mask
+}
+
+/// How many required-zero bits would have to be relaxed for the hash to pass.
+pub fn bits_off_target(hash: &[u8], difficulty_mask: Mask) -> u32 {
+    (0..=128).find(|&bits| hash_structure_good(hash, relax_mask(difficulty_mask, bits))).unwrap_or(128)
+}
+
+/// Whether the required zero bits are exactly the leading bits of the prefix.
+pub fn is_leading_zero_mask(difficulty_mask: Mask) -> bool {
+    (!difficulty_mask).leading_ones() == mask_zero_bits(difficulty_mask)
+}
+
+/// Check the difficulty on two synthesized hashes: one whose prefix has exactly the bits
+/// `mask` requires cleared must pass, and it with the first or last of those bits set must
+/// fail. The hashes are laid out at `layout.offset` in its byte order without going through
+/// `PrefixLayout::prefix`, so a mask or byte-order slip in either shows up.
+pub fn check_difficulty(mask: Mask, layout: PrefixLayout, hash_len: usize) -> Result<(), String> {
+    let hash_with = |prefix: Mask| {
+        // Every bit outside the prefix is set, so only the prefix can make it pass
+        let mut hash = vec![0xffu8; hash_len];
+        let width = layout.width.min(16).min(hash_len.saturating_sub(layout.offset));
+        for (i, &byte) in prefix.to_be_bytes()[..width].iter().enumerate() {
+            let at = if layout.little_endian { layout.offset + width - 1 - i } else { layout.offset + i };
+            hash[at] = byte;
+        }
+        hash
+    };
+    let passes = |prefix: Mask| hash_structure_good(&layout.prefix(&hash_with(prefix)), mask);
+    if !passes(mask) {
+        return Err(format!("a hash with exactly the required zero bits fails difficulty {}", format_mask(mask)));
+    }
+    let required = !mask;
+    if required == 0 {
+        return Ok(());
+    }
+    for bit in [1 << (127 - required.leading_zeros()), required & required.wrapping_neg()] {
+        if passes(mask | bit) {
+            return Err(format!(
+                "a hash with required bit {} of the prefix set still passes difficulty {}",
+                bit.leading_zeros(),
+                format_mask(mask)
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn mask_for_zero_bits(zero_bits: u32) -> Mask {
+    u128::MAX.checked_shr(zero_bits).unwrap_or(0)
+}
+
+/// The 256-bit target (64 hex digits) accepting exactly the hashes with `zero_bits` leading zeros.
+pub fn leading_zero_target(zero_bits: u32) -> String {
+    let zeros = (zero_bits as usize / 4).min(64);
+    let mut target = "0".repeat(zeros);
+    if zeros < 64 {
+        if !zero_bits.is_multiple_of(4) {
+            target.push(char::from_digit(0xf >> (zero_bits % 4), 16).unwrap());
+        }
+        target.push_str(&"f".repeat(64 - target.len()));
+    }
+    target
+}
+
+/// Render a hash count, switching to scientific notation once it stops being readable.
+pub fn format_count(count: f64) -> String {
+    if count < 1e12 {
+        format!("{:.0}", count)
+    } else {
+        format!("{:.3e}", count)
+    }
+}
+
+/// Rough human duration such as `42s`, `7m 5s`, `3h 12m` or `9d 4h`.
+pub fn format_duration(secs: f64) -> String {
+    if !secs.is_finite() {
+        return "forever".to_string();
+    }
+    match secs.round() as u64 {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+        s => format!("{}d {}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+pub fn hash_preimage(preimage: &[u8], output: &mut [u8]) {
+    let mut hasher = Blake2bVar::new(output.len()).unwrap();
+    hasher.update(preimage);
+    hasher.finalize_variable(output).unwrap();
+}
+
+/// C ABI a hash backend plugin must export:
+///
+/// ```c
+/// uint32_t portocripto_plugin_abi_version(void);  /* must return 1 */
+/// int32_t portocripto_hash(const uint8_t *preimage, size_t preimage_len,
+///                          uint8_t *out, size_t out_len);  /* 0 on success */
+/// ```
+///
+/// `portocripto_hash` is called concurrently from every worker thread. A plugin that hashes
+/// several messages faster at once may also export
+///
+/// ```c
+/// int32_t portocripto_hash_batch(const uint8_t *const *preimages, const size_t *preimage_lens,
+///                                size_t count, uint8_t *out, size_t out_len);
+/// ```
+///
+/// which writes `count` digests of `out_len` bytes back to back into `out`; workers then
+/// call it with up to `BATCH_LANES` preimages instead of `portocripto_hash`.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+type PluginHashFn = unsafe extern "C" fn(*const u8, usize, *mut u8, usize) -> i32;
+type PluginBatchFn = unsafe extern "C" fn(*const *const u8, *const usize, usize, *mut u8, usize) -> i32;
+
+static PLUGIN_HASH: OnceLock<PluginHashFn> = OnceLock::new();
+static PLUGIN_HASH_BATCH: OnceLock<PluginBatchFn> = OnceLock::new();
+
+fn plugin_hash(preimage: &[u8], output: &mut [u8]) {
+    let hash = PLUGIN_HASH.get().expect("plugin not loaded");
+    let rc = unsafe { hash(preimage.as_ptr(), preimage.len(), output.as_mut_ptr(), output.len()) };
+    assert!(rc == 0, "plugin hash failed with code {}", rc);
+}
+
+fn plugin_hash_batch(preimages: &[&[u8]], outputs: &mut [u8]) {
+    let hash = PLUGIN_HASH_BATCH.get().expect("plugin not loaded");
+    let out_len = outputs.len() / preimages.len().max(1);
+    for (preimages, outputs) in preimages.chunks(BATCH_LANES).zip(outputs.chunks_mut(BATCH_LANES * out_len)) {
+        let (mut pointers, mut lens) = ([std::ptr::null(); BATCH_LANES], [0usize; BATCH_LANES]);
+        for (i, preimage) in preimages.iter().enumerate() {
+            (pointers[i], lens[i]) = (preimage.as_ptr(), preimage.len());
+        }
+        let rc = unsafe { hash(pointers.as_ptr(), lens.as_ptr(), preimages.len(), outputs.as_mut_ptr(), out_len) };
+        assert!(rc == 0, "plugin batch hash failed with code {}", rc);
+    }
+}
+
+/// Load a hash backend plugin, with its batch entry point if it has one; the library stays
+/// loaded for the rest of the process.
+#[cfg(unix)]
+fn load_plugin(path: &Path) -> Result<(HashFn, Option<BatchFn>), PortocriptoError> {
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    unsafe extern "C" {
+        fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *mut c_char;
+    }
+    const RTLD_NOW: c_int = 2;
+
+    let last_error = || unsafe {
+        let msg = dlerror();
+        if msg.is_null() { "unknown error".to_string() } else { CStr::from_ptr(msg).to_string_lossy().into_owned() }
+    };
+    let backend = PortocriptoError::Backend;
+    let filename = CString::new(path.as_os_str().as_bytes()).map_err(|e| backend(e.to_string()))?;
+    let handle = unsafe { dlopen(filename.as_ptr(), RTLD_NOW) };
+    if handle.is_null() {
+        return Err(backend(last_error()));
+    }
+    let symbol = |name: &CStr| {
+        let sym = unsafe { dlsym(handle, name.as_ptr()) };
+        if sym.is_null() {
+            Err(backend(format!("missing symbol {}: {}", name.to_string_lossy(), last_error())))
+        } else {
+            Ok(sym)
+        }
+    };
+
+    let abi_version: unsafe extern "C" fn() -> u32 =
+        unsafe { std::mem::transmute(symbol(c"portocripto_plugin_abi_version")?) };
+    let version = unsafe { abi_version() };
+    if version != PLUGIN_ABI_VERSION {
+        return Err(backend(format!("plugin ABI version {} is not supported (expected {})", version, PLUGIN_ABI_VERSION)));
+    }
+    let hash: PluginHashFn = unsafe { std::mem::transmute(symbol(c"portocripto_hash")?) };
+
+    PLUGIN_HASH.set(hash).map_err(|_| backend("a plugin is already loaded".to_string()))?;
+    let batch = symbol(c"portocripto_hash_batch").ok().map(|sym| {
+        let batch: PluginBatchFn = unsafe { std::mem::transmute(sym) };
+        let _ = PLUGIN_HASH_BATCH.set(batch);
+        plugin_hash_batch as BatchFn
+    });
+    Ok((plugin_hash, batch))
 }
 
-pub fn hash_structure_good(hash: &[u8], difficulty_mask: u32) -> bool {
-    if hash.len() < 4 {
-        return false; // Not enough bytes to apply a u32 mask
+#[cfg(not(unix))]
+fn load_plugin(_path: &Path) -> Result<(HashFn, Option<BatchFn>), PortocriptoError> {
+    Err(PortocriptoError::Backend("plugins are only supported on unix platforms".to_string()))
+}
+
+const BLAKE2B_BLOCK: usize = 128;
+
+/// Key and wrapped backend for `--hmac-key`; set once and kept, like the plugin hash.
+static HMAC: OnceLock<(Vec<u8>, HashFn)> = OnceLock::new();
+
+/// HMAC (RFC 2104) over `hash`, with Blake2b's 128-byte block and `output.len()` digests at
+/// both levels, as Python's `hmac.new(key, msg, lambda: blake2b(digest_size=n))` computes it.
+pub fn hmac_with(hash: HashFn, key: &[u8], message: &[u8], output: &mut [u8]) {
+    let mut padded = [0u8; BLAKE2B_BLOCK];
+    if key.len() > BLAKE2B_BLOCK {
+        hash(key, &mut padded[..output.len()]);
+    } else {
+        padded[..key.len()].copy_from_slice(key);
     }
+    let mut buffer = Vec::with_capacity(BLAKE2B_BLOCK + message.len().max(output.len()));
+    buffer.extend(padded.iter().map(|b| b ^ 0x36));
+    buffer.extend_from_slice(message);
+    let mut inner = [0u8; 64];
+    hash(&buffer, &mut inner[..output.len()]);
+
+    buffer.clear();
+    buffer.extend(padded.iter().map(|b| b ^ 0x5c));
+    buffer.extend_from_slice(&inner[..output.len()]);
+    hash(&buffer, output);
+}
+
+fn hmac_hash(preimage: &[u8], output: &mut [u8]) {
+    let (key, hash) = HMAC.get().expect("HMAC key not set");
+    hmac_with(*hash, key, preimage, output);
+}
 
-    let hash_prefix = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
-    (hash_prefix & !difficulty_mask) == 0
+/// `hmac_hash` over the portable blake2b whatever backend it wraps, for --verify-one-in.
+fn hmac_reference_hash(preimage: &[u8], output: &mut [u8]) {
+    let (key, _) = HMAC.get().expect("HMAC key not set");
+    hmac_with(sim::reference(hash_preimage), key, preimage, output);
+}
+
+/// Wrap `hash` in HMAC under `key` for the rest of the process.
+fn hmac_backend(hash: HashFn, key: Vec<u8>) -> Result<HashFn, PortocriptoError> {
+    HMAC.set((key, hash)).map_err(|_| PortocriptoError::Backend("an HMAC key is already set".to_string()))?;
+    Ok(hmac_hash)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 //pub fn init_rom(no_pre_mine_hex: &str) -> Rom {
@@ -48,73 +1437,4083 @@ pub fn hash_structure_good(hash: &[u8], difficulty_mask: u32) -> bool {
 //    )
 //}
 
-fn main() {
-    let args = Args::parse();
+// (nonce, suffix, blake2b-256 of the preimage, difficulty mask, passes)
+const TEST_VECTORS: &[(u64, &str, &str, &str, bool)] = &[
+    (0x0, "ab000FFFFFcde", "70a6696c60ed1471d57511a263a5276c613b5f58a35c813d4670f5e2c2fa3d11", "000FFFFF", false),
+    (0x5e8, "ab000FFFFFcde", "0008f345ffac86f04eaddbc2be500749b571e23e8ce2f33584b984521d7d6bea", "000FFFFF", true),
+    (
+        0xdeadbeef,
+        "addr_test1qqchallenge00AAAAAAAAnopremine00latest0012",
+        "2214e1de3c9a1ed8e5cc0372b66df127401889951e6e48f99c61e29fdff78d21",
+        "3FFFFFFF",
+        true,
+    ),
+    (
+        0xdeadbeef,
+        "addr_test1qqchallenge00AAAAAAAAnopremine00latest0012",
+        "2214e1de3c9a1ed8e5cc0372b66df127401889951e6e48f99c61e29fdff78d21",
+        "1FFFFFFF",
+        false,
+    ),
+    // Realistic field lengths: the preimage spans two compression blocks
+    (
+        0x2a,
+        "addr1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh7w4ngxqysvdng4vz5nkz6uq3ygugepvgqrsyqq9tmh7a8qazmz5sq0zdxwk\
+         **D07C10000FFFFF8a9b6c1f3e2d4a5b6c7d8e9f0a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d\
+         2025-10-30T23:59:59.000Z509681483",
+        "2f2b24ff94cde50d918d966efc49224ffe4b4fd25d7580a79c50f91769f23d58",
+        "3FFFFFFF",
+        true,
+    ),
+    // Masks wider than 32 bits reach past the fourth byte
+    (0x5e8, "ab000FFFFFcde", "0008f345ffac86f04eaddbc2be500749b571e23e8ce2f33584b984521d7d6bea", "000FFFFFFFFF", true),
+    (0x5e8, "ab000FFFFFcde", "0008f345ffac86f04eaddbc2be500749b571e23e8ce2f33584b984521d7d6bea", "000FFFFFFF0F", false),
+];
 
-    // Initialize AshMaize ROM
-    //let rom = init_rom(&args.no_pre_mine);
-    //let rom = Arc::new(init_rom(&args.no_pre_mine));
+// (key, message, HMAC-Blake2b-256), from Python's hmac module; the second key is longer
+// than a block and gets hashed first
+const HMAC_VECTORS: &[(&[u8], &str, &str)] = &[
+    (b"Jefe", "what do ya want for nothing?", "3cf096eeeb2202a250db168c4823a44ef4618ebabb225789386fed316131e3a0"),
+    (
+        &[0xaa; 131],
+        "Test Using Larger Than Block-Size Key - Hash Key First",
+        "8211788e2a5a2113c9297ab147e9e0cf0630e83a52f1c7d46241bbe0e1fc7bdc",
+    ),
+];
 
-    // Parse difficulty from hex string to u32 mask
-    let difficulty_mask = u32::from_str_radix(&args.difficulty, 16).unwrap();
+// RFC 8032 section 7.1 tests 1-3 and SHA(abc): (seed, message, public key, signature)
+const ED25519_VECTORS: &[(&str, &[u8], &str, &str)] = &[
+    (
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+        b"",
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+        "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+    ),
+    (
+        "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+        &[0x72],
+        "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+        "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+    ),
+    (
+        "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+        &[0xaf, 0x82],
+        "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+        "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+    ),
+    (
+        "833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42",
+        b"\xdd\xaf\x35\xa1\x93\x61\x7a\xba\xcc\x41\x73\x49\xae\x20\x41\x31\
+          \x12\xe6\xfa\x4e\x89\xa9\x7e\xa2\x0a\x9e\xee\xe6\x4b\x55\xd3\x9a\
+          \x21\x92\x99\x2a\x27\x4f\xc1\xa8\x36\xba\x3c\x23\xa3\xfe\xeb\xbd\
+          \x45\x4d\x44\x23\x64\x3c\xe8\x0e\x2a\x9a\xc9\x4f\xa5\x4c\xa4\x9f",
+        "ec172b93ad5e563bf4932c70e1245034c35467ef2efd4d64ebf819683467e2bf",
+        "dc2a4459e7369633a52b1bf277839a00201009a3efbf3ecb69bea2186c26b58909351fc9ac90b3ecfdfbc7c66431e0303dca179c138ac17ad9bef1177331a704",
+    ),
+];
 
-    // Compute suffix once
-    let suffix = format!(
-        "{}{}{}{}{}{}",
-        args.address,
-        args.challenge_id,
-        args.difficulty,
-        args.no_pre_mine,
-        args.latest_submission,
-        args.no_pre_mine_hour
-    );
+// FIPS 180-2 SHA-256 examples: (message, digest)
+const SHA256_VECTORS: &[(&str, &str)] = &[
+    ("abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+    (
+        "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+    ),
+];
+// The AWS Signature Version 4 documentation's signing key example
+const SIGV4_KEY_VECTOR: (&str, &str, &str, &str, &str) = (
+    "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+    "20120215",
+    "us-east-1",
+    "iam",
+    "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d",
+);
+const SHA1_VECTORS: &[(&str, &str)] = &[
+    ("abc", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+    ("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq", "84983e441c3bd26ebaae4aa1f95129e5e54670f1"),
+];
+// RFC 6455's opening handshake example
+const WS_ACCEPT_VECTOR: (&str, &str) = ("dGhlIHNhbXBsZSBub25jZQ==", "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+/// Challenge fields, in preimage order, for `PREIMAGE_VECTORS`
+const PREIMAGE_FIELDS: [&str; 6] = [
+    "addr_test1qpzry9x8gf2tvdw0s3jn4syst5",
+    "**D07C10",
+    "000fFFFF",
+    "0xaBcD01",
+    "2025-10-30T12:00:00Z",
+    "2025-10-30T12",
+];
+/// Those fields with a nonce, encoding, hex case and salt, and the preimage (`printable`)
+/// spelled out by hand
+type PreimageVector = (u64, NonceEncoding, HexCase, Option<(&'static str, SaltPosition)>, &'static str);
+const PREIMAGE_VECTORS: &[PreimageVector] = &[
+    (
+        0x1816,
+        NonceEncoding::Hex16,
+        HexCase::AsIs,
+        None,
+        "0000000000001816addr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000fFFFF0xaBcD012025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        0x1816,
+        NonceEncoding::HexNopad,
+        HexCase::Lower,
+        None,
+        "1816addr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000fffff0xabcd012025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        1816,
+        NonceEncoding::Decimal,
+        HexCase::Upper,
+        None,
+        "1816addr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000FFFFF0xABCD012025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        0,
+        NonceEncoding::HexNopad,
+        HexCase::AsIs,
+        None,
+        "0addr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000fFFFF0xaBcD012025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        u64::MAX,
+        NonceEncoding::Decimal,
+        HexCase::AsIs,
+        None,
+        "18446744073709551615addr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000fFFFF0xaBcD01\
+         2025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        0x0102030405060708,
+        NonceEncoding::RawBe,
+        HexCase::AsIs,
+        None,
+        "\\x01\\x02\\x03\\x04\\x05\\x06\\x07\\x08addr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000fFFFF0xaBcD01\
+         2025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        0x0102030405060708,
+        NonceEncoding::RawLe,
+        HexCase::AsIs,
+        None,
+        "\\x08\\x07\\x06\\x05\\x04\\x03\\x02\\x01addr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000fFFFF0xaBcD01\
+         2025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        0xabc,
+        NonceEncoding::Hex16,
+        HexCase::AsIs,
+        Some(("s4lt", SaltPosition::Prepend)),
+        "0000000000000abcs4ltaddr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000fFFFF0xaBcD01\
+         2025-10-30T12:00:00Z2025-10-30T12",
+    ),
+    (
+        0xabc,
+        NonceEncoding::Hex16,
+        HexCase::Upper,
+        Some(("s4lt", SaltPosition::Append)),
+        "0000000000000abcaddr_test1qpzry9x8gf2tvdw0s3jn4syst5**D07C10000FFFFF0xABCD01\
+         2025-10-30T12:00:00Z2025-10-30T12s4lt",
+    ),
+];
+/// Nonces whose text changes length in both directions, for the round trips in `selftest`
+const PREIMAGE_NONCES: &[u64] = &[0, 1, 9, 10, 15, 16, 255, 256, 1816, 1 << 32, u64::MAX - 1, u64::MAX];
 
-    // Share ROM across threads (read-only, no mutex needed)
-    //let rom = Arc::new(rom);
+type HashFn = fn(&[u8], &mut [u8]);
+/// Hashes `preimages[i]` into the i-th of the equal `outputs.len() / preimages.len()`-byte
+/// slots of `outputs`, so SIMD and offload backends get several messages per call.
+type BatchFn = fn(&[&[u8]], &mut [u8]);
+/// Preimages a worker hands to a `BatchFn` at once
+pub const BATCH_LANES: usize = 8;
+
+/// A compiled hash backend; `supported` reports whether the running CPU can execute it.
+pub struct Backend {
+    pub name: &'static str,
+    pub hash: HashFn,
+    /// The CPU extension it is written for
+    pub simd: Option<&'static str>,
+    pub supported: fn() -> bool,
+}
+
+/// Compiled backends, slowest first.
+const BACKENDS: &[Backend] = &[
+    Backend { name: "blake2b", hash: hash_preimage, simd: None, supported: || true },
+    #[cfg(target_arch = "x86_64")]
+    Backend {
+        name: "avx2",
+        hash: blake2b::hash_avx2,
+        simd: Some("avx2"),
+        supported: || is_x86_feature_detected!("avx2"),
+    },
+    #[cfg(target_arch = "aarch64")]
+    Backend {
+        name: "neon",
+        hash: blake2b::hash_neon,
+        simd: Some("neon"),
+        supported: || std::arch::is_aarch64_feature_detected!("neon"),
+    },
+];
+
+/// The fastest backend the running CPU supports, or the portable one with `force_scalar`.
+fn select_backend(force_scalar: bool) -> &'static Backend {
+    if sim::enabled() {
+        return &sim::BACKEND;
+    }
+    if force_scalar {
+        return &BACKENDS[0];
+    }
+    BACKENDS.iter().rev().find(|b| (b.supported)()).unwrap_or(&BACKENDS[0])
+}
+
+/// SIMD extensions of the running CPU that are relevant to backend selection.
+fn cpu_features() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut features = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            features.push("avx2");
+        }
+        if is_x86_feature_detected!("avx512f") {
+            features.push("avx512f");
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            features.push("neon");
+        }
+    }
+    features
+}
+
+/// Print what every compiled backend, and the --plugin one if given, can do here: whether
+/// the CPU runs it, whether it is the one a run would pick, the extension it needs and the
+/// preimages it hashes per call. The built-in ones hash on the CPU, holding one preimage and
+/// hash per lane in each worker, so they need no device memory; a plugin does not say.
+fn list_backends(plugin: Option<&Path>, force_scalar: bool, json: bool) {
+    struct Row {
+        name: String,
+        supported: bool,
+        selected: bool,
+        simd: Option<&'static str>,
+        lanes: usize,
+        on_cpu: bool,
+    }
+    let selected = select_backend(force_scalar).name;
+    let mut rows: Vec<Row> = BACKENDS
+        .iter()
+        .chain(sim::enabled().then_some(&sim::BACKEND))
+        .map(|b| Row {
+            name: b.name.to_string(),
+            supported: (b.supported)(),
+            selected: plugin.is_none() && b.name == selected,
+            simd: b.simd,
+            lanes: 1,
+            on_cpu: true,
+        })
+        .collect();
+    if let Some(path) = plugin {
+        match load_plugin(path) {
+            Ok((_, batch)) => rows.push(Row {
+                name: format!("plugin:{}", path.display()),
+                supported: true,
+                selected: true,
+                simd: None,
+                lanes: if batch.is_some() { BATCH_LANES } else { 1 },
+                on_cpu: false,
+            }),
+            Err(e) => {
+                eprintln!("failed to load plugin {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let (model, cpus, features) = (cpu_model(), available_cpus(), cpu_features());
+    if json {
+        let backends: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                format!(
+                    concat!(
+                        r#"{{"name": {}, "supported": {}, "selected": {}, "simd": {}, "lanes": {}, "#,
+                        r#""device": {}, "device_memory_bytes": {}}}"#
+                    ),
+                    json_string(&row.name),
+                    row.supported,
+                    row.selected,
+                    row.simd.map_or("null".to_string(), json_string),
+                    row.lanes,
+                    if row.on_cpu { r#""cpu""# } else { "null" },
+                    if row.on_cpu { "0" } else { "null" }
+                )
+            })
+            .collect();
+        let features: Vec<String> = features.iter().map(|f| json_string(f)).collect();
+        println!(
+            r#"{{"backends": [{}], "cpu": {{"model": {}, "cpus": {}, "arch": {}, "features": [{}]}}}}"#,
+            backends.join(", "),
+            json_string(&model),
+            cpus,
+            json_string(std::env::consts::ARCH),
+            features.join(", ")
+        );
+        return;
+    }
+    let width = rows.iter().map(|row| row.name.len()).max().unwrap_or(0).max("backend".len());
+    println!("{:<width$}  supported  selected  simd  lanes  device", "backend");
+    for row in &rows {
+        println!(
+            "{:<width$}  {:<9}  {:<8}  {:<4}  {:<5}  {}",
+            row.name,
+            if row.supported { "yes" } else { "no" },
+            if row.selected { "yes" } else { "" },
+            row.simd.unwrap_or("-"),
+            row.lanes,
+            if row.on_cpu { "cpu, no device memory" } else { "unknown" }
+        );
+    }
+    println!("cpu: {} ({} CPUs, {}, features: {})", model, cpus, std::env::consts::ARCH, features.join(" "));
+}
+
+/// The `selftest` sections that do not depend on the hash backend: name, what it counts, and
+/// the check, which logs each failure and returns (failures, count). The unit tests run the same.
+type SelftestSection = (&'static str, &'static str, fn() -> (usize, usize));
+const SELFTEST_SECTIONS: [SelftestSection; 8] = [
+    ("difficulty", "masks and layouts", selftest_difficulty),
+    ("target", "vectors", selftest_target),
+    ("evaluator", "vectors", selftest_evaluator),
+    ("ed25519", "vectors", selftest_ed25519),
+    ("sigv4", "vectors", selftest_sigv4),
+    ("websocket", "vectors", selftest_websocket),
+    ("preimage", "vectors", selftest_preimage),
+    ("solution", "vectors", selftest_solution),
+];
+
+fn selftest() -> bool {
+    let mut all_ok = true;
+    let mut report = |name: &str, unit: &str, (failures, count): (usize, usize)| {
+        if failures == 0 {
+            println!("{}: PASS ({} {})", name, count, unit);
+        } else {
+            println!("{}: FAIL ({}/{} {})", name, failures, count, unit);
+            all_ok = false;
+        }
+    };
+    for Backend { name, hash, supported, .. } in BACKENDS {
+        if !supported() {
+            println!("{}: SKIP (not supported by this CPU)", name);
+            continue;
+        }
+        report(name, "vectors", selftest_backend(name, *hash));
+    }
+    for (name, unit, section) in SELFTEST_SECTIONS {
+        report(name, unit, section());
+    }
+    all_ok
+}
 
-    let found = Arc::new(AtomicBool::new(false));
-    let result_nonce = Arc::new(AtomicU64::new(0));
-    let start_nonce = 0u64;
+/// The shared hash and HMAC vectors through one backend's `hash`.
+fn selftest_backend(name: &str, hash: HashFn) -> (usize, usize) {
+    let mut failures = 0;
+    for (i, &(nonce, suffix, expected, mask, passes)) in TEST_VECTORS.iter().enumerate() {
+        let mut preimage = Vec::new();
+        write_preimage(&mut preimage, nonce, suffix, NonceEncoding::Hex16);
+        let mut output = [0u8; 32];
+        hash(&preimage, &mut output);
 
-    (0..NUM_THREADS).into_par_iter().for_each(|thread_id| {
-        //let rom = Arc::clone(&rom);
-        let found = Arc::clone(&found);
-        let result_nonce = Arc::clone(&result_nonce);
-        let mut local_nonce = start_nonce + thread_id as u64;
-        let stride = NUM_THREADS as u64;
+        let got = to_hex(&output);
+        if got != expected {
+            eprintln!("{}: vector {} hash mismatch: expected {}, got {}", name, i, expected, got);
+            failures += 1;
+        } else if hash_structure_good(&output, parse_mask(mask).unwrap()) != passes {
+            eprintln!("{}: vector {} difficulty check should be {}", name, i, passes);
+            failures += 1;
+        }
+    }
+    for (i, &(key, message, expected)) in HMAC_VECTORS.iter().enumerate() {
+        let mut output = [0u8; 32];
+        hmac_with(hash, key, message.as_bytes(), &mut output);
+        if to_hex(&output) != expected {
+            eprintln!("{}: HMAC vector {} mismatch: expected {}, got {}", name, i, expected, to_hex(&output));
+            failures += 1;
+        }
+    }
+    (failures, TEST_VECTORS.len() + HMAC_VECTORS.len())
+}
 
-        // Reuse preimage buffer across iterations
-        let mut preimage = String::with_capacity(16 + suffix.len());
+/// Difficulty reading over mask shapes and prefix layouts, independent of the hash backend.
+fn selftest_difficulty() -> (usize, usize) {
+    let layouts = [(0, 4, false), (3, 4, true), (0, 8, false), (8, 8, true)];
+    let mut failures = 0;
+    for mask in ["000FFFFF", "00000000", "F0F0F00F", "FFFFFFFF", "0000000000FFFFFF"] {
+        for (offset, width, little_endian) in layouts {
+            let layout = PrefixLayout { offset, width: width.max(mask.len() / 2), little_endian };
+            if let Err(e) = check_difficulty(parse_mask(mask).unwrap(), layout, 32) {
+                eprintln!("difficulty: {} at offset {}: {}", mask, offset, e);
+                failures += 1;
+            }
+        }
+    }
+    (failures, 5 * layouts.len())
+}
 
-        let mut output = vec![0u8;32];
-        while !found.load(Ordering::Acquire) {
-            preimage.clear();
-            use std::fmt::Write;
-            write!(&mut preimage, "{:016x}{}", local_nonce, &suffix).unwrap();
+/// Hashes the target and evaluator checks are read against
+const TARGET_HASHES: [&str; 2] = [
+    "000feab7d5ff6c98154ed5710a8992e70888fb6d094ca27b908f39f8f2b4aa61",
+    "000872225184183fe3f871081555a4e4faf4920927e14435ca731b148ac7c10b",
+];
 
-            // Each hash call allocates ~15-20KB temporarily
-            //let hash_result = hash(preimage.as_bytes(), &rom, 8, 256);
-            let mut hasher = Blake2bVar::new(32).unwrap();
-            //    .hash_length(32)
-            //   .key(&rom)
-            //    .to_state();
+/// Full targets, and the pre-filter mask passing every hash that is within them.
+fn selftest_target() -> (usize, usize) {
+    let hashes = TARGET_HASHES;
+    let targets = [
+        ("000fea", hashes[0], true),
+        ("000fea00", hashes[0], false),
+        ("000872225184183fe3f871081555a4e4faf4920927e14435ca731b148ac7c10b", hashes[1], true),
+        ("000872225184183fe3f871081555a4e4faf4920927e14435ca731b148ac7c10a", hashes[1], false),
+        ("0007", hashes[1], false),
+    ];
+    let mut failures = 0;
+    for (i, &(target, hash, passes)) in targets.iter().enumerate() {
+        let (target, hash) = (parse_target(&format!("{:f<64}", target)).unwrap(), parse_target(hash).unwrap());
+        let prefiltered = hash_structure_good(&hash, prefilter_mask(Mask::MAX, &target, &PrefixLayout::DEFAULT));
+        if meets_target(&hash, Some(&target)) != passes || (passes && !prefiltered) {
+            eprintln!("target: vector {} should be {}", i, passes);
+            failures += 1;
+        }
+    }
+    (failures, targets.len())
+}
 
-            hasher.update(preimage.as_bytes());
-            let mut output = vec![0u8;32];
-            hasher.finalize_variable(&mut output).unwrap();
-            //let hash_result = out;
+/// Each evaluator over the same hashes, and their prefilters passing every hash they accept.
+fn selftest_evaluator() -> (usize, usize) {
+    let [first, second] = TARGET_HASHES;
+    let hashes = [first, second, "0000000000ff41a9c2e6b0f1d3a5c7e9082b4d6f8a1c3e5b7d9f0a2c4e6b8d0f"];
+    let target = |hex: &str| parse_target(&format!("{:f<64}", hex)).unwrap();
+    let masked = |hex: &str| evaluator::Masked { mask: parse_mask(hex).unwrap(), layout: PrefixLayout::DEFAULT };
+    let evaluators: [(Box<dyn DifficultyEvaluator>, [bool; 3]); 10] = [
+        (Box::new(masked("000FFFFF")), [true, true, true]),
+        (Box::new(masked("0007FFFF")), [false, false, true]),
+        (Box::new(evaluator::LeadingBits(12)), [true, true, true]),
+        (Box::new(evaluator::LeadingBits(40)), [false, false, true]),
+        (Box::new(evaluator::LeadingBits(41)), [false, false, false]),
+        (Box::new(evaluator::FullTarget(target("000fea"))), [true, true, true]),
+        (Box::new(evaluator::FullTarget(target(hashes[1]))), [false, true, true]),
+        (
+            Box::new(evaluator::Composite(vec![
+                Box::new(masked("000FFFFF")),
+                Box::new(evaluator::FullTarget(target("0009"))),
+            ])),
+            [false, true, true],
+        ),
+        (
+            Box::new(evaluator::Composite(vec![Box::new(evaluator::LeadingBits(13)), Box::new(masked("0000FFFF"))])),
+            [false, false, true],
+        ),
+        (Box::new(evaluator::Composite(Vec::new())), [true, true, true]),
+    ];
+    let mut failures = 0;
+    for (i, (evaluator, expected)) in evaluators.iter().enumerate() {
+        for (hash, &passes) in hashes.iter().zip(expected) {
+            let hash = parse_target(hash).unwrap();
+            let prefiltered = hash_structure_good(&hash, evaluator.prefilter(&PrefixLayout::DEFAULT));
+            if evaluator.accepts(&hash) != passes || (passes && !prefiltered) {
+                let rule = evaluator.describe();
+                eprintln!("evaluator: vector {} ({}) should be {} for {}", i, rule, passes, to_hex(&hash));
+                failures += 1;
+            }
+        }
+    }
+    let vectors = evaluators.len() * hashes.len();
+    (failures, vectors)
+}
 
-            if hash_structure_good(&output, difficulty_mask) {
-                found.store(true, Ordering::Release);
-                result_nonce.store(local_nonce, Ordering::Release);
-                break;
+/// Receipt signatures, independent of the hash backend: verification always, and signing
+/// where the openssl command is there to do it.
+fn selftest_ed25519() -> (usize, usize) {
+    let openssl = ed25519::public_key(&[0; 32]).map_err(|e| eprintln!("ed25519: checking verification only: {}", e));
+    let mut failures = 0;
+    for (i, &(seed, message, public, signature)) in ED25519_VECTORS.iter().enumerate() {
+        let seed: [u8; 32] = receipt::parse_hex(seed).unwrap().try_into().unwrap();
+        let public: [u8; 32] = receipt::parse_hex(public).unwrap().try_into().unwrap();
+        let signature: [u8; 64] = receipt::parse_hex(signature).unwrap().try_into().unwrap();
+        if !ed25519::verify(&public, message, &signature) || ed25519::verify(&public, b"tampered", &signature) {
+            eprintln!("ed25519: vector {} verification is wrong", i);
+            failures += 1;
+        } else if openssl.is_ok() {
+            // OpenSSL signs no empty message, so that vector only checks the key
+            let signed = (!message.is_empty()).then(|| ed25519::sign(&seed, message).ok());
+            if ed25519::public_key(&seed).ok() != Some(public) || signed.is_some_and(|got| got != Some(signature)) {
+                eprintln!("ed25519: vector {} signed by openssl does not match", i);
+                failures += 1;
             }
+        }
+    }
+    (failures, ED25519_VECTORS.len())
+}
 
-            local_nonce += stride;
+/// Checkpoint request signing.
+fn selftest_sigv4() -> (usize, usize) {
+    let mut failures = 0;
+    for (i, &(message, digest)) in SHA256_VECTORS.iter().enumerate() {
+        let got = to_hex(&s3::sha256(message.as_bytes()));
+        if got != digest {
+            eprintln!("sigv4: sha256 vector {} mismatch: expected {}, got {}", i, digest, got);
+            failures += 1;
         }
-    });
+    }
+    let (secret, date, region, service, key) = SIGV4_KEY_VECTOR;
+    let got = to_hex(&s3::signing_key(secret, date, region, service));
+    if got != key {
+        eprintln!("sigv4: signing key mismatch: expected {}, got {}", key, got);
+        failures += 1;
+    }
+    (failures, SHA256_VECTORS.len() + 1)
+}
+
+/// The WebSocket handshake, independent of the hash backend.
+fn selftest_websocket() -> (usize, usize) {
+    let mut failures = 0;
+    for (i, &(message, digest)) in SHA1_VECTORS.iter().enumerate() {
+        let got = to_hex(&ws::sha1(message.as_bytes()));
+        if got != digest {
+            eprintln!("websocket: SHA-1 vector {} mismatch: expected {}, got {}", i, digest, got);
+            failures += 1;
+        }
+    }
+    let (key, accept) = WS_ACCEPT_VECTOR;
+    if ws::accept_key(key) != accept {
+        eprintln!("websocket: accept key mismatch: expected {}, got {}", accept, ws::accept_key(key));
+        failures += 1;
+    }
+    (failures, SHA1_VECTORS.len() + 1)
+}
+
+/// Preimage formatting, and reading back and swapping in every encoding's nonces.
+fn selftest_preimage() -> (usize, usize) {
+    let mut failures = 0;
+    let [address, challenge_id, difficulty, no_pre_mine, latest_submission, no_pre_mine_hour] = PREIMAGE_FIELDS;
+    let fields = PreimageBuilder {
+        address,
+        challenge_id,
+        difficulty,
+        no_pre_mine,
+        latest_submission,
+        no_pre_mine_hour,
+        salt: None,
+        hex_case: HexCase::AsIs,
+        encoding: NonceEncoding::Hex16,
+    };
+    for (i, &(nonce, encoding, hex_case, salt, expected)) in PREIMAGE_VECTORS.iter().enumerate() {
+        let builder = PreimageBuilder { salt, hex_case, encoding, ..fields };
+        let got = printable(&builder.build(nonce));
+        // The same into a fixed buffer, as `verifier` builds it
+        let mut buffer = [0; 256];
+        let written = verifier::write_preimage(&mut buffer, encoding.verifier(), nonce, builder.suffix().as_bytes());
+        if got != expected || written.map(|len| printable(&buffer[..len])).as_deref() != Some(expected) {
+            eprintln!("preimage: vector {} mismatch: expected {}, got {}", i, expected, got);
+            failures += 1;
+        }
+    }
+    if verifier::write_preimage(&mut [0; 16], NonceEncoding::Hex16.verifier(), 0, b"x").is_some() {
+        eprintln!("preimage: a preimage longer than its buffer was written");
+        failures += 1;
+    }
+    let encodings = NonceEncoding::value_variants();
+    for &encoding in encodings {
+        let builder = PreimageBuilder { encoding, ..fields };
+        let suffix_len = builder.suffix().len();
+        let mut preimage = builder.build(PREIMAGE_NONCES[0]);
+        // Up the list and back down, so every swap is checked growing and shrinking the nonce
+        for &nonce in PREIMAGE_NONCES.iter().chain(PREIMAGE_NONCES.iter().rev()) {
+            encoding.replace(&mut preimage, nonce, suffix_len);
+            let parsed = encoding.parse(&encoding.text(nonce));
+            if preimage != builder.build(nonce) || parsed != Ok(nonce) {
+                eprintln!("preimage: {} nonce {:016x} does not round-trip", encoding.name(), nonce);
+                failures += 1;
+            }
+        }
+    }
+    let vectors = PREIMAGE_VECTORS.len() + encodings.len() * PREIMAGE_NONCES.len() * 2 + 1;
+    (failures, vectors)
+}
+
+/// Solutions round-trip through JSON, and outbox files from before the timestamps still load.
+fn selftest_solution() -> (usize, usize) {
+    let found = std::time::UNIX_EPOCH + Duration::from_millis(1_761_000_000_123);
+    let solution = solution::Solution {
+        found: Some(found),
+        submitted: Some(found + Duration::from_millis(450)),
+        backend: Some("scalar".to_string()),
+        worker_id: Some(identity::current().id.clone()),
+        thread: Some(3),
+        worker_hashes: Some(1 << 40),
+        ..solution::Solution::new(NonceEncoding::Hex16, 0x1816, &[0xab; 32], "**D07 \"x\"\n", PREIMAGE_FIELDS[0])
+    };
+    let legacy = r#"{"nonce": "0000000000001816", "hash": "ab", "address": "a", "challenge_id": "b"}"#;
+    let checks = [
+        solution::Solution::parse(&solution.to_json()).as_ref() == Ok(&solution),
+        solution::Solution::parse(legacy).is_ok_and(|s| s.found.is_none() && s.nonce == "0000000000001816"),
+        solution::Solution::parse(r#"{"hash": "ab", "address": "a", "challenge_id": "b"}"#).is_err(),
+    ];
+    let failures = checks.iter().filter(|ok| !**ok).count();
+    (failures, checks.len())
+}
+
+/// Counters written by a single worker, padded to their own cache line so that
+/// workers never contend on each other's stores.
+#[repr(align(128))]
+pub struct WorkerState {
+    /// Next search index; every worker strides by the number of workers
+    pub cursor: AtomicU64,
+    pub hashes: AtomicU64,
+    /// Nanoseconds this worker has spent in searches, to give it a hashrate of its own
+    pub nanos: AtomicU64,
+}
+
+/// State shared between the workers and whoever drives the search.
+pub struct Progress {
+    pub stop: AtomicBool,
+    /// Workers idle at their next batch boundary while this is set
+    pub paused: AtomicBool,
+    /// Workers with an index at or past this idle the same way, see `ramp`
+    pub active: AtomicUsize,
+    pub solutions: AtomicU64,
+    /// Hashes recomputed by --verify-one-in, and how many of them the backend got wrong
+    pub verified: AtomicU64,
+    pub mismatches: AtomicU64,
+    /// Hashes the backend said pass the difficulty, and how many of them the reference rejected
+    pub candidates: AtomicU64,
+    pub false_positives: AtomicU64,
+    /// Set once the backend made more than `Verify::max_false_positives`: the workers hash
+    /// with the reference from then on
+    pub fallback: AtomicBool,
+    /// Set along with `stop` once the cloud announced it will reclaim the instance
+    pub preempted: AtomicBool,
+    /// Nanoseconds the machine slept that `Instant` counted anyway, see `suspend`
+    pub slept: AtomicU64,
+    pub workers: Vec<WorkerState>,
+}
+
+impl Progress {
+    pub fn new(threads: usize) -> Self {
+        Self::resume((0..threads as u64).collect())
+    }
+
+    pub fn resume(cursors: Vec<u64>) -> Self {
+        Progress {
+            stop: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            active: AtomicUsize::new(usize::MAX),
+            solutions: AtomicU64::new(0),
+            verified: AtomicU64::new(0),
+            mismatches: AtomicU64::new(0),
+            candidates: AtomicU64::new(0),
+            false_positives: AtomicU64::new(0),
+            fallback: AtomicBool::new(false),
+            preempted: AtomicBool::new(false),
+            slept: AtomicU64::new(0),
+            workers: cursors
+                .into_iter()
+                .map(|c| WorkerState { cursor: AtomicU64::new(c), hashes: AtomicU64::new(0), nanos: AtomicU64::new(0) })
+                .collect(),
+        }
+    }
+
+    /// Total hashes so far, summed over the workers by whoever is reporting.
+    pub fn hashes(&self) -> u64 {
+        self.workers.iter().map(|w| w.hashes.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Time since `since`, a moment at or before the start of the search, less what the
+    /// machine slept of it.
+    pub fn awake(&self, since: Instant) -> Duration {
+        since.elapsed().saturating_sub(Duration::from_nanos(self.slept.load(Ordering::Relaxed)))
+    }
+
+    /// Hashes and hashes per second of each worker over the searches it has finished.
+    pub fn worker_stats(&self) -> Vec<(u64, f64)> {
+        self.workers
+            .iter()
+            .map(|w| {
+                let (hashes, nanos) = (w.hashes.load(Ordering::Relaxed), w.nanos.load(Ordering::Relaxed));
+                (hashes, hashes as f64 / (nanos as f64 / 1e9).max(1e-9))
+            })
+            .collect()
+    }
+
+    pub fn cursor_snapshot(&self) -> Vec<u64> {
+        self.workers.iter().map(|w| w.cursor.load(Ordering::Relaxed)).collect()
+    }
+}
+
+/// Overwrite `prefix` (16 bytes) with the lowercase hex nonce, as `write_preimage` formats it
+/// for `NonceEncoding::Hex16`.
+fn encode_nonce(prefix: &mut [u8], nonce: u64) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    for (i, byte) in prefix[..16].iter_mut().enumerate() {
+        *byte = HEX[(nonce >> (60 - 4 * i)) as usize & 0xf];
+    }
+}
+
+/// Everything the workers need to know about what to mine.
+#[derive(Clone, Copy)]
+pub struct Job<'a> {
+    pub suffix: &'a str,
+    pub nonce_encoding: NonceEncoding,
+    pub difficulty_mask: Mask,
+    /// Checked after the mask, on the hashes that pass it
+    pub target: Option<Target>,
+    pub layout: PrefixLayout,
+    pub hash: HashFn,
+    /// Used instead of `hash` for up to `BATCH_LANES` preimages at a time when the backend has it
+    pub hash_batch: Option<BatchFn>,
+    /// Digest bytes `hash` produces
+    pub hash_len: usize,
+    pub order: NonceOrder,
+    pub batch_size: u64,
+    /// Log hashes that would pass with this many fewer required zero bits
+    pub near_miss_bits: Option<u32>,
+    /// Easier difficulties whose matches go to sinks of their own, for --secondary
+    pub secondaries: &'a [secondary::Secondary],
+    /// Workers stop once their index reaches this (exclusive); `u64::MAX` for no limit
+    pub end_index: u64,
+    /// Bring the workers in and out over this long, for --ramp-up
+    pub ramp: Option<Duration>,
+    /// Where sampled phase timings go, for --profile-out
+    pub timings: Option<&'a PhaseTimings>,
+    /// Where `search_range` records the ranges it covered, for --range-log
+    pub range_log: Option<&'a range_log::RangeLog>,
+    /// Recompute candidates and a sample of the hashes; `None` trusts the backend
+    pub verify: Option<Verify>,
+}
+
+/// Hashes recomputed with a reference implementation, to catch a backend or an unstable CPU
+/// that computes wrong hashes: every candidate before it counts as a solution, and with
+/// --verify-one-in a sample of the rest, which is how a missed solution shows up.
+#[derive(Clone, Copy)]
+pub struct Verify {
+    pub reference: HashFn,
+    /// About one hash in this many is recomputed, picked by its nonce
+    pub one_in: Option<u64>,
+    /// Candidates the reference may reject before the backend is dropped for it
+    pub max_false_positives: u64,
+}
+
+/// A passing nonce as reported by the worker that found it.
+#[derive(Clone, Debug)]
+pub struct Find {
+    pub nonce: u64,
+    /// Search index the nonce came from, which orders finds made in the same window
+    pub index: u64,
+    pub hash: Vec<u8>,
+    pub thread_id: usize,
+    /// Hashes this worker computed in the current search, including the winning one
+    pub hashes_tried: u64,
+    /// Time from the start of the search to the find
+    pub elapsed: Duration,
+}
+
+/// Mine one strided nonce stream per cursor until a solution is found, `progress.stop`
+/// is set (and with `job.ramp`, the workers have ramped down), or every cursor has reached
+/// `job.end_index`.
+///
+/// Returns every solution, the canonical one first: more than one means several workers
+/// found a nonce before they saw the stop flag, and which of them reported first is down to
+/// timing, so they are ordered by search index instead and the rest are kept as alternates.
+/// Fails if a worker thread cannot start or a hash backend panics; the other workers are
+/// stopped then.
+pub fn search(job: &Job, progress: &Progress) -> Result<Vec<Find>, PortocriptoError> {
+    let Job {
+        suffix, nonce_encoding, difficulty_mask, target, layout, hash, hash_batch, hash_len, order, batch_size,
+        near_miss_bits, secondaries, end_index, ramp, timings, verify, ..
+    } = *job;
+    let target = target.as_ref();
+    let difficulty_mask = target.map_or(difficulty_mask, |t| prefilter_mask(difficulty_mask, t, &layout));
+    let near_miss_mask = near_miss_bits.map(|bits| relax_mask(difficulty_mask, bits));
+    let secondary_mask = secondary::shared_mask(secondaries);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let started = Instant::now();
+    let threads = progress.workers.len();
+    let finished = AtomicBool::new(false);
+    if ramp.is_some() {
+        progress.active.store(1, Ordering::Release);
+    }
+
+    let (failed, stopped_by) = std::thread::scope(|scope| {
+        if let Some(duration) = ramp {
+            let finished = &finished;
+            scope.spawn(move || ramp::run(progress, duration, finished));
+        }
+        let mut workers = Vec::with_capacity(threads);
+        let mut failed = None;
+        for thread_id in 0..threads {
+            let sender = sender.clone();
+            let builder = std::thread::Builder::new().name(format!("worker-{}", thread_id));
+            let spawned = builder.spawn_scoped(scope, move || {
+                // Only this thread writes its slot, so plain stores of the local counts replace fetch_add
+                let worker = &progress.workers[thread_id];
+                // Starts from the worker's cursor, so a restart repeats at most the batch that panicked
+                let mine = || {
+                    let mut local_index = worker.cursor.load(Ordering::Relaxed);
+                    let mut local_hashes = worker.hashes.load(Ordering::Relaxed);
+                    let first_hash = local_hashes;
+                    let stride = threads as u64;
+                    let kill_index = chaos::kill_index(thread_id, local_index, stride);
+                    let limit = end_index.min(kill_index);
+
+                    // One preimage buffer per lane with the suffix in place; only the nonce digits
+                    // change per hash. Without a batch entry point there is a single lane.
+                    let lanes = if hash_batch.is_some() { BATCH_LANES } else { 1 };
+                    // Whole batches between stop-flag checks, so every call gets all its lanes
+                    let batch_size = batch_size.div_ceil(lanes as u64) * lanes as u64;
+                    let mut preimage = Vec::with_capacity(20 + suffix.len());
+                    write_preimage(&mut preimage, 0, suffix, nonce_encoding);
+                    let mut preimages = vec![preimage; lanes];
+                    let (mut nonces, mut indices) = ([0u64; BATCH_LANES], [0u64; BATCH_LANES]);
+
+                    let mut outputs = [0u8; 64 * BATCH_LANES];
+                    let (mut expected, mut confirmed) = ([0u8; 64], [0u8; 64]);
+                    // Out of the ramp at or past `active`; still in it after a stop while below it
+                    let in_ramp = || thread_id < progress.active.load(Ordering::Acquire);
+                    'search: while !progress.stop.load(Ordering::Acquire) || (ramp.is_some() && in_ramp()) {
+                        if progress.paused.load(Ordering::Acquire) || !in_ramp() {
+                            std::thread::sleep(Duration::from_millis(10));
+                            continue;
+                        }
+                        let mut done = 0;
+                        while done < batch_size {
+                            let sampled = timings
+                                .filter(|_| {
+                                    (local_hashes + done) / profiling::SAMPLE_INTERVAL
+                                        != (local_hashes + done + lanes as u64) / profiling::SAMPLE_INTERVAL
+                                })
+                                .map(|t| (t, Instant::now()));
+                            let mut filled = 0;
+                            while filled < lanes && done + (filled as u64) < batch_size && local_index < limit {
+                                (nonces[filled], indices[filled]) = (order.nonce(local_index), local_index);
+                                nonce_encoding.replace(&mut preimages[filled], nonces[filled], suffix.len());
+                                local_index += stride;
+                                filled += 1;
+                            }
+                            let encoded = sampled.map(|_| Instant::now());
+
+                            let outputs = &mut outputs[..filled * hash_len];
+                            // Once the backend is dropped for its false positives, the reference hashes
+                            let fallback = verify.filter(|_| progress.fallback.load(Ordering::Relaxed));
+                            let (hash, hash_batch) = fallback.map_or((hash, hash_batch), |v| (v.reference, None));
+                            match hash_batch {
+                                Some(hash_batch) if filled > 0 => {
+                                    let mut refs = [&[][..]; BATCH_LANES];
+                                    for (lane, preimage) in refs.iter_mut().zip(&preimages) {
+                                        *lane = preimage;
+                                    }
+                                    hash_batch(&refs[..filled], outputs);
+                                }
+                                _ => {
+                                    for (preimage, output) in preimages.iter().zip(outputs.chunks_exact_mut(hash_len)) {
+                                        hash(preimage, output);
+                                    }
+                                }
+                            }
+                            let hashed = sampled.map(|_| Instant::now());
+
+                            for (lane, output) in outputs.chunks_exact(hash_len).enumerate() {
+                                let nonce = nonces[lane];
+                                let picked = |v: &Verify| v.one_in.is_some_and(|n| splitmix64(nonce).is_multiple_of(n));
+                                let sample = verify.filter(picked);
+                                let output = match sample {
+                                    Some(verify) => {
+                                        let expected = &mut expected[..hash_len];
+                                        (verify.reference)(&preimages[lane], expected);
+                                        progress.verified.fetch_add(1, Ordering::Relaxed);
+                                        if *expected != *output {
+                                            progress.mismatches.fetch_add(1, Ordering::Relaxed);
+                                            eprintln!(
+                                                "verify: worker {} hashed nonce {:016x} to {}, the reference to {}",
+                                                thread_id,
+                                                nonce,
+                                                to_hex(output),
+                                                to_hex(expected)
+                                            );
+                                        }
+                                        // The reference decides, so a solution the backend got wrong is kept
+                                        &*expected
+                                    }
+                                    None => output,
+                                };
+                                let prefix = layout.prefix(output);
+                                if !secondaries.is_empty() && hash_structure_good(&prefix, secondary_mask) {
+                                    secondary::check(secondaries, &prefix, nonce, output);
+                                }
+                                if hash_structure_good(&prefix, difficulty_mask) && meets_target(output, target) {
+                                    // A sampled hash is the reference's already
+                                    let output = match verify.filter(|_| sample.is_none()) {
+                                        Some(verify) => {
+                                            let confirmed = &mut confirmed[..hash_len];
+                                            (verify.reference)(&preimages[lane], confirmed);
+                                            if !confirm(&verify, job, progress, thread_id, nonce, output, confirmed) {
+                                                continue;
+                                            }
+                                            &*confirmed
+                                        }
+                                        None => {
+                                            progress.candidates.fetch_add(1, Ordering::Relaxed);
+                                            output
+                                        }
+                                    };
+                                    let tried = done + lane as u64 + 1;
+                                    let solution = Find {
+                                        nonce: nonces[lane],
+                                        index: indices[lane],
+                                        hash: output.to_vec(),
+                                        thread_id,
+                                        hashes_tried: local_hashes + tried - first_hash,
+                                        elapsed: progress.awake(started),
+                                    };
+                                    // The receiver outlives the workers, so this cannot fail
+                                    sender.send(solution).unwrap();
+                                    worker.hashes.store(local_hashes + tried, Ordering::Relaxed);
+                                    progress.solutions.fetch_add(1, Ordering::Relaxed);
+                                    if let Some(delay) = chaos::stop_delay(nonces[lane]) {
+                                        std::thread::sleep(delay);
+                                    }
+                                    progress.stop.store(true, Ordering::Release);
+                                    break 'search;
+                                }
+                                if near_miss_mask.is_some_and(|mask| hash_structure_good(&prefix, mask)) {
+                                    let off = bits_off_target(&prefix, difficulty_mask);
+                                    eprintln!(
+                                        "near miss: nonce {:016x} hash {} ({} bits off)",
+                                        nonces[lane],
+                                        to_hex(output),
+                                        off
+                                    );
+                                }
+                            }
+                            if let (Some((timings, start)), Some(encoded), Some(hashed)) = (sampled, encoded, hashed) {
+                                let per_hash = filled.max(1) as u32;
+                                timings.record(
+                                    (encoded - start) / per_hash,
+                                    (hashed - encoded) / per_hash,
+                                    hashed.elapsed() / per_hash,
+                                );
+                            }
+                            done += filled as u64;
+
+                            if local_index >= limit && done < batch_size {
+                                #[cfg(debug_assertions)]
+                                if local_index < end_index {
+                                    panic!("chaos: killed worker at index {}", local_index);
+                                }
+                                worker.hashes.store(local_hashes + done, Ordering::Relaxed);
+                                worker.cursor.store(local_index, Ordering::Relaxed);
+                                break 'search;
+                            }
+                        }
+                        local_hashes += batch_size;
+                        worker.hashes.store(local_hashes, Ordering::Relaxed);
+                        worker.cursor.store(local_index, Ordering::Relaxed);
+                    }
+                };
+                let worker_started = Instant::now();
+                for restart in 1.. {
+                    let Err(payload) = std::panic::catch_unwind(AssertUnwindSafe(mine)) else {
+                        break;
+                    };
+                    if restart > MAX_WORKER_RESTARTS {
+                        // Stop the other workers too; `join` below returns the panic
+                        progress.stop.store(true, Ordering::Release);
+                        std::panic::resume_unwind(payload);
+                    }
+                    eprintln!(
+                        "worker {} panicked: {}; restarting it at index {} ({}/{})",
+                        thread_id,
+                        panic_message(&*payload),
+                        worker.cursor.load(Ordering::Relaxed),
+                        restart,
+                        MAX_WORKER_RESTARTS
+                    );
+                }
+                worker.nanos.fetch_add(progress.awake(worker_started).as_nanos() as u64, Ordering::Relaxed);
+            });
+            match spawned {
+                Ok(handle) => workers.push(handle),
+                Err(e) => {
+                    progress.stop.store(true, Ordering::Release);
+                    failed = Some(format!("failed to start worker thread {} of {}: {}", thread_id, threads, e));
+                    break;
+                }
+            }
+        }
+        let mut stopped_by = None;
+        for (thread_id, handle) in workers.into_iter().enumerate() {
+            if let Err(payload) = handle.join() {
+                stopped_by.get_or_insert(format!("worker {} panicked: {}", thread_id, panic_message(&*payload)));
+            }
+        }
+        finished.store(true, Ordering::Release);
+        (failed, stopped_by)
+    });
+
+    drop(sender);
+    match stopped_by.or(failed) {
+        Some(msg) => Err(PortocriptoError::Backend(msg)),
+        None => {
+            let mut solutions: Vec<Find> = receiver.into_iter().collect();
+            solutions.sort_by_key(|s| s.index);
+            Ok(solutions)
+        }
+    }
+}
+
+/// Whether a candidate the backend hashed to `output` passes with the reference's hash
+/// `confirmed` too. A false positive is logged, and past the budget drops the backend.
+fn confirm(
+    verify: &Verify,
+    job: &Job,
+    progress: &Progress,
+    thread_id: usize,
+    nonce: u64,
+    output: &[u8],
+    confirmed: &[u8],
+) -> bool {
+    let candidates = progress.candidates.fetch_add(1, Ordering::Relaxed) + 1;
+    if verifier::accepts(confirmed, job.difficulty_mask, &job.layout, job.target.as_ref()) {
+        return true;
+    }
+    let rejected = progress.false_positives.fetch_add(1, Ordering::Relaxed) + 1;
+    eprintln!(
+        "confirm: worker {} hashed nonce {:016x} to {}, but the reference to {}; discarding it",
+        thread_id,
+        nonce,
+        to_hex(output),
+        to_hex(confirmed)
+    );
+    if rejected > verify.max_false_positives && !progress.fallback.swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "confirm: {} of {} candidates were false; hashing with the portable blake2b from now on",
+            rejected, candidates
+        );
+    }
+    false
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| payload.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic")
+}
+
+/// `challenge_id` with everything but ASCII letters, digits and `-` replaced by `_`.
+fn file_name_safe(challenge_id: &str) -> String {
+    challenge_id.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+/// Where files a run keeps for itself go unless a flag names them: PORTOCRIPTO_STATE_DIR, else
+/// $XDG_STATE_HOME/portocripto, ~/.local/state/portocripto or %LOCALAPPDATA%\portocripto. Created on use.
+fn state_dir() -> PathBuf {
+    let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let dir = var("PORTOCRIPTO_STATE_DIR")
+        .or_else(|| var("XDG_STATE_HOME").map(|d| d.join("portocripto")))
+        .or_else(|| var("HOME").map(|d| d.join(".local/state/portocripto")))
+        .or_else(|| var("LOCALAPPDATA").map(|d| d.join("portocripto")))
+        .unwrap_or_else(|| std::env::temp_dir().join("portocripto"));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        eprintln!("failed to create state directory {}: {}", dir.display(), e);
+    }
+    dir
+}
+
+/// An empty directory of its own for a test, under the temp dir.
+#[cfg(test)]
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("portocripto-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Per-challenge lock file, held with an OS file lock that is released when the process exits, however it exits.
+pub struct InstanceLock {
+    _file: std::fs::File,
+}
+
+impl InstanceLock {
+    /// Take the lock for `challenge_id`; the file holds the owner's pid for the error message.
+    pub fn acquire(challenge_id: &str) -> Result<Self, String> {
+        let path = std::env::temp_dir().join(format!("portocripto-{}.lock", file_name_safe(challenge_id)));
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| format!("failed to open lock {}: {}", path.display(), e))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                let owner = std::fs::read_to_string(&path).ok().map(|s| s.trim().to_string()).unwrap_or_default();
+                let owner = if owner.is_empty() { "another process".to_string() } else { format!("pid {}", owner) };
+                return Err(format!(
+                    "challenge {} is already being mined by {} (lock {})",
+                    challenge_id,
+                    owner,
+                    path.display()
+                ));
+            }
+            Err(std::fs::TryLockError::Error(e)) => {
+                return Err(format!("failed to lock {}: {}", path.display(), e));
+            }
+        }
+        let _ = file.set_len(0).and_then(|()| writeln!(file, "{}", std::process::id()));
+        Ok(InstanceLock { _file: file })
+    }
+}
+
+/// On-disk record of an interrupted run, keyed by the preimage suffix it was mining.
+pub struct Journal {
+    pub job: String,
+    pub cursors: Vec<u64>,
+    pub solution: Option<u64>,
+    /// When the hour window the job was mined in closed, if it had one
+    pub window_closes: Option<i64>,
+}
+
+/// Read the journal for `job`; a missing, corrupt or foreign journal, or one from a window
+/// that has closed, yields `None`.
+fn read_journal(path: &Path, job: &str) -> Option<Journal> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let mut journal = Journal { job: String::new(), cursors: Vec::new(), solution: None, window_closes: None };
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim();
+        match key.trim() {
+            "job" => journal.job = value.to_string(),
+            "cursors" => journal.cursors = value.split(',').map(|c| c.trim().parse()).collect::<Result<_, _>>().ok()?,
+            "solution" => journal.solution = Some(u64::from_str_radix(value, 16).ok()?),
+            "window" => journal.window_closes = Some(value.parse().ok()?),
+            _ => {}
+        }
+    }
+
+    if journal.job != job {
+        eprintln!("journal: {} belongs to another job, starting fresh", path.display());
+        return None;
+    }
+    if journal.window_closes.is_some_and(|closes| closes <= unix_now()) {
+        eprintln!("journal: {} is from a window that has closed, starting fresh", path.display());
+        return None;
+    }
+    if journal.cursors.is_empty() && journal.solution.is_none() {
+        return None;
+    }
+    Some(journal)
+}
+
+/// Replace the journal atomically so a crash mid-write never leaves a truncated file.
+fn write_journal(path: &Path, journal: &Journal) -> std::io::Result<()> {
+    let mut text = format!("# portocripto work journal\njob = {}\n", journal.job);
+    let cursors: Vec<String> = journal.cursors.iter().map(|c| c.to_string()).collect();
+    text.push_str(&format!("cursors = {}\n", cursors.join(",")));
+    if let Some(nonce) = journal.solution {
+        text.push_str(&format!("solution = {:016x}\n", nonce));
+    }
+    if let Some(closes) = journal.window_closes {
+        text.push_str(&format!("window = {}\n", closes));
+    }
+
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)
+}
+
+fn journal_progress(path: &Path, job: &str, window_closes: Option<i64>, progress: &Progress) {
+    while !wait_for_stop(&progress.stop, JOURNAL_INTERVAL) {
+        let cursors = progress.cursor_snapshot();
+        let journal = Journal { job: job.to_string(), cursors, solution: None, window_closes };
+        if let Err(e) = write_journal(path, &journal) {
+            eprintln!("journal: failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Parse an RFC 3339 timestamp such as `2025-10-30T12:00:00.000Z` into Unix seconds.
+pub fn parse_rfc3339(s: &str) -> Option<i64> {
+    let (date, time) = s.trim().split_once(['T', 't', ' '])?;
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+
+    let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => return None,
+    };
+    let mut clock_parts = clock.splitn(3, ':');
+    let hour: i64 = clock_parts.next()?.parse().ok()?;
+    let minute: i64 = clock_parts.next()?.parse().ok()?;
+    let second: i64 = clock_parts.next()?.split('.').next()?.parse().ok()?;
+    let offset_secs = match offset {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (h, m) = offset[1..].split_once(':')?;
+            sign * (h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60)
+        }
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some(days * 86400 + hour * 3600 + minute * 60 + second - offset_secs)
+}
+
+/// When solutions for `challenge` stop being accepted: the end of the hour named by its
+/// `no_pre_mine_hour`, if that is an RFC 3339 timestamp.
+fn window_closes(challenge: &Challenge) -> Option<i64> {
+    parse_rfc3339(&challenge.no_pre_mine_hour).map(|hour| hour - hour.rem_euclid(WINDOW_SECS) + WINDOW_SECS)
+}
+
+pub fn unix_now() -> i64 {
+    if let Some(now) = sim::now() {
+        return now;
+    }
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// When `path` was last modified, if its metadata can be read.
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Poll `path` until it is modified after `since` and parses as JSON again, so a file caught
+/// halfway through a rewrite is not picked up. Returns false if `stop` is set first.
+fn wait_for_change(path: &Path, since: Option<std::time::SystemTime>, stop: &AtomicBool) -> bool {
+    while !wait_for_stop(stop, WATCH_INTERVAL) {
+        let complete = || std::fs::read_to_string(path).is_ok_and(|text| json::parse(&text).is_ok());
+        if modified(path) != since && complete() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Log how much of the window closing at `closes` is left every `REPORT_INTERVAL`, warning
+/// once it is less than the expected time to a solution, and stop the search when it closes.
+/// Returns whether it did.
+fn count_down(closes: i64, expected: f64, progress: &Progress) -> bool {
+    let started = Instant::now();
+    let mut warned = false;
+    loop {
+        let left = closes - unix_now();
+        if left <= 0 {
+            eprintln!("window: the no_pre_mine_hour window closed; stopping, no more solutions accepted");
+            progress.stop.store(true, Ordering::Release);
+            return true;
+        }
+        if wait_for_stop(&progress.stop, REPORT_INTERVAL.min(Duration::from_secs(left as u64))) {
+            return false;
+        }
+        let left = (closes - unix_now()) as f64;
+        let rate = progress.hashes() as f64 / progress.awake(started).as_secs_f64();
+        let eta = expected / rate.max(1e-9);
+        if left > 0.0 {
+            eprintln!("window: {} left, expected time to a solution {}", format_duration(left), format_duration(eta));
+        }
+        if left > 0.0 && eta > left && !warned {
+            eprintln!("window: warning: less time is left than a solution is expected to take; consider stopping");
+            warned = true;
+        }
+    }
+}
+
+/// On waking from a sleep, stop the search if the window closing at `closes` closed while the
+/// machine slept, rather than mine a round that is over until `count_down` next looks.
+fn recheck_window(closes: Option<i64>, progress: &Progress, window_closed: &AtomicBool) {
+    let Some(closes) = closes else {
+        eprintln!("suspend: resuming");
+        return;
+    };
+    let left = closes - unix_now();
+    if left <= 0 {
+        eprintln!("suspend: the no_pre_mine_hour window closed while asleep; stopping, no more solutions accepted");
+        window_closed.store(true, Ordering::Release);
+        progress.stop.store(true, Ordering::Release);
+    } else {
+        eprintln!("suspend: {} of the window left; resuming", format_duration(left as f64));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pause {
+    Schedule,
+    InUse,
+}
+
+/// Pause or resume the workers for the current time and, with --only-when-idle, for whether
+/// the machine is in use; logs each change from `last`, the previous call's reason.
+fn apply_pause(schedule: Option<&Schedule>, idle_minutes: Option<u64>, progress: &Progress, last: &mut Option<Pause>) {
+    // Unknown idle time counts as in use
+    let in_use = |minutes| idle::idle_secs().is_none_or(|secs| secs < minutes * 60);
+    let pause = if schedule.is_some_and(|s| !s.active_now()) {
+        Some(Pause::Schedule)
+    } else if idle_minutes.is_some_and(in_use) {
+        Some(Pause::InUse)
+    } else {
+        None
+    };
+    progress.paused.store(pause.is_some(), Ordering::Release);
+    if pause != *last {
+        match (pause, *last) {
+            (Some(Pause::Schedule), _) => eprintln!("schedule: outside mining hours, pausing"),
+            (Some(Pause::InUse), _) => eprintln!("idle: the machine is in use, pausing"),
+            (None, Some(Pause::Schedule)) => eprintln!("schedule: mining window open, resuming"),
+            (None, _) => eprintln!("idle: no input for {} minutes, resuming", idle_minutes.unwrap_or(0)),
+        }
+        *last = pause;
+    }
+}
+
+/// Start over with the same command line, which rereads the --watch file.
+fn restart_process() -> ! {
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from(std::env::args_os().next().unwrap_or_default()));
+    let mut command = std::process::Command::new(exe);
+    command.args(std::env::args_os().skip(1));
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let e = command.exec();
+        eprintln!("watch: failed to restart: {}", e);
+        std::process::exit(1);
+    }
+    #[cfg(not(unix))]
+    match command.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("watch: failed to restart: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Sleep for up to `duration`, returning early (with `true`) once `stop` is set.
+fn wait_for_stop(stop: &AtomicBool, duration: Duration) -> bool {
+    if let Some(stopped) = sim::wait(stop, duration) {
+        return stopped;
+    }
+    let deadline = Instant::now() + duration;
+    while !stop.load(Ordering::Acquire) {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        std::thread::sleep((deadline - now).min(Duration::from_millis(100)));
+    }
+    true
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The reason phrase for an HTTP status the servers here answer with.
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn http_post_json(url: &str, body: &str) -> std::io::Result<String> {
+    http_request("POST", url, None, body)
+}
+
+/// Minimal HTTP/1.1 request over a plain TCP connection, through a proxy if one is
+/// configured (see `proxy`), returning the response body; fails on a non-2xx status, with
+/// `ErrorKind::InvalidData` for a 4xx the server meant. Connection errors and 5xx replies
+/// are retried as the `retry` policy says.
+fn http_request(method: &str, url: &str, bearer: Option<&str>, body: &str) -> std::io::Result<String> {
+    let authorization = bearer.map_or(String::new(), |t| format!("Authorization: Bearer {}\r\n", t));
+    http_send(method, url, &format!("{}Content-Type: application/json\r\n", authorization), body)
+}
+
+/// `http_request` with `headers` (CRLF-terminated lines) sent as they are.
+fn http_send(method: &str, url: &str, headers: &str, body: &str) -> std::io::Result<String> {
+    use std::io::{Error, ErrorKind};
+
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "only http:// URLs are supported"))?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+
+    let proxy = proxy::for_target(&addr, false);
+    retry::retry(&format!("http {}", host), || {
+        let (mut stream, target, proxy_authorization) = match &proxy {
+            Some(p) => (
+                proxy::open(p)?,
+                format!("http://{}{}", host, path),
+                p.authorization.as_ref().map_or(String::new(), |a| format!("Proxy-Authorization: {}\r\n", a)),
+            ),
+            None => (retry::connect(&addr)?, path.to_string(), String::new()),
+        };
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\n{}{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            target,
+            host,
+            proxy_authorization,
+            headers,
+            body.len(),
+            body
+        )?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = response.split_whitespace().nth(1).unwrap_or("");
+        let answered = format!("server answered {:?}", response.lines().next().unwrap_or(""));
+        match status.as_bytes().first() {
+            Some(b'2') => Ok(response.split_once("\r\n\r\n").map_or("", |(_, body)| body).to_string()),
+            // The server meant it, except for a timeout or rate limit
+            Some(b'4') if status != "408" && status != "429" => Err(Error::new(ErrorKind::InvalidData, answered)),
+            _ => Err(Error::other(answered)),
+        }
+    })
+}
+
+/// A nonce range leased from a `range-server`.
+struct RangeLease {
+    id: u64,
+    start: u64,
+    end: u64,
+    expires_in: u64,
+}
+
+/// A `range-server`'s answer to a lease request.
+enum LeaseReply {
+    Range(RangeLease),
+    /// Another miner's nonce already solved the challenge
+    Solved(u64),
+}
+
+/// How a miner talks to its `range-server`: JSON over HTTP, or `wire` frames over one connection.
+enum RangeLink {
+    Http(String),
+    Wire(Mutex<wire::Client>),
+}
+
+impl RangeLink {
+    fn new(url: &str) -> Result<RangeLink, PortocriptoError> {
+        let url = url.trim_end_matches('/');
+        if url.starts_with("tcp://") {
+            Ok(RangeLink::Wire(Mutex::new(wire::Client::new(url)?)))
+        } else {
+            Ok(RangeLink::Http(url.to_string()))
+        }
+    }
+
+    fn wire(client: &Mutex<wire::Client>, message: wire::Message) -> std::io::Result<wire::Message> {
+        client.lock().unwrap_or_else(|e| e.into_inner()).request(&message)
+    }
+
+    fn lease(&self) -> Result<LeaseReply, PortocriptoError> {
+        let server = match self {
+            RangeLink::Http(server) => server,
+            RangeLink::Wire(client) => {
+                let worker = identity::current();
+                let message = wire::Message::Lease { worker_id: worker.id.clone(), worker: worker.name.clone() };
+                return match RangeLink::wire(client, message)? {
+                    wire::Message::Leased { id, start, end, expires_in } => {
+                        Ok(LeaseReply::Range(RangeLease { id, start, end, expires_in }))
+                    }
+                    wire::Message::Solved { nonce } => Ok(LeaseReply::Solved(nonce)),
+                    wire::Message::Exhausted => Err(std::io::Error::other("nonce space exhausted").into()),
+                    other => Err(PortocriptoError::Parse(format!("unexpected lease reply {:?}", other))),
+                };
+            }
+        };
+        let worker = identity::current();
+        let request =
+            format!("{{\"worker_id\": {}, \"worker\": {}}}", json_string(&worker.id), json_string(&worker.name));
+        let body = http_post_json(&format!("{}/lease", server), &request)?;
+        let reply = json::parse(&body).map_err(PortocriptoError::Parse)?;
+        let hex = |key: &str| {
+            reply.get(key).and_then(json::Json::as_str).and_then(|v| u64::from_str_radix(v, 16).ok())
+        };
+        let number = |key: &str| reply.get(key).and_then(json::Json::as_f64).map(|v| v as u64);
+        if let Some(nonce) = hex("solved") {
+            return Ok(LeaseReply::Solved(nonce));
+        }
+        match (number("id"), hex("start"), hex("end"), number("expires_in")) {
+            (Some(id), Some(start), Some(end), Some(expires_in)) => {
+                Ok(LeaseReply::Range(RangeLease { id, start, end, expires_in }))
+            }
+            _ => Err(PortocriptoError::Parse(format!("unexpected lease reply {:?}", body))),
+        }
+    }
+
+    /// `/renew/N`, reporting the hashrate and how many of the lease's nonces are searched;
+    /// returns the winning nonce once another miner has solved the challenge.
+    fn renew(&self, id: u64, hashrate: u64, covered: u64) -> std::io::Result<Option<u64>> {
+        let body = format!("{{\"hashrate\": {}, \"covered\": \"{:x}\"}}", hashrate, covered);
+        self.send(id, "renew", &body, wire::Message::Renew { id, hashrate, covered })
+    }
+
+    /// `/release/N`: hand the nonces past `covered` back for reissue.
+    fn release(&self, id: u64, covered: u64) -> std::io::Result<Option<u64>> {
+        let body = format!("{{\"covered\": \"{:x}\"}}", covered);
+        self.send(id, "release", &body, wire::Message::Release { id, covered })
+    }
+
+    /// `/done/N` with the solution, if any; returns the winning nonce if it is not ours.
+    fn done(&self, id: u64, nonce: Option<u64>) -> std::io::Result<Option<u64>> {
+        let body = nonce.map_or("{}".to_string(), |n| format!("{{\"nonce\": \"{:016x}\"}}", n));
+        self.send(id, "done", &body, wire::Message::Done { id, nonce })
+    }
+
+    fn send(&self, id: u64, route: &str, body: &str, message: wire::Message) -> std::io::Result<Option<u64>> {
+        match self {
+            RangeLink::Http(server) => http_post_json(&format!("{}/{}/{}", server, route, id), body).map(|reply| {
+                let reply = json::parse(&reply).ok();
+                let solved = reply.as_ref().and_then(|r| r.get("solved")).and_then(json::Json::as_str);
+                solved.and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            }),
+            RangeLink::Wire(client) => match RangeLink::wire(client, message)? {
+                wire::Message::Ok => Ok(None),
+                wire::Message::Solved { nonce } => Ok(Some(nonce)),
+                _ => Err(std::io::Error::other("unknown or expired lease")),
+            },
+        }
+    }
+}
+
+/// Search the nonces `start..end` with every worker, strided as usual.
+fn search_range(job: &Job, progress: &Progress, start: u64, end: u64) -> Result<Vec<Find>, PortocriptoError> {
+    for (t, worker) in progress.workers.iter().enumerate() {
+        worker.cursor.store(start.saturating_add(t as u64), Ordering::Relaxed);
+    }
+    let solutions = search(&Job { end_index: end, ..*job }, progress);
+    if let Some(log) = job.range_log {
+        log.record(start, progress.cursor_snapshot().into_iter().min().unwrap_or(start).min(end));
+    }
+    solutions
+}
+
+/// Mine chunks claimed with INCRBY on a shared Redis counter; the first SET NX of the solution key wins.
+///
+/// Keys live under `portocripto:<challenge_id>:<address>`; solutions are also PUBLISHed on
+/// `<prefix>:solutions`. Chunks of a worker that dies are not reissued. Losing the Redis
+/// connection ends mining without a solution; only backend failures are returned as errors.
+fn mine_redis_chunks(
+    url: &str,
+    challenge: &Challenge,
+    chunk: u64,
+    job: &Job,
+    progress: &Progress,
+) -> Result<Vec<Find>, PortocriptoError> {
+    let prefix = format!("portocripto:{}:{}", challenge.challenge_id, challenge.address);
+    let (next_key, solution_key) = (format!("{}:next", prefix), format!("{}:solution", prefix));
+    let chunk_arg = chunk.max(1).to_string();
+
+    let solutions = (|| -> Result<Vec<Find>, PortocriptoError> {
+        let mut redis = redis::Redis::connect(url)?;
+        loop {
+            if let redis::Reply::Bulk(Some(other)) = redis.command(&["GET", &solution_key])? {
+                eprintln!("redis: solution {} already published by another worker", other);
+                return Ok(Vec::new());
+            }
+            let end = match redis.command(&["INCRBY", &next_key, &chunk_arg])? {
+                redis::Reply::Integer(end) => end as u64,
+                other => return Err(PortocriptoError::Parse(format!("unexpected INCRBY reply {:?}", other))),
+            };
+            let start = end.saturating_sub(chunk.max(1));
+            eprintln!("redis: claimed {:016x}..{:016x}", start, end);
+
+            let solutions = search_range(job, progress, start, end)?;
+            if let Some(solution) = solutions.first() {
+                let nonce_hex = format!("{:016x}", solution.nonce);
+                if let redis::Reply::Bulk(None) = redis.command(&["SET", &solution_key, &nonce_hex, "NX"])? {
+                    eprintln!("redis: another worker published a solution first");
+                }
+                redis.command(&["PUBLISH", &format!("{}:solutions", prefix), &nonce_hex])?;
+                return Ok(solutions);
+            }
+            if progress.stop.load(Ordering::Acquire) {
+                return Ok(solutions);
+            }
+        }
+    })()
+    .or_else(|e| match e {
+        PortocriptoError::Backend(_) => Err(e),
+        e => {
+            eprintln!("redis: {}", e);
+            Ok(Vec::new())
+        }
+    });
+    progress.stop.store(true, Ordering::Release);
+    solutions
+}
+
+/// Mine leased ranges one after another until a solution is found or no server answers.
+///
+/// `servers` lists URLs in order of preference. A lease is taken from the current server,
+/// or failing that from the others in order; while on a backup, the first server is tried
+/// again every `FAILBACK_INTERVAL`. A lease in progress is mined to its end even if its
+/// server goes away, since any solution in it is still valid. A lease cut short by a
+/// preemption notice is handed back with `release`, or else left to expire.
+fn mine_leases(
+    servers: &str,
+    job: &Job,
+    progress: &Progress,
+    release: bool,
+) -> Result<Vec<Find>, PortocriptoError> {
+    let urls: Vec<&str> = servers.split(',').map(str::trim).filter(|url| !url.is_empty()).collect();
+    let links = urls.iter().map(|url| RangeLink::new(url)).collect::<Result<Vec<_>, _>>()?;
+    let (mut current, mut failback_at) = (0, Instant::now());
+    let solutions = loop {
+        let first = if current > 0 && Instant::now() >= failback_at { 0 } else { current };
+        if first != current {
+            failback_at = Instant::now() + FAILBACK_INTERVAL;
+        }
+        let leased = (0..links.len()).map(|k| (first + k) % links.len()).find_map(|i| match links[i].lease() {
+            Ok(lease) => Some((i, lease)),
+            Err(e) => {
+                eprintln!("range-server {}: failed to lease a range: {}", urls[i], e);
+                None
+            }
+        });
+        let (i, lease) = match leased {
+            Some((i, LeaseReply::Range(lease))) => (i, lease),
+            Some((i, LeaseReply::Solved(nonce))) => {
+                eprintln!("range-server {}: another miner already solved the challenge with {:016x}", urls[i], nonce);
+                break Ok(Vec::new());
+            }
+            None => break Ok(Vec::new()),
+        };
+        if i != current {
+            eprintln!("range-server: {} {}", if i == 0 { "back on" } else { "failing over to" }, urls[i]);
+            if current == 0 {
+                failback_at = Instant::now() + FAILBACK_INTERVAL;
+            }
+            current = i;
+        }
+        let server = &links[i];
+        eprintln!("range-server: lease {} covers {:016x}..{:016x}", lease.id, lease.start, lease.end);
+        let lease_done = AtomicBool::new(false);
+        let solutions = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let interval = Duration::from_secs((lease.expires_in / 3).max(1));
+                let (mut last_time, mut last_hashes) = (Instant::now(), progress.hashes());
+                while !wait_for_stop(&lease_done, interval) {
+                    let (now, hashes) = (Instant::now(), progress.hashes());
+                    let hashrate = (hashes - last_hashes) as f64 / (now - last_time).as_secs_f64();
+                    (last_time, last_hashes) = (now, hashes);
+                    let cursor = progress.cursor_snapshot().into_iter().min().unwrap_or(lease.start);
+                    let covered = cursor.clamp(lease.start, lease.end) - lease.start;
+                    match server.renew(lease.id, hashrate as u64, covered) {
+                        Ok(Some(nonce)) => {
+                            eprintln!("range-server: another miner solved the challenge with {:016x}; stopping", nonce);
+                            progress.stop.store(true, Ordering::Release);
+                        }
+                        Ok(None) => {}
+                        Err(e) => eprintln!("range-server: failed to renew lease {}: {}", lease.id, e),
+                    }
+                }
+            });
+            let solutions = search_range(job, progress, lease.start, lease.end);
+            lease_done.store(true, Ordering::Release);
+            solutions
+        });
+        let solutions = match solutions {
+            Ok(solutions) => solutions,
+            Err(e) => break Err(e),
+        };
+
+        let nonce = solutions.first().map(|s| s.nonce);
+        if nonce.is_none() && progress.preempted.load(Ordering::Acquire) {
+            if release {
+                let cursor = progress.cursor_snapshot().into_iter().min().unwrap_or(lease.start);
+                let covered = cursor.clamp(lease.start, lease.end) - lease.start;
+                match server.release(lease.id, covered) {
+                    Ok(_) => eprintln!("range-server: released lease {} after {} nonces", lease.id, covered),
+                    Err(e) => eprintln!("range-server: failed to release lease {}: {}", lease.id, e),
+                }
+            }
+            break Ok(solutions);
+        }
+        match (server.done(lease.id, nonce), nonce) {
+            (Ok(Some(winner)), Some(ours)) => {
+                eprintln!("range-server: another miner's {:016x} won over our {:016x}; not submitting", winner, ours);
+                break Ok(Vec::new());
+            }
+            (Ok(_), _) => {}
+            (Err(e), _) => eprintln!("range-server: failed to complete lease {}: {}", lease.id, e),
+        }
+        if !solutions.is_empty() || progress.stop.load(Ordering::Acquire) {
+            break Ok(solutions);
+        }
+    };
+    progress.stop.store(true, Ordering::Release);
+    solutions
+}
+
+/// `reporter` sink uploading a telemetry report to --report-to.
+struct Telemetry<'a> {
+    url: &'a str,
+    rig_name: &'a str,
+    challenge_id: &'a str,
+}
+
+impl reporter::Sink for Telemetry<'_> {
+    fn report(&mut self, sample: &reporter::Sample) {
+        let body = format!(
+            concat!(
+                "{{\"rig\":{},\"worker_id\":{},\"challenge_id\":{},\"hashrate\":{:.1},\"uptime_secs\":{},",
+                "\"hashes\":{},\"solutions\":{}}}"
+            ),
+            json_string(self.rig_name),
+            json_string(&identity::current().id),
+            json_string(self.challenge_id),
+            sample.hashrate,
+            sample.uptime.as_secs(),
+            sample.hashes,
+            sample.solutions
+        );
+        if let Err(e) = http_post_json(self.url, &body) {
+            eprintln!("telemetry: {}", e);
+        }
+    }
+}
+
+fn mqtt_client_id() -> String {
+    format!("portocripto-{}", std::process::id())
+}
+
+/// `reporter` sink publishing a status message to --mqtt-url, after the job state.
+struct MqttStatus<'a> {
+    url: &'a str,
+    topic: &'a str,
+    challenge_id: &'a str,
+    client: Option<mqtt::Mqtt>,
+}
+
+impl<'a> MqttStatus<'a> {
+    fn new(url: &'a str, topic: &'a str, challenge_id: &'a str) -> Self {
+        let mut status = MqttStatus { url, topic, challenge_id, client: None };
+        let state = format!("{{\"state\":\"mining\",\"challenge_id\":{}}}", json_string(challenge_id));
+        status.publish("state", &state, true);
+        status
+    }
+
+    fn publish(&mut self, subtopic: &str, payload: &str, retain: bool) {
+        // Reconnect lazily so a broker restart only costs the messages sent while it was down.
+        if self.client.is_none() {
+            self.client = mqtt::Mqtt::connect(self.url, &mqtt_client_id()).map_err(|e| eprintln!("mqtt: {}", e)).ok();
+        }
+        let sent = self.client.as_mut().map(|c| c.publish(&format!("{}/{}", self.topic, subtopic), payload, retain));
+        if let Some(Err(e)) = sent {
+            eprintln!("mqtt: {}", e);
+            self.client = None;
+        }
+    }
+}
+
+impl reporter::Sink for MqttStatus<'_> {
+    fn report(&mut self, sample: &reporter::Sample) {
+        let status = format!(
+            "{{\"worker\":{},\"worker_id\":{},\"challenge_id\":{},\"hashrate\":{:.1},\"uptime_secs\":{},\"hashes\":{},\"solutions\":{}}}",
+            json_string(&identity::current().name),
+            json_string(&identity::current().id),
+            json_string(self.challenge_id),
+            sample.hashrate,
+            sample.uptime.as_secs(),
+            sample.hashes,
+            sample.solutions
+        );
+        self.publish("status", &status, false);
+    }
+}
+
+/// Publish the final job state, and the solution if there is one, on a fresh connection.
+fn publish_mqtt_result(url: &str, topic: &str, challenge_id: &str, solution: Option<(u64, &str)>) {
+    let result = mqtt::Mqtt::connect(url, &mqtt_client_id()).and_then(|mut client| {
+        let challenge_id = json_string(challenge_id);
+        if let Some((nonce, hash)) = solution {
+            let payload = format!(
+                "{{\"worker\":{},\"worker_id\":{},\"challenge_id\":{},\"nonce\":\"{:016x}\",\"hash\":\"{}\"}}",
+                json_string(&identity::current().name),
+                json_string(&identity::current().id),
+                challenge_id,
+                nonce,
+                hash
+            );
+            client.publish(&format!("{}/solution", topic), &payload, false)?;
+        }
+        let state = if solution.is_some() { "solved" } else { "stopped" };
+        let payload = format!("{{\"state\":\"{}\",\"challenge_id\":{}}}", state, challenge_id);
+        client.publish(&format!("{}/state", topic), &payload, true)
+    });
+    if let Err(e) = result {
+        eprintln!("mqtt: {}", e);
+    }
+}
+
+/// `template` split into words at unquoted whitespace; single or double quotes keep the
+/// whitespace inside them in one word.
+fn command_words(template: &str) -> Result<Vec<String>, String> {
+    let (mut words, mut word, mut quote, mut started) = (Vec::new(), String::new(), None, false);
+    for c in template.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                started = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if std::mem::take(&mut started) {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("unterminated {} in {:?}", q, template));
+    }
+    if started {
+        words.push(word);
+    }
+    if words.is_empty() {
+        return Err("the command is empty".to_string());
+    }
+    Ok(words)
+}
+
+/// A solution hook's command line, checked when it is given rather than when a solution is found.
+fn parse_hook(template: &str) -> Result<String, String> {
+    command_words(template).map(|_| template.to_string())
+}
+
+/// Run the `--on-solution` command directly, not through a shell: its first word is the
+/// program, `{name}` placeholders are substituted within the other words, and every value is
+/// also in the environment as `PORTOCRIPTO_<NAME>`. A shell given the command as a script must
+/// read the variables instead of the placeholders, which would splice the values into it.
+fn run_on_solution(template: &str, values: &[(&str, &str)]) -> std::io::Result<std::process::ExitStatus> {
+    let words = command_words(template).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let substitute =
+        |word: &String| values.iter().fold(word.clone(), |w, (name, value)| w.replace(&format!("{{{}}}", name), value));
+
+    let mut command = std::process::Command::new(&words[0]);
+    command.args(words[1..].iter().map(substitute));
+    for (name, value) in values {
+        command.env(format!("PORTOCRIPTO_{}", name.to_ascii_uppercase()), value);
+    }
+    // Keep stdout reserved for the nonce line that orchestrators parse
+    command.stdout(std::io::stderr()).status()
+}
+
+/// Normalise every difficulty form to the expected hash count plus the leading-zero and mask
+/// forms, when they exist.
+fn difficulty_forms(form: &DifficultyForm) -> Result<(f64, Option<u32>, Option<Mask>), String> {
+    Ok(if let Some(mask) = &form.mask {
+        let mask = parse_mask(mask).map_err(|e| e.to_string())?;
+        let bits = mask_zero_bits(mask);
+        (2f64.powi(bits as i32), is_leading_zero_mask(mask).then_some(bits), Some(mask))
+    } else if let Some(bits) = form.zero_bits {
+        if bits > 256 {
+            return Err("a 256-bit hash has at most 256 zero bits".to_string());
+        }
+        (2f64.powi(bits as i32), Some(bits), (bits <= 128).then(|| mask_for_zero_bits(bits)))
+    } else if let Some(target) = &form.target {
+        let target = to_hex(&parse_target(target)?);
+        let value = target.chars().fold(0f64, |v, c| v * 16.0 + c.to_digit(16).unwrap() as f64);
+        let expected = 2f64.powi(256) / (value + 1.0);
+        let bits = expected.log2().round() as u32;
+        let zero_bits = (leading_zero_target(bits) == target).then_some(bits);
+        (expected, zero_bits, zero_bits.filter(|&b| b <= 128).map(mask_for_zero_bits))
+    } else if let Some(expected) = form.expected_hashes {
+        if expected < 1.0 {
+            return Err("expected hashes must be at least 1".to_string());
+        }
+        let bits = expected.log2().round() as u32;
+        if 2f64.powi(bits as i32) != expected {
+            println!("note:            rounded to the nearest power of two, 2^{}", bits);
+        }
+        (expected, Some(bits), (bits <= 128).then(|| mask_for_zero_bits(bits)))
+    } else {
+        unreachable!("clap requires one difficulty form");
+    })
+}
+
+fn describe_difficulty(form: &DifficultyForm) -> Result<(), String> {
+    let (expected, zero_bits, mask) = difficulty_forms(form)?;
+    println!("expected hashes: {} (2^{:.2})", format_count(expected), expected.log2());
+    println!("probability:     1 in {} per hash", format_count(expected));
+    match zero_bits {
+        Some(bits) => {
+            println!("leading zeros:   {} bits", bits);
+            println!("target:          {}", leading_zero_target(bits));
+        }
+        None => println!("leading zeros:   n/a (not a leading-zero difficulty)"),
+    }
+    match mask {
+        Some(mask) => println!("mask:            {} ({} zero bits)", format_mask(mask), mask_zero_bits(mask)),
+        None => println!("mask:            n/a (not expressible as a 128-bit prefix mask)"),
+    }
+    Ok(())
+}
+
+/// Chance of at least one solution in `window` at `hashrate`; solutions arrive as a Poisson
+/// process, so it is 1 - e^(-hashes/expected).
+fn describe_chance(form: &DifficultyForm, hashrate: f64, window: Duration) -> Result<(), String> {
+    if hashrate.is_nan() || hashrate <= 0.0 {
+        return Err("hashrate must be positive".to_string());
+    }
+    let (expected, _, _) = difficulty_forms(form)?;
+    let hashes = hashrate * window.as_secs_f64();
+    let chance = -(-hashes / expected).exp_m1();
+    println!("window:          {} ({} hashes)", format_duration(window.as_secs_f64()), format_count(hashes));
+    println!("expected time:   {}", format_duration(expected / hashrate));
+    println!("chance:          {:.2}%", chance * 100.0);
+    for target in [0.5, 0.9, 0.99] {
+        let needed = -(1.0f64 - target).ln() * expected / hashrate;
+        println!("{:<16} {}", format!("{:.0}% needs:", target * 100.0), format_duration(needed));
+    }
+    Ok(())
+}
+
+/// Cost per expected solution at `hashrate` hashes/s drawing `watts` at `price` per kWh,
+/// against `reward` when it is known.
+fn describe_economics(
+    form: &DifficultyForm,
+    hashrate: f64,
+    watts: f64,
+    price: f64,
+    reward: Option<f64>,
+) -> Result<(), String> {
+    if hashrate.is_nan() || hashrate <= 0.0 {
+        return Err("hashrate must be positive".to_string());
+    }
+    let (expected, _, _) = difficulty_forms(form)?;
+    let secs = expected / hashrate;
+    let kwh = watts * secs / 3.6e6;
+    let cost = kwh * price;
+    let per_day = 86400.0 / secs;
+    println!("hashrate:          {:.1} hashes/s at {} W", hashrate, watts);
+    println!("time per solution: {}", format_duration(secs));
+    println!("solutions per day: {:.3}", per_day);
+    // Easy difficulties cost fractions of a cent, keep them readable
+    let amount = |v: f64| if v != 0.0 && v.abs() < 0.01 { format!("{:.3e}", v) } else { format!("{:.4}", v) };
+    println!("energy/solution:   {} kWh", amount(kwh));
+    println!("cost/solution:     {}", amount(cost));
+    println!("cost per day:      {}", amount(watts * 24.0 / 1000.0 * price));
+    if let Some(reward) = reward {
+        println!("net/solution:      {}", amount(reward - cost));
+        if kwh > 0.0 {
+            println!("break-even price:  {} per kWh", amount(reward / kwh));
+        }
+    }
+    Ok(())
+}
+
+/// Time left until the next full hour on the local clock.
+fn until_next_hour() -> Duration {
+    let elapsed = schedule::local_minute_of_day() as i64 % 60 * 60 + unix_now().rem_euclid(60);
+    Duration::from_secs((3600 - elapsed) as u64)
+}
+
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for &byte in hash {
+        bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Hashrate of each backend the CPU supports on `challenge`, fastest first, with the expected
+/// time to a solution at that rate.
+fn compare(challenge: &Challenge, duration: Duration, threads: usize) -> Result<(), String> {
+    let expected = 2f64.powi(mask_zero_bits(challenge.difficulty_mask()?) as i32);
+    let suffix = challenge.suffix();
+    let mut results = Vec::new();
+    for backend in BACKENDS.iter().filter(|b| (b.supported)()) {
+        eprintln!("measuring {} for {}s", backend.name, duration.as_secs());
+        // A large batch keeps stop-flag checks out of the comparison
+        let rate = measure_hashrate(backend.hash, None, &suffix, threads, 256, duration)
+            .map_err(|e| format!("{}: {}", backend.name, e))?;
+        results.push((backend.name, rate));
+    }
+    results.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let fastest = results.first().map_or(0.0, |r| r.1);
+    println!("{:>4} {:<10} {:>14} {:>9} {:>12}", "rank", "backend", "hashes/s", "relative", "eta");
+    for (rank, (name, rate)) in results.iter().enumerate() {
+        println!(
+            "{:>4} {:<10} {:>14.0} {:>8.0}% {:>12}",
+            rank + 1,
+            name,
+            rate,
+            100.0 * rate / fastest,
+            format_duration(expected / rate)
+        );
+    }
+    Ok(())
+}
+
+/// Hash consecutive nonces for `duration` and compare the leading-zero distribution with the
+/// ideal one, where a fraction 2^-(k+1) of all hashes has exactly k leading zero bits.
+fn analyze(challenge: &Challenge, duration: Duration, threads: usize) {
+    let suffix = challenge.suffix();
+    let hash = match challenge.keyed(select_backend(false).hash) {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let stop = AtomicBool::new(false);
+
+    let histograms: Vec<[u64; 513]> = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(duration);
+            stop.store(true, Ordering::Release);
+        });
+        (0..threads)
+            .into_par_iter()
+            .map(|thread_id| {
+                let mut histogram = [0u64; 513];
+                let mut preimage = Vec::with_capacity(20 + suffix.len());
+                let mut output = vec![0u8; challenge.hash_len];
+                let mut nonce = thread_id as u64;
+                while !stop.load(Ordering::Relaxed) {
+                    preimage.clear();
+                    write_preimage(&mut preimage, nonce, &suffix, challenge.nonce_encoding);
+                    hash(&preimage, &mut output);
+                    histogram[leading_zero_bits(&output) as usize] += 1;
+                    nonce += threads as u64;
+                }
+                histogram
+            })
+            .collect()
+    });
+
+    let mut histogram = [0u64; 513];
+    for h in &histograms {
+        for (total, count) in histogram.iter_mut().zip(h) {
+            *total += count;
+        }
+    }
+    let total: u64 = histogram.iter().sum();
+    let last = histogram.iter().rposition(|&c| c > 0).unwrap_or(0);
+
+    println!("{} hashes in {:?}", total, duration);
+    println!("{:>5} {:>12} {:>14}", "bits", "count", "expected");
+    for (bits, &count) in histogram.iter().enumerate().take(last + 1) {
+        let expected = total as f64 / 2f64.powi(bits as i32 + 1);
+        // Half of all hashes land in the first bucket, which gets a 40 character bar
+        let bar = "#".repeat((80.0 * count as f64 / total.max(1) as f64).round() as usize);
+        println!("{:>5} {:>12} {:>14.1} {}", bits, count, expected, bar);
+    }
+}
+
+/// Check each nonce (as --nonce-encoding prints them) in parallel; returns whether every entry passed.
+fn verify(challenge: &Challenge, nonces: &[String]) -> bool {
+    let evaluator = match challenge.evaluator() {
+        Ok(evaluator) => evaluator,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let hash = match challenge.keyed(select_backend(false).hash) {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let suffix = challenge.suffix();
+
+    let results: Vec<Option<(String, bool)>> = nonces
+        .par_iter()
+        .map(|nonce| {
+            let nonce = challenge.nonce_encoding.parse(nonce).ok()?;
+            let mut preimage = Vec::new();
+            write_preimage(&mut preimage, nonce, &suffix, challenge.nonce_encoding);
+            let mut output = vec![0u8; challenge.hash_len];
+            hash(&preimage, &mut output);
+            Some((to_hex(&output), evaluator.accepts(&output)))
+        })
+        .collect();
+
+    let mut passed = 0;
+    for (nonce, result) in nonces.iter().zip(&results) {
+        match result {
+            Some((hash, true)) => {
+                passed += 1;
+                println!("{} PASS {}", nonce, hash);
+            }
+            Some((hash, false)) => println!("{} FAIL {}", nonce, hash),
+            None => println!("{} INVALID", nonce),
+        }
+    }
+    eprintln!("{}/{} nonces pass", passed, nonces.len());
+    passed == nonces.len()
+}
+
+/// JSON payload for a solution; refuses nonces that do not meet the difficulty.
+fn encode_solution(challenge: &Challenge, nonce: &str) -> Result<String, String> {
+    let difficulty_mask = challenge.difficulty_mask()?;
+    let layout = challenge.prefix_layout()?;
+    let encoding = challenge.nonce_encoding;
+    let nonce = encoding.parse(nonce)?;
+
+    let preimage = PreimageBuilder { encoding, ..challenge.preimage() }.build(nonce);
+    let mut output = vec![0u8; challenge.hash_len];
+    match &challenge.hmac_key {
+        Some(key) => hmac_with(hash_preimage, &key.to_bytes(), &preimage, &mut output),
+        None => hash_preimage(&preimage, &mut output),
+    }
+    if !hash_structure_good(&layout.prefix(&output), difficulty_mask) {
+        return Err(format!("nonce {:016x} does not meet difficulty {}", nonce, challenge.difficulty));
+    }
+    if !meets_target(&output, challenge.target()?.as_ref()) {
+        return Err(format!("nonce {:016x} is above target {}", nonce, challenge.target.as_deref().unwrap_or("")));
+    }
+
+    Ok(format!(
+        "{{\"address\": {}, \"challenge_id\": {}, \"nonce\": \"{}\", \"hash\": \"{}\", \"preimage\": {}}}",
+        json_string(&challenge.address),
+        json_string(&challenge.challenge_id),
+        encoding.text(nonce),
+        to_hex(&output),
+        json_string(&printable(&preimage))
+    ))
+}
+
+/// The hashrate this machine last reached and where it comes from: --expected-hashrate, the
+/// report a previous run left at the --report-json path, or the autotune baseline.
+fn last_hashrate(expected: Option<f64>, report: Option<&Path>, baseline: Option<f64>) -> Option<(f64, &'static str)> {
+    let reported = report.and_then(|path| std::fs::read_to_string(path).ok()).and_then(|text| {
+        json::parse(&text).ok()?.get("hashrate").and_then(json::Json::as_f64)
+    });
+    let positive = |rate: &f64| rate.is_finite() && *rate > 0.0;
+    (expected.filter(positive).map(|rate| (rate, "--expected-hashrate")))
+        .or(reported.filter(positive).map(|rate| (rate, "last run")))
+        .or(baseline.filter(positive).map(|rate| (rate, "autotune")))
+}
+
+/// The chart inputs of each report, oldest first. Reports from before `finished_unix` was
+/// recorded are dated by their modification time.
+fn load_runs(paths: &[PathBuf]) -> Result<Vec<stats::Run>, String> {
+    let mut runs = Vec::new();
+    for path in paths {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let report = json::parse(&text).map_err(|e| format!("invalid report {}: {}", path.display(), e))?;
+        let finished = match report.get("finished_unix").and_then(json::Json::as_f64) {
+            Some(t) => t as i64,
+            None => std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs() as i64),
+        };
+        let number = |key: &str| report.get(key).and_then(json::Json::as_f64).unwrap_or(0.0);
+        runs.push(stats::Run {
+            finished,
+            challenge_id: report
+                .get("parameters")
+                .and_then(|p| p.get("challenge_id"))
+                .and_then(json::Json::as_str)
+                .unwrap_or("")
+                .to_string(),
+            hashes: number("hashes") as u64,
+            secs: number("duration_secs"),
+            hashrate: number("hashrate"),
+            solved: !matches!(report.get("solution"), Some(json::Json::Null) | None),
+        });
+    }
+    runs.sort_by_key(|r| r.finished);
+    Ok(runs)
+}
+
+/// Recompute a reported solution and list every way it disagrees with the report.
+fn replay(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let report = json::parse(&text).map_err(|e| format!("invalid report {}: {}", path.display(), e))?;
+    replay_report(&report)
+}
+
+/// Recompute the solution in a report or receipt payload: its `parameters` and `solution`.
+fn replay_report(report: &json::Json) -> Result<Vec<String>, String> {
+    let parameters = report.get("parameters").ok_or("report has no parameters")?;
+    let challenge = Challenge::from_json(parameters)?;
+    // The key is never written to reports, so keyed solutions cannot be recomputed
+    if parameters.get("hmac").and_then(json::Json::as_bool) == Some(true) {
+        return Err("report was mined with --hmac-key, which reports do not record".to_string());
+    }
+    let difficulty_mask = challenge.difficulty_mask()?;
+    let layout = challenge.prefix_layout()?;
+
+    let solution = match report.get("solution") {
+        Some(json::Json::Null) | None => return Err("report has no solution".to_string()),
+        Some(solution) => solution,
+    };
+    let nonce_hex = solution.get("nonce").and_then(json::Json::as_str).ok_or("solution has no nonce")?;
+    let nonce = u64::from_str_radix(nonce_hex, 16).map_err(|e| format!("invalid nonce {:?}: {}", nonce_hex, e))?;
+    let recorded = solution.get("hash").and_then(json::Json::as_str);
+
+    let preimage = challenge.preimage().build(nonce);
+    println!("preimage: {}", printable(&preimage));
+
+    let mut problems = Vec::new();
+    let mut reference = vec![0u8; challenge.hash_len];
+    hash_preimage(&preimage, &mut reference);
+    let reference_hex = to_hex(&reference);
+    println!("hash:     {}", reference_hex);
+
+    match recorded {
+        Some(recorded) if !recorded.eq_ignore_ascii_case(&reference_hex) => {
+            problems.push(format!("recorded hash {} differs from recomputed hash", recorded))
+        }
+        Some(_) => {}
+        None => problems.push("report has no solution hash".to_string()),
+    }
+    if !hash_structure_good(&layout.prefix(&reference), difficulty_mask) {
+        problems.push(format!(
+            "hash does not meet difficulty {} ({} bits off)",
+            challenge.difficulty,
+            bits_off_target(&layout.prefix(&reference), difficulty_mask)
+        ));
+    }
+    // A backend that disagrees with the reference would explain a nonce the server rejects.
+    for backend in BACKENDS.iter().filter(|b| (b.supported)()) {
+        let mut output = vec![0u8; challenge.hash_len];
+        (backend.hash)(&preimage, &mut output);
+        if output != reference {
+            problems.push(format!("backend {} computes {}", backend.name, to_hex(&output)));
+        }
+    }
+    if let Some(backend) = report.get("host").and_then(|h| h.get("backend")).and_then(json::Json::as_str) {
+        println!("mined with backend: {}", backend);
+    }
+    Ok(problems)
+}
+
+/// CPUs granted by the cgroup CPU quota (v2 `cpu.max` or v1 `cpu.cfs_quota_us`), rounded up;
+/// `None` without a quota.
+#[cfg(target_os = "linux")]
+fn cgroup_cpu_limit() -> Option<usize> {
+    let read = |path: String| std::fs::read_to_string(path).ok();
+    let limit = |quota: &str, period: &str| {
+        let (quota, period) = (quota.trim().parse::<f64>().ok()?, period.trim().parse::<f64>().ok()?);
+        (quota > 0.0 && period > 0.0).then(|| (quota / period).ceil() as usize)
+    };
+    // Inside a container the listed path is often not mounted, so fall back to the cgroup root
+    let cgroups = read("/proc/self/cgroup".to_string())?;
+    cgroups.lines().find_map(|line| {
+        let mut fields = line.splitn(3, ':');
+        let (_, controllers, path) = (fields.next()?, fields.next()?, fields.next()?);
+        let path = path.trim_end_matches('/');
+        if controllers.is_empty() {
+            let max = read(format!("/sys/fs/cgroup{}/cpu.max", path)).or_else(|| read("/sys/fs/cgroup/cpu.max".into()))?;
+            let (quota, period) = max.split_once(' ')?;
+            limit(quota, period)
+        } else if controllers.split(',').any(|c| c == "cpu") {
+            let file = |name: &str| {
+                read(format!("/sys/fs/cgroup/cpu{}/{}", path, name)).or_else(|| read(format!("/sys/fs/cgroup/cpu/{}", name)))
+            };
+            limit(&file("cpu.cfs_quota_us")?, &file("cpu.cfs_period_us")?)
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cgroup_cpu_limit() -> Option<usize> {
+    None
+}
+
+/// CPUs this process may use: its affinity mask, lowered to the cgroup CPU quota if there is one.
+pub fn available_cpus() -> usize {
+    let cpus = std::thread::available_parallelism().map_or(NUM_THREADS, |n| n.get());
+    cgroup_cpu_limit().map_or(cpus, |limit| limit.clamp(1, cpus))
+}
+
+/// Host name from the OS, or an empty string.
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .ok()
+        .filter(|h| !h.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").or_else(|_| std::env::var("COMPUTERNAME")).ok())
+        .unwrap_or_default()
+}
+
+/// CPU model from the OS, or the architecture when it cannot be determined.
+fn cpu_model() -> String {
+    std::fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|info| {
+            info.lines()
+                .find(|l| l.starts_with("model name") || l.starts_with("Model"))
+                .and_then(|l| l.split_once(':'))
+                .map(|(_, model)| model.trim().to_string())
+        })
+        .unwrap_or_else(|| std::env::consts::ARCH.to_string())
+}
+
+/// Audit record of one mining run, written by `--report-json`.
+pub struct RunReport<'a> {
+    challenge: &'a Challenge,
+    threads: usize,
+    batch_size: u64,
+    order: NonceOrder,
+    backend: &'a str,
+    hashes: u64,
+    /// Hashes and hashrate of each worker thread
+    workers: Vec<(u64, f64)>,
+    elapsed: Duration,
+    /// Energy used over the run, as measured by each source that could
+    energy: &'a [energy::Reading],
+    solution: Option<(u64, &'a solution::Solution)>,
+    /// Other solutions found in the same window, which lost to `solution` on search index
+    alternates: &'a [Find],
+}
+
+impl RunReport<'_> {
+    pub fn to_json(&self) -> String {
+        let c = self.challenge;
+        let solution = match self.solution {
+            Some((nonce, s)) => format!(
+                concat!(
+                    r#"{{"nonce": "{:016x}", "hash": "{}", "#,
+                    r#""worker_id": {}, "thread": {}, "worker_hashes": {}}}"#
+                ),
+                nonce,
+                s.hash,
+                s.worker_id.as_deref().map_or("null".to_string(), json_string),
+                s.thread.map_or("null".to_string(), |t| t.to_string()),
+                s.worker_hashes.map_or("null".to_string(), |h| h.to_string())
+            ),
+            None => "null".to_string(),
+        };
+        let alternates: Vec<String> = self
+            .alternates
+            .iter()
+            .map(|s| format!("{{\"nonce\": \"{:016x}\", \"hash\": \"{}\"}}", s.nonce, to_hex(&s.hash)))
+            .collect();
+        let secs = self.elapsed.as_secs_f64();
+        let workers: Vec<String> = self
+            .workers
+            .iter()
+            .map(|(hashes, hashrate)| format!("{{\"hashes\": {}, \"hashrate\": {:.1}}}", hashes, hashrate))
+            .collect();
+        format!(
+            r#"{{
+  "version": {},
+  "parameters": {{
+    "address": {},
+    "challenge_id": {},
+    "difficulty": {},
+    "no_pre_mine": {},
+    "latest_submission": {},
+    "no_pre_mine_hour": {},
+    "prefix_offset": {},
+    "prefix_endian": {},
+    "hash_len": {},
+    "target": {},
+    "hmac": {},
+    "salt": {},
+    "salt_position": {},
+    "hex_case": {},
+    "nonce_encoding": {}
+  }},
+  "settings": {{
+    "threads": {},
+    "batch_size": {},
+    "nonce_order": {}
+  }},
+  "host": {{
+    {}
+  }},
+  "hashes": {},
+  "duration_secs": {:.3},
+  "hashrate": {:.1},
+  "workers": [{}],
+  "energy": {},
+  "finished_unix": {},
+  "solution": {},
+  "alternates": [{}]
+}}
+"#,
+            json_string(env!("CARGO_PKG_VERSION")),
+            json_string(&c.address),
+            json_string(&c.challenge_id),
+            json_string(&c.difficulty),
+            json_string(&c.no_pre_mine),
+            json_string(&c.latest_submission),
+            json_string(&c.no_pre_mine_hour),
+            c.prefix_offset,
+            json_string(if c.prefix_endian == Endian::Little { "little" } else { "big" }),
+            c.hash_len,
+            c.target.as_deref().map_or("null".to_string(), json_string),
+            c.hmac_key.is_some(),
+            c.salt.as_deref().map_or("null".to_string(), json_string),
+            json_string(if c.salt_position == SaltPosition::Prepend { "prepend" } else { "append" }),
+            json_string(c.hex_case.name()),
+            json_string(c.nonce_encoding.name()),
+            self.threads,
+            self.batch_size,
+            json_string(&self.order.describe()),
+            environment::members(&environment::host(self.backend), ",\n    "),
+            self.hashes,
+            secs,
+            if secs > 0.0 { self.hashes as f64 / secs } else { 0.0 },
+            workers.join(", "),
+            energy::json(self.energy, self.hashes),
+            unix_now(),
+            solution,
+            alternates.join(", ")
+        )
+    }
+}
+
+/// Hashes per second for one configuration, mining a synthetic challenge for `duration`.
+fn measure_hashrate(
+    hash: HashFn,
+    hash_batch: Option<BatchFn>,
+    suffix: &str,
+    threads: usize,
+    batch_size: u64,
+    duration: Duration,
+) -> Result<f64, PortocriptoError> {
+    let progress = Progress::new(threads);
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(duration);
+            progress.stop.store(true, Ordering::Release);
+        });
+        // A zero mask needs 32 leading zero bits, so the run practically always lasts `duration`
+        let order =
+            NonceOrder { strategy: NonceStrategy::Strided, threads: threads as u64, seed: 0, worker_index: 0, worker_count: 1 };
+        let job = Job {
+            suffix,
+            difficulty_mask: 0,
+            target: None,
+            layout: PrefixLayout::DEFAULT,
+            hash,
+            hash_batch,
+            hash_len: 32,
+            order,
+            batch_size,
+            near_miss_bits: None,
+            secondaries: &[],
+            end_index: u64::MAX,
+            ramp: None,
+            timings: None,
+            range_log: None,
+            nonce_encoding: NonceEncoding::Hex16,
+            verify: None,
+        };
+        search(&job, &progress)
+    })?;
+    Ok(progress.hashes() as f64 / start.elapsed().as_secs_f64())
+}
+
+/// Ask on stderr until `parse` accepts the answer; an empty answer stands for `default`.
+fn prompt<T>(question: &str, default: &str, parse: impl Fn(&str) -> Result<T, String>) -> std::io::Result<T> {
+    loop {
+        if default.is_empty() {
+            eprint!("{}: ", question);
+        } else {
+            eprint!("{} [{}]: ", question, default);
+        }
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "input closed before setup finished"));
+        }
+        let answer = match line.trim() {
+            "" => default,
+            answer => answer,
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(e) => eprintln!("  {}", e),
+        }
+    }
+}
+
+/// Optional URL answer: empty skips it, anything else must use `scheme`.
+fn parse_url(answer: &str, scheme: &str) -> Result<Option<String>, String> {
+    match answer {
+        "" | "none" => Ok(None),
+        url if url.starts_with(scheme) => Ok(Some(url.to_string())),
+        url => Err(format!("{:?} must start with {}", url, scheme)),
+    }
+}
+
+/// `portocripto init`: ask for each setting, validate it and save the answers to the config
+/// file. Existing values are offered as defaults; "none" clears an optional one.
+fn init_wizard(config_path: &Path) -> std::io::Result<()> {
+    let current = load_config(config_path, None).unwrap_or_default();
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    eprintln!("Setting up {} (press Enter to keep the value in brackets)", config_path.display());
+
+    let address = prompt("Cardano address to mine for", &text(&current.address), |a| {
+        a.parse::<Bech32Address>().map(|a| a.to_string())
+    })?;
+    let cpus = available_cpus();
+    let threads = prompt("Worker threads", &current.threads.unwrap_or(cpus).to_string(), |t| match t.parse() {
+        Ok(0) | Err(_) => Err(format!("{:?} is not a positive number", t)),
+        Ok(t) => Ok(t),
+    })?;
+    let report_to = prompt("Telemetry endpoint (http://..., empty to skip)", &text(&current.report_to), |u| {
+        parse_url(u, "http://")
+    })?;
+    let rig_name = match report_to {
+        Some(_) => Some(prompt("Rig name shown in telemetry", &text(&current.rig_name), |n| match n {
+            "" => Err("a rig name is required with a telemetry endpoint".to_string()),
+            n => Ok(n.to_string()),
+        })?),
+        None => None,
+    };
+    let mqtt_url = prompt("MQTT broker for notifications (mqtt://..., empty to skip)", &text(&current.mqtt_url), |u| {
+        parse_url(u, "mqtt://")
+    })?;
+    let on_solution = prompt("Command to run on each solution (empty to skip)", &text(&current.on_solution), |c| {
+        if c.is_empty() || c == "none" {
+            return Ok(None);
+        }
+        parse_hook(c).map(Some)
+    })?;
+
+    let quoted = |value: Option<String>| format!("\"{}\"", value.unwrap_or_default());
+    let mut entries = vec![("address", quoted(Some(address))), ("threads", threads.to_string())];
+    let optional = [
+        ("report_to", report_to, &current.report_to),
+        ("rig_name", rig_name, &current.rig_name),
+        ("mqtt_url", mqtt_url, &current.mqtt_url),
+        ("on_solution", on_solution, &current.on_solution),
+    ];
+    for (key, value, old) in optional {
+        // Writing an empty value clears a key saved by an earlier run
+        if value.is_some() || old.is_some() {
+            entries.push((key, quoted(value)));
+        }
+    }
+    save_config(config_path, &entries)?;
+    eprintln!("wrote {}", config_path.display());
+    Ok(())
+}
+
+fn autotune(config_path: &Path, duration: Duration, max_threads: usize) -> std::io::Result<()> {
+    let mut thread_counts: Vec<usize> = std::iter::successors(Some(1), |t| Some(t * 2))
+        .take_while(|&t| t < max_threads)
+        .collect();
+    thread_counts.push(max_threads);
+
+    let backend = select_backend(false);
+    let hash = backend.hash;
+    println!("backend: {}", backend.name);
+
+    let mut best = (0.0, NUM_THREADS, BATCH_SIZE);
+    println!("{:>8} {:>8} {:>14}", "threads", "batch", "hashes/s");
+    for &threads in &thread_counts {
+        for batch_size in [1, 16, 256] {
+            let rate = measure_hashrate(hash, None, TEST_VECTORS[2].1, threads, batch_size, duration)
+                .map_err(std::io::Error::other)?;
+            println!("{:>8} {:>8} {:>14.0}", threads, batch_size, rate);
+            if rate > best.0 {
+                best = (rate, threads, batch_size);
+            }
+        }
+    }
+
+    let (rate, threads, batch_size) = best;
+    save_config(
+        config_path,
+        &[
+            ("threads", threads.to_string()),
+            ("batch_size", batch_size.to_string()),
+            ("hashrate", format!("{:.0}", rate)),
+        ],
+    )?;
+    println!(
+        "best: threads = {}, batch_size = {} ({:.0} hashes/s), saved to {}",
+        threads,
+        batch_size,
+        rate,
+        config_path.display()
+    );
+    Ok(())
+}
+
+/// Whether `flag` appears on the raw command line, as `--flag value` or `--flag=value`.
+fn flag_given(argv: &[OsString], flag: &str) -> bool {
+    argv.iter()
+        .filter_map(|a| a.to_str())
+        .any(|a| a == flag || a.strip_prefix(flag).is_some_and(|rest| rest.starts_with('=')))
+}
+
+/// The value of `flag` on the raw command line, for the few options needed before clap runs.
+fn flag_value<'a>(argv: &'a [OsString], flag: &str) -> Option<&'a str> {
+    argv.iter().enumerate().find_map(|(i, a)| match a.to_str()? {
+        a if a == flag => argv.get(i + 1)?.to_str(),
+        a => a.strip_prefix(flag)?.strip_prefix('='),
+    })
+}
+
+/// Parse the command line; a missing --address is filled in from the config file, if it has one.
+fn parse_cli(mut argv: Vec<OsString>) -> Cli {
+    // The hex challenge fields are normalized as clap parses them
+    params::keep_0x(flag_given(&argv, "--keep-0x"));
+    let err = match Cli::try_parse_from(&argv) {
+        Ok(cli) => return cli,
+        Err(err) => err,
+    };
+    if err.kind() == clap::error::ErrorKind::MissingRequiredArgument && !flag_given(&argv, "--address") {
+        let path = flag_value(&argv, "--config").unwrap_or(CONFIG_FILE);
+        let address = load_config(Path::new(path), flag_value(&argv, "--profile")).ok().and_then(|c| c.address);
+        if let Some(address) = address {
+            argv.extend(address.split(',').map(|a| format!("--address={}", a.trim()).into()));
+            return Cli::parse_from(rotate_addresses(argv).unwrap_or_else(|e| {
+                eprintln!("address: {}", e);
+                std::process::exit(2);
+            }));
+        }
+    }
+    err.exit()
+}
+
+/// Challenge flags and the JSON keys `--args-file` takes them from.
+const ARGS_FILE_FIELDS: &[(&str, &[&str])] = &[
+    ("--address", &["address"]),
+    ("--challenge-id", &["challenge_id", "challengeId"]),
+    ("--difficulty", &["difficulty"]),
+    ("--no-pre-mine", &["no_pre_mine", "noPreMine"]),
+    ("--latest-submission", &["latest_submission", "latestSubmission"]),
+    ("--no-pre-mine-hour", &["no_pre_mine_hour", "noPreMineHour"]),
+    ("--salt", &["salt"]),
+    ("--spec-version", &["spec_version", "specVersion"]),
+];
+
+/// Append the challenge flags found in the `--args-file` (or `--watch`) JSON to the command line, skipping
+/// any flag that is already there, so clap validates them like typed arguments.
+fn expand_args_file(mut argv: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let path = flag_value(&argv, "--args-file").or_else(|| flag_value(&argv, "--watch"));
+    let Some(path) = path.map(PathBuf::from) else {
+        return Ok(argv);
+    };
+
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let file = json::parse(&text).map_err(|e| format!("invalid JSON in {}: {}", path.display(), e))?;
+    // The challenge endpoint nests the fields under "challenge"
+    let fields = file.get("challenge").unwrap_or(&file);
+    let mut extra = Vec::new();
+    for (flag, keys) in ARGS_FILE_FIELDS {
+        let value = match keys.iter().find_map(|k| fields.get(k)) {
+            None => continue,
+            Some(json::Json::String(s)) => s.clone(),
+            Some(json::Json::Number(n)) if n.fract() == 0.0 => format!("{}", n),
+            Some(other) => return Err(format!("{} in {}: expected a string, got {:?}", keys[0], path.display(), other)),
+        };
+        if !flag_given(&argv, flag) {
+            extra.push(format!("{}={}", flag, value).into());
+        }
+    }
+    argv.extend(extra);
+    Ok(argv)
+}
+
+/// Reduce several `--address ADDR[:WEIGHT]` flags to the one this challenge goes to, so a
+/// group sharing hardware splits the rounds between its addresses in proportion to the
+/// weights (default 1). The pick is a hash of the challenge id, so every miner of the group
+/// chooses the same address for a round without coordinating.
+fn rotate_addresses(argv: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    // Each --address as (position, value); the first one's position keeps the chosen address
+    let mut addresses = Vec::new();
+    let mut rest = Vec::with_capacity(argv.len());
+    let mut args = argv.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--address") => {
+                let value = args.next().and_then(|a| a.into_string().ok()).unwrap_or_default();
+                addresses.push((rest.len(), value));
+            }
+            Some(a) if a.starts_with("--address=") => addresses.push((rest.len(), a["--address=".len()..].to_string())),
+            _ => rest.push(arg),
+        }
+    }
+    let Some(&(position, _)) = addresses.first() else {
+        return Ok(rest);
+    };
+    let weighted = addresses
+        .iter()
+        .map(|(_, a)| match a.split_once(':') {
+            Some((address, weight)) => match weight.parse::<u64>() {
+                Ok(weight @ 1..) => Ok((address, weight)),
+                _ => Err(format!("{:?}: the weight after ':' must be a whole number of at least 1", a)),
+            },
+            None => Ok((a.as_str(), 1)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Check them all now rather than in the round that picks a mistyped one
+    for (address, _) in &weighted {
+        address.parse::<Bech32Address>().map_err(|e| format!("{:?}: {}", address, e))?;
+    }
+    let challenge_id = flag_value(&rest, "--challenge-id").unwrap_or("").to_string();
+    let mut digest = [0u8; 8];
+    hash_preimage(challenge_id.as_bytes(), &mut digest);
+    let total: u64 = weighted.iter().map(|(_, weight)| weight).sum();
+    let mut share = u64::from_le_bytes(digest) % total;
+    let mut chosen = weighted[0];
+    for &(address, weight) in &weighted {
+        if share < weight {
+            chosen = (address, weight);
+            break;
+        }
+        share -= weight;
+    }
+    if weighted.len() > 1 {
+        eprintln!("address: challenge {} goes to {} ({} of {} shares)", challenge_id, chosen.0, chosen.1, total);
+    }
+    rest.insert(position, format!("--address={}", chosen.0).into());
+    Ok(rest)
+}
+
+/// Rewrite `--difficulty-zeros N` into the `--difficulty` mask it stands for, which is what
+/// goes into the preimage.
+fn expand_difficulty_zeros(mut argv: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let Some(zeros) = flag_value(&argv, "--difficulty-zeros") else {
+        return Ok(argv);
+    };
+    if flag_given(&argv, "--difficulty") {
+        return Err("give either --difficulty or --difficulty-zeros, not both".to_string());
+    }
+    let zeros: u32 = match zeros.parse() {
+        Ok(n @ 1..=32) => n,
+        _ => return Err(format!("{:?} is not a number of hex digits from 1 to 32", zeros)),
+    };
+    argv.push(format!("--difficulty={}", format_mask(u128::MAX.checked_shr(4 * zeros).unwrap_or(0))).into());
+    Ok(argv)
+}
+
+/// Add the layout flags of the `--spec-version` revision that the command line leaves out.
+fn expand_spec_version(mut argv: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let Some(version) = flag_value(&argv, "--spec-version") else {
+        return Ok(argv);
+    };
+    let spec = spec::find(version)?;
+    eprintln!("spec: version {}: {}", spec.version, spec.description);
+    let missing = spec.flags.iter().filter(|(flag, _)| !flag_given(&argv, flag));
+    let flags: Vec<OsString> = missing.map(|(flag, value)| format!("{}={}", flag, value).into()).collect();
+    argv.extend(flags);
+    Ok(argv)
+}
+
+fn main() {
+    let process_started = Instant::now();
+    let argv = expand_difficulty_zeros(std::env::args_os().collect()).unwrap_or_else(|e| {
+        eprintln!("--difficulty-zeros: {}", e);
+        std::process::exit(2);
+    });
+    let argv = expand_args_file(argv).unwrap_or_else(|e| {
+        eprintln!("--args-file: {}", e);
+        std::process::exit(2);
+    });
+    let argv = expand_spec_version(argv).unwrap_or_else(|e| {
+        eprintln!("--spec-version: {}", e);
+        std::process::exit(2);
+    });
+    let argv = rotate_addresses(argv).unwrap_or_else(|e| {
+        eprintln!("--address: {}", e);
+        std::process::exit(2);
+    });
+    let cli = parse_cli(argv);
+    if let Some(path) = &cli.log_file {
+        let rotation = cli.log_rotate.clone().unwrap_or_default();
+        if let Err(e) = logfile::redirect(path, rotation) {
+            eprintln!("--log-file: {}: {}", path.display(), e);
+            std::process::exit(2);
+        }
+    }
+    if let Some(one_in) = cli.simulate {
+        let hashrate = cli.simulate_hashrate.unwrap_or(sim::DEFAULT_HASHRATE);
+        eprintln!("simulate: fake backend solving one preimage in {}, virtual clock at {} hashes/s", one_in, hashrate);
+        sim::set(one_in, hashrate);
+    }
+    let mut args = cli.args;
+    if args.offline {
+        if let Some(flag) = network_flag(&args) {
+            eprintln!("--offline: {} needs the network", flag);
+            std::process::exit(2);
+        }
+        offline::set();
+    }
+    let challenge = match (cli.command, cli.challenge) {
+        (Some(Command::Selftest), _) => std::process::exit(if selftest() { 0 } else { 1 }),
+        (Some(Command::Backends { json }), _) => {
+            list_backends(args.plugin.as_deref(), args.force_scalar, json);
+            return;
+        }
+        (Some(Command::Autotune { duration, max_threads }), _) => {
+            let max_threads = max_threads.unwrap_or_else(available_cpus);
+            if let Err(e) = autotune(&cli.config, Duration::from_secs(duration), max_threads) {
+                eprintln!("failed to write {}: {}", cli.config.display(), e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        (Some(Command::Difficulty { form }), _) => {
+            if let Err(e) = describe_difficulty(&form) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            return;
+        }
+        (Some(Command::Chance { form, hashrate, window, until_hour }), _) => {
+            let window = window.filter(|_| !until_hour).unwrap_or_else(until_next_hour);
+            if let Err(e) = describe_chance(&form, hashrate, window) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            return;
+        }
+        (Some(Command::Economics { form, hashrate, watts, price, reward }), _) => {
+            let config = load_config(&cli.config, cli.profile.as_deref()).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            });
+            let (Some(watts), Some(price)) = (watts.or(config.power_watts), price.or(config.electricity_price)) else {
+                eprintln!("economics needs --watts and --price, or power_watts and electricity_price in the config file");
+                std::process::exit(2);
+            };
+            let hashrate = hashrate.or(config.hashrate).unwrap_or_else(|| {
+                let threads = config.threads.unwrap_or(NUM_THREADS).min(available_cpus());
+                eprintln!("measuring hashrate with {} threads for 3s", threads);
+                let hash = select_backend(false).hash;
+                let duration = Duration::from_secs(3);
+                measure_hashrate(hash, None, TEST_VECTORS[2].1, threads, BATCH_SIZE, duration).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                })
+            });
+            if let Err(e) = describe_economics(&form, hashrate, watts, price, reward.or(config.reward_per_solution)) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            return;
+        }
+        (Some(Command::Analyze { challenge, duration, threads }), _) => {
+            let threads = threads.unwrap_or_else(available_cpus);
+            analyze(&challenge, Duration::from_secs(duration), threads);
+            return;
+        }
+        (Some(Command::Compare { challenge, duration, threads }), _) => {
+            let threads = threads.unwrap_or_else(available_cpus);
+            if let Err(e) = compare(&challenge, Duration::from_secs(duration), threads) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            return;
+        }
+        (Some(Command::EncodeSolution { challenge, nonce }), _) => match encode_solution(&challenge, &nonce) {
+            Ok(payload) => {
+                println!("{}", payload);
+                return;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        (Some(Command::RangeServer { listen, range_size, lease_secs, first_range, announce, difficulty }), _) => {
+            let mut server = range_server::RangeServer::new(range_size, Duration::from_secs(lease_secs), first_range);
+            if let Some(difficulty) = difficulty {
+                let mask = parse_mask(params::hex_digits(&difficulty)).unwrap_or_else(|e| {
+                    eprintln!("--difficulty: {}", e);
+                    std::process::exit(2);
+                });
+                server = server.difficulty(mask_zero_bits(mask));
+            }
+            let port = listen.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok());
+            if let Some(port) = port.filter(|_| announce) {
+                if listen.starts_with("127.") || listen.starts_with("localhost") {
+                    eprintln!("warning: announcing {}, which miners on other hosts cannot reach", listen);
+                }
+                std::thread::spawn(move || {
+                    if let Err(e) = mdns::announce(port) {
+                        eprintln!("mdns: {}", e);
+                    }
+                });
+            }
+            if let Err(e) = range_server::serve(&listen, server) {
+                eprintln!("range-server: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        (
+            Some(Command::Daemon {
+                listen,
+                token,
+                clients,
+                threads,
+                solo_on_solution,
+                pool_on_solution,
+                pool_address,
+                payout: mode,
+                solo_below,
+                submitted,
+                mut budget,
+            }),
+            _,
+        ) => {
+            let config = load_config(&cli.config, cli.profile.as_deref()).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            });
+            let token = token.or(config.daemon_token);
+            let clients = if clients.is_empty() { config.daemon_clients } else { clients };
+            if token.is_none() && clients.is_empty() {
+                eprintln!("daemon needs --token or --client, or daemon_token or daemon_clients in the config file");
+                std::process::exit(2);
+            }
+            let threads = threads.or(config.threads).unwrap_or(NUM_THREADS.min(available_cpus()));
+            let mut payout = payout::Payout {
+                mode: None,
+                solo_below,
+                solo_on_solution,
+                pool_on_solution,
+                pool_address,
+                submitted: submitted.unwrap_or_else(|| PathBuf::from(outbox::SUBMITTED_FILE)),
+            };
+            payout.mode = payout.default_mode(mode);
+            if let Some(Err(e)) = payout.mode.map(|mode| payout.check(mode)) {
+                eprintln!("daemon: {}", e);
+                std::process::exit(2);
+            }
+            budget.watts = budget.watts.or(config.power_watts);
+            let campaign = campaign::Campaign::new(budget).unwrap_or_else(|e| {
+                eprintln!("daemon: {}", e);
+                std::process::exit(2);
+            });
+            let backend = select_backend(config.force_scalar.unwrap_or(false));
+            eprintln!("backend: {} (cpu features: {})", backend.name, cpu_features().join(" "));
+            if let Err(e) = daemon::serve(&listen, token, clients, backend.hash, threads, payout, campaign) {
+                eprintln!("daemon: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        (Some(Command::Loadtest { daemon, token, jobs, rate, difficulty, cancel, seed, timeout_secs }), _) => {
+            let config = load_config(&cli.config, cli.profile.as_deref()).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            });
+            let Some(token) = token.or(config.daemon_token) else {
+                eprintln!("loadtest needs --token or daemon_token in the config file");
+                std::process::exit(2);
+            };
+            let seed = seed.unwrap_or_else(|| std::hash::BuildHasher::hash_one(&std::hash::RandomState::new(), 0u8));
+            eprintln!("loadtest: seed {}", seed);
+            let timeout = Duration::from_secs(timeout_secs);
+            let options = loadtest::Options { daemon, token, jobs, rate, difficulty, cancel, seed, timeout };
+            match loadtest::run(&options) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("loadtest: {}", e);
+                    std::process::exit(2);
+                }
+            }
+            return;
+        }
+        (Some(Command::Init), _) => {
+            if let Err(e) = init_wizard(&cli.config) {
+                eprintln!("init: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        (Some(Command::InstallSchedule { manager }), _) => {
+            if let Err(e) = autostart::run(&manager) {
+                eprintln!("install-schedule: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        (Some(Command::Stats { action: StatsCommand::Export { format: ExportFormat::Html, output, reports } }), _) => {
+            let html = load_runs(&reports).map(|runs| stats::html(&runs)).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            });
+            let written = match &output {
+                Some(path) => std::fs::write(path, html),
+                None => std::io::stdout().write_all(html.as_bytes()),
+            };
+            if let Err(e) = written {
+                eprintln!("failed to write export: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        (Some(Command::Replay { report }), _) => match replay(&report) {
+            Ok(problems) if problems.is_empty() => {
+                println!("OK: solution matches its recorded parameters");
+                return;
+            }
+            Ok(problems) => {
+                for problem in problems {
+                    println!("MISMATCH: {}", problem);
+                }
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        (Some(Command::Ranges { logs, coverage_map, coverage_cell }), _) => {
+            let mut entries = Vec::new();
+            for path in &logs {
+                match range_log::load(path) {
+                    Ok(loaded) => entries.extend(loaded),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(2);
+                    }
+                }
+            }
+            let (lines, overlapping) = range_log::audit(&entries);
+            for line in lines {
+                println!("{}", line);
+            }
+            if let Some(path) = &coverage_map {
+                let bands = coverage::bands(&entries);
+                let end = entries.iter().map(|e| e.end).max().unwrap_or(0);
+                match coverage::write(path, &bands, coverage::cell_size(coverage_cell, end)) {
+                    Ok(summary) => {
+                        summary.iter().for_each(|line| println!("{}", line));
+                        eprintln!("coverage: wrote {}", path.display());
+                    }
+                    Err(e) => {
+                        eprintln!("coverage: failed to write {}: {}", path.display(), e);
+                        std::process::exit(2);
+                    }
+                }
+            }
+            if overlapping {
+                std::process::exit(1);
+            }
+            return;
+        }
+        (Some(Command::Races { race_log }), _) => {
+            match race::summary(&race_log) {
+                Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            }
+            return;
+        }
+        (Some(Command::VerifyReceipt { receipt, public_key }), _) => {
+            let problems = receipt::verify(&receipt).and_then(|(payload, signer)| {
+                println!("signed by: {}", signer);
+                if let Some(fingerprint) = payload.get("host").and_then(|h| h.get("fingerprint")).and_then(json::Json::as_str) {
+                    println!("host:      {}", fingerprint);
+                }
+                for key in ["started_unix", "found_unix"] {
+                    if let Some(t) = payload.get(key).and_then(json::Json::as_f64) {
+                        println!("{}:{}{:.3}", key, " ".repeat(14 - key.len()), t);
+                    }
+                }
+                let mut problems = replay_report(&payload)?;
+                if public_key.is_some_and(|expected| !expected.eq_ignore_ascii_case(&signer)) {
+                    problems.push("receipt was signed by a different key than --public-key".to_string());
+                }
+                Ok(problems)
+            });
+            match problems {
+                Ok(problems) if problems.is_empty() => {
+                    println!("OK: signature is valid and the solution matches its parameters");
+                    return;
+                }
+                Ok(problems) => {
+                    for problem in problems {
+                        println!("MISMATCH: {}", problem);
+                    }
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        (Some(Command::Verify { challenge, nonce, batch }), _) => {
+            let nonces: Vec<String> = match (nonce, batch) {
+                (Some(nonce), _) => vec![nonce],
+                (None, Some(path)) => match std::fs::read_to_string(&path) {
+                    Ok(text) => text
+                        .lines()
+                        .map(str::trim)
+                        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                        .map(String::from)
+                        .collect(),
+                    Err(e) => {
+                        eprintln!("failed to read {}: {}", path.display(), e);
+                        std::process::exit(2);
+                    }
+                },
+                (None, None) => unreachable!("clap requires --nonce or --batch"),
+            };
+            std::process::exit(if verify(&challenge, &nonces) { 0 } else { 1 });
+        }
+        (None, Some(challenge)) => challenge,
+        (None, None) => {
+            use clap::CommandFactory;
+            Cli::command().print_help().unwrap();
+            std::process::exit(2);
+        }
+    };
+
+    // Initialize AshMaize ROM
+    //let rom = init_rom(&challenge.no_pre_mine);
+    //let rom = Arc::new(init_rom(&challenge.no_pre_mine));
+
+    // Parse difficulty from hex string to u32 mask
+    let difficulty_mask = challenge.difficulty_mask().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let layout = challenge.prefix_layout().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    if let Err(e) = check_difficulty(difficulty_mask, layout, challenge.hash_len) {
+        eprintln!("difficulty self-check failed: {}", e);
+        std::process::exit(1);
+    }
+    let target = challenge.target().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    if let Some(target) = &target {
+        let zero_bits = mask_zero_bits(prefilter_mask(difficulty_mask, target, &layout));
+        eprintln!("target: hashes at most {}, pre-filtered on {} prefix zero bits", to_hex(target), zero_bits);
+    }
+    let secondaries = args.secondary.iter().map(secondary::Secondary::open).collect::<Result<Vec<_>, _>>();
+    let secondaries = Arc::new(secondaries.unwrap_or_else(|e| {
+        eprintln!("secondary: {}", e);
+        std::process::exit(2);
+    }));
+    if args.discover {
+        match mdns::discover(Duration::from_secs(5)) {
+            Ok(url) => {
+                eprintln!("mdns: found range-server at {}", url);
+                args.range_server = Some(url);
+            }
+            Err(e) => {
+                eprintln!("mdns: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let zero_bits = mask_zero_bits(difficulty_mask);
+    if difficulty_mask == Mask::MAX {
+        eprintln!("warning: difficulty {} requires no zero bits; every hash passes", challenge.difficulty);
+    } else if is_leading_zero_mask(difficulty_mask) {
+        eprintln!("difficulty: {} = {} leading zero bits", challenge.difficulty, zero_bits);
+    } else {
+        eprintln!(
+            "difficulty: {} requires {} zero bits, not all leading (first {} leading)",
+            challenge.difficulty,
+            zero_bits,
+            (!difficulty_mask).leading_ones()
+        );
+    }
+
+    // Compute suffix once
+    let suffix = challenge.suffix();
+
+    let config = load_config(&cli.config, cli.profile.as_deref()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    args.force_scalar |= config.force_scalar.unwrap_or(false);
+    args.min_submit_interval = args.min_submit_interval.or(config.min_submit_interval);
+    args.report_to = args.report_to.or(config.report_to);
+    args.rig_name = args.rig_name.or(config.rig_name);
+    if let Some(url) = args.proxy.clone().or(config.proxy) {
+        proxy::set(url);
+    }
+    let defaults = retry::Policy::default();
+    retry::set(retry::Policy {
+        attempts: config.retry_attempts.unwrap_or(defaults.attempts),
+        base_delay: config.retry_base_delay_ms.map_or(defaults.base_delay, Duration::from_millis),
+        max_delay: config.retry_max_delay_ms.map_or(defaults.max_delay, Duration::from_millis),
+        timeout: config.network_timeout_secs.map_or(defaults.timeout, Duration::from_secs),
+    });
+    args.on_solution = args.on_solution.or(config.on_solution);
+    if args.solution_sink.is_empty() {
+        args.solution_sink = config.solution_sinks;
+    }
+    args.outbox = args.outbox.or(config.outbox);
+    let submitted = args.submitted.take().or(config.submitted).unwrap_or_else(|| PathBuf::from(outbox::SUBMITTED_FILE));
+    args.receipt = args.receipt.or(config.receipt);
+    args.receipt_key = args.receipt_key.or(config.receipt_key);
+    // Found out now, not after mining to a solution that then gets no receipt
+    if let Some(Err(e)) = args.receipt.as_ref().map(|_| ed25519::public_key(&[0; 32])) {
+        eprintln!("--receipt: {}", e);
+        std::process::exit(2);
+    }
+    args.mqtt_url = args.mqtt_url.or(config.mqtt_url);
+    args.otlp_endpoint = args.otlp_endpoint.or(config.otlp_endpoint);
+    args.crash_report_url = args.crash_report_url.or(config.crash_report_url);
+    if args.offline {
+        let network = [
+            ("report_to", args.report_to.take()),
+            ("on_solution", args.on_solution.take()),
+            ("mqtt_url", args.mqtt_url.take()),
+            ("otlp_endpoint", args.otlp_endpoint.take()),
+            ("crash_report_url", args.crash_report_url.take()),
+        ];
+        for (key, _) in network.iter().filter(|(_, value)| value.is_some()) {
+            eprintln!("offline: ignoring {} from the config file", key);
+        }
+        for sink in args.solution_sink.iter().filter(|s| s.needs_network()) {
+            eprintln!("offline: ignoring solution_sinks {} from the config file", sink);
+        }
+        args.solution_sink.retain(|s| !s.needs_network());
+    }
+
+    let (backend_name, hash, hash_batch) = match &args.plugin {
+        Some(path) => {
+            let (hash, hash_batch) = load_plugin(path).unwrap_or_else(|e| {
+                eprintln!("failed to load plugin {}: {}", path.display(), e);
+                std::process::exit(1);
+            });
+            if hash_batch.is_some() {
+                eprintln!("backend: plugin hashes {} preimages per batch call", BATCH_LANES);
+            }
+            (format!("plugin:{}", path.display()), hash, hash_batch)
+        }
+        None => {
+            let backend = select_backend(args.force_scalar);
+            eprintln!("backend: {} (cpu features: {})", backend.name, cpu_features().join(" "));
+            (backend.name.to_string(), backend.hash, None)
+        }
+    };
+    // The HMAC wrapper only covers `hash`
+    let hash_batch = hash_batch.filter(|_| challenge.hmac_key.is_none());
+    let hash = challenge.keyed(hash).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let reference =
+        if challenge.hmac_key.is_some() { hmac_reference_hash as HashFn } else { sim::reference(hash_preimage) };
+    let verify = Some(Verify { reference, one_in: args.verify_one_in, max_false_positives: args.max_false_positives });
+    if let Some(url) = &args.crash_report_url {
+        let mut params_hash = [0u8; 8];
+        hash_preimage(suffix.as_bytes(), &mut params_hash);
+        crash::install(url, backend_name.clone(), to_hex(&params_hash));
+    }
+
+    if args.dry_run {
+        let mut preimage = Vec::new();
+        write_preimage(&mut preimage, 0, &suffix, challenge.nonce_encoding);
+        let mut output = vec![0u8; challenge.hash_len];
+        hash(&preimage, &mut output);
+        println!("preimage: {}", printable(&preimage));
+        println!("bytes:    {}", to_hex(&preimage));
+        println!("hash:     {}", to_hex(&output));
+        println!("passes:   {}", hash_structure_good(&layout.prefix(&output), difficulty_mask));
+        return;
+    }
+
+    let identity_path =
+        args.identity.clone().or(config.identity).unwrap_or_else(|| state_dir().join(identity::IDENTITY_FILE));
+    let mut worker = identity::load(&identity_path);
+    if let Some(rig_name) = &args.rig_name {
+        worker.name = rig_name.clone();
+    }
+    eprintln!("worker: {} ({})", worker.name, worker.id);
+    #[cfg(debug_assertions)]
+    if let Some(seed) = args.chaos {
+        eprintln!("chaos: injecting faults with seed {}", seed);
+        chaos::set(seed);
+    }
+    args.rig_name = Some(worker.name.clone());
+    identity::set(worker);
+
+    let threads = args.threads.or(config.threads).unwrap_or_else(|| {
+        let cpus = available_cpus();
+        if cpus < NUM_THREADS {
+            eprintln!("threads: {} (limited by the CPUs available to this process)", cpus);
+        }
+        NUM_THREADS.min(cpus)
+    });
+    let configured_batch_size = args.batch_size.or(config.batch_size);
+    let mut batch_size = configured_batch_size.unwrap_or(BATCH_SIZE);
+
+    let lock = if args.allow_duplicate {
+        None
+    } else {
+        Some(InstanceLock::acquire(&challenge.challenge_id).unwrap_or_else(|e| {
+            eprintln!("{} (use --allow-duplicate to start anyway)", e);
+            std::process::exit(1);
+        }))
+    };
+
+    if let Some(interval) = args.min_submit_interval {
+        match parse_rfc3339(&challenge.latest_submission) {
+            Some(latest) => {
+                let wait = latest + interval as i64 - unix_now();
+                if wait > 0 {
+                    eprintln!("cooldown: waiting {}s after latest_submission before mining", wait);
+                    std::thread::sleep(Duration::from_secs(wait as u64));
+                }
+            }
+            None => eprintln!("--min-submit-interval ignored: latest_submission is not an RFC 3339 timestamp"),
+        }
+    }
+    if let Some(path) = &args.race_log {
+        race::round(path, &challenge.challenge_id, &challenge.latest_submission);
+    }
+    if let Some(minutes) = args.only_when_idle {
+        match idle::idle_secs() {
+            Some(secs) => {
+                eprintln!("idle: no input for {}, mining after {} minutes of it", format_duration(secs as f64), minutes)
+            }
+            None => {
+                eprintln!("--only-when-idle: cannot tell how long this machine has been idle");
+                std::process::exit(2);
+            }
+        }
+    }
+    let window = window_closes(&challenge).filter(|_| !args.mine_past_window);
+    if let Some(closes) = window {
+        let left = closes - unix_now();
+        if left <= 0 {
+            eprintln!(
+                "window: the no_pre_mine_hour window closed {} ago; not mining (use --mine-past-window to mine anyway)",
+                format_duration(-left as f64)
+            );
+            if let Some(path) = args.watch.as_deref() {
+                eprintln!("watch: waiting for {} to change", path.display());
+                wait_for_change(path, modified(path), &AtomicBool::new(false));
+                drop(lock);
+                restart_process();
+            }
+            std::process::exit(1);
+        }
+        eprintln!("window: solutions accepted for another {}", format_duration(left as f64));
+    }
+
+    // Share ROM across threads (read-only, no mutex needed)
+    //let rom = Arc::new(rom);
+
+    let seed = match args.nonce_strategy {
+        NonceStrategy::Random => permute_nonce(unix_now() as u64, std::process::id() as u64),
+        _ => args.nonce_seed,
+    };
+    let coordinated = args.range_server.is_some() || args.discover || args.coordination.is_some();
+    let (worker_index, worker_count) = match args.worker_count {
+        Some(count) => (args.worker_index, count),
+        None if coordinated => (0, 1),
+        None => match kubernetes::shard() {
+            Ok(Some((index, count))) => {
+                eprintln!("kubernetes: pod ordinal {} of {} replicas", index, count);
+                (index, count)
+            }
+            Ok(None) => (0, 1),
+            Err(e) => {
+                eprintln!("kubernetes: {}", e);
+                std::process::exit(2);
+            }
+        },
+    };
+    if worker_count == 0 || worker_index >= worker_count {
+        eprintln!("--worker-index must be below --worker-count (got {} of {})", worker_index, worker_count);
+        std::process::exit(2);
+    }
+    let order =
+        NonceOrder { strategy: args.nonce_strategy, threads: threads as u64, seed, worker_index, worker_count };
+    let job = format!("{} {}", suffix, order.describe());
+    // A restarted local run picks up where it stopped without being asked to; a random order
+    // gets a new seed every run, so there is nothing to pick up
+    if args.journal.is_none() && !args.no_journal && !coordinated && args.nonce_strategy != NonceStrategy::Random {
+        let name = format!("portocripto-{}.journal", file_name_safe(&challenge.challenge_id));
+        args.journal = Some(state_dir().join(name));
+    }
+
+    let bucket = args.checkpoint_url.as_deref().map(|url| {
+        let bucket = s3::Bucket::from_url(url).unwrap_or_else(|e| {
+            eprintln!("--checkpoint-url: {}", e);
+            std::process::exit(2);
+        });
+        if let Err(e) = bucket.restore(args.journal.as_deref(), args.outbox.as_deref()) {
+            eprintln!("checkpoint: failed to restore from {}: {}", bucket, e);
+        }
+        bucket
+    });
+    let journal = args.journal.as_deref().and_then(|path| read_journal(path, &job));
+    let pending = journal.as_ref().and_then(|j| j.solution);
+    let journaled = journal.as_ref().is_some_and(|j| !j.cursors.is_empty());
+    let progress = Arc::new(match journal {
+        Some(j) if j.cursors.len() == threads => Progress::resume(j.cursors),
+        // Every nonce below the slowest cursor is covered, whatever the old thread count was
+        Some(j) if !j.cursors.is_empty() => {
+            let base = j.cursors.iter().copied().min().unwrap();
+            Progress::resume((0..threads as u64).map(|t| base + t).collect())
+        }
+        _ => Progress::new(threads),
+    });
+    let local = args.range_server.is_none() && args.coordination.is_none();
+    let range_log = (args.range_log.is_some() || args.coverage_map.is_some()).then(|| {
+        let (path, worker) = (args.range_log.as_deref(), &identity::current().name);
+        // Skip what earlier runs logged, unless the journal already knows where to continue
+        let logged = path.filter(|_| local && !journaled).and_then(|path| Some((path, range_log::load(path).ok()?)));
+        if let Some((path, entries)) = logged {
+            let resume = range_log::covered_from(&entries, &challenge.challenge_id, &order.describe(), 0);
+            if resume > 0 {
+                eprintln!("range-log: {} lists indices below {:016x} as covered; resuming there", path.display(), resume);
+                for (t, worker) in progress.workers.iter().enumerate() {
+                    worker.cursor.store(resume + t as u64, Ordering::Relaxed);
+                }
+            }
+        }
+        range_log::RangeLog::open(path, worker, &challenge.challenge_id, &order.describe()).unwrap_or_else(|e| {
+            // Only opening the file can fail
+            eprintln!("range-log: failed to open {}: {}", path.unwrap_or(Path::new("")).display(), e);
+            std::process::exit(1);
+        })
+    });
+
+    banner::Banner {
+        challenge: &challenge,
+        backend: &backend_name,
+        batched: hash_batch.is_some(),
+        threads,
+        batch_size,
+        tuned: args.warmup.is_some() && configured_batch_size.is_none(),
+        difficulty_mask,
+        suffix: &suffix,
+        order: &order,
+        coordination: args.range_server.as_deref().or(args.coordination.as_deref()).unwrap_or("local"),
+        resumed_at: progress.cursor_snapshot().into_iter().min().filter(|&at| at > 0),
+        window_closes: window,
+        hashrate: last_hashrate(args.expected_hashrate, args.report_json.as_deref(), config.hashrate),
+    }
+    .print(args.output);
+    if args.machine {
+        machine::enable();
+    }
+    if let Some(listen) = &args.ws_listen {
+        ws::listen(listen).unwrap_or_else(|e| {
+            eprintln!("--ws-listen: {}: {}", listen, e);
+            std::process::exit(2);
+        });
+    }
+    machine::emit(
+        "started",
+        &[
+            ("challenge_id", json_string(&challenge.challenge_id)),
+            ("address", json_string(&challenge.address)),
+            ("difficulty", json_string(&challenge.difficulty)),
+            ("zero_bits", zero_bits.to_string()),
+            ("backend", json_string(&backend_name)),
+            ("threads", threads.to_string()),
+        ],
+    );
+
+    if let (Some(secs), None) = (args.warmup, pending) {
+        let duration = Duration::from_secs(secs);
+        let rate = measure_hashrate(hash, hash_batch, &suffix, threads, batch_size, duration).unwrap_or_else(|e| {
+            eprintln!("warmup: {}", e);
+            std::process::exit(1);
+        });
+        let expected = 2f64.powi(mask_zero_bits(difficulty_mask) as i32);
+        eprintln!(
+            "warm-up: {:.0} hashes/s, expected time to solution {}",
+            rate,
+            format_duration(expected / rate)
+        );
+        if configured_batch_size.is_none() {
+            batch_size = ((rate / threads as f64 / CHECKS_PER_SECOND) as u64).max(1);
+            eprintln!("warm-up: check interval {} hashes", batch_size);
+        }
+        let baseline = args.expected_hashrate.or(config.hashrate);
+        if let Some(baseline) = baseline.filter(|&baseline| rate < baseline / 2.0) {
+            eprintln!(
+                "warm-up: hashrate is below half of the {:.0} hashes/s baseline; aborting (re-run autotune if the machine changed)",
+                baseline
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let started = Instant::now();
+    let wall_started = std::time::SystemTime::now();
+    let meter = energy::Meter::start(args.energy_plug.as_deref(), &args.energy_plug_field);
+    let submitter = args.on_solution.as_deref().map(|template| {
+        let address = challenge.address.to_string();
+        outbox::Submitter::start(template, &address, &challenge.challenge_id, submitted.clone(), args.outbox.is_some())
+    });
+    let shm = args.stats_shm.as_deref().map(|path| {
+        shm::Stats::create(path, threads).unwrap_or_else(|e| {
+            eprintln!("stats-shm: failed to map {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    let otlp = args.otlp_endpoint.as_deref().map(|url| otlp::Otlp::new(url, identity::current()));
+    let mut progress_file = args.progress_file.as_deref().map(|path| {
+        reporter::JsonLines::open(path).unwrap_or_else(|e| {
+            eprintln!("--progress-file: {}: {}", path.display(), e);
+            std::process::exit(2);
+        })
+    });
+    let timings = args.profile_out.as_ref().map(|_| Arc::new(PhaseTimings::default()));
+    let watched = args.watch.as_deref().map(|path| (path, modified(path)));
+    let params_changed = AtomicBool::new(false);
+    let window_closed = AtomicBool::new(false);
+    let too_slow = AtomicBool::new(false);
+    let pausing = args.schedule.is_some() || args.only_when_idle.is_some();
+    let mut pause = None;
+    if pausing {
+        apply_pause(args.schedule.as_ref(), args.only_when_idle, &progress, &mut pause);
+    }
+    let solutions = match pending {
+        Some(nonce) => {
+            eprintln!("journal: retrying pending solution {:016x}", nonce);
+            Vec::new()
+        }
+        None => std::thread::scope(|scope| {
+            if args.lan_broadcast {
+                let stop = &progress.stop;
+                let solves = |nonce| {
+                    let mut preimage = Vec::new();
+                    write_preimage(&mut preimage, nonce, &suffix, challenge.nonce_encoding);
+                    let mut output = vec![0u8; challenge.hash_len];
+                    hash(&preimage, &mut output);
+                    hash_structure_good(&layout.prefix(&output), difficulty_mask)
+                };
+                scope.spawn(move || match lan::listen(solves, stop) {
+                    Ok(Some((nonce, from))) => {
+                        eprintln!("lan: {} solved the challenge with {:016x}; stopping", from, nonce)
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("lan: not listening for other miners' solutions: {}", e),
+                });
+            }
+            let mut sinks: Vec<Box<dyn reporter::Sink + '_>> = Vec::new();
+            if let (Some(url), Some(rig_name)) = (&args.report_to, &args.rig_name) {
+                sinks.push(Box::new(Telemetry { url, rig_name, challenge_id: &challenge.challenge_id }));
+            }
+            if let Some(otlp) = &otlp {
+                let start = std::time::SystemTime::now();
+                sinks.push(Box::new(otlp::Metrics { otlp, challenge_id: &challenge.challenge_id, start }));
+            }
+            if let Some(url) = &args.mqtt_url {
+                sinks.push(Box::new(MqttStatus::new(url, &args.mqtt_topic, &challenge.challenge_id)));
+            }
+            if args.log_progress {
+                sinks.push(Box::new(reporter::Stderr));
+            }
+            if let Some(file) = progress_file.take() {
+                sinks.push(Box::new(file));
+            }
+            if let Some(floor) = args.min_hashrate {
+                let (grace, action) = (args.min_hashrate_for, args.min_hashrate_action);
+                sinks.push(Box::new(reporter::HashrateFloor::new(floor, grace, action, &progress, &too_slow)));
+            }
+            if !sinks.is_empty() {
+                let progress = &progress;
+                scope.spawn(move || reporter::run(&mut sinks, progress, args.report_interval));
+            }
+            if let Some(path) = &args.journal {
+                scope.spawn(|| journal_progress(path, &job, window, &progress));
+            }
+            if args.preemptible {
+                scope.spawn(|| match preempt::detect() {
+                    Some(cloud) => {
+                        eprintln!("preempt: watching the {} metadata service for a preemption notice", cloud.name());
+                        if let Some(notice) = preempt::watch(cloud, &progress.stop) {
+                            eprintln!("preempt: {} is reclaiming this instance ({}); stopping", cloud.name(), notice);
+                            progress.preempted.store(true, Ordering::Release);
+                            progress.stop.store(true, Ordering::Release);
+                        }
+                    }
+                    None => eprintln!("preempt: no cloud metadata service answered; not watching for preemption"),
+                });
+            }
+            if machine::enabled() || ws::listening() {
+                scope.spawn(|| machine::report_progress(&progress));
+            }
+            if let Some(shm) = &shm {
+                scope.spawn(|| shm.follow(&progress, started));
+            }
+            if let Some(bucket) = &bucket {
+                scope.spawn(|| bucket.follow(args.journal.as_deref(), args.outbox.as_deref(), &progress.stop));
+            }
+            if let (Some(log), true) = (&range_log, local) {
+                scope.spawn(|| log.follow(&progress, JOURNAL_INTERVAL));
+            }
+            if pausing {
+                let interval = if args.only_when_idle.is_some() { idle::POLL_INTERVAL } else { SCHEDULE_INTERVAL };
+                let (progress, mut pause) = (&progress, pause);
+                scope.spawn(move || {
+                    while !wait_for_stop(&progress.stop, interval) {
+                        apply_pause(args.schedule.as_ref(), args.only_when_idle, progress, &mut pause);
+                    }
+                });
+            }
+            if let (Some(dir), Some(template)) = (&args.outbox, &args.on_solution) {
+                scope.spawn(|| outbox::drain(dir, template, &submitted, &progress.stop));
+            }
+            if let Some(closes) = window {
+                let (expected, progress, window_closed) =
+                    (2f64.powi(mask_zero_bits(difficulty_mask) as i32), &progress, &window_closed);
+                scope.spawn(move || {
+                    if count_down(closes, expected, progress) {
+                        window_closed.store(true, Ordering::Release);
+                    }
+                });
+            }
+            if !sim::enabled() {
+                let (progress, window_closed) = (&progress, &window_closed);
+                scope.spawn(move || suspend::watch(progress, |_| recheck_window(window, progress, window_closed)));
+            }
+            if let Some((path, since)) = watched {
+                let (stop, params_changed) = (&progress.stop, &params_changed);
+                scope.spawn(move || {
+                    if wait_for_change(path, since, stop) {
+                        eprintln!("watch: {} changed, restarting the search", path.display());
+                        params_changed.store(true, Ordering::Release);
+                        stop.store(true, Ordering::Release);
+                    }
+                });
+            }
+            let job = Job {
+                suffix: &suffix,
+                difficulty_mask,
+                target,
+                layout,
+                hash,
+                hash_batch,
+                hash_len: challenge.hash_len,
+                order,
+                batch_size,
+                near_miss_bits: args.report_near_miss,
+                secondaries: &secondaries,
+                end_index: u64::MAX,
+                ramp: args.ramp_up,
+                timings: timings.as_deref(),
+                range_log: range_log.as_ref(),
+                verify,
+                nonce_encoding: challenge.nonce_encoding,
+            };
+            match (&args.range_server, &args.coordination) {
+                (Some(url), _) => mine_leases(url, &job, &progress, args.release_on_preemption),
+                (_, Some(url)) => mine_redis_chunks(url, &challenge, args.chunk_size, &job, &progress),
+                _ => {
+                    let mut miner = miner::MinerBuilder::new(hash)
+                        .hash_batch(hash_batch)
+                        .difficulty(difficulty_mask, layout)
+                        .target(target)
+                        .hash_len(challenge.hash_len)
+                        .preimage_parts(&[&suffix])
+                        .nonce_order(order.strategy, order.seed)
+                        .nonce_encoding(challenge.nonce_encoding)
+                        .worker_slice(order.worker_index, order.worker_count)
+                        .batch_size(batch_size)
+                        .near_miss(args.report_near_miss)
+                        .secondaries(Arc::clone(&secondaries))
+                        .ramp(args.ramp_up)
+                        .verify(verify)
+                        .timings(timings.clone())
+                        .progress(Arc::clone(&progress))
+                        .build();
+                    miner.start();
+                    miner.join()
+                }
+            }
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("mining failed: {}", e);
+            machine::error(&format!("mining failed: {}", e));
+            if let Some(shm) = &shm {
+                shm.finish(&progress, started, shm::State::Failed);
+            }
+            // The workers stopped cleanly, so their cursors are safe to resume from
+            if let Some(path) = &args.journal {
+                let cursors = progress.cursor_snapshot();
+                let journal = Journal { job: job.clone(), cursors, solution: None, window_closes: window };
+                if let Err(e) = write_journal(path, &journal) {
+                    eprintln!("journal: failed to write {}: {}", path.display(), e);
+                }
+            }
+            if let Some(bucket) = &bucket {
+                bucket.sync(args.journal.as_deref(), args.outbox.as_deref());
+            }
+            std::process::exit(1);
+        }),
+    };
+    let search_elapsed = started.elapsed();
+    // Hand the solution off before anything else, as in a race the first submission wins
+    let nonce = pending.or(solutions.first().map(|s| s.nonce));
+    let solution = nonce.map(|nonce| {
+        let mut preimage = Vec::new();
+        write_preimage(&mut preimage, nonce, &suffix, challenge.nonce_encoding);
+        let mut output = vec![0u8; challenge.hash_len];
+        hash(&preimage, &mut output);
+        let (encoding, id, address) = (challenge.nonce_encoding, &challenge.challenge_id, &challenge.address);
+        // A resumed --journal solution was found by an earlier run, by a worker not recorded
+        let find = solutions.first();
+        solution::Solution {
+            found: find.map(|s| wall_started + s.elapsed),
+            backend: Some(backend_name.clone()),
+            worker_id: Some(identity::current().id.clone()),
+            thread: find.map(|s| s.thread_id),
+            worker_hashes: find.map(|s| s.hashes_tried),
+            ..solution::Solution::new(encoding, nonce, &output, id, address)
+        }
+    });
+    let mut handed_off = None;
+    if let Some(solution) = &solution {
+        if let Some(submitter) = &submitter {
+            submitter.submit(solution.clone());
+        }
+        let sinks = if args.solution_sink.is_empty() { sink::DEFAULT } else { &args.solution_sink };
+        let payload = sinks.iter().any(|s| matches!(s, sink::Sink::Submit(_))).then(|| {
+            encode_solution(&challenge, &solution.nonce).map_err(|e| eprintln!("sink: no submission payload: {}", e))
+        });
+        sink::deliver(sinks, solution, payload.and_then(Result::ok).as_deref());
+        handed_off = Some(std::time::SystemTime::now());
+    }
+    let energy = meter.finish();
+    for reading in &energy {
+        eprintln!(
+            "energy: {:.1} J over the run by {}, {:.3e} J per hash",
+            reading.joules,
+            reading.source,
+            reading.joules / progress.hashes().max(1) as f64
+        );
+    }
+    let false_positives = progress.false_positives.load(Ordering::Relaxed);
+    if false_positives > 0 {
+        let candidates = progress.candidates.load(Ordering::Relaxed);
+        eprintln!("confirm: {} of {} candidates from backend {} were false", false_positives, candidates, backend_name);
+    }
+    if args.verify_one_in.is_some() {
+        let mismatches = progress.mismatches.load(Ordering::Relaxed);
+        eprintln!("verify: recomputed {} hashes, {} differed", progress.verified.load(Ordering::Relaxed), mismatches);
+        if mismatches > 0 {
+            eprintln!("verify: warning: backend {} computes wrong hashes; check with --force-scalar", backend_name);
+        }
+    }
+    for line in secondary::summary(&secondaries) {
+        eprintln!("{}", line);
+    }
+    for (i, found) in solutions.iter().enumerate() {
+        eprintln!(
+            "{}: nonce {:016x} hash {} from thread {} after {} hashes in {:.1}s",
+            if i == 0 { "solution" } else { "alternate" },
+            found.nonce,
+            to_hex(&found.hash),
+            found.thread_id,
+            found.hashes_tried,
+            found.elapsed.as_secs_f64()
+        );
+    }
+    if let Some(shm) = &shm {
+        shm.finish(&progress, started, if nonce.is_some() { shm::State::Solved } else { shm::State::Stopped });
+    }
+    let announced = nonce.filter(|_| args.lan_broadcast).map(|nonce| lan::announce(&challenge.challenge_id, nonce));
+    if let Some(Err(e)) = announced {
+        eprintln!("lan: failed to broadcast the solution: {}", e);
+    }
+    if let Some(otlp) = &otlp {
+        let attrs = [
+            ("portocripto.challenge_id", otlp::Value::Str(challenge.challenge_id.to_string())),
+            ("portocripto.backend", otlp::Value::Str(backend_name.clone())),
+            ("portocripto.threads", otlp::Value::Int(threads as u64)),
+            ("portocripto.hashes", otlp::Value::Int(progress.hashes())),
+            ("portocripto.solved", otlp::Value::Bool(nonce.is_some())),
+        ];
+        let span = (wall_started, wall_started + search_elapsed);
+        otlp.span("search", span, Some(&otlp.job_span), &attrs, None);
+    }
+
+    if let Some(path) = &args.journal {
+        let cursors = progress.cursor_snapshot();
+        let journal = Journal { job: job.clone(), cursors, solution: nonce, window_closes: window };
+        if let Err(e) = write_journal(path, &journal) {
+            eprintln!("journal: failed to write {}: {}", path.display(), e);
+        }
+    }
+    // Before the submission, which may never come back
+    if let Some(bucket) = &bucket {
+        bucket.sync(args.journal.as_deref(), args.outbox.as_deref());
+    }
+    if nonce.is_none() {
+        let reason = if progress.preempted.load(Ordering::Acquire) {
+            "preempted"
+        } else if window_closed.load(Ordering::Acquire) {
+            "window_closed"
+        } else if too_slow.load(Ordering::Acquire) {
+            "hashrate_floor"
+        } else {
+            "stopped"
+        };
+        machine::emit("exhausted", &[("hashes", progress.hashes().to_string()), ("reason", json_string(reason))]);
+    }
+    if nonce.is_none() && progress.preempted.load(Ordering::Acquire) {
+        eprintln!("preempt: progress saved; exiting before the instance goes away");
+        std::process::exit(1);
+    }
+    if nonce.is_none() && too_slow.load(Ordering::Acquire) {
+        eprintln!("hashrate: progress saved; exiting below --min-hashrate");
+        std::process::exit(1);
+    }
+    if nonce.is_none() && params_changed.load(Ordering::Acquire) {
+        drop(lock);
+        restart_process();
+    }
+
+    if let Some(url) = &args.mqtt_url {
+        let solution = nonce.zip(solution.as_ref().map(|s| s.hash.as_str()));
+        publish_mqtt_result(url, &args.mqtt_topic, &challenge.challenge_id, solution);
+    }
+
+    if let (Some(path), Some(timings)) = (&args.profile_out, &timings) {
+        let json = timings.to_json(&backend_name, threads, progress.hashes(), started - process_started, search_elapsed);
+        if let Err(e) = std::fs::write(path, json) {
+            eprintln!("failed to write profile {}: {}", path.display(), e);
+        }
+    }
+
+    if let (Some(path), Some(log)) = (&args.coverage_map, &range_log) {
+        let label = format!("{} ({})", challenge.challenge_id, order.describe());
+        let band = coverage::Band { label, intervals: log.covered() };
+        let end = band.intervals.iter().map(|&(_, end)| end).max().unwrap_or(0);
+        match coverage::write(path, &[band], coverage::cell_size(args.coverage_cell, end)) {
+            Ok(summary) => summary.iter().for_each(|line| eprintln!("{}", line)),
+            Err(e) => eprintln!("coverage: failed to write {}: {}", path.display(), e),
+        }
+    }
+
+    if let Some(path) = &args.report_json {
+        let report = RunReport {
+            challenge: &challenge,
+            threads,
+            batch_size,
+            order,
+            backend: &backend_name,
+            hashes: progress.hashes(),
+            workers: progress.worker_stats(),
+            elapsed: progress.awake(started),
+            energy: &energy,
+            solution: nonce.zip(solution.as_ref()),
+            alternates: solutions.get(1..).unwrap_or_default(),
+        };
+        if let Err(e) = std::fs::write(path, report.to_json()) {
+            eprintln!("failed to write report {}: {}", path.display(), e);
+        }
+    }
+
+    if let (Some(dir), Some(nonce), Some(solution)) = (&args.receipt, nonce, &solution) {
+        let key_path = args.receipt_key.clone().unwrap_or_else(|| PathBuf::from(receipt::KEY_FILE));
+        let written = receipt::load_key(&key_path).and_then(|seed| {
+            let receipt = receipt::Receipt { challenge: &challenge, nonce, solution, started: wall_started };
+            receipt.write(dir, &seed)
+        });
+        match written {
+            Ok(path) => eprintln!("receipt: wrote {}", path.display()),
+            Err(e) => eprintln!("receipt: failed to write a receipt to {}: {}", dir.display(), e),
+        }
+    }
+
+    if let Some(solution) = &solution {
+        let found = solution.found.unwrap_or_else(std::time::SystemTime::now);
+        let race = |submitted| {
+            if let Some(path) = &args.race_log {
+                race::solution(path, &challenge.challenge_id, found, submitted);
+            }
+        };
+        if args.on_solution.is_none() {
+            race(handed_off);
+        }
+
+        if let Some(outbox::Submission { solution, started: hook_started, finished, failure }) =
+            submitter.and_then(outbox::Submitter::finish)
+        {
+            if let Some(otlp) = &otlp {
+                otlp.span("on_solution", (hook_started, finished), Some(&otlp.job_span), &[], failure.as_deref());
+            }
+            race(solution.submitted);
+            if let Some(failure) = failure {
+                eprintln!("{}", failure);
+                let saved = args.outbox.as_ref().and_then(|dir| {
+                    outbox::store(dir, &solution)
+                        .map_err(|e| eprintln!("outbox: failed to save the solution in {}: {}", dir.display(), e))
+                        .ok()
+                });
+                match saved {
+                    Some(path) => {
+                        eprintln!("outbox: saved the solution to {}; later runs keep submitting it", path.display())
+                    }
+                    None => {
+                        if let Some(otlp) = &otlp {
+                            otlp.span("job", (wall_started, std::time::SystemTime::now()), None, &[], Some(&failure));
+                        }
+                        machine::error(&failure);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        // The solution has been handed off, so there is nothing left to resume
+        if let Some(path) = &args.journal {
+            let _ = std::fs::remove_file(path);
+        }
+        if let Some(bucket) = &bucket {
+            bucket.sync(args.journal.as_deref(), args.outbox.as_deref());
+        }
+    }
+    if let Some(otlp) = &otlp {
+        let attrs = [("portocripto.challenge_id", otlp::Value::Str(challenge.challenge_id.to_string()))];
+        otlp.span("job", (wall_started, std::time::SystemTime::now()), None, &attrs, None);
+    }
+
+    if window_closed.load(Ordering::Acquire) && watched.is_none() {
+        std::process::exit(1);
+    }
+
+    // Keep following the file: the next round's parameters start the next search
+    if let Some((path, since)) = watched {
+        eprintln!("watch: waiting for {} to change", path.display());
+        wait_for_change(path, since, &AtomicBool::new(false));
+        drop(lock);
+        restart_process();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backends_hash_the_shared_vectors() {
+        for backend in BACKENDS.iter().filter(|backend| (backend.supported)()) {
+            assert_eq!(selftest_backend(backend.name, backend.hash).0, 0, "{}", backend.name);
+        }
+    }
+
+    #[test]
+    fn backends_match_the_blake2_crate() {
+        let input: Vec<u8> = (0..600u32).map(|i| (i.wrapping_mul(131) ^ i >> 3) as u8).collect();
+        for backend in BACKENDS.iter().filter(|backend| (backend.supported)()) {
+            for len in 0..=input.len() {
+                for size in 1..=64 {
+                    let mut expected = vec![0u8; size];
+                    let mut hasher = Blake2bVar::new(size).unwrap();
+                    hasher.update(&input[..len]);
+                    hasher.finalize_variable(&mut expected).unwrap();
+                    let mut got = vec![0u8; size];
+                    (backend.hash)(&input[..len], &mut got);
+                    assert_eq!(got, expected, "{} over {} bytes to {} bytes", backend.name, len, size);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn difficulty_layouts() {
+        assert_eq!(selftest_difficulty().0, 0);
+    }
+
+    #[test]
+    fn targets() {
+        assert_eq!(selftest_target().0, 0);
+    }
+
+    #[test]
+    fn evaluators() {
+        assert_eq!(selftest_evaluator().0, 0);
+    }
+
+    #[test]
+    fn ed25519_vectors() {
+        assert_eq!(selftest_ed25519().0, 0);
+    }
+
+    #[test]
+    fn sigv4_vectors() {
+        assert_eq!(selftest_sigv4().0, 0);
+    }
+
+    #[test]
+    fn websocket_handshake() {
+        assert_eq!(selftest_websocket().0, 0);
+    }
+
+    #[test]
+    fn preimage_formatting() {
+        assert_eq!(selftest_preimage().0, 0);
+    }
 
-    if found.load(Ordering::Acquire) {
-        println!("{:016x}", result_nonce.load(Ordering::Acquire));
+    #[test]
+    fn solution_json() {
+        assert_eq!(selftest_solution().0, 0);
     }
 }
@@ -0,0 +1,258 @@
+//! In-tree Blake2b used by the SIMD hash backends. The blake2 crate stays the
+//! portable reference implementation; everything here must match it bit for bit.
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 12] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+];
+
+/// Compress one 128-byte block into `h`; `counter` is the byte count so far, including this block.
+type Compress = unsafe fn(h: &mut [u64; 8], block: &[u8; 128], counter: u128, last: bool);
+
+/// Unkeyed Blake2b producing `output.len()` (1..=64) bytes of digest.
+///
+/// # Safety
+/// The CPU must support whatever instructions `compress` was compiled for.
+unsafe fn hash_with(compress: Compress, input: &[u8], output: &mut [u8]) {
+    debug_assert!((1..=64).contains(&output.len()));
+    let mut h = IV;
+    h[0] ^= 0x0101_0000 ^ output.len() as u64;
+
+    let mut block = [0u8; 128];
+    if input.is_empty() {
+        unsafe { compress(&mut h, &block, 0, true) };
+    }
+    let mut counter = 0u128;
+    let mut chunks = input.chunks(128).peekable();
+    while let Some(chunk) = chunks.next() {
+        counter += chunk.len() as u128;
+        block[..chunk.len()].copy_from_slice(chunk);
+        block[chunk.len()..].fill(0);
+        unsafe { compress(&mut h, &block, counter, chunks.peek().is_none()) };
+    }
+
+    for (i, byte) in output.iter_mut().enumerate() {
+        *byte = (h[i / 8] >> (8 * (i % 8))) as u8;
+    }
+}
+
+fn message_words(block: &[u8; 128]) -> [u64; 16] {
+    let mut m = [0u64; 16];
+    for (word, bytes) in m.iter_mut().zip(block.chunks_exact(8)) {
+        *word = u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+    m
+}
+
+/// G on two columns at once, one per lane of a two-lane u64 vector.
+#[cfg_attr(not(target_arch = "aarch64"), allow(unused_macros))]
+macro_rules! g2 {
+    ($a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $y:expr) => {
+        $a = add(add($a, $b), $x);
+        $d = rotr32(xor($d, $a));
+        $c = add($c, $d);
+        $b = rotr24(xor($b, $c));
+        $a = add(add($a, $b), $y);
+        $d = rotr16(xor($d, $a));
+        $c = add($c, $d);
+        $b = rotr63(xor($b, $c));
+    };
+}
+
+/// Compression over 128-bit vectors holding two state words each, written against the
+/// primitives `pair`, `lanes`, `add`, `xor`, `ext` and `rotr{32,24,16,63}` of the invoking module.
+#[cfg_attr(not(target_arch = "aarch64"), allow(unused_macros))]
+macro_rules! two_lane_compress {
+    () => {
+        pub fn compress(h: &mut [u64; 8], block: &[u8; 128], counter: u128, last: bool) {
+            let m = super::message_words(block);
+            let (mut a0, mut a1) = (pair(h[0], h[1]), pair(h[2], h[3]));
+            let (mut b0, mut b1) = (pair(h[4], h[5]), pair(h[6], h[7]));
+            let (mut c0, mut c1) = (pair(IV[0], IV[1]), pair(IV[2], IV[3]));
+            let mut d0 = pair(IV[4] ^ counter as u64, IV[5] ^ (counter >> 64) as u64);
+            let mut d1 = pair(IV[6] ^ if last { u64::MAX } else { 0 }, IV[7]);
+
+            for s in &SIGMA {
+                g2!(a0, b0, c0, d0, pair(m[s[0]], m[s[2]]), pair(m[s[1]], m[s[3]]));
+                g2!(a1, b1, c1, d1, pair(m[s[4]], m[s[6]]), pair(m[s[5]], m[s[7]]));
+                (b0, b1) = (ext(b0, b1), ext(b1, b0));
+                (c0, c1) = (c1, c0);
+                (d0, d1) = (ext(d1, d0), ext(d0, d1));
+                g2!(a0, b0, c0, d0, pair(m[s[8]], m[s[10]]), pair(m[s[9]], m[s[11]]));
+                g2!(a1, b1, c1, d1, pair(m[s[12]], m[s[14]]), pair(m[s[13]], m[s[15]]));
+                (b0, b1) = (ext(b1, b0), ext(b0, b1));
+                (c0, c1) = (c1, c0);
+                (d0, d1) = (ext(d0, d1), ext(d1, d0));
+            }
+
+            for (i, v) in [xor(a0, c0), xor(a1, c1), xor(b0, d0), xor(b1, d1)].into_iter().enumerate() {
+                let [x, y] = lanes(v);
+                h[2 * i] ^= x;
+                h[2 * i + 1] ^= y;
+            }
+        }
+    };
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::{IV, SIGMA};
+    use std::arch::aarch64::*;
+
+    // NEON is part of the aarch64 baseline, so these need no runtime feature check
+    #[inline(always)]
+    fn pair(x: u64, y: u64) -> uint64x2_t {
+        unsafe { vld1q_u64([x, y].as_ptr()) }
+    }
+
+    #[inline(always)]
+    fn lanes(v: uint64x2_t) -> [u64; 2] {
+        let mut out = [0u64; 2];
+        unsafe { vst1q_u64(out.as_mut_ptr(), v) };
+        out
+    }
+
+    #[inline(always)]
+    fn add(a: uint64x2_t, b: uint64x2_t) -> uint64x2_t {
+        unsafe { vaddq_u64(a, b) }
+    }
+
+    #[inline(always)]
+    fn xor(a: uint64x2_t, b: uint64x2_t) -> uint64x2_t {
+        unsafe { veorq_u64(a, b) }
+    }
+
+    /// `[a[1], b[0]]`
+    #[inline(always)]
+    fn ext(a: uint64x2_t, b: uint64x2_t) -> uint64x2_t {
+        unsafe { vextq_u64::<1>(a, b) }
+    }
+
+    #[inline(always)]
+    fn rotr32(x: uint64x2_t) -> uint64x2_t {
+        unsafe { vreinterpretq_u64_u32(vrev64q_u32(vreinterpretq_u32_u64(x))) }
+    }
+
+    #[inline(always)]
+    fn rotr24(x: uint64x2_t) -> uint64x2_t {
+        unsafe { vsriq_n_u64::<24>(vshlq_n_u64::<40>(x), x) }
+    }
+
+    #[inline(always)]
+    fn rotr16(x: uint64x2_t) -> uint64x2_t {
+        unsafe { vsriq_n_u64::<16>(vshlq_n_u64::<48>(x), x) }
+    }
+
+    #[inline(always)]
+    fn rotr63(x: uint64x2_t) -> uint64x2_t {
+        unsafe { vsriq_n_u64::<63>(vshlq_n_u64::<1>(x), x) }
+    }
+
+    two_lane_compress!();
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::{message_words, IV, SIGMA};
+    use std::arch::x86_64::*;
+
+    macro_rules! words {
+        ($m:expr, $s:expr, $i0:expr, $i1:expr, $i2:expr, $i3:expr) => {
+            _mm256_set_epi64x($m[$s[$i3]] as i64, $m[$s[$i2]] as i64, $m[$s[$i1]] as i64, $m[$s[$i0]] as i64)
+        };
+    }
+
+    /// Four G functions at once, one per 64-bit lane (the rows of the state are `a`, `b`, `c`, `d`).
+    macro_rules! g {
+        ($a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $y:expr, $rot24:ident, $rot16:ident) => {
+            $a = _mm256_add_epi64(_mm256_add_epi64($a, $b), $x);
+            $d = _mm256_shuffle_epi32(_mm256_xor_si256($d, $a), 0b10_11_00_01);
+            $c = _mm256_add_epi64($c, $d);
+            $b = _mm256_shuffle_epi8(_mm256_xor_si256($b, $c), $rot24);
+            $a = _mm256_add_epi64(_mm256_add_epi64($a, $b), $y);
+            $d = _mm256_shuffle_epi8(_mm256_xor_si256($d, $a), $rot16);
+            $c = _mm256_add_epi64($c, $d);
+            $b = _mm256_xor_si256($b, $c);
+            $b = _mm256_or_si256(_mm256_srli_epi64($b, 63), _mm256_add_epi64($b, $b));
+        };
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn compress(h: &mut [u64; 8], block: &[u8; 128], counter: u128, last: bool) {
+        let m = message_words(block);
+        let rot24 = _mm256_setr_epi8(
+            3, 4, 5, 6, 7, 0, 1, 2, 11, 12, 13, 14, 15, 8, 9, 10, 3, 4, 5, 6, 7, 0, 1, 2, 11, 12, 13, 14, 15, 8, 9, 10,
+        );
+        let rot16 = _mm256_setr_epi8(
+            2, 3, 4, 5, 6, 7, 0, 1, 10, 11, 12, 13, 14, 15, 8, 9, 2, 3, 4, 5, 6, 7, 0, 1, 10, 11, 12, 13, 14, 15, 8, 9,
+        );
+
+        unsafe {
+            let h_lo = _mm256_loadu_si256(h.as_ptr() as *const __m256i);
+            let h_hi = _mm256_loadu_si256(h.as_ptr().add(4) as *const __m256i);
+            let flags = _mm256_set_epi64x(0, -(last as i64), (counter >> 64) as i64, counter as i64);
+
+            let mut a = h_lo;
+            let mut b = h_hi;
+            let mut c = _mm256_loadu_si256(IV.as_ptr() as *const __m256i);
+            let mut d = _mm256_xor_si256(_mm256_loadu_si256(IV.as_ptr().add(4) as *const __m256i), flags);
+
+            for s in &SIGMA {
+                g!(a, b, c, d, words!(m, s, 0, 2, 4, 6), words!(m, s, 1, 3, 5, 7), rot24, rot16);
+                b = _mm256_permute4x64_epi64(b, 0b00_11_10_01);
+                c = _mm256_permute4x64_epi64(c, 0b01_00_11_10);
+                d = _mm256_permute4x64_epi64(d, 0b10_01_00_11);
+                g!(a, b, c, d, words!(m, s, 8, 10, 12, 14), words!(m, s, 9, 11, 13, 15), rot24, rot16);
+                b = _mm256_permute4x64_epi64(b, 0b10_01_00_11);
+                c = _mm256_permute4x64_epi64(c, 0b01_00_11_10);
+                d = _mm256_permute4x64_epi64(d, 0b00_11_10_01);
+            }
+
+            let h_lo = _mm256_xor_si256(h_lo, _mm256_xor_si256(a, c));
+            let h_hi = _mm256_xor_si256(h_hi, _mm256_xor_si256(b, d));
+            _mm256_storeu_si256(h.as_mut_ptr() as *mut __m256i, h_lo);
+            _mm256_storeu_si256(h.as_mut_ptr().add(4) as *mut __m256i, h_hi);
+        }
+    }
+}
+
+/// Blake2b with the AVX2 compression function, or the blake2 crate's on a CPU without AVX2
+/// (std caches the detection, so the check is one load).
+#[cfg(target_arch = "x86_64")]
+pub fn hash_avx2(preimage: &[u8], output: &mut [u8]) {
+    if !is_x86_feature_detected!("avx2") {
+        use blake2::digest::{Update, VariableOutput};
+        let mut hasher = blake2::Blake2bVar::new(output.len()).unwrap();
+        hasher.update(preimage);
+        return hasher.finalize_variable(output).unwrap();
+    }
+    // SAFETY: AVX2 was detected just above
+    unsafe { hash_with(avx2::compress, preimage, output) }
+}
+
+/// Blake2b with the NEON compression function.
+#[cfg(target_arch = "aarch64")]
+pub fn hash_neon(preimage: &[u8], output: &mut [u8]) {
+    unsafe { hash_with(neon::compress, preimage, output) }
+}
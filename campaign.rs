@@ -0,0 +1,234 @@
+//! `daemon --budget-hashes/--budget-time/--budget-kwh`: one budget for a campaign of many
+//! rounds. Every job the daemon runs is counted against it; a queued job whose expected cost
+//! (the hashes its difficulty takes, and the time and energy that is at the hashrate of the
+//! jobs before it) is more than what is left is skipped rather than started, and a running
+//! job is stopped once the budget is gone. The report at --campaign-report is rewritten
+//! after every job, so the one left once the budget is spent is the final one; the admin
+//! gets the same JSON from GET /campaign.
+
+use crate::{format_duration, wait_for_stop, Progress};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often a running job is checked against what is left of the budget
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(clap::Args, Debug)]
+pub struct Budget {
+    /// Hashes the campaign may spend over all its jobs
+    #[arg(long, value_name = "HASHES")]
+    pub budget_hashes: Option<u64>,
+    /// Mining time the campaign may spend over all its jobs
+    #[arg(long, value_name = "DURATION", value_parser = crate::params::parse_duration)]
+    pub budget_time: Option<Duration>,
+    /// Energy the campaign may spend over all its jobs, drawing --watts while mining
+    #[arg(long, value_name = "KWH")]
+    pub budget_kwh: Option<f64>,
+    /// Power draw while mining, in watts, for --budget-kwh (default: config `power_watts`)
+    #[arg(long)]
+    pub watts: Option<f64>,
+    /// Write the campaign report as JSON to this file after every job
+    #[arg(long, value_name = "FILE")]
+    pub campaign_report: Option<PathBuf>,
+}
+
+pub struct Campaign {
+    budget: Budget,
+    rounds: u64,
+    solved: u64,
+    skipped: u64,
+    /// Set once the budget is spent, so that is only logged once
+    spent: bool,
+}
+
+impl Campaign {
+    /// `None` without any budget given.
+    pub fn new(budget: Budget) -> Result<Option<Campaign>, String> {
+        if budget.budget_hashes.is_none() && budget.budget_time.is_none() && budget.budget_kwh.is_none() {
+            return Ok(None);
+        }
+        if budget.budget_kwh.is_some() && budget.watts.is_none() {
+            return Err("--budget-kwh needs --watts, or power_watts in the config file".to_string());
+        }
+        Ok(Some(Campaign { budget, rounds: 0, solved: 0, skipped: 0, spent: false }))
+    }
+
+    /// Hashes, and seconds of mining before the time or energy budget runs out, left after
+    /// `mined` (hashes, seconds) so far; `None` for a part without a budget.
+    pub fn left(&self, mined: (u64, f64)) -> (Option<f64>, Option<f64>) {
+        let hashes = self.budget.budget_hashes.map(|budget| budget as f64 - mined.0 as f64);
+        let time = self.budget.budget_time.map(|budget| budget.as_secs_f64() - mined.1);
+        let energy = self.budget.budget_kwh.zip(self.budget.watts).map(|(kwh, watts)| kwh * 3.6e6 / watts - mined.1);
+        (hashes, [time, energy].into_iter().flatten().reduce(f64::min))
+    }
+
+    fn is_spent(&self, mined: (u64, f64)) -> bool {
+        let (hashes, secs) = self.left(mined);
+        hashes.is_some_and(|h| h <= 0.0) || secs.is_some_and(|s| s <= 0.0)
+    }
+
+    /// Why a job expected to take `expected` hashes does not fit in what is left, if it does not.
+    pub fn refuse(&self, mined: (u64, f64), expected: f64, hashrate: Option<f64>) -> Option<String> {
+        if self.is_spent(mined) {
+            return Some("the campaign budget is spent".to_string());
+        }
+        let (hashes, secs) = self.left(mined);
+        if let Some(hashes) = hashes.filter(|&hashes| expected > hashes) {
+            return Some(format!("expects {:.0} hashes but the campaign has {:.0} left", expected, hashes));
+        }
+        match (secs, hashrate) {
+            (Some(secs), Some(rate)) if expected / rate > secs => Some(format!(
+                "expects {} of mining but the campaign budget lasts {}",
+                format_duration(expected / rate),
+                format_duration(secs)
+            )),
+            _ => None,
+        }
+    }
+
+    /// Count a finished job in `state` and rewrite the report, `mined` now including it.
+    pub fn finish(&mut self, mined: (u64, f64), state: &str) {
+        match state {
+            "skipped" => self.skipped += 1,
+            "solved" => (self.rounds, self.solved) = (self.rounds + 1, self.solved + 1),
+            _ => self.rounds += 1,
+        }
+        if let Some(path) = &self.budget.campaign_report {
+            let written = std::fs::write(path, self.json(mined) + "\n");
+            written.unwrap_or_else(|e| eprintln!("campaign: failed to write {}: {}", path.display(), e));
+        }
+        if !self.spent && self.is_spent(mined) {
+            self.spent = true;
+            eprintln!(
+                "campaign: budget spent after {} rounds, {} solved and {} skipped, in {} hashes and {}",
+                self.rounds,
+                self.solved,
+                self.skipped,
+                mined.0,
+                format_duration(mined.1)
+            );
+        }
+    }
+
+    pub fn json(&self, mined: (u64, f64)) -> String {
+        let number = |n: Option<f64>| n.map_or("null".to_string(), |n| format!("{:.3}", n));
+        let kwh = self.budget.watts.map(|watts| watts * mined.1 / 3.6e6);
+        format!(
+            concat!(
+                "{{\"budget\": {{\"hashes\": {}, \"secs\": {}, \"kwh\": {}}}, ",
+                "\"spent\": {{\"hashes\": {}, \"secs\": {:.1}, \"kwh\": {}}}, ",
+                "\"rounds\": {}, \"solved\": {}, \"skipped\": {}, \"done\": {}}}"
+            ),
+            self.budget.budget_hashes.map_or("null".to_string(), |h| h.to_string()),
+            number(self.budget.budget_time.map(|t| t.as_secs_f64())),
+            number(self.budget.budget_kwh),
+            mined.0,
+            mined.1,
+            number(kwh),
+            self.rounds,
+            self.solved,
+            self.skipped,
+            self.is_spent(mined)
+        )
+    }
+}
+
+/// Stop the job behind `progress` once it has spent `left` (from `Campaign::left`), setting
+/// `spent` if it did; returns as soon as the job stops on its own.
+pub fn enforce(left: (Option<f64>, Option<f64>), progress: &Progress, spent: &AtomicBool) {
+    let (hashes, secs) = left;
+    let started = Instant::now();
+    while !wait_for_stop(&progress.stop, CHECK_INTERVAL) {
+        let elapsed = started.elapsed().as_secs_f64();
+        if hashes.is_some_and(|h| progress.hashes() as f64 >= h) || secs.is_some_and(|s| elapsed >= s) {
+            spent.store(true, Ordering::Release);
+            progress.stop.store(true, Ordering::Release);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget() -> Budget {
+        Budget { budget_hashes: None, budget_time: None, budget_kwh: None, watts: None, campaign_report: None }
+    }
+
+    #[test]
+    fn budgets_that_need_watts() {
+        assert!(Campaign::new(budget()).unwrap().is_none());
+        let kwh = Budget { budget_kwh: Some(1.0), ..budget() };
+        assert_eq!(Campaign::new(kwh).err().unwrap(), "--budget-kwh needs --watts, or power_watts in the config file");
+        assert!(Campaign::new(Budget { budget_kwh: Some(1.0), watts: Some(100.0), ..budget() }).unwrap().is_some());
+    }
+
+    #[test]
+    fn the_tighter_of_time_and_energy() {
+        let budget = Budget {
+            budget_hashes: Some(1000),
+            budget_time: Some(Duration::from_secs(60)),
+            budget_kwh: Some(0.001),
+            watts: Some(100.0),
+            ..budget()
+        };
+        let campaign = Campaign::new(budget).unwrap().unwrap();
+        // 0.001 kWh at 100 W is 36 seconds, sooner than the minute
+        assert_eq!(campaign.left((400, 6.0)), (Some(600.0), Some(30.0)));
+        let hashes_only = Campaign::new(Budget { budget_hashes: Some(10), ..self::budget() }).unwrap().unwrap();
+        assert_eq!(hashes_only.left((20, 1.0)), (Some(-10.0), None));
+    }
+
+    #[test]
+    fn jobs_that_do_not_fit() {
+        let budget = Budget { budget_hashes: Some(1000), budget_time: Some(Duration::from_secs(60)), ..budget() };
+        let campaign = Campaign::new(budget).unwrap().unwrap();
+        assert_eq!(campaign.refuse((0, 0.0), 500.0, None), None);
+        assert_eq!(
+            campaign.refuse((600, 0.0), 500.0, None).unwrap(),
+            "expects 500 hashes but the campaign has 400 left"
+        );
+        assert_eq!(
+            campaign.refuse((0, 30.0), 500.0, Some(5.0)).unwrap(),
+            "expects 1m 40s of mining but the campaign budget lasts 30s"
+        );
+        assert_eq!(campaign.refuse((0, 30.0), 500.0, None), None);
+        assert_eq!(campaign.refuse((1000, 0.0), 0.0, None).unwrap(), "the campaign budget is spent");
+        assert_eq!(campaign.refuse((0, 60.0), 0.0, None).unwrap(), "the campaign budget is spent");
+    }
+
+    #[test]
+    fn reports() {
+        let dir = crate::scratch_dir("campaign");
+        let report = dir.join("campaign.json");
+        let budget =
+            Budget { budget_hashes: Some(100), watts: Some(360.0), campaign_report: Some(report.clone()), ..budget() };
+        let mut campaign = Campaign::new(budget).unwrap().unwrap();
+        campaign.finish((40, 10.0), "solved");
+        campaign.finish((40, 10.0), "skipped");
+        campaign.finish((100, 20.0), "failed");
+        let expected = "{\"budget\": {\"hashes\": 100, \"secs\": null, \"kwh\": null}, \
+                        \"spent\": {\"hashes\": 100, \"secs\": 20.0, \"kwh\": 0.002}, \
+                        \"rounds\": 2, \"solved\": 1, \"skipped\": 1, \"done\": true}";
+        assert_eq!(std::fs::read_to_string(&report).unwrap(), format!("{}\n", expected));
+        assert!(campaign.spent);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn enforced_on_a_running_job() {
+        let progress = Progress::new(1);
+        progress.workers[0].hashes.store(50, Ordering::Relaxed);
+        let spent = AtomicBool::new(false);
+        enforce((Some(50.0), None), &progress, &spent);
+        assert!(spent.load(Ordering::Acquire) && progress.stop.load(Ordering::Acquire));
+
+        let progress = Progress::new(1);
+        let spent = AtomicBool::new(false);
+        progress.stop.store(true, Ordering::Release);
+        enforce((Some(50.0), Some(0.0)), &progress, &spent);
+        assert!(!spent.load(Ordering::Acquire));
+    }
+}
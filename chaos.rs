@@ -0,0 +1,92 @@
+//! `--chaos SEED` (hidden): fault injection for testing recovery. Workers die with a panic
+//! at seeded points of the nonce space, the stop flag reaches the other workers late after
+//! a find, and `--on-solution` submissions are dropped at random. Every decision comes from
+//! the seed and where the worker starts, not from timing, so a run with the same seed,
+//! threads and journal fails the same way again. A killed worker is restarted at its cursor
+//! and gets killed somewhere further on, until it runs out of restarts and fails the run;
+//! a run resumed from the journal that failure left behind starts over with fresh restarts.
+//! Only builds with debug assertions have `--chaos`; in a release build the hooks below are
+//! constants and the fault paths compile away.
+
+use std::time::Duration;
+#[cfg(debug_assertions)]
+use {
+    crate::splitmix64,
+    std::sync::atomic::{AtomicU64, Ordering},
+    std::sync::OnceLock,
+};
+
+/// Hashes within which a doomed worker is killed
+#[cfg(debug_assertions)]
+const KILL_WINDOW: u64 = 1 << 20;
+/// Longest delay between a find and the stop flag
+#[cfg(debug_assertions)]
+const MAX_STOP_DELAY_MS: u64 = 500;
+
+#[cfg(debug_assertions)]
+static SEED: OnceLock<u64> = OnceLock::new();
+#[cfg(debug_assertions)]
+static SUBMISSIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Turn fault injection on for this process.
+#[cfg(debug_assertions)]
+pub fn set(seed: u64) {
+    let _ = SEED.set(seed);
+}
+
+/// A value for `what`, fixed by the seed; `None` without `--chaos`.
+#[cfg(debug_assertions)]
+fn draw(what: u64, key: u64) -> Option<u64> {
+    SEED.get().map(|seed| splitmix64(splitmix64(seed ^ what.wrapping_mul(0x9e3779b97f4a7c15)) ^ key))
+}
+
+/// The search index at which worker `thread_id`, starting at `start` with `stride`, panics;
+/// `u64::MAX` for never. About half the workers are doomed.
+#[cfg(debug_assertions)]
+pub fn kill_index(thread_id: usize, start: u64, stride: u64) -> u64 {
+    match draw(1, splitmix64(thread_id as u64) ^ start) {
+        Some(r) if r & 1 == 0 => start.saturating_add((1 + (r >> 1) % KILL_WINDOW) * stride),
+        _ => u64::MAX,
+    }
+}
+
+/// How long the worker that found `nonce` waits before setting the stop flag.
+#[cfg(debug_assertions)]
+pub fn stop_delay(nonce: u64) -> Option<Duration> {
+    draw(2, nonce).map(|r| Duration::from_millis(r % (MAX_STOP_DELAY_MS + 1)))
+}
+
+/// Whether to drop this submission instead of running the command; one in two are.
+#[cfg(debug_assertions)]
+pub fn drop_submission() -> bool {
+    draw(3, SUBMISSIONS.fetch_add(1, Ordering::Relaxed)).is_some_and(|r| r & 1 == 0)
+}
+
+#[cfg(not(debug_assertions))]
+pub fn kill_index(_thread_id: usize, _start: u64, _stride: u64) -> u64 {
+    u64::MAX
+}
+
+#[cfg(not(debug_assertions))]
+pub fn stop_delay(_nonce: u64) -> Option<Duration> {
+    None
+}
+
+#[cfg(not(debug_assertions))]
+pub fn drop_submission() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // No test sets a seed: other modules' tests rely on no faults being injected
+    #[test]
+    fn without_a_seed_nothing_fails() {
+        assert_eq!(kill_index(0, 0, 1), u64::MAX);
+        assert_eq!(kill_index(3, 1 << 40, 7), u64::MAX);
+        assert_eq!(stop_delay(6166), None);
+        assert!(!drop_submission());
+    }
+}
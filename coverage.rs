@@ -0,0 +1,191 @@
+//! `--coverage-map FILE`: a heatmap of the search indices covered, as a binary PPM image any
+//! viewer opens, for seeing at a glance where the gaps and the duplicate work are. A run
+//! writes the indices it covered once it stops, counted as conservatively as --range-log
+//! counts them; `portocripto ranges --coverage-map` writes those of a whole farm's logs,
+//! one band per challenge and order, so the overlaps between miners show up too.
+//!
+//! Each pixel is a cell of `--coverage-cell` indices from index 0, `WIDTH` to a row: black
+//! where nothing was hashed, green where some was (brighter the more of the cell), red where
+//! some indices were hashed more than once and gray past the end of a band. Without
+//! --coverage-cell the cells are sized to fit the widest band in about `AUTO_CELLS`.
+
+use crate::range_log::{self, Entry};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+pub const WIDTH: usize = 256;
+const AUTO_CELLS: u64 = 4096;
+/// Cells in one band at most; finer --coverage-cell values are coarsened to fit
+const MAX_CELLS: u64 = 1 << 22;
+const PAST_END: [u8; 3] = [64, 64, 64];
+
+/// Indices covered by a challenge and order, with the label of its band.
+pub struct Band {
+    pub label: String,
+    pub intervals: Vec<(u64, u64)>,
+}
+
+/// One band per challenge and order in `entries`, as `range_log::audit` groups them.
+pub fn bands(entries: &[Entry]) -> Vec<Band> {
+    let mut groups: BTreeMap<(&str, &str), Vec<(u64, u64)>> = BTreeMap::new();
+    for entry in entries {
+        groups.entry((&entry.challenge_id, &entry.order)).or_default().push((entry.start, entry.end));
+    }
+    groups
+        .into_iter()
+        .map(|((challenge_id, order), intervals)| Band { label: format!("{} ({})", challenge_id, order), intervals })
+        .collect()
+}
+
+/// The indices per cell: `requested`, or a power of two fitting `end` in about `AUTO_CELLS`,
+/// made coarser if it would take more than `MAX_CELLS`.
+pub fn cell_size(requested: Option<u64>, end: u64) -> u64 {
+    let cell = requested.unwrap_or_else(|| end.div_ceil(AUTO_CELLS).max(1).next_power_of_two()).max(1);
+    cell.max(end.div_ceil(MAX_CELLS))
+}
+
+/// Add each interval's indices to the cells of `cell` indices they fall in.
+fn count(counts: &mut [u64], intervals: &[(u64, u64)], cell: u64) {
+    for &(start, end) in intervals {
+        let mut at = start;
+        while at < end {
+            let index = at / cell;
+            let next = end.min((index + 1).saturating_mul(cell));
+            counts[index as usize] += next - at;
+            at = next;
+        }
+    }
+}
+
+/// (indices hashed, indices hashed again) in each cell of `band`.
+fn cells(band: &Band, cell: u64) -> Vec<(u64, u64)> {
+    let end = band.intervals.iter().map(|&(_, end)| end).max().unwrap_or(0);
+    let n = end.div_ceil(cell) as usize;
+    let (mut total, mut once) = (vec![0; n], vec![0; n]);
+    count(&mut total, &band.intervals, cell);
+    count(&mut once, &range_log::union(band.intervals.clone()), cell);
+    once.into_iter().zip(total).map(|(once, total)| (once, total - once)).collect()
+}
+
+fn pixel((once, again): (u64, u64), cell: u64) -> [u8; 3] {
+    let shade = |n: u64| (55.0 + 200.0 * n as f64 / cell as f64).min(255.0) as u8;
+    match (once, again) {
+        (0, _) => [0, 0, 0],
+        (_, 0) => [0, shade(once), 0],
+        _ => [shade(again), 0, 0],
+    }
+}
+
+/// Write the map of `bands` to `path` with `cell` indices per pixel; returns a summary
+/// line per band.
+pub fn write(path: &Path, bands: &[Band], cell: u64) -> std::io::Result<Vec<String>> {
+    let (mut pixels, mut comments, mut summary) = (Vec::new(), Vec::new(), Vec::new());
+    let mut rows = 0;
+    for (i, band) in bands.iter().enumerate() {
+        if i > 0 {
+            pixels.extend(PAST_END.repeat(WIDTH));
+            rows += 1;
+        }
+        let cells = cells(band, cell);
+        let band_rows = cells.len().div_ceil(WIDTH).max(1);
+        comments.push(format!("# rows {}..{}: {}", rows, rows + band_rows, band.label));
+        for cell_at in 0..band_rows * WIDTH {
+            pixels.extend(cells.get(cell_at).map_or(PAST_END, |&c| pixel(c, cell)));
+        }
+        rows += band_rows;
+        let full = cells.iter().filter(|&&(once, _)| once == cell).count();
+        let gaps = cells.iter().filter(|&&(once, _)| once == 0).count();
+        let doubled = cells.iter().filter(|&&(_, again)| again > 0).count();
+        summary.push(format!(
+            "coverage: {}: {} cells, {} full, {} never hashed, {} hashed more than once",
+            band.label,
+            cells.len(),
+            full,
+            gaps,
+            doubled
+        ));
+    }
+    let mut file = std::fs::File::create(path)?;
+    writeln!(file, "P6")?;
+    writeln!(file, "# portocripto coverage map: {} indices per pixel from index 0, {} per row", cell, WIDTH)?;
+    writeln!(file, "# black never hashed, green hashed (brighter: more of the cell), red hashed more than once")?;
+    for comment in comments {
+        writeln!(file, "{}", comment)?;
+    }
+    write!(file, "{} {}\n255\n", WIDTH, rows)?;
+    file.write_all(&pixels)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(challenge_id: &str, order: &str, start: u64, end: u64) -> Entry {
+        Entry { worker: "w".into(), challenge_id: challenge_id.into(), order: order.into(), start, end }
+    }
+
+    #[test]
+    fn one_band_per_challenge_and_order() {
+        let entries = [entry("b", "linear", 0, 5), entry("a", "linear", 5, 9), entry("b", "linear", 7, 8)];
+        let bands = bands(&entries);
+        let labels: Vec<_> = bands.iter().map(|band| band.label.as_str()).collect();
+        assert_eq!(labels, ["a (linear)", "b (linear)"]);
+        assert_eq!(bands[1].intervals, [(0, 5), (7, 8)]);
+    }
+
+    #[test]
+    fn cell_sizes() {
+        assert_eq!(cell_size(Some(10), 1000), 10);
+        assert_eq!(cell_size(Some(0), 1000), 1);
+        assert_eq!(cell_size(None, 0), 1);
+        assert_eq!(cell_size(None, AUTO_CELLS * 3), 4);
+        assert_eq!(cell_size(Some(1), MAX_CELLS * 8), 8);
+        assert_eq!(cell_size(None, u64::MAX), 1 << 52);
+    }
+
+    #[test]
+    fn cells_count_overlaps() {
+        let band = Band { label: "c".into(), intervals: vec![(0, 6), (4, 8), (12, 14)] };
+        assert_eq!(cells(&band, 4), [(4, 0), (4, 2), (0, 0), (2, 0)]);
+        assert_eq!(pixel((4, 0), 4), [0, 255, 0]);
+        assert_eq!(pixel((2, 0), 4), [0, 155, 0]);
+        assert_eq!(pixel((4, 2), 4), [155, 0, 0]);
+        assert_eq!(pixel((0, 0), 4), [0, 0, 0]);
+        // Indices at the very end of the space still land in a cell
+        let last = Band { label: "c".into(), intervals: vec![(u64::MAX - 2, u64::MAX)] };
+        assert_eq!(cells(&last, u64::MAX / 2), [(0, 0), (1, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn images() {
+        let dir = crate::scratch_dir("coverage");
+        let path = dir.join("map.ppm");
+        let bands = [
+            Band { label: "a (linear)".into(), intervals: vec![(0, 2), (1, 3)] },
+            Band { label: "b (linear)".into(), intervals: vec![(0, WIDTH as u64 + 1)] },
+        ];
+        let summary = write(&path, &bands, 1).unwrap();
+        assert_eq!(summary[0], "coverage: a (linear): 3 cells, 3 full, 0 never hashed, 1 hashed more than once");
+        assert_eq!(
+            summary[1],
+            format!(
+                "coverage: b (linear): {} cells, {} full, 0 never hashed, 0 hashed more than once",
+                WIDTH + 1,
+                WIDTH + 1
+            )
+        );
+        let image = std::fs::read(&path).unwrap();
+        let text = String::from_utf8_lossy(&image);
+        assert!(text.contains("# rows 0..1: a (linear)\n# rows 2..4: b (linear)\n"));
+        let header = format!("{} 4\n255\n", WIDTH);
+        let pixels = &image[text.find(&header).unwrap() + header.len()..];
+        assert_eq!(pixels.len(), WIDTH * 4 * 3);
+        assert_eq!(pixels[..9], [0, 255, 0, 255, 0, 0, 0, 255, 0]);
+        assert_eq!(pixels[9..12], PAST_END);
+        assert_eq!(pixels[WIDTH * 3..WIDTH * 3 + 3], PAST_END);
+        assert_eq!(pixels[WIDTH * 6..WIDTH * 6 + 3], [0, 255, 0]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,926 @@
+//! `daemon`: mines challenges submitted over HTTP, one at a time and highest priority
+//! first, so a fleet can be retargeted from one script. The search itself goes through
+//! `MinerBuilder`, solutions are printed to stdout like a normal run.
+//!
+//! Several participants can share one daemon: each client token belongs to a namespace,
+//! jobs are tagged with the namespace that submitted them, and a client only sees and
+//! controls its own jobs. The admin token sees everything. A client's priorities are capped
+//! at `MAX_CLIENT_PRIORITY`, so it can only put its own jobs behind the rest, and a job a
+//! client submitted is never handed to `--solo-on-solution`: that command is the operator's,
+//! and the job's challenge is the client's.
+//!
+//! A job whose `no_pre_mine_hour` window has closed fails instead of being mined, and a
+//! running one is stopped when its window closes. Under a campaign budget (see `campaign`)
+//! a job that would not fit in what is left of it is skipped. A job for another challenge_id
+//! than the one before logs which round parameters changed, difficulty first.
+//!
+//! Routes (all answer JSON; everything but the bare /status needs `Authorization: Bearer TOKEN`):
+//!   GET  /status            -> without a token only counts; with one the running job, queue
+//!                              and finished jobs in its namespace (all of them for the admin);
+//!                              the running job says how long its window stays open and
+//!                              whether that is less than its expected time to a solution
+//!   GET  /results           -> finished jobs in the token's namespace, with their solutions
+//!   GET  /rounds            -> per challenge_id in the token's namespace: jobs run, how many
+//!                              solved, cancelled, failed and skipped, hashes and seconds spent
+//!   POST /jobs              -> {"id": N}; body is a challenge with the snake_case keys of a
+//!                              run report's parameters, plus an optional "priority" (and
+//!                              "namespace" for the admin, default "default")
+//!   POST /jobs/N/cancel     -> 200, or 404 for an unknown, finished or foreign job
+//!   POST /jobs/N/priority   -> 200; body {"priority": P}, 409 once the job is running
+//!   GET  /payout            -> admin only: solo/pool mode, threshold and measured hashrate
+//!   POST /payout            -> admin only: body {"mode": "auto"|"solo"|"pool",
+//!                              "solo_below_secs": S}, either key optional; see `payout`
+//!   GET  /campaign          -> admin only: the campaign report, 404 without a budget
+
+use crate::campaign::{self, Campaign};
+use crate::payout::{Mode, Payout, Route};
+use crate::solution::Solution;
+use crate::{
+    format_duration, hash_preimage, identity, json, json_string, mask_zero_bits, miner::MinerBuilder, reason_phrase,
+    retry, sim, to_hex, unix_now, wait_for_stop, window_closes, Challenge, HashFn, Progress, Verify,
+    MAX_FALSE_POSITIVES,
+};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Largest request body accepted, far above any challenge
+const MAX_BODY: u64 = 64 * 1024;
+/// Finished jobs kept for /status; older ones are dropped
+const MAX_FINISHED: usize = 256;
+/// Challenges kept for /rounds; the one finished longest ago is dropped
+const MAX_ROUNDS: usize = 1024;
+/// Highest priority a client token may give a job; the admin's are not capped
+const MAX_CLIENT_PRIORITY: i64 = 0;
+
+struct Job {
+    id: u64,
+    namespace: String,
+    priority: i64,
+    challenge: Challenge,
+    /// Submitted with the admin token rather than a client's
+    by_admin: bool,
+}
+
+struct Running {
+    id: u64,
+    namespace: String,
+    challenge_id: String,
+    progress: Arc<Progress>,
+    started: Instant,
+    /// Unix time at which its `no_pre_mine_hour` window closes, if known
+    closes: Option<i64>,
+    /// Hashes expected per solution
+    expected: f64,
+}
+
+struct Finished {
+    id: u64,
+    namespace: String,
+    challenge_id: String,
+    /// "solved", "cancelled", "failed" or "skipped"
+    state: &'static str,
+    detail: String,
+    hashes: u64,
+    secs: f64,
+}
+
+/// Totals of the finished jobs for one challenge in one namespace.
+#[derive(Default)]
+struct Round {
+    jobs: u64,
+    solved: u64,
+    cancelled: u64,
+    failed: u64,
+    skipped: u64,
+    hashes: u64,
+    secs: f64,
+    /// Id of the last job finished, to pick the round to drop
+    last_job: u64,
+}
+
+struct Daemon {
+    admin_token: Option<String>,
+    /// (namespace, token) of each client
+    clients: Vec<(String, String)>,
+    next_id: u64,
+    queue: Vec<Job>,
+    running: Option<Running>,
+    finished: Vec<Finished>,
+    /// Keyed by (namespace, challenge_id)
+    rounds: BTreeMap<(String, String), Round>,
+    payout: Payout,
+    /// Hashes and seconds of every job run so far, for `--payout auto` and the campaign
+    mined: (u64, f64),
+    campaign: Option<Campaign>,
+}
+
+type Shared = Arc<(Mutex<Daemon>, Condvar)>;
+
+/// Compare without an early exit, so response times do not reveal a token prefix.
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Whose request this is: the admin, or the client owning one namespace.
+enum Scope {
+    Admin,
+    Namespace(String),
+}
+
+impl Scope {
+    fn allows(&self, namespace: &str) -> bool {
+        match self {
+            Scope::Admin => true,
+            Scope::Namespace(own) => own == namespace,
+        }
+    }
+}
+
+fn finished_json(f: &Finished) -> String {
+    format!(
+        "{{\"id\": {}, \"namespace\": {}, \"challenge_id\": {}, \"state\": \"{}\", \"detail\": {}, \"hashes\": {}, \"secs\": {:.1}}}",
+        f.id,
+        json_string(&f.namespace),
+        json_string(&f.challenge_id),
+        f.state,
+        json_string(&f.detail),
+        f.hashes,
+        f.secs
+    )
+}
+
+impl Daemon {
+    fn scope(&self, authorization: Option<&str>) -> Option<Scope> {
+        let given = authorization?.strip_prefix("Bearer ")?.trim();
+        if self.admin_token.as_deref().is_some_and(|t| token_matches(given, t)) {
+            return Some(Scope::Admin);
+        }
+        let client = self.clients.iter().find(|(_, token)| token_matches(given, token))?;
+        Some(Scope::Namespace(client.0.clone()))
+    }
+
+    /// Counts only, for callers without a token.
+    fn public_status(&self) -> String {
+        format!(
+            "{{\"running\": {}, \"queued\": {}, \"finished\": {}}}",
+            self.running.is_some(),
+            self.queue.len(),
+            self.finished.len()
+        )
+    }
+
+    fn status(&self, scope: &Scope) -> String {
+        let running = match self.running.as_ref().filter(|r| scope.allows(&r.namespace)) {
+            Some(r) => {
+                let secs = r.started.elapsed().as_secs_f64();
+                let eta = r.expected / (r.progress.hashes() as f64 / secs).max(1e-9);
+                let left = r.closes.map(|closes| (closes - unix_now()).max(0));
+                format!(
+                    concat!(
+                        "{{\"id\": {}, \"namespace\": {}, \"challenge_id\": {}, \"hashes\": {}, \"secs\": {:.1}, ",
+                        "\"window_left_secs\": {}, \"window_too_short\": {}}}"
+                    ),
+                    r.id,
+                    json_string(&r.namespace),
+                    json_string(&r.challenge_id),
+                    r.progress.hashes(),
+                    secs,
+                    left.map_or("null".to_string(), |left| left.to_string()),
+                    left.is_some_and(|left| eta > left as f64)
+                )
+            }
+            None => "null".to_string(),
+        };
+        let queued: Vec<String> = self
+            .queue
+            .iter()
+            .filter(|j| scope.allows(&j.namespace))
+            .map(|j| {
+                format!(
+                    "{{\"id\": {}, \"namespace\": {}, \"challenge_id\": {}, \"priority\": {}}}",
+                    j.id,
+                    json_string(&j.namespace),
+                    json_string(&j.challenge.challenge_id),
+                    j.priority
+                )
+            })
+            .collect();
+        format!(
+            "{{\"running\": {}, \"queued\": [{}], \"finished\": [{}]}}",
+            running,
+            queued.join(", "),
+            self.results(scope)
+        )
+    }
+
+    fn results(&self, scope: &Scope) -> String {
+        let finished: Vec<String> =
+            self.finished.iter().filter(|f| scope.allows(&f.namespace)).map(finished_json).collect();
+        finished.join(", ")
+    }
+
+    fn rounds(&self, scope: &Scope) -> String {
+        let rounds: Vec<String> = self
+            .rounds
+            .iter()
+            .filter(|((namespace, _), _)| scope.allows(namespace))
+            .map(|((namespace, challenge_id), r)| {
+                format!(
+                    concat!(
+                        "{{\"namespace\": {}, \"challenge_id\": {}, \"jobs\": {}, \"solved\": {}, ",
+                        "\"cancelled\": {}, \"failed\": {}, \"skipped\": {}, \"hashes\": {}, \"secs\": {:.1}}}"
+                    ),
+                    json_string(namespace),
+                    json_string(challenge_id),
+                    r.jobs,
+                    r.solved,
+                    r.cancelled,
+                    r.failed,
+                    r.skipped,
+                    r.hashes,
+                    r.secs
+                )
+            })
+            .collect();
+        format!("{{\"rounds\": [{}]}}", rounds.join(", "))
+    }
+
+    fn handle(&mut self, method: &str, path: &str, authorization: Option<&str>, body: &str) -> (u16, String) {
+        let scope = self.scope(authorization);
+        match (method, path, &scope) {
+            ("GET", "/status", None) => return (200, self.public_status()),
+            (_, _, None) => return (401, "{\"error\": \"missing or wrong bearer token\"}".to_string()),
+            ("GET", "/status", Some(scope)) => return (200, self.status(scope)),
+            ("GET", "/results", Some(scope)) => return (200, format!("{{\"finished\": [{}]}}", self.results(scope))),
+            ("GET", "/rounds", Some(scope)) => return (200, self.rounds(scope)),
+            (_, "/payout", Some(Scope::Namespace(_))) => {
+                return (403, "{\"error\": \"only the admin token manages payout\"}".to_string())
+            }
+            ("GET", "/payout", Some(_)) => return (200, self.payout.json(self.hashrate())),
+            ("GET", "/campaign", Some(Scope::Namespace(_))) => {
+                return (403, "{\"error\": \"only the admin token sees the campaign\"}".to_string())
+            }
+            ("GET", "/campaign", Some(_)) => match &self.campaign {
+                Some(campaign) => return (200, campaign.json(self.mined)),
+                None => return (404, "{\"error\": \"no campaign budget set\"}".to_string()),
+            },
+            ("POST", _, _) => {}
+            _ => return (404, "{\"error\": \"not found\"}".to_string()),
+        }
+        let scope = scope.unwrap();
+        let body = match json::parse(if body.trim().is_empty() { "{}" } else { body }) {
+            Ok(body) => body,
+            Err(e) => return (400, format!("{{\"error\": {}}}", json_string(&format!("invalid JSON: {}", e)))),
+        };
+        let priority = body.get("priority").and_then(json::Json::as_f64).map(|p| match scope {
+            Scope::Admin => p as i64,
+            Scope::Namespace(_) => (p as i64).min(MAX_CLIENT_PRIORITY),
+        });
+
+        let job_route = path.strip_prefix("/jobs/").and_then(|rest| rest.split_once('/'));
+        match (path, job_route.and_then(|(id, action)| Some((id.parse::<u64>().ok()?, action)))) {
+            ("/jobs", _) => match Challenge::from_json(&body).and_then(|c| Ok((c.difficulty_mask()?, c))) {
+                Ok((_, challenge)) => {
+                    let namespace = match &scope {
+                        Scope::Admin => body.get("namespace").and_then(json::Json::as_str).unwrap_or("default").to_string(),
+                        Scope::Namespace(own) => own.clone(),
+                    };
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    eprintln!("daemon: queued job {} for challenge {} in {}", id, challenge.challenge_id, namespace);
+                    let by_admin = matches!(scope, Scope::Admin);
+                    self.queue.push(Job { id, namespace, priority: priority.unwrap_or(0), challenge, by_admin });
+                    (200, format!("{{\"id\": {}}}", id))
+                }
+                Err(e) => (400, format!("{{\"error\": {}}}", json_string(&e))),
+            },
+            ("/payout", _) => self.set_payout(&body),
+            (_, Some((id, "cancel"))) => {
+                if let Some(i) = self.queue.iter().position(|j| j.id == id && scope.allows(&j.namespace)) {
+                    let job = self.queue.remove(i);
+                    self.finish(Finished {
+                        id,
+                        namespace: job.namespace,
+                        challenge_id: job.challenge.challenge_id.to_string(),
+                        state: "cancelled",
+                        detail: "cancelled before it started".to_string(),
+                        hashes: 0,
+                        secs: 0.0,
+                    });
+                    return (200, "{}".to_string());
+                }
+                match self.running.as_ref().filter(|r| r.id == id && scope.allows(&r.namespace)) {
+                    Some(running) => {
+                        running.progress.stop.store(true, std::sync::atomic::Ordering::Release);
+                        (200, "{}".to_string())
+                    }
+                    None => (404, "{\"error\": \"unknown or finished job\"}".to_string()),
+                }
+            }
+            (_, Some((id, "priority"))) => {
+                let Some(priority) = priority else {
+                    return (400, "{\"error\": \"body needs a numeric priority\"}".to_string());
+                };
+                match self.queue.iter_mut().find(|j| j.id == id && scope.allows(&j.namespace)) {
+                    Some(job) => {
+                        job.priority = priority;
+                        (200, "{}".to_string())
+                    }
+                    None if self.running.as_ref().is_some_and(|r| r.id == id && scope.allows(&r.namespace)) => {
+                        (409, "{\"error\": \"job is already running\"}".to_string())
+                    }
+                    None => (404, "{\"error\": \"unknown or finished job\"}".to_string()),
+                }
+            }
+            _ => (404, "{\"error\": \"not found\"}".to_string()),
+        }
+    }
+
+    /// Average over the jobs run so far, once they add up to a second.
+    fn hashrate(&self) -> Option<f64> {
+        let (hashes, secs) = self.mined;
+        (secs >= 1.0).then(|| hashes as f64 / secs)
+    }
+
+    fn set_payout(&mut self, body: &json::Json) -> (u16, String) {
+        let error = |e: &str| (400, format!("{{\"error\": {}}}", json_string(e)));
+        let mode = match body.get("mode").and_then(json::Json::as_str) {
+            Some(name) => match Mode::from_str(name, false) {
+                Ok(mode) => Some(mode),
+                Err(e) => return error(&format!("mode: {}", e)),
+            },
+            None => self.payout.mode,
+        };
+        if let Some(Err(e)) = mode.map(|mode| self.payout.check(mode)) {
+            return error(&e);
+        }
+        let solo_below = body.get("solo_below_secs").and_then(json::Json::as_f64).unwrap_or(self.payout.solo_below);
+        if solo_below.is_nan() || solo_below < 0.0 {
+            return error("solo_below_secs must be a number of seconds");
+        }
+        (self.payout.mode, self.payout.solo_below) = (mode, solo_below);
+        let json = self.payout.json(self.hashrate());
+        eprintln!("daemon: payout is now {}", json);
+        (200, json)
+    }
+
+    fn finish(&mut self, finished: Finished) {
+        let key = (finished.namespace.clone(), finished.challenge_id.clone());
+        if !self.rounds.contains_key(&key) && self.rounds.len() == MAX_ROUNDS {
+            let oldest = self.rounds.iter().min_by_key(|(_, r)| r.last_job).map(|(k, _)| k.clone());
+            self.rounds.remove(&oldest.unwrap_or_default());
+        }
+        let round = self.rounds.entry(key).or_default();
+        round.jobs += 1;
+        match finished.state {
+            "solved" => round.solved += 1,
+            "cancelled" => round.cancelled += 1,
+            "skipped" => round.skipped += 1,
+            _ => round.failed += 1,
+        }
+        round.hashes += finished.hashes;
+        round.secs += finished.secs;
+        round.last_job = finished.id;
+        if let Some(campaign) = &mut self.campaign {
+            campaign.finish(self.mined, finished.state);
+        }
+
+        if self.finished.len() == MAX_FINISHED {
+            self.finished.remove(0);
+        }
+        self.finished.push(finished);
+    }
+
+    /// The queued job to run next: highest priority, then oldest.
+    fn take_next(&mut self) -> Option<Job> {
+        let i = (0..self.queue.len()).max_by_key(|&i| (self.queue[i].priority, std::cmp::Reverse(self.queue[i].id)))?;
+        Some(self.queue.remove(i))
+    }
+}
+
+/// The parameters that differ between the rounds of `previous` and `next`, as "field a -> b".
+fn round_diff(previous: &Challenge, next: &Challenge) -> Vec<String> {
+    let mut changes = Vec::new();
+    if previous.difficulty.to_string() != next.difficulty.to_string() {
+        let bits = |c: &Challenge| c.difficulty_mask().map(mask_zero_bits).unwrap_or(0) as i64;
+        changes.push(format!(
+            "difficulty {} -> {} ({:+} zero bits)",
+            previous.difficulty,
+            next.difficulty,
+            bits(next) - bits(previous)
+        ));
+    }
+    let fields = [
+        ("no_pre_mine", previous.no_pre_mine.to_string(), next.no_pre_mine.to_string()),
+        ("latest_submission", previous.latest_submission.to_string(), next.latest_submission.to_string()),
+        ("no_pre_mine_hour", previous.no_pre_mine_hour.to_string(), next.no_pre_mine_hour.to_string()),
+        ("address", previous.address.to_string(), next.address.to_string()),
+    ];
+    for (name, before, after) in fields {
+        if before != after {
+            changes.push(format!("{} {} -> {}", name, before, after));
+        }
+    }
+    changes
+}
+
+/// Mine queued jobs one after another, forever.
+fn run_jobs(shared: &Shared, hash: HashFn, threads: usize) {
+    let (lock, wakeup) = &**shared;
+    let mut previous: Option<Challenge> = None;
+    loop {
+        let mut daemon = lock.lock().unwrap();
+        let mut job = loop {
+            match daemon.take_next() {
+                Some(job) => break job,
+                None => daemon = wakeup.wait(daemon).unwrap(),
+            }
+        };
+        let (challenge_id, namespace) = (job.challenge.challenge_id.to_string(), job.namespace.clone());
+        let target = job.challenge.difficulty_mask().and_then(|m| Ok((m, job.challenge.prefix_layout()?)));
+        let (difficulty_mask, layout) = match target {
+            Ok(target) => target,
+            Err(detail) => {
+                daemon.finish(Finished { id: job.id, namespace, challenge_id, state: "failed", detail, hashes: 0, secs: 0.0 });
+                continue;
+            }
+        };
+        let closes = window_closes(&job.challenge);
+        if closes.is_some_and(|closes| closes <= unix_now()) {
+            let detail = "the no_pre_mine_hour window has closed".to_string();
+            daemon.finish(Finished { id: job.id, namespace, challenge_id, state: "failed", detail, hashes: 0, secs: 0.0 });
+            continue;
+        }
+        let expected = 2f64.powi(mask_zero_bits(difficulty_mask) as i32);
+        let refused = daemon.campaign.as_ref().and_then(|c| c.refuse(daemon.mined, expected, daemon.hashrate()));
+        if let Some(detail) = refused {
+            eprintln!("daemon: skipping job {}: {}", job.id, detail);
+            let state = "skipped";
+            daemon.finish(Finished { id: job.id, namespace, challenge_id, state, detail, hashes: 0, secs: 0.0 });
+            continue;
+        }
+        let mut submit = None;
+        if let Some((route, reason)) = daemon.payout.route(expected, daemon.hashrate()) {
+            let (template, address) = daemon.payout.target(route);
+            if let Some(address) = address {
+                job.challenge.address = address.clone();
+            }
+            eprintln!("daemon: job {} goes {} ({})", job.id, route.name(), reason);
+            if route == Route::Solo && !job.by_admin {
+                eprintln!("daemon: job {} came from a client, so --solo-on-solution does not run for it", job.id);
+            } else {
+                submit = template.map(|t| (route, t.to_string(), daemon.payout.submitted.clone()));
+            }
+        }
+        if let (Some(closes), Some(rate)) = (closes, daemon.hashrate()) {
+            let (eta, left) = (expected / rate.max(1e-9), (closes - unix_now()) as f64);
+            if eta > left {
+                eprintln!(
+                    "daemon: job {} expects a solution in {} but its window closes in {}",
+                    job.id,
+                    format_duration(eta),
+                    format_duration(left)
+                );
+            }
+        }
+        let suffix = job.challenge.suffix();
+        let mut miner = MinerBuilder::new(hash)
+            .threads(threads)
+            .difficulty(difficulty_mask, layout)
+            .hash_len(job.challenge.hash_len)
+            .preimage_parts(&[&suffix])
+            .nonce_encoding(job.challenge.nonce_encoding)
+            .verify(Some(Verify {
+                reference: sim::reference(hash_preimage),
+                one_in: None,
+                max_false_positives: MAX_FALSE_POSITIVES,
+            }))
+            .build();
+        let progress = Arc::clone(miner.progress());
+        let budget_left = daemon.campaign.as_ref().map(|c| c.left(daemon.mined));
+        let (started, wall_started) = (Instant::now(), SystemTime::now());
+        daemon.running = Some(Running {
+            id: job.id,
+            namespace: namespace.clone(),
+            challenge_id: challenge_id.clone(),
+            progress: Arc::clone(&progress),
+            started,
+            closes,
+            expected,
+        });
+        drop(daemon);
+
+        eprintln!("daemon: starting job {} for challenge {} in {}", job.id, challenge_id, namespace);
+        if let Some(previous) = previous.as_ref().filter(|p| p.challenge_id.to_string() != challenge_id) {
+            let changes = round_diff(previous, &job.challenge);
+            eprintln!(
+                "daemon: round {} after {}: {}",
+                challenge_id,
+                previous.challenge_id,
+                if changes.is_empty() { "same parameters".to_string() } else { changes.join(", ") }
+            );
+        }
+        previous = Some(job.challenge.clone());
+        miner.start();
+        let window_closed = Arc::new(AtomicBool::new(false));
+        if let Some(closes) = closes {
+            let (progress, window_closed) = (Arc::clone(&progress), Arc::clone(&window_closed));
+            std::thread::spawn(move || {
+                let left = Duration::from_secs((closes - unix_now()).max(0) as u64);
+                if !wait_for_stop(&progress.stop, left) {
+                    window_closed.store(true, Ordering::Release);
+                    progress.stop.store(true, Ordering::Release);
+                }
+            });
+        }
+        let budget_spent = Arc::new(AtomicBool::new(false));
+        if let Some(left) = budget_left {
+            let (progress, budget_spent) = (Arc::clone(&progress), Arc::clone(&budget_spent));
+            std::thread::spawn(move || campaign::enforce(left, &progress, &budget_spent));
+        }
+        let result = miner.join();
+
+        let mut submitted = String::new();
+        if let (Some(solution), Some((route, template, ledger))) =
+            (result.as_ref().ok().and_then(|s| s.first()), &submit)
+        {
+            let (encoding, address) = (job.challenge.nonce_encoding, &job.challenge.address);
+            let solution = Solution {
+                found: Some(wall_started + solution.elapsed),
+                worker_id: Some(identity::current().id.clone()),
+                thread: Some(solution.thread_id),
+                worker_hashes: Some(solution.hashes_tried),
+                ..Solution::new(encoding, solution.nonce, &solution.hash, &challenge_id, address)
+            };
+            let sent = retry::retry("on-solution", || solution.submit(template, ledger).map_err(std::io::Error::other));
+            submitted = match sent {
+                Ok(_) => format!(", {} submission done", route.name()),
+                Err(e) => format!(", {} submission failed: {}", route.name(), e),
+            };
+        }
+
+        let mut daemon = lock.lock().unwrap();
+        daemon.running = None;
+        let (state, detail) = match result {
+            Ok(solutions) => match solutions.first() {
+                Some(solution) => {
+                    let nonce = job.challenge.nonce_encoding.text(solution.nonce);
+                    println!("{}", nonce);
+                    ("solved", format!("nonce {} hash {}{}", nonce, to_hex(&solution.hash), submitted))
+                }
+                None if window_closed.load(Ordering::Acquire) => {
+                    ("failed", "stopped when the no_pre_mine_hour window closed".to_string())
+                }
+                None if budget_spent.load(Ordering::Acquire) => {
+                    ("failed", "stopped when the campaign budget ran out".to_string())
+                }
+                None => ("cancelled", "cancelled while running".to_string()),
+            },
+            Err(e) => ("failed", e.to_string()),
+        };
+        eprintln!("daemon: job {} for challenge {} in {} {}: {}", job.id, challenge_id, namespace, state, detail);
+        let (hashes, secs) = (progress.hashes(), started.elapsed().as_secs_f64());
+        daemon.mined = (daemon.mined.0 + hashes, daemon.mined.1 + secs);
+        daemon.finish(Finished { id: job.id, namespace, challenge_id, state, detail, hashes, secs });
+    }
+}
+
+/// Accept jobs on `listen` from the admin and the `clients` (namespace, token), mine them
+/// with `threads` workers and submit solutions as `payout` says, within the `campaign`
+/// budget if there is one; only fails if the listener cannot be set up.
+#[allow(clippy::too_many_arguments)]
+pub fn serve(
+    listen: &str,
+    admin_token: Option<String>,
+    clients: Vec<(String, String)>,
+    hash: HashFn,
+    threads: usize,
+    payout: Payout,
+    campaign: Option<Campaign>,
+) -> std::io::Result<()> {
+    let listener = crate::offline::network()?.listen(listen)?;
+    eprintln!("daemon: listening on {}, {} client namespaces", listener.local_addr()?, clients.len());
+    let daemon = Daemon {
+        admin_token,
+        clients,
+        next_id: 1,
+        queue: Vec::new(),
+        running: None,
+        finished: Vec::new(),
+        rounds: BTreeMap::new(),
+        payout,
+        mined: (0, 0.0),
+        campaign,
+    };
+    let shared: Shared = Arc::new((Mutex::new(daemon), Condvar::new()));
+    let worker = Arc::clone(&shared);
+    std::thread::spawn(move || run_jobs(&worker, hash, threads));
+
+    for stream in listener.incoming() {
+        if let Err(e) = stream.and_then(|stream| handle_connection(stream, &shared)) {
+            eprintln!("daemon: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, shared: &Shared) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let (mut content_length, mut authorization) = (0u64, None);
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "authorization" => authorization = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    let mut body = String::new();
+    reader.by_ref().take(content_length.min(MAX_BODY)).read_to_string(&mut body)?;
+
+    let (status, body) = {
+        let (lock, wakeup) = &**shared;
+        let response = lock.lock().unwrap().handle(method, path, authorization.as_deref(), &body);
+        wakeup.notify_one();
+        response
+    };
+    write!(
+        &stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason_phrase(status),
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+
+    const ADMIN: Option<&str> = Some("Bearer admin-secret");
+    const ALICE: Option<&str> = Some("Bearer alice-secret");
+    const BOB: Option<&str> = Some("Bearer bob-secret");
+
+    fn daemon() -> Daemon {
+        let payout = Payout {
+            mode: None,
+            solo_below: 60.0,
+            solo_on_solution: Some("solo {nonce}".to_string()),
+            pool_on_solution: None,
+            pool_address: None,
+            submitted: PathBuf::from("submitted"),
+        };
+        Daemon {
+            admin_token: Some("admin-secret".to_string()),
+            clients: vec![
+                ("alice".to_string(), "alice-secret".to_string()),
+                ("bob".to_string(), "bob-secret".to_string()),
+            ],
+            next_id: 1,
+            queue: Vec::new(),
+            running: None,
+            finished: Vec::new(),
+            rounds: BTreeMap::new(),
+            payout,
+            mined: (0, 0.0),
+            campaign: None,
+        }
+    }
+
+    fn job(challenge_id: &str, extra: &str) -> String {
+        format!(
+            "{{\"address\": \"addr_test1qpzry9x8gf2tvdw0s3jn4syst5\", \"challenge_id\": \"{}\", \
+             \"difficulty\": \"0fffffff\", \"no_pre_mine\": \"00112233\", \
+             \"latest_submission\": \"2099-12-31T23:59:59.000Z\", \"no_pre_mine_hour\": \"7\"{}}}",
+            challenge_id, extra
+        )
+    }
+
+    fn challenge(body: &str) -> Challenge {
+        Challenge::from_json(&json::parse(body).unwrap()).unwrap()
+    }
+
+    /// The ids of the jobs listed under `key` of a /status reply.
+    fn ids(status: &str, key: &str) -> Vec<f64> {
+        let status = json::parse(status).unwrap();
+        let Some(json::Json::Array(jobs)) = status.get(key) else { panic!("no {} in {:?}", key, status) };
+        jobs.iter().filter_map(|job| job.get("id").and_then(json::Json::as_f64)).collect()
+    }
+
+    #[test]
+    fn tokens() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret") && !token_matches("secret", "secret2") && !token_matches("", "x"));
+        let mut daemon = daemon();
+        assert_eq!(
+            daemon.handle("GET", "/status", None, ""),
+            (200, "{\"running\": false, \"queued\": 0, \"finished\": 0}".to_string())
+        );
+        // A bad token on /status gets the counts, like no token at all
+        assert_eq!(
+            daemon.handle("GET", "/status", Some("Bearer wrong"), ""),
+            daemon.handle("GET", "/status", None, "")
+        );
+        assert_eq!(daemon.handle("GET", "/results", Some("alice-secret"), "").0, 401);
+        assert_eq!(daemon.handle("GET", "/results", None, "").0, 401);
+        assert_eq!(daemon.handle("POST", "/jobs", None, &job("c", "")).0, 401);
+        assert!(matches!(daemon.scope(Some("Bearer  admin-secret ")), Some(Scope::Admin)));
+        assert_eq!(daemon.handle("GET", "/nowhere", ALICE, "").0, 404);
+        assert_eq!(daemon.handle("DELETE", "/jobs/1", ADMIN, "").0, 404);
+    }
+
+    #[test]
+    fn clients_only_see_their_own_jobs() {
+        let mut daemon = daemon();
+        assert_eq!(
+            daemon.handle("POST", "/jobs", ALICE, &job("c1", ", \"priority\": 5, \"namespace\": \"bob\"")),
+            (200, "{\"id\": 1}".to_string())
+        );
+        assert_eq!(
+            daemon.handle("POST", "/jobs", ADMIN, &job("c1", ", \"priority\": 5, \"namespace\": \"bob\"")).1,
+            "{\"id\": 2}"
+        );
+        assert_eq!(daemon.handle("POST", "/jobs", ADMIN, &job("c2", "")).1, "{\"id\": 3}");
+        // A client cannot pick another namespace or jump the queue
+        assert_eq!(
+            (daemon.queue[0].namespace.as_str(), daemon.queue[0].priority, daemon.queue[0].by_admin),
+            ("alice", 0, false)
+        );
+        assert_eq!(ids(&daemon.handle("GET", "/status", ALICE, "").1, "queued"), [1.0]);
+        assert_eq!(ids(&daemon.handle("GET", "/status", BOB, "").1, "queued"), [2.0]);
+        assert_eq!(ids(&daemon.handle("GET", "/status", ADMIN, "").1, "queued"), [1.0, 2.0, 3.0]);
+        // Highest priority first, then the oldest
+        let order: Vec<u64> = std::iter::from_fn(|| daemon.take_next().map(|job| job.id)).collect();
+        assert_eq!(order, [2, 1, 3]);
+    }
+
+    #[test]
+    fn cancels_and_priorities() {
+        let mut daemon = daemon();
+        daemon.handle("POST", "/jobs", ALICE, &job("c1", ""));
+        daemon.handle("POST", "/jobs", ALICE, &job("c1", ""));
+        assert_eq!(daemon.handle("POST", "/jobs/1/cancel", BOB, "").0, 404);
+        assert_eq!(daemon.handle("POST", "/jobs/1/priority", ALICE, "{}").0, 400);
+        assert_eq!(daemon.handle("POST", "/jobs/1/priority", ALICE, "{\"priority\": 9}").0, 200);
+        assert_eq!(daemon.queue[0].priority, 0);
+        assert_eq!(daemon.handle("POST", "/jobs/1/priority", ADMIN, "{\"priority\": 9}").0, 200);
+        assert_eq!(daemon.queue[0].priority, 9);
+        assert_eq!(daemon.handle("POST", "/jobs/1/cancel", ALICE, ""), (200, "{}".to_string()));
+        assert_eq!(daemon.handle("POST", "/jobs/1/cancel", ALICE, "").0, 404);
+        assert_eq!(daemon.handle("POST", "/jobs/x/cancel", ALICE, "").0, 404);
+        let results = daemon.handle("GET", "/results", ALICE, "").1;
+        assert!(
+            results.contains("\"state\": \"cancelled\", \"detail\": \"cancelled before it started\""),
+            "{}",
+            results
+        );
+        assert!(daemon.handle("GET", "/results", BOB, "").1.ends_with("[]}"));
+        let rounds = daemon.handle("GET", "/rounds", ALICE, "").1;
+        assert!(
+            rounds.contains("\"challenge_id\": \"c1\", \"jobs\": 1, \"solved\": 0, \"cancelled\": 1"),
+            "{}",
+            rounds
+        );
+
+        let job = daemon.take_next().unwrap();
+        let progress = Arc::new(Progress::new(1));
+        daemon.running = Some(Running {
+            id: job.id,
+            namespace: job.namespace,
+            challenge_id: job.challenge.challenge_id.to_string(),
+            progress: Arc::clone(&progress),
+            started: Instant::now(),
+            closes: Some(unix_now() + 60),
+            expected: 1e12,
+        });
+        assert_eq!(daemon.handle("POST", "/jobs/2/priority", ALICE, "{\"priority\": -1}").0, 409);
+        let status = json::parse(&daemon.handle("GET", "/status", ALICE, "").1).unwrap();
+        let running = status.get("running").unwrap();
+        assert_eq!(running.get("window_too_short"), Some(&json::Json::Bool(true)));
+        assert_eq!(daemon.handle("GET", "/status", BOB, "").1, "{\"running\": null, \"queued\": [], \"finished\": []}");
+        assert_eq!(daemon.handle("POST", "/jobs/2/cancel", BOB, "").0, 404);
+        assert!(!progress.stop.load(Ordering::Acquire));
+        assert_eq!(daemon.handle("POST", "/jobs/2/cancel", ALICE, "").0, 200);
+        assert!(progress.stop.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn bad_requests() {
+        let mut daemon = daemon();
+        let (status, body) = daemon.handle("POST", "/jobs", ADMIN, "{\"challenge_id\": ");
+        assert_eq!(status, 400);
+        assert!(body.starts_with("{\"error\": \"invalid JSON: "), "{}", body);
+        assert_eq!(
+            daemon.handle("POST", "/jobs", ADMIN, "{}"),
+            (400, "{\"error\": \"parameter address missing\"}".to_string())
+        );
+        let bad_difficulty = job("c", "").replace("0fffffff", "0fffffffff");
+        assert_eq!(daemon.handle("POST", "/jobs", ADMIN, &bad_difficulty).0, 200);
+        assert_eq!(daemon.handle("POST", "/jobs", ADMIN, &job("c", "").replace("0fffffff", "zz")).0, 400);
+        assert_eq!(daemon.handle("POST", "/elsewhere", ADMIN, "").0, 404);
+        assert!(daemon.queue.len() == 1 && daemon.next_id == 2);
+    }
+
+    #[test]
+    fn payout_is_the_admins() {
+        let mut daemon = daemon();
+        assert_eq!(daemon.handle("GET", "/payout", ALICE, "").0, 403);
+        assert_eq!(daemon.handle("POST", "/payout", ALICE, "{\"mode\": \"solo\"}").0, 403);
+        assert_eq!(daemon.handle("GET", "/campaign", ALICE, "").0, 403);
+        assert_eq!(daemon.handle("GET", "/campaign", ADMIN, "").0, 404);
+        let (status, body) = daemon.handle("POST", "/payout", ADMIN, "{\"mode\": \"pool\"}");
+        assert_eq!(
+            (status, body.as_str()),
+            (400, "{\"error\": \"pool payout needs --pool-on-solution and --pool-address\"}")
+        );
+        assert!(daemon.handle("POST", "/payout", ADMIN, "{\"mode\": \"all\"}").1.starts_with("{\"error\": \"mode: "));
+        let negative = daemon.handle("POST", "/payout", ADMIN, "{\"solo_below_secs\": -1}");
+        assert_eq!(negative, (400, "{\"error\": \"solo_below_secs must be a number of seconds\"}".to_string()));
+        daemon.mined = (5000, 2.0);
+        let (status, body) = daemon.handle("POST", "/payout", ADMIN, "{\"mode\": \"solo\", \"solo_below_secs\": 90}");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            "{\"mode\": \"solo\", \"solo_below_secs\": 90, \"solo\": true, \"pool\": false, \"hashrate\": 2500.0}"
+        );
+        assert_eq!(daemon.handle("GET", "/payout", ADMIN, "").1, body);
+    }
+
+    #[test]
+    fn finished_jobs_are_capped() {
+        let mut daemon = daemon();
+        for id in 0..MAX_FINISHED as u64 + 2 {
+            let state = if id % 2 == 0 { "solved" } else { "failed" };
+            let (namespace, challenge_id, detail) = ("default".to_string(), "c".to_string(), String::new());
+            daemon.finish(Finished { id, namespace, challenge_id, state, detail, hashes: 10, secs: 0.5 });
+        }
+        assert_eq!(daemon.finished.len(), MAX_FINISHED);
+        assert_eq!(daemon.finished[0].id, 2);
+        let round = &daemon.rounds[&("default".to_string(), "c".to_string())];
+        assert_eq!((round.jobs, round.solved, round.failed, round.hashes), (258, 129, 129, 2580));
+    }
+
+    #[test]
+    fn round_changes() {
+        let before = challenge(&job("c1", ""));
+        assert!(round_diff(&before, &before).is_empty());
+        let after = challenge(&job("c2", "").replace("0fffffff", "00ffffff").replace("\"7\"", "\"8\""));
+        assert_eq!(
+            round_diff(&before, &after),
+            ["difficulty 0fffffff -> 00ffffff (+4 zero bits)", "no_pre_mine_hour 7 -> 8"]
+        );
+    }
+
+    #[test]
+    fn connections() {
+        let shared: Shared = Arc::new((Mutex::new(daemon()), Condvar::new()));
+        let exchange = |request: String| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            client.write_all(request.as_bytes()).unwrap();
+            client.shutdown(std::net::Shutdown::Write).unwrap();
+            handle_connection(listener.accept().unwrap().0, &shared).unwrap();
+            let mut reply = String::new();
+            client.read_to_string(&mut reply).unwrap();
+            reply
+        };
+        let body = job("c1", "");
+        let reply = exchange(format!(
+            "POST /jobs HTTP/1.1\r\nHost: d\r\nAUTHORIZATION: Bearer alice-secret\r\ncontent-length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n"), "{}", reply);
+        assert!(reply.ends_with("\r\n\r\n{\"id\": 1}"), "{}", reply);
+        let reply = exchange("GET /status HTTP/1.1\r\n\r\n".to_string());
+        assert!(reply.ends_with("{\"running\": false, \"queued\": 1, \"finished\": 0}"), "{}", reply);
+        let reply = exchange("GET /results HTTP/1.1\r\nAuthorization: Bearer nope\r\n\r\n".to_string());
+        assert!(reply.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{}", reply);
+        // A body cut short by its sender is read as far as it goes
+        let reply = exchange(
+            "POST /jobs HTTP/1.1\r\nAuthorization: Bearer bob-secret\r\nContent-Length: 90\r\n\r\n{\"a\"".to_string(),
+        );
+        assert!(reply.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", reply);
+    }
+}
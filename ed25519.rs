@@ -0,0 +1,575 @@
+//! Ed25519 (RFC 8032) for solution receipts. Anything that touches the secret seed, signing
+//! and deriving the public key, runs in the `openssl` command (1.1.1 or later), so the key
+//! only meets a vetted constant-time implementation; the tree has no manifest to add a
+//! crypto crate to. Verifying a receipt uses public values only and stays here, so
+//! `verify-receipt` needs no OpenSSL: SHA-512 and TweetNaCl's field arithmetic, sixteen
+//! 16-bit limbs in i64s. Both halves are checked against the RFC's vectors by `selftest`.
+
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
+const SHA512_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+fn sha512_block(state: &mut [u64; 8], block: &[u8]) {
+    let mut w = [0u64; 80];
+    for (i, word) in block.chunks_exact(8).enumerate() {
+        w[i] = u64::from_be_bytes(word.try_into().unwrap());
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..80 {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA512_K[i]).wrapping_add(w[i]);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let t2 = s0.wrapping_add((a & b) ^ (a & c) ^ (b & c));
+        (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+    }
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// SHA-512 of the concatenation of `parts`.
+pub fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    let mut state = SHA512_IV;
+    let mut buffer = Vec::with_capacity(128);
+    let mut len = 0u128;
+    for part in parts {
+        len += part.len() as u128;
+        buffer.extend_from_slice(part);
+        let full = buffer.len() / 128 * 128;
+        for block in buffer[..full].chunks_exact(128) {
+            sha512_block(&mut state, block);
+        }
+        buffer.drain(..full);
+    }
+    buffer.push(0x80);
+    while buffer.len() % 128 != 112 {
+        buffer.push(0);
+    }
+    buffer.extend_from_slice(&(len * 8).to_be_bytes());
+    for block in buffer.chunks_exact(128) {
+        sha512_block(&mut state, block);
+    }
+    let mut out = [0u8; 64];
+    for (chunk, s) in out.chunks_exact_mut(8).zip(state) {
+        chunk.copy_from_slice(&s.to_be_bytes());
+    }
+    out
+}
+
+/// An element of GF(2^255 - 19).
+type Gf = [i64; 16];
+
+const GF0: Gf = [0; 16];
+const GF1: Gf = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// The curve constant d, 2d, the base point's coordinates and sqrt(-1)
+const D: Gf = [
+    0x78a3, 0x1359, 0x4dca, 0x75eb, 0xd8ab, 0x4141, 0x0a4d, 0x0070, 0xe898, 0x7779, 0x4079, 0x8cc7, 0xfe73, 0x2b6f,
+    0x6cee, 0x5203,
+];
+const D2: Gf = [
+    0xf159, 0x26b2, 0x9b94, 0xebd6, 0xb156, 0x8283, 0x149a, 0x00e0, 0xd130, 0xeef3, 0x80f2, 0x198e, 0xfce7, 0x56df,
+    0xd9dc, 0x2406,
+];
+const X: Gf = [
+    0xd51a, 0x8f25, 0x2d60, 0xc956, 0xa7b2, 0x9525, 0xc760, 0x692c, 0xdc5c, 0xfdd6, 0xe231, 0xc0a4, 0x53fe, 0xcd6e,
+    0x36d3, 0x2169,
+];
+const Y: Gf = [
+    0x6658, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666,
+    0x6666, 0x6666,
+];
+const I: Gf = [
+    0xa0b0, 0x4a0e, 0x1b27, 0xc4ee, 0xe478, 0xad2f, 0x1806, 0x2f43, 0xd7a7, 0x3dfb, 0x0099, 0x2b4d, 0xdf0b, 0x4fc1,
+    0x2480, 0x2b83,
+];
+/// The group order l, little-endian
+const L: [i64; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+];
+
+fn carry(o: &mut Gf) {
+    for i in 0..16 {
+        o[i] += 1 << 16;
+        let c = o[i] >> 16;
+        if i < 15 {
+            o[i + 1] += c - 1;
+        } else {
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c << 16;
+    }
+}
+
+/// Swap `p` and `q` when `b` is 1, without branching on it.
+fn select(p: &mut Gf, q: &mut Gf, b: i64) {
+    let mask = !(b - 1);
+    for i in 0..16 {
+        let t = mask & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+fn pack_gf(n: &Gf) -> [u8; 32] {
+    let mut t = *n;
+    carry(&mut t);
+    carry(&mut t);
+    carry(&mut t);
+    for _ in 0..2 {
+        let mut m = GF0;
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        select(&mut t, &mut m, 1 - b);
+    }
+    let mut out = [0u8; 32];
+    for i in 0..16 {
+        out[2 * i] = t[i] as u8;
+        out[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    out
+}
+
+fn unpack_gf(n: &[u8; 32]) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = n[2 * i] as i64 + ((n[2 * i + 1] as i64) << 8);
+    }
+    o[15] &= 0x7fff;
+    o
+}
+
+fn parity(a: &Gf) -> u8 {
+    pack_gf(a)[0] & 1
+}
+
+fn add(a: &Gf, b: &Gf) -> Gf {
+    std::array::from_fn(|i| a[i] + b[i])
+}
+
+fn sub(a: &Gf, b: &Gf) -> Gf {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+fn mul(a: &Gf, b: &Gf) -> Gf {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut o: Gf = t[..16].try_into().unwrap();
+    carry(&mut o);
+    carry(&mut o);
+    o
+}
+
+/// `i`^(p-2), the inverse.
+fn invert(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..=253).rev() {
+        c = mul(&c, &c);
+        if a != 2 && a != 4 {
+            c = mul(&c, i);
+        }
+    }
+    c
+}
+
+/// `i`^((p-5)/8), for square roots.
+fn pow2523(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..=250).rev() {
+        c = mul(&c, &c);
+        if a != 1 {
+            c = mul(&c, i);
+        }
+    }
+    c
+}
+
+/// A point in extended coordinates (X, Y, Z, T).
+type Point = [Gf; 4];
+
+fn point_add(p: &mut Point, q: &Point) {
+    let a = mul(&sub(&p[1], &p[0]), &sub(&q[1], &q[0]));
+    let b = mul(&add(&p[0], &p[1]), &add(&q[0], &q[1]));
+    let c = mul(&mul(&p[3], &q[3]), &D2);
+    let d = mul(&p[2], &q[2]);
+    let d = add(&d, &d);
+    let (e, f, g, h) = (sub(&b, &a), sub(&d, &c), add(&d, &c), add(&b, &a));
+    *p = [mul(&e, &f), mul(&h, &g), mul(&g, &f), mul(&e, &h)];
+}
+
+fn pack_point(p: &Point) -> [u8; 32] {
+    let zi = invert(&p[2]);
+    let mut out = pack_gf(&mul(&p[1], &zi));
+    out[31] ^= parity(&mul(&p[0], &zi)) << 7;
+    out
+}
+
+/// `s` times `q`, by a ladder over the bits of `s`.
+fn scalar_mult(mut q: Point, s: &[u8; 32]) -> Point {
+    let mut p = [GF0, GF1, GF1, GF0];
+    for i in (0..256).rev() {
+        let b = ((s[i / 8] >> (i & 7)) & 1) as i64;
+        for (a, c) in p.iter_mut().zip(q.iter_mut()) {
+            select(a, c, b);
+        }
+        point_add(&mut q, &p);
+        let p2 = p;
+        point_add(&mut p, &p2);
+        for (a, c) in p.iter_mut().zip(q.iter_mut()) {
+            select(a, c, b);
+        }
+    }
+    p
+}
+
+fn scalar_base(s: &[u8; 32]) -> Point {
+    scalar_mult([X, Y, GF1, mul(&X, &Y)], s)
+}
+
+/// `x` modulo l, as 32 little-endian bytes.
+fn mod_l(x: &mut [i64; 64]) -> [u8; 32] {
+    for i in (32..64).rev() {
+        let mut carry = 0;
+        for j in i - 32..i - 12 {
+            x[j] += carry - 16 * x[i] * L[j - (i - 32)];
+            carry = (x[j] + 128) >> 8;
+            x[j] -= carry << 8;
+        }
+        x[i - 12] += carry;
+        x[i] = 0;
+    }
+    let mut carry = 0;
+    for j in 0..32 {
+        x[j] += carry - (x[31] >> 4) * L[j];
+        carry = x[j] >> 8;
+        x[j] &= 255;
+    }
+    for j in 0..32 {
+        x[j] -= carry * L[j];
+    }
+    let mut out = [0u8; 32];
+    for i in 0..32 {
+        x[i + 1] += x[i] >> 8;
+        out[i] = x[i] as u8;
+    }
+    out
+}
+
+fn reduce(h: &[u8; 64]) -> [u8; 32] {
+    let mut x = [0i64; 64];
+    for (x, &b) in x.iter_mut().zip(h) {
+        *x = b as i64;
+    }
+    mod_l(&mut x)
+}
+
+/// The negation of the point encoded in `p`, or `None` if it is not on the curve.
+fn unpack_negated(p: &[u8; 32]) -> Option<Point> {
+    let y = unpack_gf(p);
+    let num = mul(&y, &y);
+    let den = mul(&num, &D);
+    let num = sub(&num, &GF1);
+    let den = add(&GF1, &den);
+
+    let den2 = mul(&den, &den);
+    let den4 = mul(&den2, &den2);
+    let den6 = mul(&den4, &den2);
+    let t = pow2523(&mul(&mul(&den6, &num), &den));
+    let mut x = mul(&mul(&mul(&mul(&t, &num), &den), &den), &den);
+    if pack_gf(&mul(&mul(&x, &x), &den)) != pack_gf(&num) {
+        x = mul(&x, &I);
+    }
+    if pack_gf(&mul(&mul(&x, &x), &den)) != pack_gf(&num) {
+        return None;
+    }
+    if parity(&x) == p[31] >> 7 {
+        x = sub(&GF0, &x);
+    }
+    Some([x, y, GF1, mul(&x, &y)])
+}
+
+pub fn verify(public: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    let Some(negated) = unpack_negated(public) else {
+        return false;
+    };
+    let s: [u8; 32] = signature[32..].try_into().unwrap();
+    // Reject a non-canonical s (>= l), which would make signatures malleable
+    if s.iter().zip(L).rev().find(|&(&a, b)| a as i64 != b).is_none_or(|(&a, b)| a as i64 > b) {
+        return false;
+    }
+    let h = reduce(&sha512(&[&signature[..32], public, message]));
+    let mut p = scalar_mult(negated, &h);
+    point_add(&mut p, &scalar_base(&s));
+    pack_point(&p)[..] == signature[..32]
+}
+
+/// PKCS#8 DER of an Ed25519 private key, up to its 32-byte seed (RFC 8410)
+const PKCS8_PREFIX: [u8; 16] =
+    [0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20];
+/// SubjectPublicKeyInfo DER of an Ed25519 public key, up to its 32 bytes
+const SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// A file in the temp directory that only this user can read, removed when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(what: &str, contents: &[u8]) -> std::io::Result<Scratch> {
+        static CREATED: AtomicU64 = AtomicU64::new(0);
+        let name = format!("portocripto-{}-{}.{}", std::process::id(), CREATED.fetch_add(1, Ordering::Relaxed), what);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let path = std::env::temp_dir().join(name);
+        let mut file = options.open(&path)?;
+        let scratch = Scratch(path);
+        file.write_all(contents)?;
+        Ok(scratch)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Run `openssl` with `args` and `input` on its stdin; what it wrote to stdout.
+fn openssl(args: &[&OsStr], input: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut child = Command::new("openssl")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            Error::new(e.kind(), format!("receipts are signed by the openssl command, which did not start: {}", e))
+        })?;
+    child.stdin.take().expect("stdin is piped").write_all(input)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let command = args.first().map_or("".into(), |a| a.to_string_lossy());
+        return Err(Error::other(format!("openssl {}: {}", command, String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(output.stdout)
+}
+
+fn private_key(seed: &[u8; 32]) -> Vec<u8> {
+    [&PKCS8_PREFIX[..], seed].concat()
+}
+
+/// The public key of `seed`, derived by OpenSSL.
+pub fn public_key(seed: &[u8; 32]) -> std::io::Result<[u8; 32]> {
+    let args = ["pkey", "-inform", "DER", "-pubout", "-outform", "DER"].map(OsStr::new);
+    let der = openssl(&args, &private_key(seed))?;
+    der.strip_prefix(&SPKI_PREFIX[..])
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "openssl printed something other than an Ed25519 key"))
+}
+
+/// `message` signed with `seed` by OpenSSL. Its one-shot signing reads the message from a
+/// file of known size and refuses an empty one, so `message` must not be empty.
+pub fn sign(seed: &[u8; 32], message: &[u8]) -> std::io::Result<[u8; 64]> {
+    if message.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "openssl cannot sign an empty message"));
+    }
+    let key = Scratch::new("key", &private_key(seed))?;
+    let input = Scratch::new("message", message)?;
+    let args = [
+        OsStr::new("pkeyutl"),
+        OsStr::new("-sign"),
+        OsStr::new("-rawin"),
+        OsStr::new("-keyform"),
+        OsStr::new("DER"),
+        OsStr::new("-inkey"),
+        key.0.as_os_str(),
+        OsStr::new("-in"),
+        input.0.as_os_str(),
+    ];
+    let signature = openssl(&args, &[])?;
+    signature
+        .try_into()
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "openssl printed a signature that is not 64 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt::parse_hex;
+
+    fn vector(i: usize) -> ([u8; 32], &'static [u8], [u8; 32], [u8; 64]) {
+        let (seed, message, public, signature) = crate::ED25519_VECTORS[i];
+        let hex = |text: &str| parse_hex(text).unwrap();
+        (hex(seed).try_into().unwrap(), message, hex(public).try_into().unwrap(), hex(signature).try_into().unwrap())
+    }
+
+    #[test]
+    fn sha512_vectors() {
+        let abc = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                   2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+        assert_eq!(crate::to_hex(&sha512(&[b"abc"])), abc);
+        assert_eq!(sha512(&[b"a", b"", b"bc"]), sha512(&[b"abc"]));
+        let empty = "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+                     47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e";
+        assert_eq!(crate::to_hex(&sha512(&[])), empty);
+        // Lengths either side of a block and of the padding that spills into another
+        for len in [111, 112, 127, 128, 129] {
+            let message = vec![b'x'; len];
+            let (head, tail) = message.split_at(len / 3);
+            assert_eq!(sha512(&[head, tail]), sha512(&[&message]), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn forgeries_are_rejected() {
+        let (_, message, public, signature) = vector(1);
+        assert!(verify(&public, message, &signature));
+        assert!(!verify(&public, b"s", &signature));
+        let mut flipped = signature;
+        flipped[5] ^= 1;
+        assert!(!verify(&public, message, &flipped));
+        // s + l is the same signature modulo l, and must not verify
+        let mut malleated = signature;
+        let mut carry = 0;
+        for (byte, l) in malleated[32..].iter_mut().zip(L) {
+            let sum = *byte as i64 + l + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        assert!(!verify(&public, message, &malleated));
+        let (_, _, other, _) = vector(0);
+        assert!(!verify(&other, message, &signature));
+        // y = 2 is on no point of the curve
+        let mut off_curve = [0u8; 32];
+        off_curve[0] = 2;
+        assert!(!verify(&off_curve, message, &signature));
+    }
+
+    #[test]
+    fn openssl_signs_like_the_rfc() {
+        let (seed, message, public, signature) = vector(1);
+        assert_eq!(sign(&seed, b"").unwrap_err().kind(), ErrorKind::InvalidInput);
+        let Ok(derived) = public_key(&seed) else {
+            return;
+        };
+        assert_eq!(derived, public);
+        assert_eq!(sign(&seed, message).unwrap(), signature);
+        let long = vec![0xa5; 4096];
+        assert!(verify(&public, &long, &sign(&seed, &long).unwrap()));
+    }
+}
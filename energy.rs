@@ -0,0 +1,210 @@
+//! Energy a run used, for the final report: on Linux from the RAPL counters of the CPU
+//! packages, which cover the whole package rather than just this process, and with
+//! --energy-plug from a smart plug's HTTP API, which sees the whole machine at the wall.
+//! The plug is asked for its power reading every `PLUG_INTERVAL` and the readings are
+//! integrated, since the cumulative counters plugs keep only move in whole watt-hours.
+
+use crate::json::{self, Json};
+use crate::{http_request, json_string, wait_for_stop};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const POWERCAP: &str = "/sys/class/powercap";
+pub const PLUG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Energy one source measured over the run.
+pub struct Reading {
+    pub source: &'static str,
+    pub joules: f64,
+}
+
+/// The RAPL package domains: (energy_uj, max_energy_range_uj) of each.
+fn rapl_domains() -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = std::fs::read_dir(POWERCAP) else {
+        return Vec::new();
+    };
+    let mut domains: Vec<(PathBuf, u64)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        // intel-rapl:0 is a package; intel-rapl:0:0 and the like are parts of it
+        .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.matches(':').count() == 1))
+        .filter_map(|path| {
+            let range = std::fs::read_to_string(path.join("max_energy_range_uj")).ok()?.trim().parse().ok()?;
+            Some((path.join("energy_uj"), range))
+        })
+        .collect();
+    domains.sort();
+    domains
+}
+
+fn read_uj(path: &PathBuf) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// The number at the dotted `field` path of the plug's JSON answer.
+fn plug_watts(url: &str, field: &str) -> Result<f64, String> {
+    let body = http_request("GET", url, None, "").map_err(|e| e.to_string())?;
+    let answer = json::parse(&body)?;
+    field
+        .split('.')
+        .try_fold(&answer, |json, key| json.get(key))
+        .and_then(Json::as_f64)
+        .ok_or_else(|| format!("no number at {:?} in the answer", field))
+}
+
+struct Plug {
+    done: Arc<AtomicBool>,
+    thread: JoinHandle<Option<f64>>,
+}
+
+/// Joules from the plug's readings until `done`; `None` without any reading.
+fn sample_plug(url: String, field: String, done: Arc<AtomicBool>) -> Option<f64> {
+    let (mut joules, mut last) = (0.0, None::<(Instant, f64)>);
+    let (mut failing, mut first) = (false, true);
+    loop {
+        let stopped = !first && wait_for_stop(&done, PLUG_INTERVAL);
+        first = false;
+        match plug_watts(&url, &field) {
+            Ok(watts) => {
+                let now = Instant::now();
+                if let Some((then, before)) = last {
+                    joules += (before + watts) / 2.0 * (now - then).as_secs_f64();
+                }
+                last = Some((now, watts));
+                failing = false;
+            }
+            Err(e) if !failing => {
+                eprintln!("energy: reading the plug at {} failed: {}", url, e);
+                failing = true;
+            }
+            Err(_) => {}
+        }
+        if stopped {
+            return last.map(|_| joules);
+        }
+    }
+}
+
+/// Counters read at the start of a run, finished into `Reading`s at its end.
+pub struct Meter {
+    /// (energy_uj, max_energy_range_uj, energy_uj at the start) of each RAPL package domain
+    rapl: Vec<(PathBuf, u64, u64)>,
+    plug: Option<Plug>,
+}
+
+impl Meter {
+    pub fn start(plug_url: Option<&str>, plug_field: &str) -> Meter {
+        let domains = rapl_domains();
+        let found = domains.len();
+        let rapl: Vec<_> =
+            domains.into_iter().filter_map(|(path, range)| Some((path.clone(), range, read_uj(&path)?))).collect();
+        if rapl.len() < found {
+            eprintln!("energy: cannot read the RAPL counters in {}; they are often readable by root only", POWERCAP);
+        }
+        let plug = plug_url.map(|url| {
+            let done = Arc::new(AtomicBool::new(false));
+            let (url, field, flag) = (url.to_string(), plug_field.to_string(), Arc::clone(&done));
+            Plug { done, thread: std::thread::spawn(move || sample_plug(url, field, flag)) }
+        });
+        Meter { rapl, plug }
+    }
+
+    pub fn finish(self) -> Vec<Reading> {
+        let mut readings = Vec::new();
+        let rapl: Option<u64> = self
+            .rapl
+            .iter()
+            .map(|(path, range, start)| {
+                read_uj(path).map(|end| if end >= *start { end - start } else { end + range - start })
+            })
+            .sum();
+        if let Some(uj) = rapl.filter(|_| !self.rapl.is_empty()) {
+            readings.push(Reading { source: "rapl", joules: uj as f64 / 1e6 });
+        }
+        if let Some(plug) = self.plug {
+            plug.done.store(true, Ordering::Release);
+            if let Ok(Some(joules)) = plug.thread.join() {
+                readings.push(Reading { source: "plug", joules });
+            }
+        }
+        readings
+    }
+}
+
+/// `readings` as the report's JSON list, with the energy per hash of `hashes`.
+pub fn json(readings: &[Reading], hashes: u64) -> String {
+    let readings: Vec<String> = readings
+        .iter()
+        .map(|r| {
+            format!(
+                "{{\"source\": {}, \"joules\": {:.3}, \"joules_per_hash\": {:e}}}",
+                json_string(r.source),
+                r.joules,
+                r.joules / hashes.max(1) as f64
+            )
+        })
+        .collect();
+    format!("[{}]", readings.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// The URL of a plug that answers every request with `body`.
+    fn plug(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rpc/Switch.GetStatus?id=0", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                let reply = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                let _ = reader.get_mut().write_all(reply.as_bytes());
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn plug_readings() {
+        let url = plug("{\"id\": 0, \"apower\": 41.5, \"aenergy\": {\"total\": 1200, \"unit\": \"Wh\"}}");
+        assert_eq!(plug_watts(&url, "apower"), Ok(41.5));
+        assert_eq!(plug_watts(&url, "aenergy.total"), Ok(1200.0));
+        assert_eq!(plug_watts(&url, "aenergy.unit").unwrap_err(), "no number at \"aenergy.unit\" in the answer");
+        assert_eq!(plug_watts(&url, "power").unwrap_err(), "no number at \"power\" in the answer");
+        assert!(plug_watts(&plug("<html>"), "apower").is_err());
+    }
+
+    #[test]
+    fn plugs_are_integrated() {
+        let done = Arc::new(AtomicBool::new(true));
+        // One reading on starting and one on stopping, 100 W between them
+        let joules = sample_plug(plug("{\"apower\": 100}"), "apower".to_string(), done).unwrap();
+        assert!((0.0..1.0).contains(&joules), "{}", joules);
+        let readings = Meter::start(Some(&plug("{\"apower\": 100}")), "apower").finish();
+        assert!(readings.iter().any(|r| r.source == "plug"));
+        let failing = Arc::new(AtomicBool::new(true));
+        assert_eq!(sample_plug(plug("{}"), "apower".to_string(), failing), None);
+    }
+
+    #[test]
+    fn report_json() {
+        assert_eq!(json(&[], 10), "[]");
+        let readings = [Reading { source: "rapl", joules: 12.5 }, Reading { source: "plug", joules: 0.0 }];
+        assert_eq!(
+            json(&readings, 1000),
+            "[{\"source\": \"rapl\", \"joules\": 12.500, \"joules_per_hash\": 1.25e-2}, \
+             {\"source\": \"plug\", \"joules\": 0.000, \"joules_per_hash\": 0e0}]"
+        );
+        assert!(json(&readings, 0).contains("\"joules_per_hash\": 1.25e1"));
+    }
+}
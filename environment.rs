@@ -0,0 +1,74 @@
+//! The machine a run happened on, as the `host` object of reports and receipts, so hashrates
+//! collected from different people can be compared and an odd one traced to its hardware,
+//! OS or backend. The built-in backends are versioned with the miner, whose version both
+//! record next to it; a value the OS does not give is null.
+
+use crate::{available_cpus, cpu_features, cpu_model, json_string};
+
+/// `PRETTY_NAME` from os-release, e.g. "Debian GNU/Linux 12 (bookworm)".
+fn os_release() -> Option<String> {
+    let text = std::fs::read_to_string("/etc/os-release").ok()?;
+    let line = text.lines().find_map(|l| l.strip_prefix("PRETTY_NAME="))?;
+    Some(line.trim_matches('"').to_string())
+}
+
+fn kernel() -> Option<String> {
+    Some(std::fs::read_to_string("/proc/sys/kernel/osrelease").ok()?.trim().to_string())
+}
+
+fn memory_bytes() -> Option<u64> {
+    let text = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kib: u64 =
+        text.lines().find_map(|l| l.strip_prefix("MemTotal:"))?.trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Keys and JSON values of the `host` object, for a run hashing with `backend`.
+pub fn host(backend: &str) -> Vec<(&'static str, String)> {
+    let text = |value: Option<String>| value.as_deref().map_or("null".to_string(), json_string);
+    vec![
+        ("os", json_string(std::env::consts::OS)),
+        ("os_release", text(os_release())),
+        ("kernel", text(kernel())),
+        ("arch", json_string(std::env::consts::ARCH)),
+        ("cpu", json_string(&cpu_model())),
+        ("cores", available_cpus().to_string()),
+        ("memory_bytes", memory_bytes().map_or("null".to_string(), |m| m.to_string())),
+        ("cpu_features", json_string(&cpu_features().join(" "))),
+        ("backend", json_string(backend)),
+    ]
+}
+
+/// `fields` as the members of a JSON object, joined by `separator`.
+pub fn members(fields: &[(&str, String)], separator: &str) -> String {
+    let members: Vec<String> = fields.iter().map(|(key, value)| format!("{}: {}", json_string(key), value)).collect();
+    members.join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_objects() {
+        let host = host("avx2");
+        let keys: Vec<_> = host.iter().map(|(key, _)| *key).collect();
+        let expected =
+            ["os", "os_release", "kernel", "arch", "cpu", "cores", "memory_bytes", "cpu_features", "backend"];
+        assert_eq!(keys, expected);
+        assert_eq!(host.last().unwrap().1, "\"avx2\"");
+        let object = format!("{{{}}}", members(&host, ", "));
+        let parsed = crate::json::parse(&object).unwrap();
+        assert_eq!(parsed.get("os").and_then(crate::json::Json::as_str), Some(std::env::consts::OS));
+        assert!(parsed.get("cores").and_then(crate::json::Json::as_f64).is_some_and(|cores| cores >= 1.0));
+    }
+
+    #[test]
+    fn member_lists() {
+        assert_eq!(members(&[], ", "), "");
+        assert_eq!(
+            members(&[("a", "1".to_string()), ("b\"", "null".to_string())], ",\n"),
+            "\"a\": 1,\n\"b\\\"\": null"
+        );
+    }
+}
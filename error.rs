@@ -0,0 +1,54 @@
+//! Errors returned by the search and embedding API, so a failing backend or server
+//! reaches the caller as a value instead of a panic inside a worker thread.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum PortocriptoError {
+    /// A difficulty mask or server reply that could not be parsed
+    Parse(String),
+    /// A hash backend or worker thread that could not start, or a worker that panicked
+    Backend(String),
+    /// A range server, Redis coordinator or broker that could not be reached
+    Network(std::io::Error),
+}
+
+impl fmt::Display for PortocriptoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PortocriptoError::Parse(msg) | PortocriptoError::Backend(msg) => f.write_str(msg),
+            PortocriptoError::Network(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PortocriptoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PortocriptoError::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PortocriptoError {
+    fn from(e: std::io::Error) -> Self {
+        PortocriptoError::Network(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn messages_and_sources() {
+        assert_eq!(PortocriptoError::Parse("bad mask".into()).to_string(), "bad mask");
+        assert!(PortocriptoError::Backend("no GPU".into()).source().is_none());
+        let network: PortocriptoError = std::io::Error::new(std::io::ErrorKind::TimedOut, "range server").into();
+        assert_eq!(network.to_string(), "range server");
+        let source = network.source().and_then(|e| e.downcast_ref::<std::io::Error>()).unwrap();
+        assert_eq!(source.kind(), std::io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,146 @@
+//! The acceptance check behind a trait, for challenge rules the mask and target do not
+//! express. A challenge's own rule is a `Composite` of its `Masked` difficulty and, when it
+//! has one, its `FullTarget`, and `portocripto verify` checks nonces through it. A rule's
+//! `prefilter` is the mask every hash it accepts passes, so a cheap mask check can go first.
+
+use crate::{
+    format_mask, hash_structure_good, leading_zero_bits, mask_for_zero_bits, meets_target, prefilter_mask, to_hex,
+    Mask, PrefixLayout, Target,
+};
+
+pub trait DifficultyEvaluator: Send + Sync {
+    /// Whether `hash`, the whole digest, is a solution.
+    fn accepts(&self, hash: &[u8]) -> bool;
+
+    /// A mask the prefix `layout` reads out of every accepted hash passes, checked first to
+    /// turn most hashes away cheaply; the default passes them all.
+    fn prefilter(&self, _layout: &PrefixLayout) -> Mask {
+        Mask::MAX
+    }
+
+    /// The rule in a few words, for logs.
+    fn describe(&self) -> String;
+}
+
+/// A difficulty mask over the prefix `layout` reads, as the challenge's `difficulty` gives it.
+pub struct Masked {
+    pub mask: Mask,
+    pub layout: PrefixLayout,
+}
+
+impl DifficultyEvaluator for Masked {
+    fn accepts(&self, hash: &[u8]) -> bool {
+        hash_structure_good(&self.layout.prefix(hash), self.mask)
+    }
+
+    fn prefilter(&self, layout: &PrefixLayout) -> Mask {
+        if *layout == self.layout {
+            self.mask
+        } else {
+            Mask::MAX
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("difficulty {}", format_mask(self.mask))
+    }
+}
+
+/// At least `bits` leading zero bits over the whole digest, including past the 128 a mask
+/// covers.
+pub struct LeadingBits(pub u32);
+
+impl DifficultyEvaluator for LeadingBits {
+    fn accepts(&self, hash: &[u8]) -> bool {
+        leading_zero_bits(hash) >= self.0
+    }
+
+    fn prefilter(&self, layout: &PrefixLayout) -> Mask {
+        if layout.offset != 0 || layout.little_endian {
+            return Mask::MAX;
+        }
+        mask_for_zero_bits(self.0.min(128))
+    }
+
+    fn describe(&self) -> String {
+        format!("{} leading zero bits", self.0)
+    }
+}
+
+/// Numerically at most a 256-bit target, as `meets_target` compares.
+pub struct FullTarget(pub Target);
+
+impl DifficultyEvaluator for FullTarget {
+    fn accepts(&self, hash: &[u8]) -> bool {
+        meets_target(hash, Some(&self.0))
+    }
+
+    fn prefilter(&self, layout: &PrefixLayout) -> Mask {
+        prefilter_mask(Mask::MAX, &self.0, layout)
+    }
+
+    fn describe(&self) -> String {
+        format!("target {}", to_hex(&self.0))
+    }
+}
+
+/// Every one of the parts; none at all accepts any hash.
+pub struct Composite(pub Vec<Box<dyn DifficultyEvaluator>>);
+
+impl DifficultyEvaluator for Composite {
+    fn accepts(&self, hash: &[u8]) -> bool {
+        self.0.iter().all(|part| part.accepts(hash))
+    }
+
+    fn prefilter(&self, layout: &PrefixLayout) -> Mask {
+        self.0.iter().fold(Mask::MAX, |mask, part| mask & part.prefilter(layout))
+    }
+
+    fn describe(&self) -> String {
+        match self.0.len() {
+            0 => "any hash".to_string(),
+            _ => self.0.iter().map(|part| part.describe()).collect::<Vec<_>>().join(" and "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIFTED: PrefixLayout = PrefixLayout { offset: 1, width: 4, little_endian: false };
+
+    #[test]
+    fn prefilters_only_for_their_layout() {
+        let masked = Masked { mask: crate::parse_mask("000FFFFF").unwrap(), layout: PrefixLayout::DEFAULT };
+        assert_eq!(masked.prefilter(&PrefixLayout::DEFAULT), masked.mask);
+        assert_eq!(masked.prefilter(&SHIFTED), Mask::MAX);
+        assert_eq!(LeadingBits(12).prefilter(&PrefixLayout::DEFAULT), Mask::MAX >> 12);
+        assert_eq!(LeadingBits(12).prefilter(&SHIFTED), Mask::MAX);
+        assert_eq!(LeadingBits(200).prefilter(&PrefixLayout::DEFAULT), 0);
+        let target = FullTarget([0; 32]);
+        assert_eq!(target.prefilter(&PrefixLayout { little_endian: true, ..PrefixLayout::DEFAULT }), Mask::MAX);
+    }
+
+    #[test]
+    fn composites_narrow_the_prefilter() {
+        let mut target = [0xff; 32];
+        target[0] = 0;
+        let both = Composite(vec![Box::new(LeadingBits(4)), Box::new(FullTarget(target))]);
+        assert_eq!(both.prefilter(&PrefixLayout::DEFAULT), Mask::MAX >> 8);
+        assert!(both.accepts(&[0x00, 0xff]));
+        assert!(!both.accepts(&[0x01]));
+        assert_eq!(both.describe(), format!("4 leading zero bits and target 00{}", "ff".repeat(31)));
+        assert_eq!(Composite(Vec::new()).describe(), "any hash");
+    }
+
+    #[test]
+    fn leading_bits_past_the_mask() {
+        let mut hash = [0u8; 32];
+        hash[17] = 0x10;
+        assert!(LeadingBits(139).accepts(&hash));
+        assert!(!LeadingBits(140).accepts(&hash));
+        assert!(LeadingBits(256).accepts(&[0; 32]));
+        assert_eq!(Masked { mask: Mask::MAX >> 20, layout: SHIFTED }.describe(), "difficulty 00000FFF");
+    }
+}
@@ -0,0 +1,133 @@
+//! A worker identity that survives restarts: a random UUID and a friendly name derived from
+//! it, kept in `--identity` (default `portocripto.id` in the state directory) and created on
+//! first run. Telemetry, MQTT, OTLP, range-server leases, the range log and receipts carry it,
+//! so fleet dashboards can attribute hashrate and solutions to a machine. The name may be
+//! edited in the file; `--rig-name` overrides it for one run.
+
+use std::hash::{BuildHasher, RandomState};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// File name of the default `--identity`, in the state directory.
+pub const IDENTITY_FILE: &str = "portocripto.id";
+
+const ADJECTIVES: [&str; 32] = [
+    "amber", "ashen", "bold", "brisk", "calm", "cobalt", "coral", "crimson", "dusky", "eager", "faint", "fierce",
+    "frosty", "gentle", "golden", "hardy", "hollow", "ivory", "jade", "keen", "lively", "lunar", "mellow", "misty",
+    "nimble", "olive", "quiet", "rapid", "rusty", "silver", "steady", "swift",
+];
+const NOUNS: [&str; 32] = [
+    "badger", "beacon", "comet", "condor", "cypress", "delta", "ember", "falcon", "fjord", "forge", "glacier",
+    "harbor", "heron", "iris", "kestrel", "lantern", "lynx", "maple", "meadow", "otter", "pebble", "pine", "quarry",
+    "raven", "reef", "ridge", "sparrow", "summit", "thistle", "tundra", "willow", "wren",
+];
+
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub id: String,
+    pub name: String,
+}
+
+impl Identity {
+    /// A fresh random (version 4) UUID and the name it maps to.
+    fn generate() -> Identity {
+        let random = RandomState::new();
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&random.hash_one(0u8).to_le_bytes());
+        bytes[8..].copy_from_slice(&random.hash_one(1u8).to_le_bytes());
+        bytes[6] = bytes[6] & 0x0f | 0x40;
+        bytes[8] = bytes[8] & 0x3f | 0x80;
+        let hex = crate::to_hex(&bytes);
+        let id = format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]);
+        let name = format!("{}-{}-{}", ADJECTIVES[bytes[0] as usize % 32], NOUNS[bytes[1] as usize % 32], &hex[28..]);
+        Identity { id, name }
+    }
+
+    fn parse(text: &str) -> Option<Identity> {
+        let field = |key: &str| {
+            text.lines()
+                .filter_map(|line| line.split_once('='))
+                .find(|(k, _)| k.trim() == key)
+                .map(|(_, v)| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Some(Identity { id: field("id")?, name: field("name")? })
+    }
+}
+
+/// The identity in `path`, creating the file if it does not exist. If it cannot be read or
+/// written, the run goes on with a new identity that is not kept.
+pub fn load(path: &Path) -> Identity {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Identity::parse(&text).unwrap_or_else(|| {
+            eprintln!("identity: {} has no id and name; using a temporary identity", path.display());
+            Identity::generate()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let identity = Identity::generate();
+            let text = format!(
+                "# portocripto worker identity; the name may be edited\nid = {}\nname = {}\n",
+                identity.id, identity.name
+            );
+            match std::fs::write(path, text) {
+                Ok(()) => eprintln!("identity: created {}", path.display()),
+                Err(e) => eprintln!("identity: failed to write {}: {}; this identity is temporary", path.display(), e),
+            }
+            identity
+        }
+        Err(e) => {
+            eprintln!("identity: failed to read {}: {}; using a temporary identity", path.display(), e);
+            Identity::generate()
+        }
+    }
+}
+
+static CURRENT: OnceLock<Identity> = OnceLock::new();
+
+/// Use `identity` for this process; call before the first connection.
+pub fn set(identity: Identity) {
+    let _ = CURRENT.set(identity);
+}
+
+/// This process's identity; a temporary one if `set` was never called.
+pub fn current() -> &'static Identity {
+    CURRENT.get_or_init(Identity::generate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_ids_are_v4_uuids() {
+        let identity = Identity::generate();
+        let groups: Vec<&str> = identity.id.split('-').collect();
+        assert_eq!(groups.iter().map(|g| g.len()).collect::<Vec<_>>(), [8, 4, 4, 4, 12]);
+        assert!(groups[2].starts_with('4'));
+        assert!("89ab".contains(&groups[3][..1]));
+        assert!(identity.name.ends_with(&identity.id[identity.id.len() - 4..]));
+        assert_ne!(Identity::generate().id, identity.id);
+    }
+
+    #[test]
+    fn files() {
+        let identity = Identity::parse("# comment\n name = my rig \nid=abc\nother = x\n").unwrap();
+        assert_eq!((identity.id.as_str(), identity.name.as_str()), ("abc", "my rig"));
+        assert!(Identity::parse("id = abc\n").is_none());
+        assert!(Identity::parse("id = \nname = rig\n").is_none());
+    }
+
+    #[test]
+    fn created_once_and_kept() {
+        let dir = crate::scratch_dir("identity");
+        let path = dir.join(IDENTITY_FILE);
+        let created = load(&path);
+        assert_eq!(load(&path).id, created.id);
+        std::fs::write(&path, "garbage").unwrap();
+        assert_ne!(load(&path).id, created.id);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "garbage", "a broken file is left alone");
+        // Unwritable: the run still gets an identity
+        assert!(!load(&dir.join("missing/portocripto.id")).id.is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
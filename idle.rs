@@ -0,0 +1,127 @@
+//! `--only-when-idle MINUTES`: mine only once nobody has touched the machine for a while, so
+//! a desktop can be lent out for its off-hours without slowing down whoever sits at it. The
+//! time since the last input comes from `GetLastInputInfo` on Windows, `HIDIdleTime` from
+//! `ioreg` on macOS, and elsewhere from the X screensaver extension, falling back to the
+//! access times of the terminal devices (as `w` does) without an X display.
+
+use std::time::Duration;
+
+/// How often the idle time is checked: a returning user should get the CPU back promptly
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Seconds since the last keyboard or mouse input; `None` if this system cannot tell.
+#[cfg(windows)]
+pub fn idle_secs() -> Option<u64> {
+    #[repr(C)]
+    struct LastInputInfo {
+        size: u32,
+        time: u32,
+    }
+    #[link(name = "user32")]
+    unsafe extern "system" {
+        fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+    }
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    let mut info = LastInputInfo { size: std::mem::size_of::<LastInputInfo>() as u32, time: 0 };
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are milliseconds since boot and wrap after 49 days
+    Some(unsafe { GetTickCount() }.wrapping_sub(info.time) as u64 / 1000)
+}
+
+#[cfg(target_os = "macos")]
+pub fn idle_secs() -> Option<u64> {
+    let output = std::process::Command::new("ioreg").args(["-c", "IOHIDSystem", "-d", "4"]).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let line = text.lines().find(|line| line.contains("\"HIDIdleTime\""))?;
+    let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+    Some(nanos / 1_000_000_000)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn idle_secs() -> Option<u64> {
+    x11_idle_secs().or_else(terminal_idle_secs)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn idle_secs() -> Option<u64> {
+    None
+}
+
+/// The X screensaver extension's idle time for `$DISPLAY`, with libX11 and libXss loaded at
+/// run time so the binary does not need them.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn x11_idle_secs() -> Option<u64> {
+    use std::ffi::{c_char, c_int, c_ulong, c_void, CStr};
+
+    unsafe extern "C" {
+        fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+    const RTLD_NOW: c_int = 2;
+
+    // XScreenSaverInfo
+    #[repr(C)]
+    struct Info {
+        _window: c_ulong,
+        _state: c_int,
+        _kind: c_int,
+        _til_or_since: c_ulong,
+        idle: c_ulong,
+        _event_mask: c_ulong,
+    }
+    type OpenDisplay = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+    type RootWindow = unsafe extern "C" fn(*mut c_void) -> c_ulong;
+    type CloseDisplay = unsafe extern "C" fn(*mut c_void) -> c_int;
+    type QueryInfo = unsafe extern "C" fn(*mut c_void, c_ulong, *mut Info) -> c_int;
+
+    std::env::var_os("DISPLAY")?;
+    let library = |name: &CStr| {
+        let handle = unsafe { dlopen(name.as_ptr(), RTLD_NOW) };
+        (!handle.is_null()).then_some(handle)
+    };
+    let (x11, xss) = (library(c"libX11.so.6")?, library(c"libXss.so.1")?);
+    let symbol = |handle: *mut c_void, name: &CStr| {
+        let sym = unsafe { dlsym(handle, name.as_ptr()) };
+        (!sym.is_null()).then_some(sym)
+    };
+    let open: OpenDisplay = unsafe { std::mem::transmute(symbol(x11, c"XOpenDisplay")?) };
+    let root: RootWindow = unsafe { std::mem::transmute(symbol(x11, c"XDefaultRootWindow")?) };
+    let close: CloseDisplay = unsafe { std::mem::transmute(symbol(x11, c"XCloseDisplay")?) };
+    let query: QueryInfo = unsafe { std::mem::transmute(symbol(xss, c"XScreenSaverQueryInfo")?) };
+
+    let display = unsafe { open(std::ptr::null()) };
+    if display.is_null() {
+        return None;
+    }
+    let mut info: Info = unsafe { std::mem::zeroed() };
+    let ok = unsafe { query(display, root(display), &mut info) } != 0;
+    unsafe { close(display) };
+    // Milliseconds
+    ok.then_some(info.idle as u64 / 1000)
+}
+
+/// Time since the last keystroke on a virtual console or pseudo-terminal: reading input
+/// updates the device's access time.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn terminal_idle_secs() -> Option<u64> {
+    let number = |name: &str| !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit());
+    let mut latest = None;
+    for (dir, prefix) in [("/dev/pts", ""), ("/dev", "tty")] {
+        let Ok(entries) = std::fs::read_dir(dir) else { continue };
+        for entry in entries.flatten() {
+            if !entry.file_name().to_str().and_then(|name| name.strip_prefix(prefix)).is_some_and(number) {
+                continue;
+            }
+            if let Ok(accessed) = entry.metadata().and_then(|m| m.accessed()) {
+                latest = latest.max(Some(accessed));
+            }
+        }
+    }
+    latest.map(|at| std::time::SystemTime::now().duration_since(at).unwrap_or_default().as_secs())
+}
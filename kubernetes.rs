@@ -0,0 +1,76 @@
+//! Sharding a StatefulSet without per-pod flags: every replica runs the same command line,
+//! so inside a pod --worker-index defaults to the pod's ordinal and --worker-count to
+//! `PORTOCRIPTO_REPLICAS`, set in the pod template from the same value as `replicas: N`.
+//! The ordinal is `POD_INDEX` when the template exposes the `apps.kubernetes.io/pod-index`
+//! label through the downward API, and otherwise the `-N` the StatefulSet controller gives
+//! the pod name, which Kubernetes sets as `HOSTNAME`.
+
+const REPLICAS: &str = "PORTOCRIPTO_REPLICAS";
+
+/// This pod's ordinal and the replica count, when running in a pod with `PORTOCRIPTO_REPLICAS` set.
+pub fn shard() -> Result<Option<(u64, u64)>, String> {
+    shard_from(|name| std::env::var(name).ok())
+}
+
+/// `shard` with the environment looked up by `var`.
+fn shard_from(var: impl Fn(&str) -> Option<String>) -> Result<Option<(u64, u64)>, String> {
+    let env = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if env("KUBERNETES_SERVICE_HOST").is_none() {
+        return Ok(None);
+    }
+    let Some(replicas) = env(REPLICAS) else {
+        return Ok(None);
+    };
+    let replicas: u64 = match replicas.parse() {
+        Ok(n) if n > 0 => n,
+        _ => return Err(format!("{} should be the StatefulSet's replica count, not {:?}", REPLICAS, replicas)),
+    };
+    let ordinal = match env("POD_INDEX") {
+        Some(index) => index.parse().map_err(|_| format!("POD_INDEX should be the pod ordinal, not {:?}", index))?,
+        None => {
+            let pod = env("HOSTNAME").unwrap_or_default();
+            let ordinal = pod.rsplit_once('-').and_then(|(_, n)| n.parse().ok());
+            ordinal.ok_or_else(|| format!("pod name {:?} has no StatefulSet ordinal; set POD_INDEX", pod))?
+        }
+    };
+    if ordinal >= replicas {
+        return Err(format!(
+            "pod ordinal {} is not below {} = {}; update it after scaling",
+            ordinal, REPLICAS, replicas
+        ));
+    }
+    Ok(Some((ordinal, replicas)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard_in(vars: &[(&str, &str)]) -> Result<Option<(u64, u64)>, String> {
+        let vars: Vec<(&str, &str)> =
+            [("KUBERNETES_SERVICE_HOST", "10.0.0.1")].into_iter().chain(vars.to_vec()).collect();
+        shard_from(|name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string()))
+    }
+
+    #[test]
+    fn ordinals() {
+        // Outside a pod the same variables are someone else's
+        assert_eq!(shard_from(|name| (name != "KUBERNETES_SERVICE_HOST").then(|| "1".to_string())), Ok(None));
+        assert_eq!(shard_in(&[("HOSTNAME", "miner-2")]), Ok(None));
+        assert_eq!(shard_in(&[(REPLICAS, " 4 "), ("HOSTNAME", "portocripto-miner-2")]), Ok(Some((2, 4))));
+        assert_eq!(shard_in(&[(REPLICAS, "4"), ("POD_INDEX", "3"), ("HOSTNAME", "miner-0")]), Ok(Some((3, 4))));
+        assert_eq!(shard_in(&[(REPLICAS, "4"), ("POD_INDEX", ""), ("HOSTNAME", "miner-1")]), Ok(Some((1, 4))));
+    }
+
+    #[test]
+    fn misconfigured_pods() {
+        let zero = shard_in(&[(REPLICAS, "0"), ("HOSTNAME", "miner-0")]);
+        assert_eq!(zero.unwrap_err(), "PORTOCRIPTO_REPLICAS should be the StatefulSet's replica count, not \"0\"");
+        let index = shard_in(&[(REPLICAS, "4"), ("POD_INDEX", "first")]);
+        assert_eq!(index.unwrap_err(), "POD_INDEX should be the pod ordinal, not \"first\"");
+        let name = shard_in(&[(REPLICAS, "4"), ("HOSTNAME", "miner")]);
+        assert_eq!(name.unwrap_err(), "pod name \"miner\" has no StatefulSet ordinal; set POD_INDEX");
+        let scaled = shard_in(&[(REPLICAS, "2"), ("HOSTNAME", "miner-2")]);
+        assert_eq!(scaled.unwrap_err(), "pod ordinal 2 is not below PORTOCRIPTO_REPLICAS = 2; update it after scaling");
+    }
+}
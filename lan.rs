@@ -0,0 +1,89 @@
+//! `--lan-broadcast`: miners on one LAN working the same preimage without a coordinator tell
+//! each other about a solution, so the rest stop instead of burning power on a search that
+//! is already won. The finder broadcasts `portocripto/1 solved <nonce> <challenge_id>` on UDP
+//! port 47474 a few times; a listener stops only once it has hashed the nonce against its
+//! own preimage and difficulty, so a miner for another address or a forged datagram cannot
+//! stop it.
+//!
+//! The listener binds the port without SO_REUSEADDR, so only one miner per host hears the
+//! broadcasts; the others on that host still send theirs.
+
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const PORT: u16 = 47474;
+const MAGIC: &str = "portocripto/1 solved";
+/// Copies of each announcement, since datagrams can be dropped
+const REPEATS: usize = 3;
+const REPEAT_INTERVAL: Duration = Duration::from_millis(50);
+
+fn parse(datagram: &[u8]) -> Option<(u64, &str)> {
+    let text = std::str::from_utf8(datagram).ok()?.strip_prefix(MAGIC)?.strip_prefix(' ')?;
+    let (nonce, challenge_id) = text.split_once(' ')?;
+    Some((u64::from_str_radix(nonce, 16).ok()?, challenge_id))
+}
+
+/// Broadcast that `nonce` solves `challenge_id`.
+pub fn announce(challenge_id: &str, nonce: u64) -> std::io::Result<()> {
+    let socket = crate::offline::network()?.udp((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+    let datagram = format!("{} {:016x} {}", MAGIC, nonce, challenge_id);
+    for i in 0..REPEATS {
+        if i > 0 {
+            std::thread::sleep(REPEAT_INTERVAL);
+        }
+        socket.send_to(datagram.as_bytes(), (Ipv4Addr::BROADCAST, PORT))?;
+    }
+    Ok(())
+}
+
+/// Listen until `stop` is set or a peer announces a nonce `solves` accepts; sets `stop` and
+/// returns the nonce and who sent it in that case.
+pub fn listen(solves: impl Fn(u64) -> bool, stop: &AtomicBool) -> std::io::Result<Option<(u64, SocketAddr)>> {
+    let socket = crate::offline::network()?.udp((Ipv4Addr::UNSPECIFIED, PORT))?;
+    socket.set_read_timeout(Some(Duration::from_millis(250)))?;
+    let mut buf = [0u8; 512];
+    // Each announcement arrives several times
+    let mut ignored = None;
+    while !stop.load(Ordering::Acquire) {
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) => match parse(&buf[..len]) {
+                Some((nonce, _)) if solves(nonce) => {
+                    stop.store(true, Ordering::Release);
+                    return Ok(Some((nonce, from)));
+                }
+                Some((nonce, challenge_id)) if ignored != Some(nonce) => {
+                    ignored = Some(nonce);
+                    eprintln!(
+                        "lan: ignoring {:016x} for {} from {}: it does not solve our preimage",
+                        nonce, challenge_id, from
+                    )
+                }
+                _ => {}
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announcements() {
+        assert_eq!(parse(b"portocripto/1 solved 0000000000001816 **D07"), Some((6166, "**D07")));
+        assert_eq!(parse(b"portocripto/1 solved ff a b"), Some((255, "a b")));
+        assert_eq!(parse(b"portocripto/1 solved 1816"), None);
+        assert_eq!(parse(b"portocripto/1 solved  1816 c"), None);
+        assert_eq!(parse(b"portocripto/1 solved zz c"), None);
+        assert_eq!(parse(b"portocripto/1 solved 10000000000000000 c"), None);
+        assert_eq!(parse(b"portocripto/2 solved 1816 c"), None);
+        assert_eq!(parse(b"portocripto/1 solved\xff1816 c"), None);
+        assert_eq!(parse(b""), None);
+    }
+}
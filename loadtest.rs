@@ -0,0 +1,232 @@
+//! `loadtest`: feeds a running `daemon` synthetic challenges at a set rate and difficulty,
+//! cancels a share of them while they are queued or running, then waits for every job to
+//! finish and recomputes each solution the daemon reports. This exercises the daemon's
+//! queue, priorities, cancellation and results without a real challenge server.
+
+use crate::{hash_preimage, hash_structure_good, http_request, json, json_string, sim, splitmix64, Challenge};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+/// Address of every synthetic challenge; solutions stay in the daemon's results.
+const ADDRESS: &str = "addr_test1qpzry9x8gf2tvdw0s3jn4syst5";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct Options {
+    /// Base URL of the daemon
+    pub daemon: String,
+    pub token: String,
+    pub jobs: u64,
+    /// Challenges submitted per second
+    pub rate: f64,
+    pub difficulty: String,
+    /// Share of jobs cancelled some time after they are submitted
+    pub cancel: f64,
+    pub seed: u64,
+    /// How long to wait for the last job once all are submitted
+    pub timeout: Duration,
+}
+
+struct Submitted {
+    challenge: Challenge,
+    cancel_at: Option<Instant>,
+    cancel_requested: bool,
+}
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        splitmix64(self.0)
+    }
+
+    /// Uniform in [0, 1)
+    fn fraction(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Whether `detail` ("nonce N hash H") names a nonce that solves `challenge`.
+fn check_solution(challenge: &Challenge, detail: &str) -> Result<(), String> {
+    let nonce = detail
+        .strip_prefix("nonce ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|text| challenge.nonce_encoding.parse(text).ok())
+        .ok_or_else(|| format!("no nonce in {:?}", detail))?;
+    let preimage = challenge.preimage().build(nonce);
+    let mut hash = vec![0u8; challenge.hash_len];
+    sim::reference(hash_preimage)(&preimage, &mut hash);
+    if hash_structure_good(&challenge.prefix_layout()?.prefix(&hash), challenge.difficulty_mask()?) {
+        Ok(())
+    } else {
+        Err(format!("nonce {:016x} does not meet the difficulty", nonce))
+    }
+}
+
+/// Run the load test, printing a summary; false if any job failed, went missing, was
+/// cancelled unasked or reported a wrong solution.
+pub fn run(options: &Options) -> Result<bool, String> {
+    let daemon = options.daemon.trim_end_matches('/');
+    let request = |method: &str, path: &str, body: &str| {
+        http_request(method, &format!("{}{}", daemon, path), Some(&options.token), body)
+    };
+    let mut rng = Rng(options.seed);
+    let period = Duration::from_secs_f64(1.0 / options.rate.max(0.001));
+    let mut jobs: BTreeMap<u64, Submitted> = BTreeMap::new();
+    let mut finished: BTreeMap<u64, (String, String, u64, f64)> = BTreeMap::new();
+
+    // Cancels that fall due before `until`, then a poll of the results
+    let tend = |jobs: &mut BTreeMap<u64, Submitted>, finished: &mut BTreeMap<u64, _>, until: Instant| loop {
+        let now = Instant::now();
+        let due: Vec<u64> =
+            jobs.iter().filter(|(_, j)| j.cancel_at.is_some_and(|at| at <= now)).map(|(&id, _)| id).collect();
+        for id in due {
+            let job = jobs.get_mut(&id).unwrap();
+            job.cancel_at = None;
+            match request("POST", &format!("/jobs/{}/cancel", id), "") {
+                Ok(_) => job.cancel_requested = true,
+                // Finished before the cancel arrived
+                Err(e) if e.kind() == ErrorKind::InvalidData => {}
+                Err(e) => eprintln!("loadtest: failed to cancel job {}: {}", id, e),
+            }
+        }
+        match request("GET", "/results", "").map_err(|e| e.to_string()).and_then(|body| json::parse(&body)) {
+            Ok(results) => {
+                let Some(json::Json::Array(entries)) = results.get("finished") else {
+                    return Err("daemon /results has no finished list".to_string());
+                };
+                for entry in entries {
+                    let id = entry.get("id").and_then(json::Json::as_f64).map_or(0, |id| id as u64);
+                    if jobs.contains_key(&id) {
+                        let text = |key: &str| entry.get(key).and_then(json::Json::as_str).unwrap_or("").to_string();
+                        let number = |key: &str| entry.get(key).and_then(json::Json::as_f64).unwrap_or(0.0);
+                        finished.insert(id, (text("state"), text("detail"), number("hashes") as u64, number("secs")));
+                    }
+                }
+            }
+            Err(e) => eprintln!("loadtest: failed to read results: {}", e),
+        }
+        let now = Instant::now();
+        if now >= until {
+            return Ok(());
+        }
+        std::thread::sleep(POLL_INTERVAL.min(until - now));
+    };
+
+    let started = Instant::now();
+    for i in 0..options.jobs {
+        let body = format!(
+            concat!(
+                "{{\"address\": \"{}\", \"challenge_id\": \"loadtest-{:08x}-{}\", \"difficulty\": {}, ",
+                "\"no_pre_mine\": \"{:016x}\", \"latest_submission\": \"2099-12-31T23:59:59.000Z\", ",
+                "\"no_pre_mine_hour\": \"{}\", \"priority\": {}}}"
+            ),
+            ADDRESS,
+            options.seed as u32,
+            i,
+            json_string(&options.difficulty),
+            rng.next(),
+            rng.next() % 1000,
+            rng.next() % 3
+        );
+        let parsed = json::parse(&body)?;
+        let challenge = Challenge::from_json(&parsed)?;
+        let reply = request("POST", "/jobs", &body).map_err(|e| format!("failed to submit job {}: {}", i, e))?;
+        let id = json::parse(&reply)?
+            .get("id")
+            .and_then(json::Json::as_f64)
+            .ok_or_else(|| format!("daemon answered {:?} without an id", reply))? as u64;
+        // Up to four submission periods later, so some are cancelled queued and some running
+        let cancel_at =
+            (rng.fraction() < options.cancel).then(|| Instant::now() + period.mul_f64(4.0 * rng.fraction()));
+        jobs.insert(id, Submitted { challenge, cancel_at, cancel_requested: false });
+        tend(&mut jobs, &mut finished, started + period * (i + 1) as u32)?;
+    }
+    let submitted_secs = started.elapsed().as_secs_f64();
+    let deadline = Instant::now() + options.timeout;
+    while finished.len() < jobs.len() && Instant::now() < deadline {
+        tend(&mut jobs, &mut finished, Instant::now() + POLL_INTERVAL)?;
+    }
+
+    let mut problems = Vec::new();
+    let (mut solved, mut cancelled, mut failed, mut hashes, mut secs) = (0, 0, 0, 0u64, 0.0);
+    for (id, job) in &jobs {
+        let Some((state, detail, job_hashes, job_secs)) = finished.get(id) else {
+            problems.push(format!("job {} did not finish", id));
+            continue;
+        };
+        hashes += job_hashes;
+        secs += job_secs;
+        match state.as_str() {
+            "solved" => {
+                solved += 1;
+                if let Err(e) = check_solution(&job.challenge, detail) {
+                    problems.push(format!("job {}: {}", id, e));
+                }
+            }
+            "cancelled" if job.cancel_requested => cancelled += 1,
+            "cancelled" => problems.push(format!("job {} was cancelled without a request", id)),
+            _ => {
+                failed += 1;
+                problems.push(format!("job {} {}: {}", id, state, detail));
+            }
+        }
+    }
+    let requested = jobs.values().filter(|j| j.cancel_requested).count();
+    println!(
+        "submitted: {} jobs in {:.1}s ({:.2}/s) at difficulty {}",
+        jobs.len(),
+        submitted_secs,
+        jobs.len() as f64 / submitted_secs.max(1e-9),
+        options.difficulty
+    );
+    println!("solved:    {} (solutions recomputed)", solved);
+    println!("cancelled: {} of {} accepted cancel requests", cancelled, requested);
+    println!("failed:    {}", failed);
+    println!("missing:   {}", jobs.len() - finished.len());
+    println!("mining:    {:.1}s, {} hashes ({:.0} H/s)", secs, hashes, hashes as f64 / f64::max(secs, 1e-9));
+    for problem in &problems {
+        println!("PROBLEM {}", problem);
+    }
+    Ok(problems.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(difficulty: &str) -> Challenge {
+        let body = format!(
+            "{{\"address\": \"{}\", \"challenge_id\": \"loadtest-1-0\", \"difficulty\": \"{}\", \
+             \"no_pre_mine\": \"0011223344556677\", \"latest_submission\": \"2099-12-31T23:59:59.000Z\", \
+             \"no_pre_mine_hour\": \"7\"}}",
+            ADDRESS, difficulty
+        );
+        Challenge::from_json(&json::parse(&body).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn seeded_rngs_repeat() {
+        let (mut a, mut b) = (Rng(6166), Rng(6166));
+        let first: Vec<u64> = (0..4).map(|_| a.next()).collect();
+        assert_eq!(first, (0..4).map(|_| b.next()).collect::<Vec<_>>());
+        assert_ne!(first[0], Rng(6167).next());
+        assert!((0..1000).map(|_| a.fraction()).all(|f| (0.0..1.0).contains(&f)));
+    }
+
+    #[test]
+    fn solutions_are_recomputed() {
+        let easy = challenge("ffffffff");
+        let nonce = easy.nonce_encoding.text(6166);
+        assert_eq!(check_solution(&easy, &format!("nonce {} hash 00", nonce)), Ok(()));
+        // 64 zero bits
+        let hard = challenge("0000000000000000");
+        assert_eq!(
+            check_solution(&hard, &format!("nonce {} hash 00", nonce)).unwrap_err(),
+            "nonce 0000000000001816 does not meet the difficulty"
+        );
+        assert_eq!(check_solution(&easy, "solved").unwrap_err(), "no nonce in \"solved\"");
+        assert!(check_solution(&easy, "nonce zz hash 00").unwrap_err().starts_with("no nonce"));
+    }
+}
@@ -0,0 +1,215 @@
+//! `--log-file FILE`: everything the process and its on-solution commands write to stderr
+//! goes to FILE instead, so a long-lived daemon keeps its history without a supervisor
+//! capturing it. The file is opened for appending and put in place of stderr itself, so no
+//! line is lost when the process exits right after logging it.
+//!
+//! With `--log-rotate SIZE,AGE` (both parts optional, default `50MB,7d`) the file is moved
+//! aside as FILE.YYYYMMDDTHHMMSSZ, the time it was rotated, once it passes SIZE or a UTC day
+//! ends, and rotated files older than AGE are deleted. The size is checked every
+//! `CHECK_INTERVAL`, so a file can overshoot it by what is logged in between.
+
+use crate::params::parse_duration;
+use crate::unix_now;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct Rotation {
+    /// Rotate once the file is this many bytes
+    pub size: Option<u64>,
+    /// Delete rotated files older than this
+    pub keep: Option<Duration>,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Rotation { size: Some(50 << 20), keep: Some(Duration::from_secs(7 * 86400)) }
+    }
+}
+
+fn parse_size(s: &str) -> Option<u64> {
+    let upper = s.to_ascii_uppercase();
+    let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = match &upper[digits.len()..] {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(unit).filter(|&size| size > 0)
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut rotation = Rotation { size: None, keep: None };
+        for part in s.split(',').map(str::trim) {
+            // A size ends in B (or K, M, G); anything else should be a duration
+            if let Some(size) = parse_size(part) {
+                rotation.size = Some(size);
+                continue;
+            }
+            let keep = parse_duration(part)
+                .map_err(|_| format!("{:?} is neither a size like 50MB nor an age like 7d", part))?;
+            rotation.keep = Some(keep);
+        }
+        Ok(rotation)
+    }
+}
+
+/// Send stderr to `path` from now on, rotating it by `rotation` on a background thread.
+pub fn redirect(path: &Path, rotation: Rotation) -> std::io::Result<()> {
+    let file = open(path)?;
+    replace_stderr(&file)?;
+    let path = path.to_path_buf();
+    std::thread::spawn(move || rotate(path, rotation, file));
+    Ok(())
+}
+
+fn open(path: &Path) -> std::io::Result<File> {
+    std::fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn rotate(path: PathBuf, rotation: Rotation, mut file: File) {
+    let mut day = unix_now().div_euclid(86400);
+    prune(&path, &rotation);
+    loop {
+        std::thread::sleep(CHECK_INTERVAL);
+        let size = file.metadata().map_or(0, |m| m.len());
+        let today = unix_now().div_euclid(86400);
+        // An empty file carries on into the new day rather than being kept
+        if size == 0 {
+            day = today;
+        }
+        if today == day && rotation.size.is_none_or(|limit| size < limit) {
+            continue;
+        }
+        day = today;
+        let rotated = PathBuf::from(format!("{}.{}", path.display(), crate::s3::amz_date(unix_now())));
+        let next = std::fs::rename(&path, &rotated).and_then(|_| open(&path));
+        match next.and_then(|next| replace_stderr(&next).map(|_| next)) {
+            // The old file closes here; stderr already writes to the new one
+            Ok(next) => file = next,
+            Err(e) => eprintln!("log: failed to rotate {}: {}", path.display(), e),
+        }
+        prune(&path, &rotation);
+    }
+}
+
+/// Delete the rotated copies of `path` older than `rotation.keep`.
+fn prune(path: &Path, rotation: &Rotation) {
+    let Some(keep) = rotation.keep else { return };
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str())) else {
+        return;
+    };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let prefix = format!("{}.", name);
+    for entry in entries.flatten() {
+        let rotated = entry.file_name().to_str().is_some_and(|n| n.starts_with(&prefix) && n.ends_with('Z'));
+        let age = entry.metadata().and_then(|m| m.modified()).ok().and_then(|t| t.elapsed().ok());
+        if rotated && age.is_some_and(|age| age > keep) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(unix)]
+fn replace_stderr(file: &File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    unsafe extern "C" {
+        fn dup2(old: i32, new: i32) -> i32;
+    }
+    if unsafe { dup2(file.as_raw_fd(), 2) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn replace_stderr(file: &File) -> std::io::Result<()> {
+    use std::ffi::c_void;
+    use std::os::windows::io::IntoRawHandle;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetStdHandle(std_handle: u32, handle: *mut c_void) -> i32;
+    }
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+
+    // The standard handle has to stay open for as long as it is in place, so it gets its own
+    let handle = file.try_clone()?.into_raw_handle();
+    if unsafe { SetStdHandle(STD_ERROR_HANDLE, handle) } == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn replace_stderr(_: &File) -> std::io::Result<()> {
+    Err(std::io::Error::other("--log-file is not supported on this system"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("512b"), Some(512));
+        assert_eq!(parse_size("4K"), Some(4096));
+        assert_eq!(parse_size("50MB"), Some(50 << 20));
+        assert_eq!(parse_size("2gb"), Some(2 << 30));
+        assert_eq!(parse_size("0MB"), None);
+        assert_eq!(parse_size("7d"), None);
+        assert_eq!(parse_size("MB"), None);
+        assert_eq!(parse_size("99999999999G"), None);
+    }
+
+    #[test]
+    fn rotations() {
+        let both: Rotation = "10MB, 1d".parse().unwrap();
+        assert_eq!((both.size, both.keep), (Some(10 << 20), Some(Duration::from_secs(86400))));
+        let age: Rotation = "12h".parse().unwrap();
+        assert_eq!((age.size, age.keep), (None, Some(Duration::from_secs(12 * 3600))));
+        let size: Rotation = "1G".parse().unwrap();
+        assert_eq!((size.size, size.keep), (Some(1 << 30), None));
+        assert_eq!(
+            "10MB,soon".parse::<Rotation>().unwrap_err(),
+            "\"soon\" is neither a size like 50MB nor an age like 7d"
+        );
+        let default = Rotation::default();
+        assert_eq!((default.size, default.keep), (Some(50 << 20), Some(Duration::from_secs(7 * 86400))));
+    }
+
+    #[test]
+    fn old_rotations_are_pruned() {
+        let dir = crate::scratch_dir("logfile");
+        let path = dir.join("miner.log");
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 86400);
+        for name in
+            ["miner.log", "miner.log.20260101T000000Z", "miner.log.20261013T000000Z", "miner.log.bak", "other.log.1Z"]
+        {
+            let file = File::create(dir.join(name)).unwrap();
+            if name != "miner.log.20261013T000000Z" {
+                file.set_modified(week_ago).unwrap();
+            }
+        }
+        prune(&path, &Rotation { size: None, keep: None });
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 5);
+        prune(&path, &Rotation { size: None, keep: Some(Duration::from_secs(86400)) });
+        let mut left: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        left.sort();
+        assert_eq!(left, ["miner.log", "miner.log.20261013T000000Z", "miner.log.bak", "other.log.1Z"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
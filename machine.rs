@@ -0,0 +1,110 @@
+//! `--machine`: stdout carries newline-delimited JSON events instead of the bare nonce, for
+//! GUIs and bots supervising the miner. Every event is one object on one line with
+//! `"schema": 1`, `"event"` and `"unix"` (seconds); the other keys depend on the event:
+//!
+//!   started    challenge_id, address, difficulty, zero_bits, backend, threads
+//!   progress   hashes, hashrate (H/s over the last interval), elapsed_secs, paused
+//!   solution   the `Solution` fields: nonce (as submitted), hash, challenge_id, address,
+//!              found_unix, submitted_unix (null, as it is sent before the submission), backend,
+//!              worker_id, thread, worker_hashes
+//!   exhausted  hashes, reason: "stopped", "window_closed" or "preempted"; the search
+//!              ended without a solution
+//!   error      message; the run failed after `started` and exits non-zero
+//!
+//! A run rejected before `started` (bad flags or challenge fields) only exits 2 with the
+//! reason on stderr. Keys are only ever added under the same schema number; logs stay on
+//! stderr. `ws` streams the same events to WebSocket clients, with or without --machine.
+
+use crate::{json_string, unix_now, wait_for_stop, ws, Progress};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+pub const SCHEMA: u32 = 1;
+/// Between `progress` events
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Print `event` with `fields` (keys and JSON values) as one line, if --machine is on, and
+/// send it to --ws-listen clients.
+pub fn emit(event: &str, fields: &[(&str, String)]) {
+    if !enabled() && !ws::listening() {
+        return;
+    }
+    let line = line(event, fields);
+    ws::broadcast(event, &line);
+    if enabled() {
+        print(&line);
+    }
+}
+
+/// `event` with `fields` as the line `emit` sends.
+pub fn line(event: &str, fields: &[(&str, String)]) -> String {
+    let mut line = format!("{{\"schema\": {}, \"event\": {}, \"unix\": {}", SCHEMA, json_string(event), unix_now());
+    for (key, value) in fields {
+        line.push_str(&format!(", {}: {}", json_string(key), value));
+    }
+    line.push('}');
+    line
+}
+
+/// Write an event `line` to stdout.
+pub fn print(line: &str) {
+    // One write per line, so events from different threads never interleave
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+pub fn error(message: &str) {
+    emit("error", &[("message", json_string(message))]);
+}
+
+/// A `progress` event every `PROGRESS_INTERVAL` until the search stops.
+pub fn report_progress(progress: &Progress) {
+    let started = Instant::now();
+    let (mut last_elapsed, mut last_hashes) = (Duration::ZERO, progress.hashes());
+    while !wait_for_stop(&progress.stop, PROGRESS_INTERVAL) {
+        let (elapsed, hashes) = (progress.awake(started), progress.hashes());
+        let hashrate = (hashes - last_hashes) as f64 / elapsed.saturating_sub(last_elapsed).as_secs_f64();
+        (last_elapsed, last_hashes) = (elapsed, hashes);
+        emit(
+            "progress",
+            &[
+                ("hashes", hashes.to_string()),
+                ("hashrate", format!("{:.1}", hashrate)),
+                ("elapsed_secs", format!("{:.1}", elapsed.as_secs_f64())),
+                ("paused", progress.paused.load(Ordering::Relaxed).to_string()),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{self, Json};
+
+    #[test]
+    fn event_lines() {
+        let line = line("solution", &[("nonce", json_string("1816")), ("thread", "3".to_string())]);
+        assert!(!line.contains('\n'));
+        let event = json::parse(&line).unwrap();
+        assert_eq!(event.get("schema").and_then(Json::as_f64), Some(SCHEMA as f64));
+        assert_eq!(event.get("event").and_then(Json::as_str), Some("solution"));
+        assert!(event.get("unix").and_then(Json::as_f64).is_some_and(|unix| unix > 1.7e9));
+        assert_eq!(event.get("nonce").and_then(Json::as_str), Some("1816"));
+        assert_eq!(event.get("thread").and_then(Json::as_f64), Some(3.0));
+        let bare = json::parse(&super::line("error", &[])).unwrap();
+        assert!(matches!(bare, Json::Object(members) if members.len() == 3));
+    }
+}
@@ -0,0 +1,245 @@
+//! Just enough mDNS (RFC 6762) for miners on a LAN to find a `range-server` by itself:
+//! `range-server --announce` answers PTR queries for `_portocripto._tcp.local` with an SRV
+//! record carrying its port, and `--discover` asks the multicast group and mines from the
+//! address of the first answer, over the binary protocol.
+//!
+//! The announcer binds UDP 5353 without SO_REUSEADDR, so it cannot share the port with
+//! another responder such as avahi on the same host.
+
+use std::io::{Error, ErrorKind};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+const GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const PORT: u16 = 5353;
+const SERVICE: &str = "_portocripto._tcp.local";
+const TYPE_PTR: u16 = 12;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+/// Answer record TTL, in seconds
+const TTL: u32 = 120;
+
+fn push_name(name: &str, out: &mut Vec<u8>) {
+    for label in name.split('.') {
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+}
+
+/// Read the (possibly compressed) name at `pos`; returns it and the offset after it.
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..64 {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => return Some((labels.join("."), end.unwrap_or(pos + 1))),
+            _ if len & 0xc0 == 0xc0 => {
+                end.get_or_insert(pos + 2);
+                pos = (len & 0x3f) << 8 | *packet.get(pos + 1)? as usize;
+            }
+            _ => {
+                labels.push(String::from_utf8_lossy(packet.get(pos + 1..pos + 1 + len)?).into_owned());
+                pos += 1 + len;
+            }
+        }
+    }
+    None // a pointer loop
+}
+
+fn u16_at(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*packet.get(pos)?, *packet.get(pos + 1)?]))
+}
+
+/// One question for our service, asking for a unicast reply (the QU bit).
+fn query() -> Vec<u8> {
+    let mut packet = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    push_name(SERVICE, &mut packet);
+    packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    packet.extend_from_slice(&0x8001u16.to_be_bytes());
+    packet
+}
+
+/// Whether `packet` is a query with a PTR (or ANY) question for our service.
+fn asks_for_service(packet: &[u8]) -> bool {
+    let (Some(flags), Some(questions)) = (u16_at(packet, 2), u16_at(packet, 4)) else {
+        return false;
+    };
+    let mut pos = 12;
+    for _ in 0..questions {
+        let Some((name, next)) = read_name(packet, pos) else {
+            return false;
+        };
+        let Some(kind) = u16_at(packet, next) else {
+            return false;
+        };
+        if flags & 0x8000 == 0 && name.eq_ignore_ascii_case(SERVICE) && (kind == TYPE_PTR || kind == TYPE_ANY) {
+            return true;
+        }
+        pos = next + 4;
+    }
+    false
+}
+
+/// PTR from the service to our instance and SRV from the instance to `port`.
+fn answer(id: u16, port: u16) -> Vec<u8> {
+    let instance = format!("portocripto-{}.{}", port, SERVICE);
+    let mut packet = id.to_be_bytes().to_vec();
+    packet.extend_from_slice(&[0x84, 0, 0, 0, 0, 2, 0, 0, 0, 0]);
+    let record = |name: &str, kind: u16, rdata: &[u8], packet: &mut Vec<u8>| {
+        push_name(name, packet);
+        packet.extend_from_slice(&kind.to_be_bytes());
+        packet.extend_from_slice(&1u16.to_be_bytes());
+        packet.extend_from_slice(&TTL.to_be_bytes());
+        packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        packet.extend_from_slice(rdata);
+    };
+    let mut ptr = Vec::new();
+    push_name(&instance, &mut ptr);
+    record(SERVICE, TYPE_PTR, &ptr, &mut packet);
+    let mut srv = vec![0, 0, 0, 0];
+    srv.extend_from_slice(&port.to_be_bytes());
+    push_name(&format!("portocripto-{}.local", port), &mut srv);
+    record(&instance, TYPE_SRV, &srv, &mut packet);
+    packet
+}
+
+/// The port in the first SRV answer of a response.
+fn srv_port(packet: &[u8]) -> Option<u16> {
+    let flags = u16_at(packet, 2)?;
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let (questions, answers) = (u16_at(packet, 4)?, u16_at(packet, 6)?);
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(packet, pos)?.1 + 4;
+    }
+    for _ in 0..answers {
+        let (name, next) = read_name(packet, pos)?;
+        let (kind, len) = (u16_at(packet, next)?, u16_at(packet, next + 8)? as usize);
+        if kind == TYPE_SRV && name.to_ascii_lowercase().ends_with(SERVICE) {
+            return u16_at(packet, next + 10 + 4);
+        }
+        pos = next + 10 + len;
+    }
+    None
+}
+
+/// Answer queries for the service until the socket fails, advertising `port`.
+pub fn announce(port: u16) -> std::io::Result<()> {
+    let socket = crate::offline::network()?.udp((Ipv4Addr::UNSPECIFIED, PORT))?;
+    socket.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    eprintln!("mdns: announcing {} on port {}", SERVICE, port);
+    let mut buf = [0u8; 1500];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf)?;
+        let packet = &buf[..len];
+        if !asks_for_service(packet) {
+            continue;
+        }
+        // Queries from port 5353 get the reply on the group, one-shot queriers a unicast one
+        let id = if from.port() == PORT { 0 } else { u16_at(packet, 0).unwrap_or(0) };
+        let to = if from.port() == PORT { SocketAddr::from((GROUP, PORT)) } else { from };
+        if let Err(e) = socket.send_to(&answer(id, port), to) {
+            eprintln!("mdns: failed to answer {}: {}", from, e);
+        }
+    }
+}
+
+/// Ask the LAN for a range-server for up to `timeout`; returns its tcp:// URL.
+pub fn discover(timeout: Duration) -> std::io::Result<String> {
+    let socket = crate::offline::network()?.udp((Ipv4Addr::UNSPECIFIED, 0))?;
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 1500];
+    while Instant::now() < deadline {
+        socket.send_to(&query(), (GROUP, PORT))?;
+        let resend = (Instant::now() + Duration::from_secs(1)).min(deadline);
+        while let Some(wait) = resend.checked_duration_since(Instant::now()).filter(|w| !w.is_zero()) {
+            socket.set_read_timeout(Some(wait))?;
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) => {
+                    if let Some(port) = srv_port(&buf[..len]) {
+                        return Ok(format!("tcp://{}:{}", from.ip(), port));
+                    }
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Err(Error::new(ErrorKind::TimedOut, format!("no range-server answered for {} within {:?}", SERVICE, timeout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(name: &str, kind: u16, flags: u16) -> Vec<u8> {
+        let mut packet = vec![0, 0];
+        packet.extend_from_slice(&flags.to_be_bytes());
+        packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+        push_name(name, &mut packet);
+        packet.extend_from_slice(&kind.to_be_bytes());
+        packet.extend_from_slice(&1u16.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn our_query_and_answer_agree() {
+        assert!(asks_for_service(&query()));
+        assert_eq!(srv_port(&answer(7, 7000)), Some(7000));
+        assert_eq!(&answer(7, 7000)[..2], [0, 7]);
+        assert!(!asks_for_service(&answer(0, 7000)));
+        assert_eq!(srv_port(&query()), None);
+    }
+
+    #[test]
+    fn which_questions_are_ours() {
+        assert!(asks_for_service(&question("_PortoCripto._tcp.local", TYPE_ANY, 0)));
+        assert!(!asks_for_service(&question(SERVICE, TYPE_SRV, 0)));
+        assert!(!asks_for_service(&question("_http._tcp.local", TYPE_PTR, 0)));
+        assert!(!asks_for_service(&question(SERVICE, TYPE_PTR, 0x8400)));
+        let mut second = question("_http._tcp.local", TYPE_PTR, 0);
+        second[5] = 2;
+        second.extend_from_slice(&[0xc0, 12 + 6]);
+        second.extend_from_slice(&[0, 12, 0, 1]);
+        assert!(!asks_for_service(&second));
+    }
+
+    #[test]
+    fn compressed_names() {
+        // Header, a question for the service, then an SRV answer whose name points into it
+        let mut packet = question(SERVICE, TYPE_PTR, 0x8400);
+        packet[7] = 1;
+        let at = packet.len();
+        packet.extend_from_slice(&[4, b'r', b'i', b'g', b'1', 0xc0, 12]);
+        packet.extend_from_slice(&[0, 33, 0, 1, 0, 0, 0, 120, 0, 8, 0, 0, 0, 0, 0x1f, 0x40, 0xc0, 12]);
+        assert_eq!(read_name(&packet, at).unwrap().0, format!("rig1.{}", SERVICE));
+        assert_eq!(srv_port(&packet), Some(8000));
+    }
+
+    #[test]
+    fn malformed_names() {
+        assert_eq!(read_name(&[0xc0, 0], 0), None, "pointer to itself");
+        assert_eq!(read_name(&[0xc0, 2, 0xc0, 0], 0), None, "pointer loop");
+        assert_eq!(read_name(&[3, b'a', b'b'], 0), None, "label past the end");
+        assert_eq!(read_name(&[1, b'a'], 0), None, "no terminator");
+        assert_eq!(read_name(&[0xc0], 0), None, "half a pointer");
+        assert_eq!(read_name(&[1, b'a', 0], 0), Some(("a".to_string(), 3)));
+    }
+
+    #[test]
+    fn truncated_packets_do_not_parse() {
+        let (query, answer) = (query(), answer(1, 7000));
+        for len in 0..query.len() - 4 {
+            assert!(!asks_for_service(&query[..len]), "{}", len);
+        }
+        // The SRV target's name follows the port and is not read
+        let target = "portocripto-7000.local".len() + 2;
+        for len in 0..answer.len() - target {
+            assert_eq!(srv_port(&answer[..len]), None, "{}", len);
+        }
+    }
+}
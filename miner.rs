@@ -0,0 +1,288 @@
+//! Configure a search with `MinerBuilder`, then run it on its own thread through the
+//! `Miner` handle instead of blocking in `search`.
+
+use crate::secondary::Secondary;
+use crate::{
+    search, BatchFn, Find, HashFn, Job, Mask, NonceEncoding, NonceOrder, NonceStrategy, PhaseTimings,
+    PortocriptoError, PrefixLayout, Progress, Target, Verify,
+};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub struct MinerBuilder {
+    threads: usize,
+    hash: HashFn,
+    hash_batch: Option<BatchFn>,
+    hash_len: usize,
+    difficulty_mask: Mask,
+    target: Option<Target>,
+    layout: PrefixLayout,
+    suffix: String,
+    nonce_encoding: NonceEncoding,
+    strategy: NonceStrategy,
+    seed: u64,
+    worker_slice: (u64, u64),
+    batch_size: u64,
+    near_miss_bits: Option<u32>,
+    secondaries: Arc<Vec<Secondary>>,
+    ramp: Option<Duration>,
+    verify: Option<Verify>,
+    timings: Option<Arc<PhaseTimings>>,
+    progress: Option<Arc<Progress>>,
+}
+
+impl MinerBuilder {
+    /// A miner for `hash` with the CLI defaults; everything else is set through the builder.
+    pub fn new(hash: HashFn) -> Self {
+        MinerBuilder {
+            threads: crate::NUM_THREADS.min(crate::available_cpus()),
+            hash,
+            hash_batch: None,
+            hash_len: 32,
+            difficulty_mask: Mask::MAX,
+            target: None,
+            layout: PrefixLayout::DEFAULT,
+            suffix: String::new(),
+            nonce_encoding: NonceEncoding::Hex16,
+            strategy: NonceStrategy::Strided,
+            seed: 0,
+            worker_slice: (0, 1),
+            batch_size: crate::BATCH_SIZE,
+            near_miss_bits: None,
+            secondaries: Arc::new(Vec::new()),
+            ramp: None,
+            verify: None,
+            timings: None,
+            progress: None,
+        }
+    }
+
+    /// Workers for this miner, each on a thread of its own for as long as the search runs; the
+    /// global rayon pool is left alone, so this count is the miner's whole reservation.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn difficulty(mut self, mask: Mask, layout: PrefixLayout) -> Self {
+        self.difficulty_mask = mask;
+        self.layout = layout;
+        self
+    }
+
+    /// A full 256-bit target the hashes that pass the difficulty mask must also be within.
+    pub fn target(mut self, target: Option<Target>) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// A batch entry point of the same backend as `hash`, called with up to `BATCH_LANES`
+    /// preimages at a time instead of `hash`.
+    pub fn hash_batch(mut self, hash_batch: Option<BatchFn>) -> Self {
+        self.hash_batch = hash_batch;
+        self
+    }
+
+    /// Digest bytes to request from the backend, 1 to 64.
+    pub fn hash_len(mut self, hash_len: usize) -> Self {
+        self.hash_len = hash_len.clamp(1, 64);
+        self
+    }
+
+    /// Preimage fields that follow the nonce, concatenated in order.
+    pub fn preimage_parts(mut self, parts: &[&str]) -> Self {
+        self.suffix = parts.concat();
+        self
+    }
+
+    /// How the nonce is written in front of the preimage parts.
+    pub fn nonce_encoding(mut self, encoding: NonceEncoding) -> Self {
+        self.nonce_encoding = encoding;
+        self
+    }
+
+    pub fn nonce_order(mut self, strategy: NonceStrategy, seed: u64) -> Self {
+        self.strategy = strategy;
+        self.seed = seed;
+        self
+    }
+
+    /// Mine only the `index`-th of `count` disjoint slices of the nonce order, for fleets
+    /// sharing a seed without a coordinator.
+    pub fn worker_slice(mut self, index: u64, count: u64) -> Self {
+        self.worker_slice = (index.min(count.max(1) - 1), count.max(1));
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: u64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Log hashes that miss the difficulty by at most `bits` zero bits.
+    pub fn near_miss(mut self, bits: Option<u32>) -> Self {
+        self.near_miss_bits = bits;
+        self
+    }
+
+    /// Easier difficulties to check each hash against too, each writing its matches to its
+    /// own sink.
+    pub fn secondaries(mut self, secondaries: Arc<Vec<Secondary>>) -> Self {
+        self.secondaries = secondaries;
+        self
+    }
+
+    /// Start on one worker and add the others one at a time over `ramp`, and take them away
+    /// the same way when the search stops.
+    pub fn ramp(mut self, ramp: Option<Duration>) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    /// Recompute a sample of the hashes with `verify.reference`.
+    pub fn verify(mut self, verify: Option<Verify>) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Collect sampled phase timings into `timings`.
+    pub fn timings(mut self, timings: Option<Arc<PhaseTimings>>) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    /// Continue from existing per-worker state, e.g. cursors read back from a journal; its
+    /// worker count overrides `threads`.
+    pub fn progress(mut self, progress: Arc<Progress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn build(self) -> Miner {
+        let progress = self.progress.clone().unwrap_or_else(|| Arc::new(Progress::new(self.threads)));
+        Miner { progress, config: Some(self), handle: None }
+    }
+}
+
+/// Handle to a search running on its own threads.
+pub struct Miner {
+    progress: Arc<Progress>,
+    config: Option<MinerBuilder>,
+    handle: Option<JoinHandle<Result<Vec<Find>, PortocriptoError>>>,
+}
+
+impl Miner {
+    /// Shared counters, for callers that report progress themselves.
+    pub fn progress(&self) -> &Arc<Progress> {
+        &self.progress
+    }
+
+    /// Spawn the search; calling it again has no effect.
+    pub fn start(&mut self) {
+        let Some(config) = self.config.take() else {
+            return;
+        };
+        let progress = Arc::clone(&self.progress);
+        self.handle = Some(std::thread::spawn(move || run(config, &progress)));
+    }
+
+    /// Wait for the search to end and return its solutions (empty if never started).
+    pub fn join(mut self) -> Result<Vec<Find>, PortocriptoError> {
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .unwrap_or_else(|_| Err(PortocriptoError::Backend("miner thread panicked".to_string()))),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+fn run(config: MinerBuilder, progress: &Progress) -> Result<Vec<Find>, PortocriptoError> {
+    let MinerBuilder {
+        hash,
+        hash_batch,
+        hash_len,
+        difficulty_mask,
+        target,
+        layout,
+        suffix,
+        nonce_encoding,
+        strategy,
+        seed,
+        worker_slice,
+        batch_size,
+        near_miss_bits,
+        ramp,
+        verify,
+        ..
+    } = config;
+    let threads = progress.workers.len() as u64;
+    let job = Job {
+        suffix: &suffix,
+        nonce_encoding,
+        difficulty_mask,
+        target,
+        layout,
+        hash,
+        hash_batch,
+        hash_len,
+        order: NonceOrder { strategy, threads, seed, worker_index: worker_slice.0, worker_count: worker_slice.1 },
+        batch_size,
+        near_miss_bits,
+        secondaries: &config.secondaries,
+        end_index: u64::MAX,
+        ramp,
+        timings: config.timings.as_deref(),
+        range_log: None,
+        verify,
+    };
+    search(&job, progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_preimage, hash_structure_good, parse_mask};
+
+    #[test]
+    fn builder_limits() {
+        let builder = MinerBuilder::new(hash_preimage).threads(0).hash_len(100).worker_slice(9, 4).batch_size(0);
+        assert_eq!((builder.threads, builder.hash_len, builder.worker_slice, builder.batch_size), (1, 64, (3, 4), 1));
+        let builder = builder.hash_len(0).worker_slice(2, 0).preimage_parts(&["addr", "**D07", "ff"]);
+        assert_eq!((builder.hash_len, builder.worker_slice, builder.suffix.as_str()), (1, (0, 1), "addr**D07ff"));
+    }
+
+    #[test]
+    fn searches_find_what_the_reference_accepts() {
+        // Four zero bits: a solution within a few dozen hashes
+        let mask = parse_mask("0fffffff").unwrap();
+        let mut miner = MinerBuilder::new(hash_preimage)
+            .threads(2)
+            .difficulty(mask, PrefixLayout::DEFAULT)
+            .preimage_parts(&["addr_test1", "**D07"])
+            .batch_size(8)
+            .build();
+        assert_eq!(miner.progress().workers.len(), 2);
+        miner.start();
+        miner.start();
+        let finds = miner.join().unwrap();
+        assert!(!finds.is_empty());
+        for find in finds {
+            let mut hash = [0u8; 32];
+            hash_preimage(format!("{:016x}addr_test1**D07", find.nonce).as_bytes(), &mut hash);
+            assert_eq!(find.hash, hash);
+            assert!(hash_structure_good(&hash, mask));
+            assert!(find.thread_id < 2 && find.hashes_tried >= 1);
+        }
+    }
+
+    #[test]
+    fn unstarted_miners_find_nothing() {
+        let progress = Arc::new(Progress::resume(vec![0, 1, 2]));
+        let miner = MinerBuilder::new(hash_preimage).threads(8).progress(Arc::clone(&progress)).build();
+        assert!(Arc::ptr_eq(miner.progress(), &progress));
+        assert_eq!(miner.progress().workers.len(), 3);
+        assert!(miner.join().unwrap().is_empty());
+    }
+}
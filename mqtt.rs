@@ -0,0 +1,170 @@
+//! Minimal MQTT 3.1.1 publisher: CONNECT, QoS 0 PUBLISH and DISCONNECT over plain TCP.
+
+use crate::{proxy, retry};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+pub struct Mqtt {
+    stream: TcpStream,
+}
+
+/// MQTT's variable-length "remaining length" prefix.
+fn encode_length(mut len: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn push_str(s: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![kind];
+    encode_length(body.len(), &mut out);
+    out.extend_from_slice(body);
+    out
+}
+
+type Auth<'a> = Option<(&'a str, &'a str)>;
+
+/// `mqtt://[user[:password]@]host[:port]` as host:port and the user and password.
+fn parse_url(url: &str) -> std::io::Result<(String, Auth<'_>)> {
+    let rest = url
+        .strip_prefix("mqtt://")
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "only mqtt:// URLs are supported"))?;
+    let rest = rest.trim_end_matches('/');
+    let (auth, host) = match rest.rsplit_once('@') {
+        Some((auth, host)) => (Some(auth.split_once(':').unwrap_or((auth, ""))), host),
+        None => (None, rest),
+    };
+    let addr = if host.contains(':') { host.to_string() } else { format!("{}:1883", host) };
+    Ok((addr, auth))
+}
+
+fn connect_packet(client_id: &str, auth: Auth) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_str("MQTT", &mut body);
+    body.push(4); // protocol level 3.1.1
+    let (user, password) = auth.map_or((None, None), |(u, p)| (Some(u), Some(p).filter(|p| !p.is_empty())));
+    let mut flags = 0x02; // clean session
+    if user.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+    body.push(flags);
+    body.extend_from_slice(&0u16.to_be_bytes()); // no keep-alive
+    push_str(client_id, &mut body);
+    for field in [user, password].into_iter().flatten() {
+        push_str(field, &mut body);
+    }
+    packet(0x10, &body)
+}
+
+fn check_connack(connack: [u8; 4]) -> std::io::Result<()> {
+    match connack {
+        [0x20, 0x02, _, 0] => Ok(()),
+        [0x20, 0x02, _, code] => Err(Error::other(format!("broker refused connection (code {})", code))),
+        _ => Err(Error::new(ErrorKind::InvalidData, format!("expected a CONNACK, got {:02x?}", connack))),
+    }
+}
+
+fn publish_packet(topic: &str, payload: &str, retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_str(topic, &mut body);
+    body.extend_from_slice(payload.as_bytes());
+    packet(if retain { 0x31 } else { 0x30 }, &body)
+}
+
+impl Mqtt {
+    /// Connect to `mqtt://[user[:password]@]host[:port]` and wait for the CONNACK.
+    pub fn connect(url: &str, client_id: &str) -> std::io::Result<Mqtt> {
+        let (addr, auth) = parse_url(url)?;
+        let mut stream = retry::retry("mqtt", || proxy::connect(&addr))?;
+        stream.write_all(&connect_packet(client_id, auth))?;
+        let mut connack = [0u8; 4];
+        stream.read_exact(&mut connack)?;
+        check_connack(connack)?;
+        Ok(Mqtt { stream })
+    }
+
+    pub fn publish(&mut self, topic: &str, payload: &str, retain: bool) -> std::io::Result<()> {
+        self.stream.write_all(&publish_packet(topic, payload, retain))
+    }
+}
+
+impl Drop for Mqtt {
+    fn drop(&mut self) {
+        let _ = self.stream.write_all(&[0xe0, 0x00]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls() {
+        assert_eq!(parse_url("mqtt://broker").unwrap(), ("broker:1883".to_string(), None));
+        assert_eq!(parse_url("mqtt://u@broker:8883/").unwrap(), ("broker:8883".to_string(), Some(("u", ""))));
+        assert_eq!(parse_url("mqtt://u:p:q@broker").unwrap().1, Some(("u", "p:q")));
+        assert_eq!(parse_url("mqtts://broker").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn remaining_length() {
+        for (len, bytes) in [
+            (0, &[0x00][..]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (16_383, &[0xff, 0x7f]),
+            (16_384, &[0x80, 0x80, 0x01]),
+            (268_435_455, &[0xff, 0xff, 0xff, 0x7f]),
+        ] {
+            let mut out = Vec::new();
+            encode_length(len, &mut out);
+            assert_eq!(out, bytes, "{}", len);
+        }
+    }
+
+    #[test]
+    fn connect_flags() {
+        let anonymous = connect_packet("rig", None);
+        assert_eq!(anonymous, [&[0x10, 15, 0, 4][..], b"MQTT", &[4, 0x02, 0, 0, 0, 3], b"rig"].concat());
+        let user = connect_packet("r", Some(("u", "")));
+        assert_eq!(user[9], 0x82);
+        assert_eq!(&user[user.len() - 3..], [0, 1, b'u']);
+        let both = connect_packet("r", Some(("u", "pw")));
+        assert_eq!(both[9], 0xc2);
+        assert_eq!(&both[both.len() - 7..], [0, 1, b'u', 0, 2, b'p', b'w']);
+    }
+
+    #[test]
+    fn publish_is_qos_0() {
+        assert_eq!(publish_packet("t/a", "{}", false), [&[0x30, 7, 0, 3][..], b"t/a{}"].concat());
+        assert_eq!(publish_packet("t", "", true), [0x31, 3, 0, 1, b't']);
+        let long = publish_packet("t", &"x".repeat(200), false);
+        assert_eq!(&long[..3], [0x30, 0xcb, 0x01]);
+    }
+
+    #[test]
+    fn connack() {
+        assert!(check_connack([0x20, 0x02, 0x00, 0x00]).is_ok());
+        assert!(check_connack([0x20, 0x02, 0x01, 0x00]).is_ok());
+        let refused = check_connack([0x20, 0x02, 0x00, 0x05]).unwrap_err();
+        assert_eq!(refused.to_string(), "broker refused connection (code 5)");
+        assert_eq!(check_connack(*b"HTTP").unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(check_connack([0x20, 0x03, 0x00, 0x00]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,63 @@
+//! `--offline`: no sockets at all, for air-gapped and policy-restricted machines; solutions
+//! are printed, and written to whatever local files were asked for, but never sent.
+//!
+//! Every socket the crate opens comes from a `Network` method, and `network()` is the only
+//! way to get a `Network`; it refuses once `set` has been called, so code that reaches the
+//! network cannot be written without going through that check. The CLI also turns the
+//! network flags down at startup and drops network settings read from the config file, so an
+//! offline run fails before it starts rather than at its first solution. --on-solution is
+//! turned down too, as what the command does is out of the miner's hands.
+
+use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Permission to open sockets, from `network`.
+pub struct Network(());
+
+/// Refuse network access for the rest of the process.
+pub fn set() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+pub fn network() -> std::io::Result<Network> {
+    if OFFLINE.load(Ordering::Relaxed) {
+        return Err(Error::new(ErrorKind::PermissionDenied, "network access is disabled by --offline"));
+    }
+    Ok(Network(()))
+}
+
+impl Network {
+    pub fn connect(&self, addr: &SocketAddr, timeout: Duration) -> std::io::Result<TcpStream> {
+        TcpStream::connect_timeout(addr, timeout)
+    }
+
+    pub fn listen(&self, addr: impl ToSocketAddrs) -> std::io::Result<TcpListener> {
+        TcpListener::bind(addr)
+    }
+
+    pub fn udp(&self, addr: impl ToSocketAddrs) -> std::io::Result<UdpSocket> {
+        UdpSocket::bind(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `set` is for the whole process, so only the permitted side is tested here
+    #[test]
+    fn sockets_come_from_a_network() {
+        let network = network().unwrap();
+        let listener = network.listen("127.0.0.1:0").unwrap();
+        let stream = network.connect(&listener.local_addr().unwrap(), Duration::from_secs(1)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+        let (a, b) = (network.udp("127.0.0.1:0").unwrap(), network.udp("127.0.0.1:0").unwrap());
+        a.send_to(b"ping", b.local_addr().unwrap()).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(b.recv_from(&mut buf).unwrap(), (4, a.local_addr().unwrap()));
+    }
+}
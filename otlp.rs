@@ -0,0 +1,265 @@
+//! OpenTelemetry export over OTLP/HTTP with the JSON encoding, so a collector on the usual
+//! port 4318 takes it without an SDK or protobuf. Spans cover the job and its phases,
+//! metrics the hash counters; both are best effort and only logged when they fail.
+
+use crate::identity::Identity;
+use crate::{http_post_json, json_string, reporter};
+use std::hash::{BuildHasher, RandomState};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub enum Value {
+    Str(String),
+    Int(u64),
+    Bool(bool),
+}
+
+fn attributes(attrs: &[(&str, Value)]) -> String {
+    let attrs: Vec<String> = attrs
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Str(s) => format!("{{\"stringValue\":{}}}", json_string(s)),
+                Value::Int(n) => format!("{{\"intValue\":\"{}\"}}", n),
+                Value::Bool(b) => format!("{{\"boolValue\":{}}}", b),
+            };
+            format!("{{\"key\":{},\"value\":{}}}", json_string(key), value)
+        })
+        .collect();
+    format!("[{}]", attrs.join(","))
+}
+
+fn nanos(t: SystemTime) -> String {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
+
+/// `bytes` random bytes as hex, enough for trace and span ids.
+pub fn random_id(bytes: usize) -> String {
+    (0..bytes.div_ceil(8))
+        .map(|_| format!("{:016x}", RandomState::new().hash_one(SystemTime::now())))
+        .collect::<String>()[..bytes * 2]
+        .to_string()
+}
+
+pub struct Otlp {
+    endpoint: String,
+    resource: String,
+    trace_id: String,
+    /// Parent of every span this process exports
+    pub job_span: String,
+}
+
+impl Otlp {
+    pub fn new(endpoint: &str, worker: &Identity) -> Self {
+        let resource = [
+            ("service.name", Value::Str("portocripto".to_string())),
+            ("service.version", Value::Str(env!("CARGO_PKG_VERSION").to_string())),
+            ("service.instance.id", Value::Str(worker.id.clone())),
+            ("portocripto.worker", Value::Str(worker.name.clone())),
+        ];
+        Otlp {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            resource: format!("{{\"attributes\":{}}}", attributes(&resource)),
+            trace_id: random_id(16),
+            job_span: random_id(8),
+        }
+    }
+
+    /// Export one finished span; `parent` is `None` for the job span itself. An `error`
+    /// marks the span failed.
+    pub fn span(
+        &self,
+        name: &str,
+        (start, end): (SystemTime, SystemTime),
+        parent: Option<&str>,
+        attrs: &[(&str, Value)],
+        error: Option<&str>,
+    ) {
+        let (id, parent) = match parent {
+            Some(parent) => (random_id(8), format!(",\"parentSpanId\":\"{}\"", parent)),
+            None => (self.job_span.clone(), String::new()),
+        };
+        let status = match error {
+            Some(message) => format!("{{\"code\":2,\"message\":{}}}", json_string(message)),
+            None => "{\"code\":1}".to_string(),
+        };
+        let body = format!(
+            "{{\"resourceSpans\":[{{\"resource\":{},\"scopeSpans\":[{{\"scope\":{{\"name\":\"portocripto\"}},\"spans\":[{{\
+             \"traceId\":\"{}\",\"spanId\":\"{}\"{},\"name\":{},\"kind\":1,\"startTimeUnixNano\":\"{}\",\
+             \"endTimeUnixNano\":\"{}\",\"attributes\":{},\"status\":{}}}]}}]}}]}}",
+            self.resource,
+            self.trace_id,
+            id,
+            parent,
+            json_string(name),
+            nanos(start),
+            nanos(end),
+            attributes(attrs),
+            status
+        );
+        if let Err(e) = http_post_json(&format!("{}/v1/traces", self.endpoint), &body) {
+            eprintln!("otlp: {}", e);
+        }
+    }
+
+    fn metrics(&self, challenge_id: &str, start: SystemTime, hashes: u64, hashrate: f64, solutions: u64) {
+        let (start, now) = (nanos(start), nanos(SystemTime::now()));
+        let point = attributes(&[("portocripto.challenge_id", Value::Str(challenge_id.to_string()))]);
+        let sum = |name: &str, unit: &str, value: u64| {
+            format!(
+                "{{\"name\":\"{}\",\"unit\":\"{}\",\"sum\":{{\"aggregationTemporality\":2,\"isMonotonic\":true,\
+                 \"dataPoints\":[{{\"startTimeUnixNano\":\"{}\",\"timeUnixNano\":\"{}\",\"asInt\":\"{}\",\
+                 \"attributes\":{}}}]}}}}",
+                name, unit, start, now, value, point
+            )
+        };
+        let body = format!(
+            "{{\"resourceMetrics\":[{{\"resource\":{},\"scopeMetrics\":[{{\"scope\":{{\"name\":\"portocripto\"}},\"metrics\":[\
+             {},{},{{\"name\":\"portocripto.hashrate\",\"unit\":\"{{hash}}/s\",\"gauge\":{{\"dataPoints\":[\
+             {{\"timeUnixNano\":\"{}\",\"asDouble\":{:.1},\"attributes\":{}}}]}}}}]}}]}}]}}",
+            self.resource,
+            sum("portocripto.hashes", "{hash}", hashes),
+            sum("portocripto.solutions", "{solution}", solutions),
+            now,
+            hashrate,
+            point
+        );
+        if let Err(e) = http_post_json(&format!("{}/v1/metrics", self.endpoint), &body) {
+            eprintln!("otlp: {}", e);
+        }
+    }
+}
+
+/// `reporter` sink exporting the hash metrics, tagged with `challenge_id`.
+pub struct Metrics<'a> {
+    pub otlp: &'a Otlp,
+    pub challenge_id: &'a str,
+    pub start: SystemTime,
+}
+
+impl reporter::Sink for Metrics<'_> {
+    fn report(&mut self, sample: &reporter::Sample) {
+        self.otlp.metrics(self.challenge_id, self.start, sample.hashes, sample.hashrate, sample.solutions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{self, Json};
+    use crate::reporter::Sink;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Duration;
+
+    /// An endpoint that takes one export and hands over its path and JSON body.
+    fn collector() -> (String, Receiver<(String, Json)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(listener.accept().unwrap().0);
+            let (mut line, mut path, mut length) = (String::new(), String::new(), 0);
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some(rest) = line.strip_prefix("POST ") {
+                    path = rest.split(' ').next().unwrap().to_string();
+                }
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                line.clear();
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}").unwrap();
+            sender.send((path, json::parse(std::str::from_utf8(&body).unwrap()).unwrap())).unwrap();
+        });
+        (endpoint, receiver)
+    }
+
+    fn otlp(endpoint: &str) -> Otlp {
+        Otlp::new(endpoint, &Identity { id: "b7f3".to_string(), name: "rig \"7\"".to_string() })
+    }
+
+    /// The member at `path`, a dotted list of keys and array indices.
+    fn at<'a>(json: &'a Json, path: &str) -> &'a Json {
+        path.split('.').fold(json, |json, key| match (json, key.parse::<usize>()) {
+            (Json::Array(items), Ok(i)) => &items[i],
+            _ => json.get(key).unwrap_or_else(|| panic!("no {} in {:?}", key, path)),
+        })
+    }
+
+    #[test]
+    fn ids_and_attributes() {
+        for bytes in [8, 16] {
+            let id = random_id(bytes);
+            assert_eq!(id.len(), bytes * 2);
+            assert!(id.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()), "{}", id);
+        }
+        assert_ne!(random_id(16), random_id(16));
+        assert_eq!(nanos(UNIX_EPOCH + Duration::from_millis(1500)), "1500000000");
+        let attrs = [("a", Value::Str("x\"y".into())), ("n", Value::Int(u64::MAX)), ("b", Value::Bool(false))];
+        assert_eq!(
+            attributes(&attrs),
+            "[{\"key\":\"a\",\"value\":{\"stringValue\":\"x\\\"y\"}},\
+             {\"key\":\"n\",\"value\":{\"intValue\":\"18446744073709551615\"}},\
+             {\"key\":\"b\",\"value\":{\"boolValue\":false}}]"
+        );
+        assert_eq!(attributes(&[]), "[]");
+    }
+
+    #[test]
+    fn spans() {
+        let (endpoint, exported) = collector();
+        let otlp = otlp(&endpoint);
+        let start = UNIX_EPOCH + Duration::from_secs(1_761_000_000);
+        let times = (start, start + Duration::from_secs(2));
+        otlp.span("search", times, Some(&otlp.job_span), &[("threads", Value::Int(8))], Some("backend failed"));
+        let (path, body) = exported.recv().unwrap();
+        assert_eq!(path, "/v1/traces");
+        let resource = at(&body, "resourceSpans.0.resource.attributes.3");
+        assert_eq!(at(resource, "value.stringValue").as_str(), Some("rig \"7\""));
+        let span = at(&body, "resourceSpans.0.scopeSpans.0.spans.0");
+        assert_eq!(at(span, "parentSpanId").as_str(), Some(otlp.job_span.as_str()));
+        assert_ne!(at(span, "spanId").as_str(), Some(otlp.job_span.as_str()));
+        assert_eq!(at(span, "startTimeUnixNano").as_str(), Some("1761000000000000000"));
+        assert_eq!(at(span, "endTimeUnixNano").as_str(), Some("1761000002000000000"));
+        assert_eq!(at(span, "status.code").as_f64(), Some(2.0));
+        assert_eq!(at(span, "status.message").as_str(), Some("backend failed"));
+        assert_eq!(at(span, "attributes.0.value.intValue").as_str(), Some("8"));
+
+        let (endpoint, exported) = collector();
+        let otlp = self::otlp(&endpoint);
+        otlp.span("job", times, None, &[], None);
+        let span = at(&exported.recv().unwrap().1, "resourceSpans.0.scopeSpans.0.spans.0").clone();
+        assert_eq!(at(&span, "spanId").as_str(), Some(otlp.job_span.as_str()));
+        assert!(span.get("parentSpanId").is_none());
+        assert_eq!(at(&span, "status.code").as_f64(), Some(1.0));
+    }
+
+    #[test]
+    fn metrics() {
+        let (endpoint, exported) = collector();
+        let otlp = otlp(&endpoint);
+        let mut sink = Metrics { otlp: &otlp, challenge_id: "**D07", start: UNIX_EPOCH };
+        let sample = reporter::Sample {
+            hashes: 1234,
+            hashrate: 56.78,
+            uptime: Duration::ZERO,
+            solutions: 1,
+            paused: false,
+            last: false,
+        };
+        sink.report(&sample);
+        let (path, body) = exported.recv().unwrap();
+        assert_eq!(path, "/v1/metrics");
+        let metrics = at(&body, "resourceMetrics.0.scopeMetrics.0.metrics");
+        assert_eq!(at(metrics, "0.name").as_str(), Some("portocripto.hashes"));
+        assert_eq!(at(metrics, "0.sum.dataPoints.0.asInt").as_str(), Some("1234"));
+        assert_eq!(at(metrics, "0.sum.dataPoints.0.startTimeUnixNano").as_str(), Some("0"));
+        assert_eq!(at(metrics, "1.sum.dataPoints.0.asInt").as_str(), Some("1"));
+        assert_eq!(at(metrics, "2.gauge.dataPoints.0.asDouble").as_f64(), Some(56.8));
+        let point = at(metrics, "2.gauge.dataPoints.0.attributes.0");
+        assert_eq!(at(point, "value.stringValue").as_str(), Some("**D07"));
+    }
+}
@@ -0,0 +1,299 @@
+//! `--outbox DIR`: solutions whose `--on-solution` command kept failing are saved here, one
+//! JSON file each, and handed to the command again in the background of every later run
+//! until it succeeds, so a nonce found during a network outage is not lost.
+//!
+//! Every (challenge_id, nonce) the command accepted is also appended to `--submitted`
+//! (default `portocripto.submitted`), and a pair found there is never handed to it again,
+//! so replaying the outbox after a crash between the submission and the file's removal
+//! cannot submit a solution twice.
+//!
+//! A run's own solution goes through a `Submitter`, started before the search with the
+//! command already filled in but for the nonce and hash, so the submission starts as soon
+//! as the search ends rather than after the journal, reports and receipt are written.
+
+use crate::solution::Solution;
+use crate::{chaos, retry, run_on_solution, wait_for_stop};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Pause between passes over the outbox while a run is mining.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Default `--submitted`, next to the config file.
+pub const SUBMITTED_FILE: &str = "portocripto.submitted";
+/// Submissions remembered; the oldest are forgotten first
+const MAX_SUBMITTED: usize = 4096;
+
+/// Serializes the run's submission and the outbox drain on the --submitted file
+static SUBMITTED: Mutex<()> = Mutex::new(());
+
+impl Solution {
+    /// This solution's line in the --submitted file: challenge id, a tab, the nonce.
+    fn submitted_line(&self) -> String {
+        format!("{}\t{}", self.challenge_id.replace(['\t', '\n'], " "), self.nonce)
+    }
+
+    /// Run `template` for this solution unless `submitted` shows it already succeeded, and
+    /// record it there when it does; false if it was skipped as a duplicate. The error says
+    /// why it did not succeed.
+    pub fn submit(&self, template: &str, submitted: &Path) -> Result<bool, String> {
+        let _guard = SUBMITTED.lock().unwrap_or_else(|e| e.into_inner());
+        let line = self.submitted_line();
+        let known = std::fs::read_to_string(submitted).unwrap_or_default();
+        if known.lines().any(|l| l == line) {
+            eprintln!(
+                "outbox: {} for challenge {} was already submitted (see {}); not submitting it again",
+                self.nonce,
+                self.challenge_id,
+                submitted.display()
+            );
+            return Ok(false);
+        }
+        if chaos::drop_submission() {
+            return Err("chaos: dropped the submission".to_string());
+        }
+        match run_on_solution(template, &self.placeholders()) {
+            Ok(status) if status.success() => {
+                if let Err(e) = remember(submitted, &known, &line) {
+                    eprintln!("outbox: failed to record the submission in {}: {}", submitted.display(), e);
+                }
+                Ok(true)
+            }
+            Ok(status) => Err(format!("--on-solution command exited with {}", status)),
+            Err(e) => Err(format!("failed to run --on-solution command: {}", e)),
+        }
+    }
+}
+
+/// How a `Submitter` submission went.
+pub struct Submission {
+    /// With `submitted` set when the command succeeded
+    pub solution: Solution,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    /// Why the command did not succeed, after the retries if there were any
+    pub failure: Option<String>,
+}
+
+/// A thread waiting to run --on-solution for the run's solution.
+pub struct Submitter {
+    sender: mpsc::Sender<Solution>,
+    thread: JoinHandle<Option<Submission>>,
+}
+
+impl Submitter {
+    /// Wait for a solution of `challenge_id` paid to `address`; with `retrying`, a failing
+    /// command is retried as the `retry` policy says.
+    pub fn start(template: &str, address: &str, challenge_id: &str, submitted: PathBuf, retrying: bool) -> Submitter {
+        let template = template.replace("{address}", address).replace("{challenge_id}", challenge_id);
+        let (sender, receiver) = mpsc::channel::<Solution>();
+        let thread = std::thread::spawn(move || {
+            let mut solution = receiver.recv().ok()?;
+            let started = SystemTime::now();
+            let failure = if retrying {
+                retry::retry("on-solution", || solution.submit(&template, &submitted).map_err(std::io::Error::other))
+                    .err()
+                    .map(|e| e.to_string())
+            } else {
+                solution.submit(&template, &submitted).err()
+            };
+            let finished = SystemTime::now();
+            if failure.is_none() {
+                solution.submitted = Some(finished);
+            }
+            Some(Submission { solution, started, finished, failure })
+        });
+        Submitter { sender, thread }
+    }
+
+    pub fn submit(&self, solution: Solution) {
+        // The thread only ends after taking a solution, and takes a single one
+        let _ = self.sender.send(solution);
+    }
+
+    /// Wait for the submission; `None` when no solution was handed over.
+    pub fn finish(self) -> Option<Submission> {
+        drop(self.sender);
+        self.thread.join().unwrap_or(None)
+    }
+}
+
+/// Append `line` to the --submitted file already holding `known`, rewriting it without the
+/// oldest lines once it holds `MAX_SUBMITTED`.
+fn remember(path: &Path, known: &str, line: &str) -> std::io::Result<()> {
+    let count = known.lines().count();
+    if count < MAX_SUBMITTED {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        return writeln!(file, "{}", line);
+    }
+    let mut text: String = known.lines().skip(count + 1 - MAX_SUBMITTED).flat_map(|l| [l, "\n"]).collect();
+    text.push_str(line);
+    text.push('\n');
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Save `solution` under `dir`; rewriting the same solution replaces its file.
+pub fn store(dir: &Path, solution: &Solution) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let name: String = format!("{}-{}", solution.challenge_id, solution.nonce)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let path = dir.join(format!("{}.json", name));
+    let text = format!("{}\n", solution.to_json());
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// Solutions found under `dir`, oldest name first; unreadable files are reported and skipped.
+fn load(dir: &Path) -> Vec<(PathBuf, Solution)> {
+    let Ok(files) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> =
+        files.flatten().map(|f| f.path()).filter(|p| p.extension().is_some_and(|e| e == "json")).collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let parsed =
+                std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| Solution::parse(&text));
+            match parsed {
+                Ok(solution) => Some((path, solution)),
+                Err(e) => {
+                    eprintln!("outbox: skipping {}: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Hand every saved solution to `template` again, every `RETRY_INTERVAL` until the outbox is
+/// empty or `stop` is set; files are deleted once their command succeeds or `submitted`
+/// shows it did before.
+pub fn drain(dir: &Path, template: &str, submitted: &Path, stop: &AtomicBool) {
+    loop {
+        let solutions = load(dir);
+        if solutions.is_empty() {
+            return;
+        }
+        eprintln!("outbox: {} saved solution(s) to submit", solutions.len());
+        for (path, solution) in solutions {
+            match solution.submit(template, submitted) {
+                Ok(sent) => {
+                    if sent {
+                        eprintln!("outbox: submitted {} for challenge {}", solution.nonce, solution.challenge_id);
+                    }
+                    if let Err(e) = std::fs::remove_file(&path) {
+                        eprintln!("outbox: failed to remove {}: {}", path.display(), e);
+                    }
+                }
+                Err(e) => eprintln!("outbox: {} for challenge {}: {}", solution.nonce, solution.challenge_id, e),
+            }
+        }
+        if wait_for_stop(stop, RETRY_INTERVAL) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NonceEncoding;
+
+    fn solution(nonce: u64) -> Solution {
+        Solution::new(NonceEncoding::Hex16, nonce, &[0xab], "**D07/x", "addr_test1")
+    }
+
+    #[test]
+    fn stored_names_are_safe() {
+        let dir = crate::scratch_dir("outbox-store");
+        let path = store(&dir.join("new"), &solution(1)).unwrap();
+        assert_eq!(path.file_name().unwrap(), "__D07_x-0000000000000001.json");
+        assert_eq!(store(&dir.join("new"), &solution(1)).unwrap(), path);
+        std::fs::write(dir.join("new/broken.json"), "{").unwrap();
+        std::fs::write(dir.join("new/notes.txt"), "").unwrap();
+        let loaded = load(&dir.join("new"));
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].1, solution(1));
+        assert!(load(&dir.join("missing")).is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn submitted_lines() {
+        let solution = Solution { challenge_id: "a\tb\nc".into(), ..solution(2) };
+        assert_eq!(solution.submitted_line(), "a b c\t0000000000000002");
+    }
+
+    #[test]
+    fn remembered_submissions_are_capped() {
+        let dir = crate::scratch_dir("outbox-remember");
+        let path = dir.join("submitted");
+        remember(&path, "", "first").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\n");
+        let known: String = (0..MAX_SUBMITTED).map(|i| format!("{}\n", i)).collect();
+        remember(&path, &known, "last").unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), MAX_SUBMITTED);
+        assert_eq!((text.lines().next(), text.lines().last()), (Some("1"), Some("last")));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn submissions_happen_once() {
+        let dir = crate::scratch_dir("outbox-submit");
+        let submitted = dir.join("submitted");
+        assert_eq!(
+            solution(3).submit("false", &submitted),
+            Err("--on-solution command exited with exit status: 1".into())
+        );
+        assert!(!submitted.exists());
+        assert!(solution(3).submit("/nonexistent/hook", &submitted).unwrap_err().starts_with("failed to run"));
+        assert_eq!(solution(3).submit("true {nonce}", &submitted), Ok(true));
+        assert_eq!(solution(3).submit("true", &submitted), Ok(false));
+        assert_eq!(solution(4).submit("true", &submitted), Ok(true));
+        assert_eq!(std::fs::read_to_string(&submitted).unwrap().lines().count(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn drain_removes_what_went_through() {
+        let dir = crate::scratch_dir("outbox-drain");
+        let outbox = dir.join("outbox");
+        let submitted = dir.join("submitted");
+        store(&outbox, &solution(5)).unwrap();
+        store(&outbox, &solution(6)).unwrap();
+        solution(6).submit("true", &submitted).unwrap();
+        drain(&outbox, "true", &submitted, &AtomicBool::new(true));
+        assert!(load(&outbox).is_empty());
+        assert_eq!(std::fs::read_to_string(&submitted).unwrap().lines().count(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn submitter_fills_the_template() {
+        let dir = crate::scratch_dir("outbox-submitter");
+        let submitter =
+            Submitter::start("test {address} = addr_test1", "addr_test1", "c", dir.join("submitted"), false);
+        submitter.submit(solution(7));
+        let submission = submitter.finish().unwrap();
+        assert_eq!(submission.failure, None);
+        assert!(submission.solution.submitted.is_some());
+        let idle = Submitter::start("true", "a", "c", dir.join("submitted"), false);
+        assert!(idle.finish().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,413 @@
+//! Validated challenge parameters. Each type checks its value when clap parses the command
+//! line, so a truncated or mistyped copy-paste is rejected with a precise message before
+//! any hashing starts. Whitespace, quotes and a `0x` around a hex value (difficulty and
+//! no_pre_mine) are stripped first, with a note on stderr saying what changed; every other
+//! field is kept exactly as given, since it goes into the preimage verbatim, and a stray
+//! character in it is an error. With `--keep-0x` the prefix stays in the preimage too, and
+//! only the digits after it are read as a number.
+
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+macro_rules! text_newtype {
+    ($name:ident) => {
+        impl Deref for $name {
+            type Target = String;
+
+            fn deref(&self) -> &String {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    };
+}
+
+/// ASCII a non-ASCII character is commonly mistaken for when copied from a web page.
+fn lookalike(c: char) -> Option<char> {
+    match c {
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => Some('-'),
+        '\u{2018}' | '\u{2019}' => Some('\''),
+        '\u{201c}' | '\u{201d}' => Some('"'),
+        // Fullwidth forms of printable ASCII
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0),
+        // Cyrillic letters drawn like Latin ones
+        'а' => Some('a'),
+        'е' => Some('e'),
+        'о' => Some('o'),
+        'р' => Some('p'),
+        'с' => Some('c'),
+        'х' => Some('x'),
+        'А' => Some('A'),
+        'В' => Some('B'),
+        'С' => Some('C'),
+        'Е' => Some('E'),
+        'О' => Some('O'),
+        'Х' => Some('X'),
+        _ => None,
+    }
+}
+
+static KEEP_0X: AtomicBool = AtomicBool::new(false);
+
+/// Keep `0x` prefixes in the preimage instead of stripping them; set before parsing.
+pub fn keep_0x(keep: bool) {
+    KEEP_0X.store(keep, Ordering::Relaxed);
+}
+
+/// The hex digits of `s` after a `0x` or `0X` prefix; `s` itself if it has none.
+pub fn hex_digits(s: &str) -> &str {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(digits) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit()) => digits,
+        _ => s,
+    }
+}
+
+/// Quote pairs a value is commonly copied with.
+const QUOTES: [(char, char); 5] =
+    [('"', '"'), ('\'', '\''), ('`', '`'), ('\u{201c}', '\u{201d}'), ('\u{2018}', '\u{2019}')];
+
+/// Strip what a copy-paste commonly drags along from a hex value: surrounding whitespace and
+/// line breaks, matching quotes around it and, unless `--keep-0x`, a `0x` before the digits.
+/// Each change is reported, since the stripped value is the one that goes into the preimage.
+fn normalize(s: &str) -> String {
+    let (mut value, mut applied) = (s, Vec::new());
+    loop {
+        let trailing = &value[value.trim_end().len()..];
+        if trailing.contains(['\n', '\r']) {
+            applied.push("a trailing line break");
+        }
+        if value.trim_start().len() < value.len()
+            || trailing.contains(|c: char| c.is_whitespace() && !"\r\n".contains(c))
+        {
+            applied.push("surrounding whitespace");
+        }
+        value = value.trim();
+        let unquoted = QUOTES.iter().find_map(|&(open, close)| value.strip_prefix(open)?.strip_suffix(close));
+        let Some(inner) = unquoted else { break };
+        applied.push("surrounding quotes");
+        value = inner;
+    }
+    let digits = hex_digits(value);
+    if digits.len() < value.len() && !KEEP_0X.load(Ordering::Relaxed) {
+        applied.push("a 0x prefix");
+        value = digits;
+    }
+    // The same text can be parsed more than once; report it the first time
+    static REPORTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    let mut reported = REPORTED.lock().unwrap_or_else(|e| e.into_inner());
+    if !applied.is_empty() && !reported.iter().any(|r| r == s) {
+        reported.push(s.to_string());
+        eprintln!("input: using {:?} for {:?}: removed {}", value, s, applied.join(", "));
+    }
+    value.to_string()
+}
+
+/// Reject characters outside `allowed` with a diff of the value against what was probably
+/// meant: stray characters dropped and lookalikes replaced, with a caret under each one.
+fn check_charset(s: &str, allowed: fn(char) -> bool) -> Result<(), String> {
+    if s.chars().all(allowed) {
+        return Ok(());
+    }
+    let (mut given, mut carets, mut meant, mut notes) = (String::new(), String::new(), String::new(), Vec::new());
+    for (i, c) in s.chars().enumerate() {
+        if allowed(c) {
+            given.push(c);
+            carets.push(' ');
+            meant.push(c);
+            continue;
+        }
+        let shown: String = match c {
+            '\t' | '\n' | '\r' => c.escape_default().collect(),
+            _ if c.is_whitespace() || c.is_control() => c.escape_unicode().collect(),
+            _ => c.to_string(),
+        };
+        carets.push_str(&"^".repeat(shown.chars().count()));
+        given.push_str(&shown);
+        let kind = match lookalike(c).filter(|&l| allowed(l)) {
+            Some(l) => {
+                meant.push(l);
+                format!("looks like {:?}", l)
+            }
+            None if c.is_whitespace() => "whitespace".to_string(),
+            None if c.is_control() => "control character".to_string(),
+            None => "not allowed here".to_string(),
+        };
+        notes.push(format!("  position {}: U+{:04X} ({})", i, c as u32, kind));
+    }
+    Err(format!(
+        "unexpected characters (copied with surrounding text?):\n  - {}\n    {}\n  + {}\n{}",
+        given,
+        carets.trim_end(),
+        meant,
+        notes.join("\n")
+    ))
+}
+
+/// A plain challenge field such as `challenge_id`: printable ASCII without spaces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field(String);
+text_newtype!(Field);
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        check_charset(s, |c| c.is_ascii_graphic())?;
+        if s.is_empty() {
+            return Err("empty value".to_string());
+        }
+        Ok(Field(s.to_string()))
+    }
+}
+
+/// A difficulty mask: 1 to 32 hex digits, see `parse_mask`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HexMask(String);
+text_newtype!(HexMask);
+
+impl FromStr for HexMask {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = &normalize(s);
+        check_charset(hex_digits(s), |c| c.is_ascii_hexdigit())?;
+        crate::parse_mask(hex_digits(s)).map_err(|e| e.to_string())?;
+        Ok(HexMask(s.to_string()))
+    }
+}
+
+/// A challenge id: ASCII letters, digits, `_`, `-` and `*` (Scavenger Mine ids look like
+/// `**D07C10`). It reaches solution hooks and file names, so nothing a shell or a path
+/// would read specially gets in, whichever client submitted the challenge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChallengeId(String);
+text_newtype!(ChallengeId);
+
+impl FromStr for ChallengeId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        check_charset(s, |c| c.is_ascii_alphanumeric() || "_-*".contains(c))?;
+        if s.is_empty() {
+            return Err("empty value".to_string());
+        }
+        Ok(ChallengeId(s.to_string()))
+    }
+}
+
+/// A non-empty string of hex digits with an even length, such as `no_pre_mine`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HexString(String);
+text_newtype!(HexString);
+
+impl FromStr for HexString {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = &normalize(s);
+        let digits = hex_digits(s);
+        check_charset(digits, |c| c.is_ascii_hexdigit())?;
+        if digits.is_empty() || !digits.len().is_multiple_of(2) {
+            return Err(format!(
+                "{:?} has {} hex digits, expected a non-empty even number (truncated?)",
+                s,
+                digits.len()
+            ));
+        }
+        Ok(HexString(s.to_string()))
+    }
+}
+
+impl HexString {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let digits = hex_digits(&self.0);
+        (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap()).collect()
+    }
+}
+
+/// A Cardano bech32 address (`addr1...` or `addr_test1...`) with a valid checksum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bech32Address(String);
+text_newtype!(Bech32Address);
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn bech32_polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk = 1u32;
+    for value in values {
+        let top = chk >> 25;
+        chk = (chk & 0x1ffffff) << 5 ^ value as u32;
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if top >> i & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+impl FromStr for Bech32Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        check_charset(s, |c| c.is_ascii_alphanumeric() || c == '_')?;
+        if s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase()) {
+            return Err(format!("address {:?} mixes upper and lower case", s));
+        }
+        let lower = s.to_ascii_lowercase();
+        let (hrp, data) =
+            lower.rsplit_once('1').ok_or_else(|| format!("address {:?} has no bech32 separator '1'", s))?;
+        if hrp != "addr" && hrp != "addr_test" {
+            return Err(format!("address {:?} should start with addr1 or addr_test1", s));
+        }
+        let data = data
+            .bytes()
+            .enumerate()
+            .map(|(i, c)| {
+                BECH32_CHARSET.iter().position(|&d| d == c).map(|v| v as u8).ok_or_else(|| {
+                    format!("address {:?} has invalid character {:?} at position {}", s, c as char, hrp.len() + 1 + i)
+                })
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        if data.len() < 6 {
+            return Err(format!("address {:?} is too short to hold a checksum", s));
+        }
+        let expanded = hrp.bytes().map(|b| b >> 5).chain([0]).chain(hrp.bytes().map(|b| b & 31));
+        if bech32_polymod(expanded.chain(data.iter().copied())) != 1 {
+            return Err(format!("address {:?} fails its bech32 checksum (truncated or mistyped?)", s));
+        }
+        Ok(Bech32Address(s.to_string()))
+    }
+}
+
+/// `--hash-len`: a Blake2b digest length in bytes.
+pub fn parse_hash_len(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(len @ 1..=64) => Ok(len),
+        _ => Err(format!("{:?} is not a Blake2b digest length (1 to 64 bytes)", s)),
+    }
+}
+
+/// A duration such as `90`, `90s`, `45m`, `1h30m` or `2d`; a bare number is seconds.
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let invalid = || format!("{:?} is not a duration like 90s, 45m or 1h30m", s);
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(std::time::Duration::from_secs(secs));
+    }
+    let (mut secs, mut digits) = (0u64, String::new());
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(invalid()),
+        };
+        let n = digits.parse::<u64>().map_err(|_| invalid())?;
+        secs = n.checked_mul(unit).and_then(|n| n.checked_add(secs)).ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() || s.is_empty() {
+        return Err(invalid());
+    }
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const ADDRESS: &str = "addr_test1qpzry9x8gf2tvdw0s3jn4syst5";
+
+    #[test]
+    fn hex_values_are_normalized() {
+        assert_eq!(normalize("  \"0x00ffFF\"\r\n"), "00ffFF");
+        assert_eq!(normalize("\u{201c} 'abcd' \u{201d}"), "abcd");
+        assert_eq!(normalize("0x"), "0x");
+        assert_eq!(normalize("0xzz"), "0xzz");
+        assert_eq!(normalize("\"ab'"), "\"ab'");
+        assert_eq!("0X00FFFFFF".parse::<HexMask>().unwrap().to_string(), "00FFFFFF");
+        assert_eq!(" `0xA1b2` ".parse::<HexString>().unwrap().to_bytes(), [0xa1, 0xb2]);
+    }
+
+    #[test]
+    fn hex_errors() {
+        assert!("00FFFFFG".parse::<HexMask>().unwrap_err().contains("position 7: U+0047 (not allowed here)"));
+        assert!("0".repeat(33).parse::<HexMask>().unwrap_err().contains("hex digits"));
+        assert!("".parse::<HexMask>().is_err());
+        assert!("abc".parse::<HexString>().unwrap_err().contains("has 3 hex digits"));
+        assert!("\"\"".parse::<HexString>().unwrap_err().contains("has 0 hex digits"));
+    }
+
+    #[test]
+    fn fields_are_verbatim() {
+        assert_eq!(" d ".parse::<Field>().unwrap_err().lines().count(), 6);
+        assert_eq!("".parse::<Field>().unwrap_err(), "empty value");
+        assert_eq!("2025-10-01T00:00Z".parse::<Field>().unwrap().to_string(), "2025-10-01T00:00Z");
+        assert_eq!("**D07C10".parse::<ChallengeId>().unwrap().as_str(), "**D07C10");
+        assert!("../etc".parse::<ChallengeId>().is_err());
+        assert!("a;b".parse::<ChallengeId>().is_err());
+        assert_eq!("".parse::<ChallengeId>().unwrap_err(), "empty value");
+    }
+
+    #[test]
+    fn lookalikes_are_named() {
+        let error = "2025\u{2013}10\u{00a0}x".parse::<Field>().unwrap_err();
+        assert_eq!(
+            error,
+            "unexpected characters (copied with surrounding text?):\n  - 2025\u{2013}10\\u{a0}x\n        ^  ^^^^^^\n  \
+             + 2025-10x\n  position 4: U+2013 (looks like '-')\n  position 7: U+00A0 (whitespace)"
+        );
+        let error = "\u{ff21}\u{0430}\t".parse::<Field>().unwrap_err();
+        assert!(error.contains("+ Aa\n"), "{}", error);
+        assert!(error.contains("- \u{ff21}\u{0430}\\t\n"), "{}", error);
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(ADDRESS.parse::<Bech32Address>().unwrap().as_str(), ADDRESS);
+        assert!(ADDRESS.to_ascii_uppercase().parse::<Bech32Address>().is_ok());
+        let error = |s: &str| s.parse::<Bech32Address>().unwrap_err();
+        assert!(error(&ADDRESS.replace("st5", "st6")).contains("fails its bech32 checksum"));
+        assert!(error(&ADDRESS[..ADDRESS.len() - 1]).contains("fails its bech32 checksum"));
+        assert!(error("addr_test1Qpzry9x8gf2tvdw0s3jn4syst5").contains("mixes upper and lower case"));
+        assert!(error("stake_test1qpzry9x8gf2tvdw0s3jn4syst5").contains("should start with addr1 or addr_test1"));
+        assert!(error("addrqpzry9x8").contains("no bech32 separator"));
+        assert!(error("addr_test1qpzryb").contains("invalid character 'b' at position 15"));
+        assert!(error("addr_test1qpz").contains("too short"));
+        assert!(error("addr_test1 qpz").contains("U+0020 (whitespace)"));
+    }
+
+    #[test]
+    fn hash_lengths() {
+        assert_eq!(parse_hash_len("1"), Ok(1));
+        assert_eq!(parse_hash_len("64"), Ok(64));
+        for s in ["0", "65", "-1", "", "32b"] {
+            assert!(parse_hash_len(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn durations() {
+        for (s, secs) in [("90", 90), ("90s", 90), ("45m", 2700), ("1h30m", 5400), ("2d", 172800), ("1m1m", 120)] {
+            assert_eq!(parse_duration(s), Ok(Duration::from_secs(secs)), "{}", s);
+        }
+        for s in ["", "h", "1h30", "1.5h", "1w", "-5s", " 5s", "99999999999999999999s", "999999999999999999d"] {
+            assert!(parse_duration(s).is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Solo and pool submission for the daemon. A solo job is mined for the address it was
+//! submitted with and its solution handed to `--solo-on-solution`; a pool job is mined for
+//! `--pool-address` instead and handed to `--pool-on-solution`. With `--payout auto` each
+//! job goes solo when its expected time to a solution at the hashrate measured on earlier
+//! jobs is under `--solo-below`, and to the pool otherwise, so easy rounds pay in full and
+//! hard ones still earn a share. The admin changes the mode and threshold through
+//! `POST /payout` without restarting the daemon.
+
+use crate::json_string;
+use crate::params::Bech32Address;
+use clap::ValueEnum;
+use std::path::PathBuf;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Solo when the expected time to a solution is under --solo-below, else the pool
+    Auto,
+    Solo,
+    Pool,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Auto => "auto",
+            Mode::Solo => "solo",
+            Mode::Pool => "pool",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Route {
+    Solo,
+    Pool,
+}
+
+impl Route {
+    pub fn name(self) -> &'static str {
+        match self {
+            Route::Solo => "solo",
+            Route::Pool => "pool",
+        }
+    }
+}
+
+pub struct Payout {
+    /// `None` when neither submission command is set: solutions are only reported
+    pub mode: Option<Mode>,
+    /// Longest expected time to a solution, in seconds, that `auto` mines solo
+    pub solo_below: f64,
+    pub solo_on_solution: Option<String>,
+    pub pool_on_solution: Option<String>,
+    pub pool_address: Option<Bech32Address>,
+    /// The --submitted file guarding both commands against duplicates
+    pub submitted: PathBuf,
+}
+
+impl Payout {
+    /// The mode given, else whichever side is configured, `auto` if both are.
+    pub fn default_mode(&self, mode: Option<Mode>) -> Option<Mode> {
+        match (mode, &self.solo_on_solution, &self.pool_on_solution) {
+            (Some(mode), _, _) => Some(mode),
+            (None, Some(_), Some(_)) => Some(Mode::Auto),
+            (None, Some(_), None) => Some(Mode::Solo),
+            (None, None, Some(_)) => Some(Mode::Pool),
+            (None, None, None) => None,
+        }
+    }
+
+    /// Why `mode` cannot be used with the commands and address configured, if it cannot.
+    pub fn check(&self, mode: Mode) -> Result<(), String> {
+        let solo = self.solo_on_solution.is_some();
+        let pool = self.pool_on_solution.is_some() && self.pool_address.is_some();
+        match mode {
+            Mode::Solo if !solo => Err("solo payout needs --solo-on-solution".to_string()),
+            Mode::Pool if !pool => Err("pool payout needs --pool-on-solution and --pool-address".to_string()),
+            Mode::Auto if !(solo && pool) => {
+                Err("auto payout needs --solo-on-solution, --pool-on-solution and --pool-address".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Where a job expecting `expected` hashes goes at `hashrate`, and why; `None` if
+    /// solutions are not submitted at all.
+    pub fn route(&self, expected: f64, hashrate: Option<f64>) -> Option<(Route, String)> {
+        Some(match self.mode? {
+            Mode::Solo => (Route::Solo, "payout mode solo".to_string()),
+            Mode::Pool => (Route::Pool, "payout mode pool".to_string()),
+            Mode::Auto => match hashrate {
+                None => (Route::Pool, "no hashrate measured yet".to_string()),
+                Some(rate) => {
+                    let eta = expected / rate.max(1e-9);
+                    let route = if eta < self.solo_below { Route::Solo } else { Route::Pool };
+                    let comparison = if route == Route::Solo { "under" } else { "not under" };
+                    let reason = format!(
+                        "expected {} at {:.0} H/s, {} {}",
+                        crate::format_duration(eta),
+                        rate,
+                        comparison,
+                        crate::format_duration(self.solo_below)
+                    );
+                    (route, reason)
+                }
+            },
+        })
+    }
+
+    /// The submission command and mining address for `route`; `None` keeps the job's own.
+    pub fn target(&self, route: Route) -> (Option<&str>, Option<&Bech32Address>) {
+        match route {
+            Route::Solo => (self.solo_on_solution.as_deref(), None),
+            Route::Pool => (self.pool_on_solution.as_deref(), self.pool_address.as_ref()),
+        }
+    }
+
+    pub fn json(&self, hashrate: Option<f64>) -> String {
+        format!(
+            "{{\"mode\": {}, \"solo_below_secs\": {}, \"solo\": {}, \"pool\": {}, \"hashrate\": {}}}",
+            self.mode.map_or("null".to_string(), |m| json_string(m.name())),
+            self.solo_below,
+            self.solo_on_solution.is_some(),
+            self.pool_on_solution.is_some(),
+            hashrate.map_or("null".to_string(), |h| format!("{:.1}", h))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payout(solo: bool, pool: bool) -> Payout {
+        Payout {
+            mode: None,
+            solo_below: 3600.0,
+            solo_on_solution: solo.then(|| "submit-solo {nonce}".to_string()),
+            pool_on_solution: pool.then(|| "submit-pool {nonce}".to_string()),
+            pool_address: pool.then(|| "addr_test1qpzry9x8gf2tvdw0s3jn4syst5".parse().unwrap()),
+            submitted: PathBuf::from("submitted"),
+        }
+    }
+
+    #[test]
+    fn default_modes() {
+        assert_eq!(payout(true, true).default_mode(None), Some(Mode::Auto));
+        assert_eq!(payout(true, false).default_mode(None), Some(Mode::Solo));
+        assert_eq!(payout(false, true).default_mode(None), Some(Mode::Pool));
+        assert_eq!(payout(false, false).default_mode(None), None);
+        assert_eq!(payout(false, false).default_mode(Some(Mode::Pool)), Some(Mode::Pool));
+    }
+
+    #[test]
+    fn modes_need_their_commands() {
+        assert_eq!(payout(true, true).check(Mode::Auto), Ok(()));
+        assert_eq!(payout(true, false).check(Mode::Solo), Ok(()));
+        assert_eq!(payout(false, true).check(Mode::Solo).unwrap_err(), "solo payout needs --solo-on-solution");
+        let no_address = Payout { pool_address: None, ..payout(true, true) };
+        assert_eq!(
+            no_address.check(Mode::Pool).unwrap_err(),
+            "pool payout needs --pool-on-solution and --pool-address"
+        );
+        assert!(no_address.check(Mode::Auto).unwrap_err().starts_with("auto payout needs"));
+    }
+
+    #[test]
+    fn routes() {
+        let auto = Payout { mode: Some(Mode::Auto), ..payout(true, true) };
+        assert_eq!(auto.route(1e6, None), Some((Route::Pool, "no hashrate measured yet".to_string())));
+        let (route, reason) = auto.route(1e6, Some(1000.0)).unwrap();
+        assert_eq!((route, reason.as_str()), (Route::Solo, "expected 16m 40s at 1000 H/s, under 1h 0m"));
+        let (route, reason) = auto.route(3.6e6, Some(1000.0)).unwrap();
+        assert_eq!((route, reason.as_str()), (Route::Pool, "expected 1h 0m at 1000 H/s, not under 1h 0m"));
+        assert_eq!(auto.route(1.0, Some(0.0)).unwrap().0, Route::Pool);
+        let solo = Payout { mode: Some(Mode::Solo), ..payout(true, true) };
+        assert_eq!(solo.route(1e12, Some(1.0)), Some((Route::Solo, "payout mode solo".to_string())));
+        assert_eq!(payout(true, true).route(1.0, Some(1.0)), None);
+    }
+
+    #[test]
+    fn targets_and_json() {
+        let payout = Payout { mode: Some(Mode::Auto), ..payout(true, true) };
+        assert_eq!(payout.target(Route::Solo), (Some("submit-solo {nonce}"), None));
+        let (command, address) = payout.target(Route::Pool);
+        assert_eq!(command, Some("submit-pool {nonce}"));
+        assert_eq!(address.map(|a| a.as_str()), Some("addr_test1qpzry9x8gf2tvdw0s3jn4syst5"));
+        assert_eq!(
+            payout.json(Some(1234.56)),
+            "{\"mode\": \"auto\", \"solo_below_secs\": 3600, \"solo\": true, \"pool\": true, \"hashrate\": 1234.6}"
+        );
+        let unset = Payout { mode: None, ..payout };
+        assert!(unset.json(None).starts_with("{\"mode\": null,") && unset.json(None).ends_with("\"hashrate\": null}"));
+    }
+}
@@ -0,0 +1,167 @@
+//! `--preemptible`: on a spot or preemptible cloud VM, watch the instance metadata service
+//! for the notice that the instance is about to be reclaimed (two minutes ahead on AWS, 30
+//! seconds on GCP and Azure) and stop the search right away, so the journal, the
+//! --checkpoint-url bucket and the range server learn where it got to before the machine
+//! disappears. The provider is whichever of AWS (IMDSv2), GCP and Azure answers at
+//! 169.254.169.254.
+
+use crate::json::{self, Json};
+use crate::wait_for_stop;
+use std::io::{Error, Read, Write};
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// How often the metadata service is asked; well inside the shortest notice
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const METADATA: ([u8; 4], u16) = ([169, 254, 169, 254], 80);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Azure may take this long to answer its first scheduled events request
+const DETECT_TIMEOUT: Duration = Duration::from_secs(120);
+const READ_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cloud {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+impl Cloud {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cloud::Aws => "aws",
+            Cloud::Gcp => "gcp",
+            Cloud::Azure => "azure",
+        }
+    }
+}
+
+/// A plain HTTP request to the metadata service, never through a proxy; returns the status
+/// and body.
+fn request(method: &str, path: &str, headers: &str, timeout: Duration) -> std::io::Result<(u16, String)> {
+    let mut stream = crate::offline::network()?.connect(&SocketAddr::from(METADATA), CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: 169.254.169.254\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        method, path, headers
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    parse_response(&response)
+}
+
+/// The status and body of a metadata service `response`.
+fn parse_response(response: &str) -> std::io::Result<(u16, String)> {
+    let status = response.split_whitespace().nth(1).and_then(|s| s.parse().ok());
+    let status = status.ok_or_else(|| Error::other(format!("metadata service answered {:?}", response)))?;
+    Ok((status, response.split_once("\r\n\r\n").map_or("", |(_, body)| body).to_string()))
+}
+
+fn aws_token(timeout: Duration) -> std::io::Result<Option<String>> {
+    let (status, token) =
+        request("PUT", "/latest/api/token", "X-aws-ec2-metadata-token-ttl-seconds: 300\r\n", timeout)?;
+    Ok((status == 200).then_some(token))
+}
+
+/// The provider whose metadata service answers, if any.
+pub fn detect() -> Option<Cloud> {
+    if let Ok(Some(_)) = aws_token(READ_TIMEOUT) {
+        return Some(Cloud::Aws);
+    }
+    let gcp = request("GET", "/computeMetadata/v1/instance/preempted", "Metadata-Flavor: Google\r\n", READ_TIMEOUT);
+    if matches!(gcp, Ok((200, _))) {
+        return Some(Cloud::Gcp);
+    }
+    let azure =
+        request("GET", "/metadata/scheduledevents?api-version=2020-07-01", "Metadata: true\r\n", DETECT_TIMEOUT);
+    matches!(azure, Ok((200, _))).then_some(Cloud::Azure)
+}
+
+/// What the provider said about reclaiming the instance, once it has.
+fn notice(cloud: Cloud) -> std::io::Result<Option<String>> {
+    match cloud {
+        Cloud::Aws => {
+            let token = aws_token(READ_TIMEOUT)?.ok_or_else(|| Error::other("no IMDSv2 token"))?;
+            let headers = format!("X-aws-ec2-metadata-token: {}\r\n", token);
+            // 404 until the spot instance is marked for interruption
+            let (status, body) = request("GET", "/latest/meta-data/spot/instance-action", &headers, READ_TIMEOUT)?;
+            Ok((status == 200).then_some(body))
+        }
+        Cloud::Gcp => {
+            let headers = "Metadata-Flavor: Google\r\n";
+            let (_, body) = request("GET", "/computeMetadata/v1/instance/preempted", headers, READ_TIMEOUT)?;
+            Ok((body.trim() == "TRUE").then(|| "preempted".to_string()))
+        }
+        Cloud::Azure => {
+            let path = "/metadata/scheduledevents?api-version=2020-07-01";
+            let (_, body) = request("GET", path, "Metadata: true\r\n", READ_TIMEOUT)?;
+            azure_notice(&body)
+        }
+    }
+}
+
+/// The eviction among Azure's scheduled events in `body`, if there is one.
+fn azure_notice(body: &str) -> std::io::Result<Option<String>> {
+    let reply = json::parse(body).map_err(Error::other)?;
+    let Some(Json::Array(events)) = reply.get("Events") else {
+        return Ok(None);
+    };
+    let preempt = events
+        .iter()
+        .find(|event| matches!(event.get("EventType").and_then(Json::as_str), Some("Preempt" | "Terminate")));
+    Ok(preempt.map(|event| {
+        let field = |key: &str| event.get(key).and_then(Json::as_str).unwrap_or("?").to_string();
+        format!("{} at {}", field("EventType"), field("NotBefore"))
+    }))
+}
+
+/// Poll `cloud` every `POLL_INTERVAL` until `stop` is set or the instance is about to be
+/// reclaimed; returns the notice in that case.
+pub fn watch(cloud: Cloud, stop: &AtomicBool) -> Option<String> {
+    // Logged once, not every poll
+    let mut failing = false;
+    while !wait_for_stop(stop, POLL_INTERVAL) {
+        match notice(cloud) {
+            Ok(Some(notice)) => return Some(notice),
+            Ok(None) => failing = false,
+            Err(e) if !failing => {
+                failing = true;
+                eprintln!("preempt: failed to ask the {} metadata service: {}", cloud.name(), e);
+            }
+            Err(_) => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_responses() {
+        let (status, body) = parse_response("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nTRUE").unwrap();
+        assert_eq!((status, body.as_str()), (200, "TRUE"));
+        let (status, body) = parse_response("HTTP/1.1 404 Not Found\r\n").unwrap();
+        assert_eq!((status, body.as_str()), (404, ""));
+        assert!(parse_response("").is_err());
+        assert!(parse_response("HTTP/1.1 OK\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn azure_events() {
+        let evicted =
+            "{\"DocumentIncarnation\": 2, \"Events\": [{\"EventType\": \"Freeze\", \"NotBefore\": \"soon\"}, \
+                       {\"EventType\": \"Preempt\", \"NotBefore\": \"Mon, 19 Sep 2016 18:29:47 GMT\"}]}";
+        assert_eq!(azure_notice(evicted).unwrap().unwrap(), "Preempt at Mon, 19 Sep 2016 18:29:47 GMT");
+        let terminated = "{\"Events\": [{\"EventType\": \"Terminate\"}]}";
+        assert_eq!(azure_notice(terminated).unwrap().unwrap(), "Terminate at ?");
+        assert_eq!(azure_notice("{\"DocumentIncarnation\": 1, \"Events\": []}").unwrap(), None);
+        assert_eq!(azure_notice("{\"Events\": [{\"EventType\": \"Reboot\"}]}").unwrap(), None);
+        assert_eq!(azure_notice("{}").unwrap(), None);
+        assert!(azure_notice("Service Unavailable").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! The preimage the server hashes: the nonce as a `NonceEncoding` writes it, then the
+//! challenge fields concatenated without separators,
+//!
+//!   nonce address challenge_id difficulty no_pre_mine latest_submission no_pre_mine_hour
+//!
+//! with the hex fields in the --hex-case asked for and a --salt right after the nonce or
+//! after the last field. Values arrive here already cleaned up by `params` (whitespace,
+//! quotes and, unless --keep-0x, a `0x` stripped). One wrong byte and every hash is one
+//! the server rejects, so `selftest` checks `PreimageBuilder` against spelled-out preimages.
+
+use crate::verifier::{self, Nonce};
+use clap::ValueEnum;
+
+/// The challenge fields that follow the nonce, and how they are written.
+#[derive(Clone, Copy, Debug)]
+pub struct PreimageBuilder<'a> {
+    pub address: &'a str,
+    pub challenge_id: &'a str,
+    pub difficulty: &'a str,
+    pub no_pre_mine: &'a str,
+    pub latest_submission: &'a str,
+    pub no_pre_mine_hour: &'a str,
+    pub salt: Option<(&'a str, SaltPosition)>,
+    pub hex_case: HexCase,
+    pub encoding: NonceEncoding,
+}
+
+impl PreimageBuilder<'_> {
+    /// Everything after the nonce; the miner writes it once and only swaps the nonce in front.
+    pub fn suffix(&self) -> String {
+        let fields = format!(
+            "{}{}{}{}{}{}",
+            self.address,
+            self.challenge_id,
+            self.hex_case.apply(self.difficulty),
+            self.hex_case.apply(self.no_pre_mine),
+            self.latest_submission,
+            self.no_pre_mine_hour
+        );
+        match self.salt {
+            Some((salt, SaltPosition::Prepend)) => format!("{}{}", salt, fields),
+            Some((salt, SaltPosition::Append)) => format!("{}{}", fields, salt),
+            None => fields,
+        }
+    }
+
+    /// The whole preimage for `nonce`.
+    pub fn build(&self, nonce: u64) -> Vec<u8> {
+        let mut preimage = Vec::new();
+        write_preimage(&mut preimage, nonce, &self.suffix(), self.encoding);
+        preimage
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexCase {
+    Lower,
+    Upper,
+    /// Keep the case the field was given in
+    AsIs,
+}
+
+impl HexCase {
+    /// A `0x` kept by --keep-0x is left as written.
+    pub fn apply(self, hex: &str) -> String {
+        let digits = crate::params::hex_digits(hex);
+        let prefix = &hex[..hex.len() - digits.len()];
+        match self {
+            HexCase::Lower => format!("{}{}", prefix, digits.to_ascii_lowercase()),
+            HexCase::Upper => format!("{}{}", prefix, digits.to_ascii_uppercase()),
+            HexCase::AsIs => hex.to_string(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HexCase::Lower => "lower",
+            HexCase::Upper => "upper",
+            HexCase::AsIs => "as-is",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaltPosition {
+    /// Right after the nonce, before the address
+    Prepend,
+    /// After the last challenge field
+    Append,
+}
+
+/// How the nonce opens the preimage; servers define its text differently.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonceEncoding {
+    /// 16 lowercase hex digits, zero-padded
+    Hex16,
+    /// Lowercase hex without leading zeros
+    HexNopad,
+    /// Decimal digits
+    Decimal,
+    /// The 8 bytes of the nonce, most significant first
+    RawBe,
+    /// The 8 bytes of the nonce, least significant first
+    RawLe,
+}
+
+impl NonceEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            NonceEncoding::Hex16 => "hex16",
+            NonceEncoding::HexNopad => "hex-nopad",
+            NonceEncoding::Decimal => "decimal",
+            NonceEncoding::RawBe => "raw-be",
+            NonceEncoding::RawLe => "raw-le",
+        }
+    }
+
+    /// The nonce as it is printed and handed to --on-solution: the digits that were hashed,
+    /// or for the raw encodings the hex of the hashed bytes.
+    pub fn text(self, nonce: u64) -> String {
+        match self {
+            NonceEncoding::Hex16 | NonceEncoding::RawBe => format!("{:016x}", nonce),
+            NonceEncoding::HexNopad => format!("{:x}", nonce),
+            NonceEncoding::Decimal => nonce.to_string(),
+            NonceEncoding::RawLe => crate::to_hex(&nonce.to_le_bytes()),
+        }
+    }
+
+    /// Read back a nonce written as `text` writes it.
+    pub fn parse(self, text: &str) -> Result<u64, String> {
+        let parsed = match self {
+            NonceEncoding::Decimal => text.parse::<u64>().map_err(|e| e.to_string()),
+            NonceEncoding::RawLe if text.len() != 16 => Err("expected the hex of 8 bytes".to_string()),
+            NonceEncoding::RawLe => u64::from_str_radix(text, 16).map(u64::swap_bytes).map_err(|e| e.to_string()),
+            _ => u64::from_str_radix(text, 16).map_err(|e| e.to_string()),
+        };
+        parsed.map_err(|e| format!("invalid {} nonce {:?}: {}", self.name(), text, e))
+    }
+
+    /// The same encoding in `verifier`, which writes the bytes.
+    pub fn verifier(self) -> Nonce {
+        match self {
+            NonceEncoding::Hex16 => Nonce::Hex16,
+            NonceEncoding::HexNopad => Nonce::HexNopad,
+            NonceEncoding::Decimal => Nonce::Decimal,
+            NonceEncoding::RawBe => Nonce::RawBe,
+            NonceEncoding::RawLe => Nonce::RawLe,
+        }
+    }
+
+    /// Swap the nonce at the start of `preimage`, which ends in `suffix_len` bytes of suffix,
+    /// for `nonce`; the suffix only moves when the nonce's length changes.
+    #[inline]
+    pub fn replace(self, preimage: &mut Vec<u8>, nonce: u64, suffix_len: usize) {
+        match self {
+            NonceEncoding::Hex16 => crate::encode_nonce(preimage, nonce),
+            NonceEncoding::RawBe => preimage[..8].copy_from_slice(&nonce.to_be_bytes()),
+            NonceEncoding::RawLe => preimage[..8].copy_from_slice(&nonce.to_le_bytes()),
+            NonceEncoding::HexNopad | NonceEncoding::Decimal => {
+                let mut buffer = [0; 20];
+                let digits = verifier::nonce_bytes(self.verifier(), nonce, &mut buffer);
+                let old = preimage.len() - suffix_len;
+                if old == digits.len() {
+                    preimage[..old].copy_from_slice(digits);
+                } else {
+                    preimage.splice(..old, digits.iter().copied());
+                }
+            }
+        }
+    }
+}
+
+pub fn write_preimage(preimage: &mut Vec<u8>, nonce: u64, suffix: &str, encoding: NonceEncoding) {
+    preimage.extend_from_slice(verifier::nonce_bytes(encoding.verifier(), nonce, &mut [0; 20]));
+    preimage.extend_from_slice(suffix.as_bytes());
+}
+
+/// A preimage for log lines: text as is, other bytes (from the raw encodings) as \xNN.
+pub fn printable(preimage: &[u8]) -> String {
+    preimage
+        .iter()
+        .map(|&b| if (0x20..0x7f).contains(&b) { (b as char).to_string() } else { format!("\\x{:02x}", b) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_case_keeps_a_kept_prefix() {
+        assert_eq!(HexCase::Lower.apply("0xAbC"), "0xabc");
+        assert_eq!(HexCase::Upper.apply("0Xabc"), "0XABC");
+        assert_eq!(HexCase::Upper.apply("00ffzz"), "00FFZZ");
+        assert_eq!(HexCase::AsIs.apply("0xAbC"), "0xAbC");
+    }
+
+    #[test]
+    fn salt_positions() {
+        let builder = PreimageBuilder {
+            address: "a",
+            challenge_id: "c",
+            difficulty: "dD",
+            no_pre_mine: "fF",
+            latest_submission: "l",
+            no_pre_mine_hour: "h",
+            salt: Some(("S", SaltPosition::Prepend)),
+            hex_case: HexCase::Lower,
+            encoding: NonceEncoding::Decimal,
+        };
+        assert_eq!(builder.build(42), b"42Sacddfflh");
+        let appended = PreimageBuilder { salt: Some(("S", SaltPosition::Append)), ..builder };
+        assert_eq!(appended.build(0), b"0acddfflhS");
+    }
+
+    #[test]
+    fn nonce_text() {
+        let nonce = 0x0102_0304_0506_0708;
+        assert_eq!(NonceEncoding::Hex16.text(1), "0000000000000001");
+        assert_eq!(NonceEncoding::HexNopad.text(0), "0");
+        assert_eq!(NonceEncoding::Decimal.text(u64::MAX), "18446744073709551615");
+        assert_eq!(NonceEncoding::RawBe.text(nonce), "0102030405060708");
+        assert_eq!(NonceEncoding::RawLe.text(nonce), "0807060504030201");
+    }
+
+    #[test]
+    fn bad_nonce_text() {
+        assert!(NonceEncoding::RawLe.parse("0807").unwrap_err().contains("expected the hex of 8 bytes"));
+        assert!(NonceEncoding::Decimal.parse("0x10").unwrap_err().starts_with("invalid decimal nonce \"0x10\""));
+        assert!(NonceEncoding::Hex16.parse("10000000000000000").is_err());
+        assert!(NonceEncoding::HexNopad.parse("").is_err());
+        assert!(NonceEncoding::RawBe.parse("zz").is_err());
+    }
+
+    #[test]
+    fn printable_escapes_raw_bytes() {
+        assert_eq!(printable(b"ab \x00\x7f\xff~"), "ab \\x00\\x7f\\xff~");
+        let mut preimage = Vec::new();
+        write_preimage(&mut preimage, 0x41, "x", NonceEncoding::RawLe);
+        assert_eq!(printable(&preimage), "A\\x00\\x00\\x00\\x00\\x00\\x00\\x00x");
+    }
+}
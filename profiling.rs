@@ -0,0 +1,91 @@
+//! `--profile-out`: sampled per-phase timings of the hash loop, written as JSON so two
+//! backends or two builds can be compared without an external profiler.
+//!
+//! Timing every hash would cost more than the phases being measured, so workers time one
+//! hash in `SAMPLE_INTERVAL` and the per-hash figures are averages over those samples.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+pub const SAMPLE_INTERVAL: u64 = 4096;
+
+#[derive(Default)]
+pub struct PhaseTimings {
+    samples: AtomicU64,
+    preimage_ns: AtomicU64,
+    hash_ns: AtomicU64,
+    check_ns: AtomicU64,
+}
+
+impl PhaseTimings {
+    pub fn record(&self, preimage: Duration, hash: Duration, check: Duration) {
+        self.samples.fetch_add(1, Ordering::Relaxed);
+        self.preimage_ns.fetch_add(preimage.as_nanos() as u64, Ordering::Relaxed);
+        self.hash_ns.fetch_add(hash.as_nanos() as u64, Ordering::Relaxed);
+        self.check_ns.fetch_add(check.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// The report: wall-clock phases of the run plus the sampled per-hash phases in nanoseconds.
+    pub fn to_json(&self, backend: &str, threads: usize, hashes: u64, setup: Duration, search: Duration) -> String {
+        let samples = self.samples.load(Ordering::Relaxed);
+        let per_hash = |total: &AtomicU64| total.load(Ordering::Relaxed) as f64 / samples.max(1) as f64;
+        let (preimage, hash, check) = (per_hash(&self.preimage_ns), per_hash(&self.hash_ns), per_hash(&self.check_ns));
+        format!(
+            r#"{{
+  "backend": {},
+  "threads": {},
+  "hashes": {},
+  "hashrate": {:.1},
+  "wall_secs": {{"setup": {:.6}, "search": {:.6}}},
+  "sample_interval": {},
+  "samples": {},
+  "ns_per_hash": {{"preimage": {:.1}, "hash": {:.1}, "difficulty_check": {:.1}}}
+}}
+"#,
+            crate::json_string(backend),
+            threads,
+            hashes,
+            hashes as f64 / search.as_secs_f64().max(f64::MIN_POSITIVE),
+            setup.as_secs_f64(),
+            search.as_secs_f64(),
+            SAMPLE_INTERVAL,
+            samples,
+            preimage,
+            hash,
+            check
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_over_samples() {
+        let timings = PhaseTimings::default();
+        let ns = Duration::from_nanos;
+        timings.record(ns(100), ns(1000), ns(10));
+        timings.record(ns(200), ns(3000), ns(20));
+        let report = timings.to_json("avx2", 4, 8192, Duration::from_millis(5), Duration::from_secs(2));
+        let parsed = crate::json::parse(&report).unwrap();
+        let number = |path: &str| {
+            let value = path.split('.').try_fold(&parsed, |json, key| json.get(key));
+            value.and_then(crate::json::Json::as_f64).unwrap()
+        };
+        assert_eq!(parsed.get("backend").and_then(crate::json::Json::as_str), Some("avx2"));
+        assert_eq!(number("hashrate"), 4096.0);
+        assert_eq!(number("wall_secs.setup"), 0.005);
+        assert_eq!(number("samples"), 2.0);
+        assert_eq!(number("ns_per_hash.preimage"), 150.0);
+        assert_eq!(number("ns_per_hash.hash"), 2000.0);
+        assert_eq!(number("ns_per_hash.difficulty_check"), 15.0);
+    }
+
+    #[test]
+    fn empty_runs() {
+        let report = PhaseTimings::default().to_json("portable", 1, 0, Duration::ZERO, Duration::ZERO);
+        assert!(report.contains("\"hashrate\": 0.0,"), "{}", report);
+        assert!(report.contains("\"ns_per_hash\": {\"preimage\": 0.0, \"hash\": 0.0, \"difficulty_check\": 0.0}"));
+    }
+}
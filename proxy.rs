@@ -0,0 +1,236 @@
+//! Outgoing connections through a proxy, for networks that only allow egress that way. Hosts
+//! in `NO_PROXY` are always reached directly; for the rest, `--proxy` applies, else the
+//! environment (either case). Plain HTTP requests go to `HTTP_PROXY` with an absolute URI.
+//! Every other protocol (tcp://, redis://, mqtt://) is tunnelled with CONNECT through
+//! `HTTPS_PROXY`, else `ALL_PROXY`. There is no TLS stack, so no https:// target reaches
+//! the tunnel.
+
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::OnceLock;
+
+static OVERRIDE: OnceLock<String> = OnceLock::new();
+
+/// Use `url` for every request instead of the environment; call before the first request.
+pub fn set(url: String) {
+    let _ = OVERRIDE.set(url);
+}
+
+pub struct Proxy {
+    /// host:port of the proxy
+    pub addr: String,
+    /// `Proxy-Authorization` value when the proxy URL carries user:password
+    pub authorization: Option<String>,
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            out.push(if i <= chunk.len() { ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char } else { '=' });
+        }
+    }
+    out
+}
+
+/// `[http://][user:password@]host[:port]`; the port defaults to 1080 like curl's.
+fn parse(url: &str) -> Option<Proxy> {
+    let rest = url.trim().strip_prefix("http://").unwrap_or(url.trim()).trim_end_matches('/');
+    let (auth, host) = match rest.rsplit_once('@') {
+        Some((auth, host)) => (Some(auth), host),
+        None => (None, rest),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let addr = if host.contains(':') { host.to_string() } else { format!("{}:1080", host) };
+    Some(Proxy { addr, authorization: auth.map(|auth| format!("Basic {}", base64(auth.as_bytes()))) })
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).or_else(|_| std::env::var(name.to_ascii_lowercase())).ok().filter(|v| !v.is_empty())
+}
+
+/// `NO_PROXY` entries match the host itself and its subdomains; `*` matches everything.
+fn bypassed(host: &str, no_proxy: &str) -> bool {
+    no_proxy.split(',').map(|entry| entry.trim().trim_start_matches('.')).filter(|entry| !entry.is_empty()).any(
+        |entry| {
+            entry == "*"
+                || host.eq_ignore_ascii_case(entry)
+                || host.to_ascii_lowercase().ends_with(&format!(".{}", entry.to_ascii_lowercase()))
+        },
+    )
+}
+
+/// The proxy to reach `addr` (host:port) through, if any; `tunnelled` for a CONNECT tunnel
+/// rather than a plain HTTP request.
+pub fn for_target(addr: &str, tunnelled: bool) -> Option<Proxy> {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host).trim_start_matches('[').trim_end_matches(']');
+    if env("NO_PROXY").is_some_and(|list| bypassed(host, &list)) {
+        return None;
+    }
+    if let Some(url) = OVERRIDE.get() {
+        return parse(url);
+    }
+    let url = if tunnelled { env("HTTPS_PROXY").or_else(|| env("ALL_PROXY")) } else { env("HTTP_PROXY") };
+    url.and_then(|url| parse(&url))
+}
+
+/// A connection to the proxy itself, for plain requests with an absolute URI.
+pub fn open(proxy: &Proxy) -> std::io::Result<TcpStream> {
+    crate::retry::connect(&proxy.addr)
+}
+
+/// A connection to `addr` for a protocol other than HTTP: tunnelled if a proxy applies to
+/// it, direct otherwise.
+pub fn connect(addr: &str) -> std::io::Result<TcpStream> {
+    match for_target(addr, true) {
+        Some(proxy) => tunnel(&proxy, addr),
+        None => crate::retry::connect(addr),
+    }
+}
+
+/// Longest CONNECT reply header read before giving up on the proxy
+const MAX_REPLY: usize = 8192;
+
+/// Ask `proxy` to CONNECT to `addr`; the stream carries the target's bytes once it says 2xx.
+pub fn tunnel(proxy: &Proxy, addr: &str) -> std::io::Result<TcpStream> {
+    let mut stream = open(proxy)?;
+    let authorization =
+        proxy.authorization.as_ref().map_or(String::new(), |a| format!("Proxy-Authorization: {}\r\n", a));
+    write!(stream, "CONNECT {} HTTP/1.1\r\nHost: {}\r\n{}\r\n", addr, addr, authorization)?;
+
+    // A byte at a time, so nothing the target sends after the reply is taken with it
+    let mut reply = Vec::new();
+    let mut byte = [0u8];
+    while !reply.ends_with(b"\r\n\r\n") {
+        if reply.len() >= MAX_REPLY {
+            return Err(Error::new(ErrorKind::InvalidData, format!("proxy {} sent an endless reply", proxy.addr)));
+        }
+        stream.read_exact(&mut byte)?;
+        reply.push(byte[0]);
+    }
+    check_reply(&reply).map_err(|status| {
+        Error::new(ErrorKind::ConnectionRefused, format!("proxy {} refused CONNECT {}: {:?}", proxy.addr, addr, status))
+    })?;
+    Ok(stream)
+}
+
+/// `Err` with the status line unless the reply to CONNECT is a 2xx.
+fn check_reply(reply: &[u8]) -> Result<(), String> {
+    let reply = String::from_utf8_lossy(reply);
+    let status = reply.lines().next().unwrap_or("");
+    match status.split_whitespace().collect::<Vec<_>>()[..] {
+        [version, code, ..] if version.starts_with("HTTP/1.") && code.len() == 3 && code.starts_with('2') => Ok(()),
+        _ => Err(status.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    #[test]
+    fn base64_vectors() {
+        // RFC 4648 section 10
+        for (input, output) in
+            [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE=")]
+        {
+            assert_eq!(base64(input.as_bytes()), output);
+        }
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn proxy_urls() {
+        let proxy = parse(" http://user:p@ss@gw:3128/ ").unwrap();
+        assert_eq!(proxy.addr, "gw:3128");
+        assert_eq!(proxy.authorization.as_deref(), Some("Basic dXNlcjpwQHNz"));
+        let proxy = parse("gw").unwrap();
+        assert_eq!((proxy.addr.as_str(), proxy.authorization), ("gw:1080", None));
+        assert!(parse("http://").is_none());
+        assert!(parse("user@").is_none());
+    }
+
+    #[test]
+    fn no_proxy_entries() {
+        let list = " internal.example, .Corp.local,,10.0.0.1 ";
+        assert!(bypassed("internal.example", list));
+        assert!(bypassed("api.INTERNAL.example", list));
+        assert!(bypassed("corp.local", list));
+        assert!(bypassed("rig.corp.LOCAL", list));
+        assert!(bypassed("10.0.0.1", list));
+        assert!(!bypassed("notinternal.example", list));
+        assert!(!bypassed("10.0.0.10", list));
+        assert!(!bypassed("example", list));
+        assert!(bypassed("anything", "*"));
+        assert!(!bypassed("anything", ""));
+    }
+
+    #[test]
+    fn connect_replies() {
+        assert!(check_reply(b"HTTP/1.1 200 Connection established\r\n\r\n").is_ok());
+        assert!(check_reply(b"HTTP/1.0 204\r\n\r\n").is_ok());
+        assert_eq!(
+            check_reply(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").unwrap_err(),
+            "HTTP/1.1 407 Proxy Authentication Required"
+        );
+        for reply in
+            [&b""[..], b"\r\n\r\n", b"HTTP/2 200\r\n\r\n", b"HTTP/1.1 2000 ok\r\n\r\n", b"SSH-2.0-OpenSSH\r\n\r\n"]
+        {
+            assert!(check_reply(reply).is_err(), "{:?}", reply);
+        }
+    }
+
+    /// A proxy on loopback that answers one CONNECT with `reply` and then sends `after`;
+    /// returns its address and a handle yielding the request it read.
+    fn fake_proxy(reply: &[u8], after: &'static [u8]) -> (Proxy, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let reply = reply.to_vec();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream);
+            let mut request = String::new();
+            while !request.ends_with("\r\n\r\n") && reader.read_line(&mut request).unwrap() > 0 {}
+            // The tunnel may hang up part way through a reply it rejects
+            let _ = reader.get_mut().write_all(&reply).and_then(|()| reader.get_mut().write_all(after));
+            request
+        });
+        (Proxy { addr, authorization: Some("Basic dTpw".into()) }, handle)
+    }
+
+    #[test]
+    fn tunnel_leaves_the_target_bytes() {
+        let (proxy, handle) = fake_proxy(b"HTTP/1.1 200 OK\r\nVia: gw\r\n\r\n", b"\x02hi");
+        let mut stream = tunnel(&proxy, "farm:7000").unwrap();
+        let mut rest = [0u8; 3];
+        stream.read_exact(&mut rest).unwrap();
+        assert_eq!(&rest, b"\x02hi");
+        assert_eq!(
+            handle.join().unwrap(),
+            "CONNECT farm:7000 HTTP/1.1\r\nHost: farm:7000\r\nProxy-Authorization: Basic dTpw\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn tunnel_refusals() {
+        let (proxy, handle) = fake_proxy(b"HTTP/1.1 403 Forbidden\r\n\r\n", b"");
+        let error = tunnel(&proxy, "farm:7000").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::ConnectionRefused);
+        assert!(error.to_string().contains("403 Forbidden"), "{}", error);
+        handle.join().unwrap();
+
+        let (proxy, handle) = fake_proxy(b"HTTP/1.1 200 OK\r\n", b"");
+        assert_eq!(tunnel(&proxy, "farm:7000").err().unwrap().kind(), ErrorKind::UnexpectedEof);
+        handle.join().unwrap();
+
+        let (proxy, handle) = fake_proxy(&[b'x'; MAX_REPLY + 1], b"");
+        assert_eq!(tunnel(&proxy, "farm:7000").err().unwrap().kind(), ErrorKind::InvalidData);
+        handle.join().unwrap();
+    }
+}
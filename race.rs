@@ -0,0 +1,210 @@
+//! `--race-log FILE`: how often this miner's solutions win the race to the network, to tell
+//! whether a faster submission path is worth the trouble. Each solution appends a line with
+//! when it was found and when --on-solution finished submitting it; each run appends the
+//! `latest_submission` of the round it starts, which is when the network accepted the
+//! solution that ended the round before. `portocripto races` pairs every solution with the
+//! first later round and sorts it into won (accepted no earlier than the second it was
+//! submitted in), lost while submitting (accepted after it was found but before the
+//! submission got there: the latency cost it), or beaten (accepted before it was found).
+
+use crate::{json, json_string, parse_rfc3339};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+enum Line {
+    Solution { challenge_id: String, found_ms: u64, submitted_ms: Option<u64> },
+    Round { challenge_id: String, accepted_ms: u64 },
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+fn append(path: &Path, line: &str) {
+    let written =
+        std::fs::OpenOptions::new().create(true).append(true).open(path).and_then(|mut f| writeln!(f, "{}", line));
+    if let Err(e) = written {
+        eprintln!("race-log: failed to write {}: {}", path.display(), e);
+    }
+}
+
+/// Record the start of the round `challenge_id`, whose `latest_submission` closed the last one.
+pub fn round(path: &Path, challenge_id: &str, latest_submission: &str) {
+    let Some(accepted) = parse_rfc3339(latest_submission) else {
+        eprintln!("race-log: latest_submission is not an RFC 3339 timestamp; this round cannot settle a race");
+        return;
+    };
+    append(
+        path,
+        &format!("{{\"round\": {}, \"accepted_ms\": {}}}", json_string(challenge_id), accepted.max(0) as u64 * 1000),
+    );
+}
+
+/// Record a solution for `challenge_id`; `submitted` is `None` when it did not get through.
+pub fn solution(path: &Path, challenge_id: &str, found: SystemTime, submitted: Option<SystemTime>) {
+    let submitted = submitted.map_or("null".to_string(), |t| unix_ms(t).to_string());
+    append(
+        path,
+        &format!(
+            "{{\"solution\": {}, \"found_ms\": {}, \"submitted_ms\": {}}}",
+            json_string(challenge_id),
+            unix_ms(found),
+            submitted
+        ),
+    );
+}
+
+fn load(path: &Path) -> Result<Vec<Line>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut lines = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let parsed = json::parse(line).and_then(|value| {
+            let ms = |key: &str| value.get(key).and_then(json::Json::as_f64).map(|v| v as u64);
+            let text = |key: &str| value.get(key).and_then(json::Json::as_str).map(str::to_string);
+            match (text("solution"), text("round")) {
+                (Some(challenge_id), _) => Ok(Line::Solution {
+                    challenge_id,
+                    found_ms: ms("found_ms").ok_or("no found_ms")?,
+                    submitted_ms: ms("submitted_ms"),
+                }),
+                (_, Some(challenge_id)) => {
+                    Ok(Line::Round { challenge_id, accepted_ms: ms("accepted_ms").ok_or("no accepted_ms")? })
+                }
+                _ => Err("neither a solution nor a round".to_string()),
+            }
+        });
+        match parsed {
+            Ok(line) => lines.push(line),
+            Err(e) => eprintln!("race-log: {}:{}: skipping: {}", path.display(), i + 1, e),
+        }
+    }
+    Ok(lines)
+}
+
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+/// The win statistics of the log at `path`, as printable lines.
+pub fn summary(path: &Path) -> Result<Vec<String>, String> {
+    let lines = load(path)?;
+    let (mut won, mut beaten, mut unsent, mut waiting) = (0, 0, 0, 0);
+    let (mut lost_by, mut latencies) = (Vec::new(), Vec::new());
+    for (i, line) in lines.iter().enumerate() {
+        let Line::Solution { challenge_id, found_ms, submitted_ms } = line else {
+            continue;
+        };
+        let accepted = lines[i + 1..].iter().find_map(|later| match later {
+            Line::Round { challenge_id: next, accepted_ms } if next != challenge_id => Some(*accepted_ms),
+            _ => None,
+        });
+        let Some(submitted_ms) = *submitted_ms else {
+            unsent += 1;
+            continue;
+        };
+        latencies.push(submitted_ms.saturating_sub(*found_ms));
+        match accepted {
+            None => waiting += 1,
+            // The acceptance time only has whole seconds
+            Some(accepted) if accepted + 1000 > submitted_ms => won += 1,
+            Some(accepted) if accepted + 1000 > *found_ms => lost_by.push(submitted_ms - accepted),
+            Some(_) => beaten += 1,
+        }
+    }
+    let settled = won + lost_by.len() + beaten;
+    let share = |n: usize| if settled == 0 { 0.0 } else { n as f64 * 100.0 / settled as f64 };
+    let mut out = vec![
+        format!(
+            "solutions:       {} settled, {} waiting for the next round, {} not submitted",
+            settled, waiting, unsent
+        ),
+        format!("won:             {} ({:.1}%)", won, share(won)),
+    ];
+    lost_by.sort_unstable();
+    out.push(match lost_by.len() {
+        0 => "lost submitting: 0".to_string(),
+        n => format!(
+            "lost submitting: {} ({:.1}%), by a median of {} ms after the accepted one",
+            n,
+            share(n),
+            percentile(&lost_by, 0.5)
+        ),
+    });
+    out.push(format!("beaten:          {} ({:.1}%), accepted before this miner found it", beaten, share(beaten)));
+    latencies.sort_unstable();
+    if !latencies.is_empty() {
+        out.push(format!(
+            "submission:      median {} ms, p90 {} ms from find to --on-solution done",
+            percentile(&latencies, 0.5),
+            percentile(&latencies, 0.9)
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(ms: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn races_are_settled_by_the_next_round() {
+        let dir = crate::scratch_dir("race");
+        let log = dir.join("race.log");
+        round(&log, "a", "2026-10-14T00:00:00Z");
+        let start = 1_791_936_000_000;
+        // Won: the next round's acceptance is within the second it was submitted in
+        solution(&log, "a", at(start + 10_000), Some(at(start + 10_400)));
+        round(&log, "b", "2026-10-14T00:00:10Z");
+        // Lost by 1.6 s: accepted after the find but before the submission
+        solution(&log, "b", at(start + 18_000), Some(at(start + 20_600)));
+        round(&log, "c", "2026-10-14T00:00:19Z");
+        // Beaten: accepted before it was found
+        solution(&log, "c", at(start + 40_000), Some(at(start + 40_100)));
+        solution(&log, "c", at(start + 41_000), None);
+        round(&log, "d", "2026-10-14T00:00:30Z");
+        // Waiting: a line for the same round settles nothing
+        solution(&log, "d", at(start + 50_000), Some(at(start + 50_200)));
+        round(&log, "d", "2026-10-14T00:00:30Z");
+        round(&log, "x", "not a time");
+        assert_eq!(
+            summary(&log).unwrap(),
+            [
+                "solutions:       3 settled, 1 waiting for the next round, 1 not submitted",
+                "won:             1 (33.3%)",
+                "lost submitting: 1 (33.3%), by a median of 1600 ms after the accepted one",
+                "beaten:          1 (33.3%), accepted before this miner found it",
+                "submission:      median 400 ms, p90 2600 ms from find to --on-solution done",
+            ]
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn odd_lines_are_skipped() {
+        let dir = crate::scratch_dir("race-odd");
+        let log = dir.join("race.log");
+        std::fs::write(
+            &log,
+            "{\"solution\": \"a\", \"submitted_ms\": 5}\n\nnot json\n{\"other\": 1}\n{\"round\": \"a\"}\n\
+             {\"solution\": \"a\", \"found_ms\": 1000, \"submitted_ms\": null}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            summary(&log).unwrap(),
+            [
+                "solutions:       0 settled, 0 waiting for the next round, 1 not submitted",
+                "won:             0 (0.0%)",
+                "lost submitting: 0",
+                "beaten:          0 (0.0%), accepted before this miner found it",
+            ]
+        );
+        assert!(summary(&dir.join("missing")).unwrap_err().starts_with("failed to read"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
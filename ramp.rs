@@ -0,0 +1,91 @@
+//! `--ramp-up DURATION`: start the search on one worker and add the others one at a time over
+//! DURATION, and on a stop take them away again the same way, highest first, so the power
+//! draw climbs and falls in steps instead of jumping to full load and back, which trips PSU
+//! and thermal limits on marginal rigs. Workers outside the ramp idle like paused ones, and
+//! those still inside it after a stop go on mining. A found solution or a preemption notice
+//! still stops every worker at once, as there is something to lose by being slow then.
+
+use crate::Progress;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Move `progress.active` along the ramp until the workers are all gone after a stop, or
+/// `finished` says they ended on their own.
+pub fn run(progress: &Progress, duration: Duration, finished: &AtomicBool) {
+    let threads = progress.workers.len();
+    let step = duration / threads.max(1) as u32;
+    let mut next = Instant::now() + step;
+    while !finished.load(Ordering::Acquire) {
+        let stopping = progress.stop.load(Ordering::Acquire);
+        let active = progress.active.load(Ordering::Relaxed);
+        let urgent = progress.solutions.load(Ordering::Relaxed) > 0 || progress.preempted.load(Ordering::Relaxed);
+        if stopping && (urgent || active == 0) {
+            progress.active.store(0, Ordering::Release);
+            return;
+        }
+        if Instant::now() >= next {
+            let active = if stopping { active - 1 } else { (active + 1).min(threads) };
+            progress.active.store(active, Ordering::Release);
+            next += step;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `progress.active` every 5 ms while `run` ramps 4 workers over 80 ms, calling `stop` at
+    /// 150 ms; also how long `run` kept going after that.
+    fn ramp(progress: &Progress, stop: impl Fn(&Progress)) -> (Vec<usize>, Duration) {
+        progress.active.store(1, Ordering::Release);
+        let finished = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let ramping = scope.spawn(|| run(progress, Duration::from_millis(80), &finished));
+            let (started, mut stopped, mut seen) = (Instant::now(), None, Vec::new());
+            while !ramping.is_finished() {
+                if started.elapsed() >= Duration::from_millis(150) && stopped.is_none() {
+                    stop(progress);
+                    stopped = Some(Instant::now());
+                }
+                seen.push(progress.active.load(Ordering::Acquire));
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            seen.push(progress.active.load(Ordering::Acquire));
+            (seen, stopped.unwrap().elapsed())
+        })
+    }
+
+    #[test]
+    fn workers_come_and_go_one_at_a_time() {
+        let progress = Progress::new(4);
+        let (seen, winding_down) = ramp(&progress, |p| p.stop.store(true, Ordering::Release));
+        let peak = seen.iter().position(|&n| n == 4).unwrap();
+        assert!(seen[..peak].is_sorted() && seen[peak..].is_sorted_by(|a, b| a >= b), "{:?}", seen);
+        assert_eq!((seen[0], seen.last()), (1, Some(&0)));
+        // Four steps of 20 ms down to none
+        assert!(winding_down >= Duration::from_millis(60), "{:?}", winding_down);
+    }
+
+    #[test]
+    fn a_solution_stops_everyone_at_once() {
+        let progress = Progress::new(4);
+        let (seen, _) = ramp(&progress, |p| {
+            p.solutions.store(1, Ordering::Relaxed);
+            p.stop.store(true, Ordering::Release);
+        });
+        let peak = seen.iter().position(|&n| n == 4).unwrap();
+        assert!(seen[peak..].iter().all(|&n| n == 4 || n == 0), "{:?}", seen);
+    }
+
+    #[test]
+    fn finished_searches_end_the_ramp() {
+        let progress = Progress::new(2);
+        let finished = AtomicBool::new(true);
+        run(&progress, Duration::from_secs(3600), &finished);
+        assert_eq!(progress.active.load(Ordering::Relaxed), usize::MAX);
+    }
+}
@@ -0,0 +1,268 @@
+//! `--range-log FILE`: an append-only record of the nonce ranges a miner has covered, one
+//! JSON line per interval of search indices, so the logs of a farm can be merged afterwards
+//! to show that no two workers mined the same nonces (`portocripto ranges`) and a local run
+//! can resume after the last range it logged.
+//!
+//! Intervals are conservative: a worker's interleaved stream counts as covered up to the
+//! slowest thread's cursor, so up to a batch per thread done past it is not logged and may
+//! be redone after a resume. Indices map to nonces through `order` (see `NonceOrder`).
+//!
+//! The intervals are also kept in memory for the run's --coverage-map, which records them
+//! the same way with or without a file to append them to.
+
+use crate::{json, json_string, unix_now, wait_for_stop, Progress};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub struct RangeLog {
+    file: Option<Mutex<File>>,
+    covered: Mutex<Vec<(u64, u64)>>,
+    worker: String,
+    challenge_id: String,
+    order: String,
+}
+
+pub struct Entry {
+    pub worker: String,
+    pub challenge_id: String,
+    pub order: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl RangeLog {
+    /// Append to `path`, or with `None` only keep the intervals for `covered`.
+    pub fn open(path: Option<&Path>, worker: &str, challenge_id: &str, order: &str) -> std::io::Result<RangeLog> {
+        let file = path.map(|path| std::fs::OpenOptions::new().create(true).append(true).open(path)).transpose()?;
+        Ok(RangeLog {
+            file: file.map(Mutex::new),
+            covered: Mutex::new(Vec::new()),
+            worker: worker.to_string(),
+            challenge_id: challenge_id.to_string(),
+            order: order.to_string(),
+        })
+    }
+
+    /// Append the interval `start..end` of search indices; empty intervals are skipped.
+    pub fn record(&self, start: u64, end: u64) {
+        if end <= start {
+            return;
+        }
+        self.covered.lock().unwrap().push((start, end));
+        let Some(file) = &self.file else {
+            return;
+        };
+        let line = format!(
+            "{{\"time\": {}, \"worker\": {}, \"challenge_id\": {}, \"order\": {}, \"start\": \"{:016x}\", \"end\": \"{:016x}\"}}\n",
+            unix_now(),
+            json_string(&self.worker),
+            json_string(&self.challenge_id),
+            json_string(&self.order),
+            start,
+            end
+        );
+        // One write per line, so lines from miners sharing the file do not interleave
+        if let Err(e) = file.lock().unwrap().write_all(line.as_bytes()) {
+            eprintln!("range-log: failed to write: {}", e);
+        }
+    }
+
+    /// Every interval recorded so far.
+    pub fn covered(&self) -> Vec<(u64, u64)> {
+        self.covered.lock().unwrap().clone()
+    }
+
+    /// Record what `progress`'s workers cover every `interval`, and a last time once
+    /// `progress.stop` is set. Only for workers that stay on one interleaved stream.
+    pub fn follow(&self, progress: &Progress, interval: Duration) {
+        let mut logged = progress.cursor_snapshot().into_iter().min().unwrap_or(0);
+        loop {
+            let stopped = wait_for_stop(&progress.stop, interval);
+            let covered = progress.cursor_snapshot().into_iter().min().unwrap_or(0);
+            self.record(logged, covered);
+            logged = logged.max(covered);
+            if stopped {
+                return;
+            }
+        }
+    }
+}
+
+/// Every entry in `path`; malformed lines are reported and skipped.
+pub fn load(path: &Path) -> Result<Vec<Entry>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let parsed = json::parse(line).and_then(|value| {
+            let text = |key: &str| value.get(key).and_then(json::Json::as_str).ok_or(format!("no {}", key));
+            let index = |key: &str| {
+                text(key).and_then(|hex| u64::from_str_radix(hex, 16).map_err(|e| format!("{}: {}", key, e)))
+            };
+            Ok(Entry {
+                worker: text("worker")?.to_string(),
+                challenge_id: text("challenge_id")?.to_string(),
+                order: text("order")?.to_string(),
+                start: index("start")?,
+                end: index("end")?,
+            })
+        });
+        match parsed {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("range-log: {}:{}: skipping: {}", path.display(), i + 1, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Merge `intervals` into sorted, disjoint ones.
+pub fn union(mut intervals: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::new();
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// The end of the run of indices from `from` that `entries` cover for this challenge and
+/// order, i.e. where a resumed search can start.
+pub fn covered_from(entries: &[Entry], challenge_id: &str, order: &str, from: u64) -> u64 {
+    let intervals =
+        entries.iter().filter(|e| e.challenge_id == challenge_id && e.order == order).map(|e| (e.start, e.end));
+    union(intervals.collect())
+        .into_iter()
+        .find(|&(start, end)| start <= from && from < end)
+        .map_or(from, |(_, end)| end)
+}
+
+/// Coverage, gaps and overlaps per challenge and order, as printable lines; the flag says
+/// whether any two entries overlap.
+pub fn audit(entries: &[Entry]) -> (Vec<String>, bool) {
+    let mut groups: BTreeMap<(&str, &str), Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        groups.entry((&entry.challenge_id, &entry.order)).or_default().push(entry);
+    }
+    let (mut lines, mut overlapping) = (Vec::new(), false);
+    for ((challenge_id, order), mut group) in groups {
+        group.sort_by_key(|e| (e.start, e.end));
+        let mut workers: Vec<&str> = group.iter().map(|e| e.worker.as_str()).collect();
+        workers.sort_unstable();
+        workers.dedup();
+        let merged = union(group.iter().map(|e| (e.start, e.end)).collect());
+        let covered: u128 = merged.iter().map(|&(start, end)| (end - start) as u128).sum();
+        lines.push(format!(
+            "challenge {} ({}): {} nonces in {} range(s) from {} worker(s): {}",
+            challenge_id,
+            order,
+            covered,
+            merged.len(),
+            workers.len(),
+            workers.join(", ")
+        ));
+        for pair in merged.windows(2) {
+            lines.push(format!("  gap {:016x}..{:016x}", pair[0].1, pair[1].0));
+        }
+        // Each entry against the one reaching furthest before it
+        let mut reach: Option<&Entry> = None;
+        for &entry in &group {
+            if let Some(previous) = reach.filter(|previous| entry.start < previous.end) {
+                overlapping = true;
+                lines.push(format!(
+                    "  OVERLAP {:016x}..{:016x}: {} and {}",
+                    entry.start,
+                    entry.end.min(previous.end),
+                    previous.worker,
+                    entry.worker
+                ));
+            }
+            if reach.is_none_or(|previous| entry.end > previous.end) {
+                reach = Some(entry);
+            }
+        }
+    }
+    (lines, overlapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(worker: &str, challenge_id: &str, start: u64, end: u64) -> Entry {
+        Entry { worker: worker.into(), challenge_id: challenge_id.into(), order: "linear".into(), start, end }
+    }
+
+    #[test]
+    fn unions() {
+        assert_eq!(union(vec![(5, 8), (0, 2), (2, 3), (7, 10), (12, 13)]), [(0, 3), (5, 10), (12, 13)]);
+        assert_eq!(union(vec![(0, 10), (2, 3)]), [(0, 10)]);
+        assert_eq!(union(Vec::new()), []);
+    }
+
+    #[test]
+    fn resume_points() {
+        let entries = [entry("a", "c1", 0, 100), entry("b", "c1", 100, 150), entry("a", "c2", 150, 200)];
+        assert_eq!(covered_from(&entries, "c1", "linear", 0), 150);
+        assert_eq!(covered_from(&entries, "c1", "linear", 120), 150);
+        assert_eq!(covered_from(&entries, "c1", "linear", 150), 150);
+        assert_eq!(covered_from(&entries, "c1", "random", 0), 0);
+        assert_eq!(covered_from(&entries, "c2", "linear", 0), 0);
+    }
+
+    #[test]
+    fn audits_find_gaps_and_overlaps() {
+        let (lines, overlapping) = audit(&[entry("b", "c", 10, 20), entry("a", "c", 0, 10), entry("a", "c", 30, 40)]);
+        assert!(!overlapping);
+        assert_eq!(
+            lines,
+            [
+                "challenge c (linear): 30 nonces in 2 range(s) from 2 worker(s): a, b",
+                "  gap 0000000000000014..000000000000001e"
+            ]
+        );
+        // The third entry overlaps the first, which reaches past the second
+        let (lines, overlapping) = audit(&[entry("a", "c", 0, 100), entry("b", "c", 10, 20), entry("c", "c", 50, 150)]);
+        assert!(overlapping);
+        assert_eq!(
+            lines[1..],
+            [
+                "  OVERLAP 000000000000000a..0000000000000014: a and b",
+                "  OVERLAP 0000000000000032..0000000000000064: a and c"
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_round_trip() {
+        let dir = crate::scratch_dir("range-log");
+        let path = dir.join("ranges.jsonl");
+        let log = RangeLog::open(Some(&path), "rig \"1\"", "**D07", "linear").unwrap();
+        log.record(0, 0x10);
+        log.record(5, 5);
+        log.record(0x10, 0x20);
+        assert_eq!(log.covered(), [(0, 0x10), (0x10, 0x20)]);
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        let bad_start =
+            "{\"worker\": \"x\", \"challenge_id\": \"c\", \"order\": \"o\", \"start\": \"zz\", \"end\": \"1\"}";
+        writeln!(file, "not json\n\n{}", bad_start).unwrap();
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[1].worker.as_str(), entries[1].challenge_id.as_str()), ("rig \"1\"", "**D07"));
+        assert_eq!((entries[1].start, entries[1].end), (0x10, 0x20));
+        assert!(load(&dir.join("missing")).err().unwrap().starts_with("failed to read"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn memory_only_logs() {
+        let log = RangeLog::open(None, "w", "c", "linear").unwrap();
+        log.record(3, 9);
+        assert_eq!(log.covered(), [(3, 9)]);
+    }
+}
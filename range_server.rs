@@ -0,0 +1,715 @@
+//! `range-server`: hands out exclusive nonce ranges over HTTP so several miners can
+//! split one challenge. Leases expire unless renewed and their ranges are reissued.
+//!
+//! Routes (all answer JSON):
+//!   POST /lease       -> {"id": N, "start": "hex", "end": "hex", "expires_in": secs}
+//!                        (body: {"worker_id": "uuid", "worker": "name"}, optional)
+//!   POST /renew/N     -> 200, or 404 once the lease has expired
+//!                        (body: {"hashrate": H/s, "covered": "hex"}, optional heartbeat)
+//!   POST /done/N      -> 200; the range is never handed out again
+//!                        (body: {"nonce": "hex"} when the lease held a solution)
+//!   POST /release/N   -> 200; the nonces past "covered" are handed out again right away
+//!                        (body: {"covered": "hex"}, from a miner about to be shut down)
+//!   GET  /status      -> counters
+//!   GET  /fleet       -> {"hashrate": H/s, "covered": N, "eta_secs": S, "workers": [...],
+//!                        "solutions": {...}}: each worker's last heartbeat, leases and
+//!                        nonces searched, the expected time to a solution at the fleet's
+//!                        hashrate (with --difficulty), and what `/solutions` answers
+//!   GET  /solutions   -> {"winner": "hex", "candidates": [...]}, every solution reported
+//!
+//! The first solution reported wins; an earliest-first rule needs no waiting for stragglers,
+//! so the winner can submit right away. Once there is a winner, every other request answers
+//! `{"solved": "hex"}` (a `Solved` frame): miners still mining stop, and one reporting a
+//! solution of its own keeps it to itself, so a farm submits the challenge once.
+//!
+//! The same port speaks the binary protocol of `wire` to miners given a tcp:// URL.
+//! Renewals double as heartbeats; a worker that stops sending them loses its leases when
+//! they expire, and is dropped from `/fleet` after a few lease times of silence.
+
+use crate::wire::{self, Message};
+use crate::{json, json_string, reason_phrase};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct Lease {
+    id: u64,
+    start: u64,
+    end: u64,
+    expires: Instant,
+    /// Identity name of the miner holding it, empty if it sent none
+    worker: String,
+    worker_id: String,
+    /// Nonces searched from `start`, as of the last heartbeat
+    covered: u64,
+}
+
+/// What `/fleet` shows for a miner.
+struct Worker {
+    id: String,
+    name: String,
+    hashrate: u64,
+    last_seen: Instant,
+    completed: u64,
+    /// Nonces searched in leases it finished or released
+    covered: u64,
+}
+
+/// A solution reported with `/done`, kept whether or not it won.
+struct Candidate {
+    nonce: u64,
+    lease: u64,
+    /// `Lease::holder` of the lease, if it was still live when the solution came in
+    holder: String,
+    reported_unix: f64,
+}
+
+/// Miners are told apart by worker id, or by name if they sent no id.
+fn worker_key<'a>(worker_id: &'a str, worker: &'a str) -> &'a str {
+    if worker_id.is_empty() {
+        worker
+    } else {
+        worker_id
+    }
+}
+
+impl Lease {
+    /// The miner holding the lease, for log lines.
+    fn holder(&self) -> String {
+        match (self.worker.as_str(), self.worker_id.as_str()) {
+            ("", "") => "an unnamed miner".to_string(),
+            (worker, "") => worker.to_string(),
+            (worker, id) => format!("{} ({})", worker, id),
+        }
+    }
+}
+
+pub struct RangeServer {
+    range_size: u64,
+    lease_time: Duration,
+    next: u64,
+    next_id: u64,
+    leases: Vec<Lease>,
+    free: Vec<(u64, u64)>,
+    completed: u64,
+    reclaimed: u64,
+    workers: BTreeMap<String, Worker>,
+    candidates: Vec<Candidate>,
+    /// Hashes to an expected solution, when the server was told the difficulty
+    expected_hashes: Option<f64>,
+}
+
+impl RangeServer {
+    /// Ranges of `range_size` nonces, the first `first_range` of them skipped.
+    pub fn new(range_size: u64, lease_time: Duration, first_range: u64) -> Self {
+        let range_size = range_size.max(1);
+        RangeServer {
+            range_size,
+            lease_time,
+            next: range_size.saturating_mul(first_range),
+            next_id: 1,
+            leases: Vec::new(),
+            free: Vec::new(),
+            completed: 0,
+            reclaimed: 0,
+            workers: BTreeMap::new(),
+            candidates: Vec::new(),
+            expected_hashes: None,
+        }
+    }
+
+    /// Estimate the fleet's time to a solution for a difficulty with `zero_bits` zero bits.
+    pub fn difficulty(mut self, zero_bits: u32) -> Self {
+        self.expected_hashes = Some(2f64.powi(zero_bits as i32));
+        self
+    }
+
+    /// Return the ranges of expired leases to the free list.
+    fn reclaim(&mut self, now: Instant) {
+        let (expired, live): (Vec<_>, Vec<_>) = self.leases.drain(..).partition(|l| l.expires <= now);
+        self.leases = live;
+        for lease in expired {
+            eprintln!(
+                "range-server: lease {} of {} expired, reclaiming {:016x}..{:016x}",
+                lease.id,
+                lease.holder(),
+                lease.start,
+                lease.end
+            );
+            self.free.push((lease.start, lease.end));
+            self.reclaimed += 1;
+        }
+        let silent = self.lease_time * 3;
+        self.workers.retain(|_, w| now.duration_since(w.last_seen) < silent);
+    }
+
+    /// The record for the miner holding a lease, created on first contact.
+    fn seen(&mut self, worker_id: &str, worker: &str, now: Instant) -> &mut Worker {
+        let record = self.workers.entry(worker_key(worker_id, worker).to_string()).or_insert_with(|| Worker {
+            id: worker_id.to_string(),
+            name: worker.to_string(),
+            hashrate: 0,
+            last_seen: now,
+            completed: 0,
+            covered: 0,
+        });
+        record.last_seen = now;
+        record
+    }
+
+    /// Reissue a reclaimed range first, otherwise carve a new one; `None` once the nonce space is used up.
+    fn lease(&mut self, now: Instant, worker_id: &str, worker: &str) -> Option<&Lease> {
+        let (start, end) = match self.free.pop() {
+            Some(range) => range,
+            None if self.next == u64::MAX => return None,
+            None => {
+                let start = self.next;
+                self.next = start.saturating_add(self.range_size);
+                (start, self.next)
+            }
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        self.seen(worker_id, worker, now);
+        let (worker_id, worker) = (worker_id.to_string(), worker.to_string());
+        self.leases.push(Lease { id, start, end, expires: now + self.lease_time, worker, worker_id, covered: 0 });
+        self.leases.last()
+    }
+
+    /// Extend a live lease and note the holder's heartbeat; false once it has expired.
+    /// A zero `hashrate` (older miners send none) leaves the last one reported.
+    fn renew(&mut self, id: u64, now: Instant, hashrate: u64, covered: u64) -> bool {
+        let lease_time = self.lease_time;
+        let Some(lease) = self.leases.iter_mut().find(|l| l.id == id) else {
+            return false;
+        };
+        lease.expires = now + lease_time;
+        lease.covered = lease.covered.max(covered.min(lease.end - lease.start));
+        let (worker_id, worker) = (lease.worker_id.clone(), lease.worker.clone());
+        let record = self.seen(&worker_id, &worker, now);
+        if hashrate > 0 {
+            record.hashrate = hashrate;
+        }
+        true
+    }
+
+    /// Retire a live lease so its range is never handed out again.
+    fn done(&mut self, id: u64) -> bool {
+        let Some(i) = self.leases.iter().position(|l| l.id == id) else {
+            return false;
+        };
+        let lease = self.leases.remove(i);
+        self.completed += 1;
+        let record = self.seen(&lease.worker_id, &lease.worker, Instant::now());
+        record.completed += 1;
+        record.covered += lease.end - lease.start;
+        true
+    }
+
+    /// Retire a live lease whose holder is going away, returning the nonces it did not
+    /// search to the free list instead of waiting for the lease to expire.
+    fn release(&mut self, id: u64, covered: u64) -> bool {
+        let Some(i) = self.leases.iter().position(|l| l.id == id) else {
+            return false;
+        };
+        let lease = self.leases.remove(i);
+        let resume = lease.start + covered.max(lease.covered).min(lease.end - lease.start);
+        eprintln!(
+            "range-server: lease {} of {} released, reissuing {:016x}..{:016x}",
+            lease.id,
+            lease.holder(),
+            resume,
+            lease.end
+        );
+        if resume < lease.end {
+            self.free.push((resume, lease.end));
+            self.reclaimed += 1;
+        }
+        self.seen(&lease.worker_id, &lease.worker, Instant::now()).covered += resume - lease.start;
+        true
+    }
+
+    /// The winning nonce: the first one reported.
+    fn winner(&self) -> Option<u64> {
+        self.candidates.first().map(|c| c.nonce)
+    }
+
+    /// Record the solution `nonce` found in lease `id`; returns the winner if it is another.
+    fn report(&mut self, id: u64, nonce: u64) -> Option<u64> {
+        let winner = self.winner();
+        if !self.candidates.iter().any(|c| c.nonce == nonce) {
+            let holder = self
+                .leases
+                .iter()
+                .find(|l| l.id == id)
+                .map_or("a miner whose lease expired".to_string(), Lease::holder);
+            match winner {
+                None => {
+                    eprintln!("range-server: {} solved lease {} with {:016x}; stopping the rest", holder, id, nonce)
+                }
+                Some(winner) => {
+                    eprintln!(
+                        "range-server: {} also solved lease {} with {:016x}; {:016x} won",
+                        holder, id, nonce, winner
+                    )
+                }
+            }
+            let reported_unix =
+                std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64());
+            self.candidates.push(Candidate { nonce, lease: id, holder, reported_unix });
+        }
+        winner.filter(|&winner| winner != nonce)
+    }
+
+    fn solutions(&self) -> String {
+        let candidates: Vec<String> = self
+            .candidates
+            .iter()
+            .map(|c| {
+                format!(
+                    "{{\"nonce\": \"{:016x}\", \"lease\": {}, \"holder\": {}, \"reported_unix\": {:.3}}}",
+                    c.nonce,
+                    c.lease,
+                    json_string(&c.holder),
+                    c.reported_unix
+                )
+            })
+            .collect();
+        let winner = self.winner().map_or("null".to_string(), |w| format!("\"{:016x}\"", w));
+        format!("{{\"winner\": {}, \"candidates\": [{}]}}", winner, candidates.join(", "))
+    }
+
+    /// Every worker heard from recently, with its leases; hashrates are totalled over workers
+    /// that sent a heartbeat within one lease time. A worker's `covered` counts the nonces
+    /// searched in its finished and released leases and, as of the last heartbeat, its live
+    /// ones; workers dropped for silence no longer count.
+    fn fleet(&self, now: Instant) -> String {
+        let (mut total, mut covered) = (0, 0);
+        let workers: Vec<String> = self
+            .workers
+            .iter()
+            .map(|(key, w)| {
+                let silent = now.duration_since(w.last_seen);
+                if silent < self.lease_time {
+                    total += w.hashrate;
+                }
+                let held: Vec<&Lease> =
+                    self.leases.iter().filter(|l| worker_key(&l.worker_id, &l.worker) == key).collect();
+                let worker_covered = w.covered + held.iter().map(|l| l.covered).sum::<u64>();
+                covered += worker_covered;
+                let leases: Vec<String> = held
+                    .iter()
+                    .map(|l| {
+                        format!(
+                            "{{\"id\": {}, \"start\": \"{:016x}\", \"end\": \"{:016x}\", \"covered\": \"{:x}\"}}",
+                            l.id, l.start, l.end, l.covered
+                        )
+                    })
+                    .collect();
+                format!(
+                    concat!(
+                        "{{\"worker_id\": {}, \"worker\": {}, \"hashrate\": {}, \"last_seen_secs\": {}, ",
+                        "\"completed\": {}, \"covered\": {}, \"leases\": [{}]}}"
+                    ),
+                    json_string(&w.id),
+                    json_string(&w.name),
+                    w.hashrate,
+                    silent.as_secs(),
+                    w.completed,
+                    worker_covered,
+                    leases.join(", ")
+                )
+            })
+            .collect();
+        // Hashes are memoryless, so the nonces already searched do not bring a solution closer
+        let eta = match self.expected_hashes {
+            Some(hashes) if total > 0 && self.winner().is_none() => format!("{:.0}", hashes / total as f64),
+            _ => "null".to_string(),
+        };
+        format!(
+            "{{\"hashrate\": {}, \"covered\": {}, \"eta_secs\": {}, \"workers\": [{}], \"solutions\": {}}}",
+            total,
+            covered,
+            eta,
+            workers.join(", "),
+            self.solutions()
+        )
+    }
+
+    /// Answer one binary request from a miner speaking wire `version`.
+    fn handle_message(&mut self, message: Message, version: u8) -> Message {
+        let now = Instant::now();
+        self.reclaim(now);
+        let expires_in = self.lease_time.as_secs();
+        let found = |found: bool| if found { Message::Ok } else { Message::UnknownLease };
+        // Version 1 miners stop at the end of their lease, once they get no new one
+        let solved = |nonce: u64, old: Message| if version >= 2 { Message::Solved { nonce } } else { old };
+        match message {
+            Message::Lease { .. } if self.winner().is_some() => solved(self.winner().unwrap(), Message::Exhausted),
+            Message::Lease { worker_id, worker } => match self.lease(now, &worker_id, &worker) {
+                Some(l) => Message::Leased { id: l.id, start: l.start, end: l.end, expires_in },
+                None => Message::Exhausted,
+            },
+            Message::Renew { id, hashrate, covered } => match (self.renew(id, now, hashrate, covered), self.winner()) {
+                (true, Some(winner)) => solved(winner, Message::Ok),
+                (renewed, _) => found(renewed),
+            },
+            Message::Done { id, nonce } => {
+                let lost = nonce.and_then(|nonce| self.report(id, nonce));
+                match (self.done(id), lost) {
+                    (_, Some(winner)) => solved(winner, Message::Ok),
+                    (done, None) => found(done || nonce.is_some()),
+                }
+            }
+            Message::Release { id, covered } => found(self.release(id, covered)),
+            // Replies are never requests
+            _ => Message::UnknownLease,
+        }
+    }
+
+    pub fn handle(&mut self, method: &str, path: &str, body: &str) -> (u16, String) {
+        let now = Instant::now();
+        self.reclaim(now);
+
+        let id = |prefix: &str| path.strip_prefix(prefix).and_then(|id| id.parse::<u64>().ok());
+        let found = |found: bool| {
+            if found {
+                (200, "{}".to_string())
+            } else {
+                (404, "{\"error\": \"unknown or expired lease\"}".to_string())
+            }
+        };
+        let solved = |winner: u64| (200, format!("{{\"solved\": \"{:016x}\"}}", winner));
+        match (method, path) {
+            ("POST", "/lease") if self.winner().is_some() => solved(self.winner().unwrap()),
+            ("POST", "/lease") => {
+                let secs = self.lease_time.as_secs();
+                let request = json::parse(body).ok();
+                let field = |key: &str| {
+                    request.as_ref().and_then(|r| r.get(key)).and_then(json::Json::as_str).unwrap_or("").to_string()
+                };
+                match self.lease(now, &field("worker_id"), &field("worker")) {
+                    Some(l) => (
+                        200,
+                        format!(
+                            "{{\"id\": {}, \"start\": \"{:016x}\", \"end\": \"{:016x}\", \"expires_in\": {}}}",
+                            l.id, l.start, l.end, secs
+                        ),
+                    ),
+                    None => (503, "{\"error\": \"nonce space exhausted\"}".to_string()),
+                }
+            }
+            ("POST", _) if id("/renew/").is_some() => {
+                let beat = json::parse(body).ok();
+                let hashrate = beat.as_ref().and_then(|b| b.get("hashrate")).and_then(json::Json::as_f64);
+                let covered = beat.as_ref().and_then(|b| b.get("covered")).and_then(json::Json::as_str);
+                let covered = covered.and_then(|hex| u64::from_str_radix(hex, 16).ok());
+                let renewed =
+                    self.renew(id("/renew/").unwrap(), now, hashrate.unwrap_or(0.0) as u64, covered.unwrap_or(0));
+                match self.winner() {
+                    Some(winner) if renewed => solved(winner),
+                    _ => found(renewed),
+                }
+            }
+            ("POST", _) if id("/done/").is_some() => {
+                let id = id("/done/").unwrap();
+                let nonce = json::parse(body).ok().and_then(|done| {
+                    done.get("nonce").and_then(json::Json::as_str).and_then(|hex| u64::from_str_radix(hex, 16).ok())
+                });
+                let lost = nonce.and_then(|nonce| self.report(id, nonce));
+                match (self.done(id), lost) {
+                    (_, Some(winner)) => solved(winner),
+                    // A solution still counts when its lease expired under it
+                    (done, None) => found(done || nonce.is_some()),
+                }
+            }
+            ("POST", _) if id("/release/").is_some() => {
+                let release = json::parse(body).ok();
+                let covered = release.as_ref().and_then(|r| r.get("covered")).and_then(json::Json::as_str);
+                let covered = covered.and_then(|hex| u64::from_str_radix(hex, 16).ok());
+                found(self.release(id("/release/").unwrap(), covered.unwrap_or(0)))
+            }
+            ("GET", "/status") => (
+                200,
+                format!(
+                    concat!(
+                        "{{\"next\": \"{:016x}\", \"active\": {}, \"free\": {}, \"completed\": {}, ",
+                        "\"reclaimed\": {}, \"solutions\": {}}}"
+                    ),
+                    self.next,
+                    self.leases.len(),
+                    self.free.len(),
+                    self.completed,
+                    self.reclaimed,
+                    self.candidates.len()
+                ),
+            ),
+            ("GET", "/fleet") => (200, self.fleet(now)),
+            ("GET", "/solutions") => (200, self.solutions()),
+            _ => (404, "{\"error\": \"not found\"}".to_string()),
+        }
+    }
+}
+
+/// Serve requests until the listener fails, each connection on its own thread since binary
+/// ones stay open for as long as the miner runs.
+pub fn serve(listen: &str, server: RangeServer) -> std::io::Result<()> {
+    let listener = crate::offline::network()?.listen(listen)?;
+    eprintln!("range-server: listening on {}", listener.local_addr()?);
+    let server = Arc::new(Mutex::new(server));
+    for stream in listener.incoming() {
+        let server = Arc::clone(&server);
+        let served = stream.and_then(|stream| {
+            std::thread::Builder::new().spawn(move || {
+                if let Err(e) = handle_connection(stream, &server) {
+                    eprintln!("range-server: {}", e);
+                }
+            })
+        });
+        if let Err(e) = served {
+            eprintln!("range-server: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, server: &Mutex<RangeServer>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream);
+    if reader.fill_buf()?.first() == Some(&wire::MAGIC[0]) {
+        return serve_binary(reader, server);
+    }
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let mut content_length = 0u64;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let field = header.split_once(':').filter(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"));
+        if let Some((_, value)) = field {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut request = Vec::new();
+    reader.by_ref().take(content_length.min(64 * 1024)).read_to_end(&mut request)?;
+    let request = String::from_utf8_lossy(&request);
+
+    let (status, body) = server.lock().unwrap_or_else(|e| e.into_inner()).handle(method, path, &request);
+    write!(
+        reader.get_mut(),
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason_phrase(status),
+        body.len(),
+        body
+    )
+}
+
+/// Answer binary frames until the miner hangs up. Its renewals come every third of the lease
+/// time, so a connection silent for longer than a lease belongs to a miner that is gone.
+fn serve_binary(mut stream: BufReader<TcpStream>, server: &Mutex<RangeServer>) -> std::io::Result<()> {
+    let Some(version) = wire::accept(&mut stream)? else {
+        return Ok(());
+    };
+    let lease_time = server.lock().unwrap_or_else(|e| e.into_inner()).lease_time;
+    stream.get_ref().set_read_timeout(Some(lease_time + Duration::from_secs(10)))?;
+    while let Some(message) = wire::read_message(&mut stream)? {
+        let reply = server.lock().unwrap_or_else(|e| e.into_inner()).handle_message(message, version);
+        wire::write_message(stream.get_mut(), &reply)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn field(body: &str, key: &str) -> json::Json {
+        json::parse(body).unwrap().get(key).cloned().unwrap_or(json::Json::Null)
+    }
+
+    fn lease(server: &mut RangeServer) -> (u64, String, String) {
+        let (status, body) = server.handle("POST", "/lease", r#"{"worker_id": "w1", "worker": "rig"}"#);
+        assert_eq!(status, 200, "{}", body);
+        let id = field(&body, "id").as_f64().unwrap() as u64;
+        let text = |key| field(&body, key).as_str().unwrap().to_string();
+        (id, text("start"), text("end"))
+    }
+
+    #[test]
+    fn leases_are_consecutive() {
+        let mut server = RangeServer::new(0x100, MINUTE, 2);
+        assert_eq!(lease(&mut server), (1, "0000000000000200".into(), "0000000000000300".into()));
+        assert_eq!(lease(&mut server), (2, "0000000000000300".into(), "0000000000000400".into()));
+        let (_, status) = server.handle("GET", "/status", "");
+        assert_eq!(field(&status, "active").as_f64(), Some(2.0));
+        assert_eq!(field(&status, "next").as_str(), Some("0000000000000400"));
+    }
+
+    #[test]
+    fn renew_done_and_unknown_leases() {
+        let mut server = RangeServer::new(0x100, MINUTE, 0);
+        let (id, ..) = lease(&mut server);
+        assert_eq!(server.handle("POST", &format!("/renew/{}", id), "not json"), (200, "{}".into()));
+        assert_eq!(server.handle("POST", &format!("/done/{}", id), ""), (200, "{}".into()));
+        assert_eq!(server.handle("POST", &format!("/done/{}", id), "").0, 404);
+        assert_eq!(server.handle("POST", &format!("/renew/{}", id), "").0, 404);
+        assert_eq!(server.handle("POST", "/renew/x", "").0, 404);
+        assert_eq!(server.handle("GET", "/lease", "").1, "{\"error\": \"not found\"}");
+        // A finished range is never handed out again
+        assert_eq!(lease(&mut server).1, "0000000000000100");
+    }
+
+    #[test]
+    fn expired_leases_are_reissued() {
+        let mut server = RangeServer::new(0x100, Duration::ZERO, 0);
+        let (first, start, _) = lease(&mut server);
+        let (second, again, _) = lease(&mut server);
+        assert_eq!((start, again), ("0000000000000000".to_string(), "0000000000000000".to_string()));
+        assert_ne!(first, second);
+        assert_eq!(server.handle("POST", &format!("/renew/{}", first), "").0, 404);
+        assert_eq!(field(&server.handle("GET", "/status", "").1, "reclaimed").as_f64(), Some(2.0));
+    }
+
+    #[test]
+    fn released_leases_resume_past_covered() {
+        let mut server = RangeServer::new(0x100, MINUTE, 0);
+        let (id, ..) = lease(&mut server);
+        server.handle("POST", &format!("/renew/{}", id), r#"{"hashrate": 5000, "covered": "40"}"#);
+        // Less than the heartbeat said does not move the resume point back
+        assert_eq!(server.handle("POST", &format!("/release/{}", id), r#"{"covered": "10"}"#).0, 200);
+        assert_eq!(lease(&mut server), (2, "0000000000000040".into(), "0000000000000100".into()));
+        let mut server = RangeServer::new(0x100, MINUTE, 0);
+        let (id, ..) = lease(&mut server);
+        server.handle("POST", &format!("/release/{}", id), r#"{"covered": "ffff"}"#);
+        assert_eq!(lease(&mut server).1, "0000000000000100", "a fully covered release reissues nothing");
+    }
+
+    #[test]
+    fn the_nonce_space_runs_out() {
+        let mut server = RangeServer::new(u64::MAX, MINUTE, 0);
+        lease(&mut server);
+        assert_eq!(server.handle("POST", "/lease", "").0, 503);
+        let mut server = RangeServer::new(u64::MAX, MINUTE, 0);
+        assert!(matches!(
+            server.handle_message(Message::Lease { worker_id: "".into(), worker: "".into() }, 2),
+            Message::Leased { .. }
+        ));
+        assert_eq!(
+            server.handle_message(Message::Lease { worker_id: "".into(), worker: "".into() }, 2),
+            Message::Exhausted
+        );
+    }
+
+    #[test]
+    fn the_first_solution_wins() {
+        let mut server = RangeServer::new(0x100, MINUTE, 0);
+        let (a, ..) = lease(&mut server);
+        let (b, ..) = lease(&mut server);
+        let (c, ..) = lease(&mut server);
+        assert_eq!(server.handle("POST", &format!("/done/{}", a), r#"{"nonce": "2a"}"#), (200, "{}".into()));
+        let solved = (200, "{\"solved\": \"000000000000002a\"}".to_string());
+        assert_eq!(server.handle("POST", &format!("/done/{}", b), r#"{"nonce": "99"}"#), solved);
+        assert_eq!(server.handle("POST", &format!("/renew/{}", c), ""), solved);
+        assert_eq!(server.handle("POST", "/lease", ""), solved);
+        // Reporting the winner again, after its lease is gone, is still accepted
+        assert_eq!(server.handle("POST", &format!("/done/{}", a), r#"{"nonce": "2a"}"#), (200, "{}".into()));
+        let solutions = server.handle("GET", "/solutions", "").1;
+        assert_eq!(field(&solutions, "winner").as_str(), Some("000000000000002a"));
+        let json::Json::Array(candidates) = field(&solutions, "candidates") else { panic!("{}", solutions) };
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[1].get("holder").and_then(json::Json::as_str), Some("rig (w1)"));
+    }
+
+    #[test]
+    fn old_miners_are_not_sent_solved() {
+        let mut server = RangeServer::new(0x100, MINUTE, 0);
+        let lease = |server: &mut RangeServer, version| {
+            server.handle_message(Message::Lease { worker_id: "".into(), worker: "".into() }, version)
+        };
+        let Message::Leased { id, .. } = lease(&mut server, 1) else { panic!() };
+        let Message::Leased { id: other, .. } = lease(&mut server, 2) else { panic!() };
+        assert_eq!(server.handle_message(Message::Done { id, nonce: Some(7) }, 2), Message::Ok);
+        assert_eq!(server.handle_message(Message::Renew { id: other, hashrate: 0, covered: 0 }, 1), Message::Ok);
+        assert_eq!(
+            server.handle_message(Message::Renew { id: other, hashrate: 0, covered: 0 }, 2),
+            Message::Solved { nonce: 7 }
+        );
+        assert_eq!(lease(&mut server, 1), Message::Exhausted);
+        assert_eq!(lease(&mut server, 2), Message::Solved { nonce: 7 });
+        assert_eq!(server.handle_message(Message::Renew { id: 99, hashrate: 0, covered: 0 }, 2), Message::UnknownLease);
+        assert_eq!(server.handle_message(Message::Ok, 2), Message::UnknownLease);
+    }
+
+    #[test]
+    fn fleet_totals() {
+        let mut server = RangeServer::new(0x100, MINUTE, 0).difficulty(20);
+        let (id, ..) = lease(&mut server);
+        server.handle("POST", &format!("/renew/{}", id), r#"{"hashrate": 1024, "covered": "80"}"#);
+        server.handle("POST", "/lease", r#"{"worker": "other"}"#);
+        let fleet = server.handle("GET", "/fleet", "").1;
+        assert_eq!(field(&fleet, "hashrate").as_f64(), Some(1024.0));
+        assert_eq!(field(&fleet, "covered").as_f64(), Some(128.0));
+        assert_eq!(field(&fleet, "eta_secs").as_f64(), Some(1024.0));
+        let json::Json::Array(workers) = field(&fleet, "workers") else { panic!("{}", fleet) };
+        let names: Vec<_> = workers.iter().filter_map(|w| w.get("worker").and_then(json::Json::as_str)).collect();
+        assert_eq!(names, ["other", "rig"]);
+    }
+
+    /// One connection to `handle_connection` carrying `request`; returns what came back.
+    fn exchange(request: &[u8]) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(request).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let server = Mutex::new(RangeServer::new(0x100, MINUTE, 0));
+        handle_connection(listener.accept().unwrap().0, &server).unwrap();
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).unwrap();
+        reply
+    }
+
+    #[test]
+    fn http_connections() {
+        let body = r#"{"worker": "rig"}"#;
+        let request = format!("POST /lease HTTP/1.1\r\nHost: s\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+        let reply = String::from_utf8(exchange(request.as_bytes())).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{}", reply);
+        assert!(
+            reply.ends_with(r#"{"id": 1, "start": "0000000000000000", "end": "0000000000000100", "expires_in": 60}"#)
+        );
+        let reply = String::from_utf8(exchange(b"GET /nowhere HTTP/1.1\r\n\r\n")).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", reply);
+    }
+
+    #[test]
+    fn binary_connections() {
+        let mut request = wire::MAGIC.to_vec();
+        request.push(wire::VERSION);
+        wire::write_message(&mut request, &Message::Lease { worker_id: "".into(), worker: "".into() }).unwrap();
+        wire::write_message(&mut request, &Message::Release { id: 1, covered: 0 }).unwrap();
+        let reply = exchange(&request);
+        assert_eq!(reply[0], wire::VERSION);
+        let mut rest = &reply[1..];
+        assert_eq!(
+            wire::read_message(&mut rest).unwrap(),
+            Some(Message::Leased { id: 1, start: 0, end: 0x100, expires_in: 60 })
+        );
+        assert_eq!(wire::read_message(&mut rest).unwrap(), Some(Message::Ok));
+        assert_eq!(wire::read_message(&mut rest).unwrap(), None);
+        // A version this server does not speak is answered with its own and dropped
+        assert_eq!(exchange(&[0xFC, b'P', b'C', 9]), [wire::VERSION]);
+    }
+}
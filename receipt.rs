@@ -0,0 +1,256 @@
+//! `--receipt DIR`: a signed, timestamped record of each solution, so a pool or an auditor
+//! can check who found what and when without trusting the miner's logs. The receipt holds
+//! a JSON `payload` (challenge parameters, nonce, hash, search start and find times, host
+//! fingerprint and environment, worker identity, the thread that found it and its hash
+//! count) as a string, its Ed25519 `signature` and the signer's `public_key`, so any
+//! Ed25519 library can verify it; `portocripto verify-receipt` also recomputes the hash.
+//!
+//! The signing key is a 32-byte seed stored as hex in `--receipt-key`, created on first use;
+//! `ed25519` hands it to OpenSSL to sign.
+
+use crate::solution::{unix_secs, Solution};
+use crate::{
+    cpu_model, ed25519, environment, hash_preimage, hostname, identity, json, json_string, to_hex, Challenge, Endian,
+    SaltPosition,
+};
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Default `--receipt-key`, next to the config file.
+pub const KEY_FILE: &str = "portocripto.key";
+
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    // from_str_radix would take a leading '+'
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// The seed in `path`, generating one from the OS random source if the file does not exist.
+pub fn load_key(path: &Path) -> std::io::Result<[u8; 32]> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse_hex(&text)
+            .and_then(|seed| seed.try_into().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{}: expected 64 hex digits", path.display()))),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let mut seed = [0u8; 32];
+            std::fs::File::open("/dev/urandom").and_then(|mut random| random.read_exact(&mut seed)).map_err(|e| {
+                Error::new(e.kind(), format!("no random source for a new key ({}); write 64 hex digits to it", e))
+            })?;
+            // Before the file exists, so a host without openssl is not left a key it cannot use
+            let public = ed25519::public_key(&seed)?;
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            std::io::Write::write_all(&mut options.open(path)?, format!("{}\n", to_hex(&seed)).as_bytes())?;
+            eprintln!(
+                "receipt: created signing key {} (public key {})",
+                path.display(),
+                to_hex(&public)
+            );
+            Ok(seed)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Hash of the host name, machine id and CPU model: stable for a machine, but naming it
+/// only to someone who already knows those.
+fn host_fingerprint() -> String {
+    let read = |path: &str| std::fs::read_to_string(path).map(|s| s.trim().to_string()).unwrap_or_default();
+    let ids = format!("{}\n{}\n{}", hostname(), read("/etc/machine-id"), cpu_model());
+    let mut digest = [0u8; 32];
+    hash_preimage(ids.as_bytes(), &mut digest);
+    to_hex(&digest)
+}
+
+pub struct Receipt<'a> {
+    pub challenge: &'a Challenge,
+    /// `solution.nonce` as a number, as the payload records it whatever the nonce encoding
+    pub nonce: u64,
+    pub solution: &'a Solution,
+    pub started: SystemTime,
+}
+
+impl Receipt<'_> {
+    /// The signed text: the same `parameters` and `solution` objects as `--report-json`.
+    fn payload(&self) -> String {
+        let c = self.challenge;
+        format!(
+            concat!(
+                r#"{{"version": 1, "parameters": {{"address": {}, "challenge_id": {}, "difficulty": {}, "#,
+                r#""no_pre_mine": {}, "latest_submission": {}, "no_pre_mine_hour": {}, "prefix_offset": {}, "#,
+                r#""prefix_endian": {}, "hash_len": {}, "hmac": {}, "salt": {}, "salt_position": {}, "#,
+                r#""hex_case": {}, "nonce_encoding": {}}}, "solution": {{"nonce": "{:016x}", "hash": {}}}, "#,
+                r#""started_unix": {:.3}, "found_unix": {:.3}, "#,
+                r#""host": {{"fingerprint": {}, {}}}, "#,
+                r#""worker": {{"id": {}, "name": {}, "thread": {}, "hashes": {}}}, "miner": {}}}"#
+            ),
+            json_string(&c.address),
+            json_string(&c.challenge_id),
+            json_string(&c.difficulty),
+            json_string(&c.no_pre_mine),
+            json_string(&c.latest_submission),
+            json_string(&c.no_pre_mine_hour),
+            c.prefix_offset,
+            json_string(if c.prefix_endian == Endian::Little { "little" } else { "big" }),
+            c.hash_len,
+            c.hmac_key.is_some(),
+            c.salt.as_deref().map_or("null".to_string(), json_string),
+            json_string(if c.salt_position == SaltPosition::Prepend { "prepend" } else { "append" }),
+            json_string(c.hex_case.name()),
+            json_string(c.nonce_encoding.name()),
+            self.nonce,
+            json_string(&self.solution.hash),
+            unix_secs(self.started),
+            unix_secs(self.solution.found.unwrap_or(self.started)),
+            json_string(&host_fingerprint()),
+            environment::members(&environment::host(self.solution.backend.as_deref().unwrap_or("")), ", "),
+            json_string(&identity::current().id),
+            json_string(&identity::current().name),
+            self.solution.thread.map_or("null".to_string(), |t| t.to_string()),
+            self.solution.worker_hashes.map_or("null".to_string(), |h| h.to_string()),
+            json_string(concat!("portocripto ", env!("CARGO_PKG_VERSION"))),
+        )
+    }
+
+    /// Sign with `seed` and write `<challenge_id>-<nonce>.receipt.json` under `dir`.
+    pub fn write(&self, dir: &Path, seed: &[u8; 32]) -> std::io::Result<PathBuf> {
+        let payload = self.payload();
+        let signature = ed25519::sign(seed, payload.as_bytes())?;
+        let text = format!(
+            "{{\n  \"payload\": {},\n  \"public_key\": \"{}\",\n  \"signature\": \"{}\"\n}}\n",
+            json_string(&payload),
+            to_hex(&ed25519::public_key(seed)?),
+            to_hex(&signature)
+        );
+        std::fs::create_dir_all(dir)?;
+        let name: String = format!("{}-{:016x}", self.challenge.challenge_id, self.nonce)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}.receipt.json", name));
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+/// Check the signature of the receipt in `path`; returns the parsed payload and the hex
+/// public key that signed it.
+pub fn verify(path: &Path) -> Result<(json::Json, String), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let receipt = json::parse(&text).map_err(|e| format!("invalid receipt {}: {}", path.display(), e))?;
+    let field = |key: &str| receipt.get(key).and_then(json::Json::as_str).ok_or(format!("receipt has no {}", key));
+    let payload = field("payload")?;
+    let public_key = field("public_key")?;
+    let public: [u8; 32] =
+        parse_hex(public_key).and_then(|key| key.try_into().ok()).ok_or("public_key is not 64 hex digits")?;
+    let signature: [u8; 64] = parse_hex(field("signature")?)
+        .and_then(|signature| signature.try_into().ok())
+        .ok_or("signature is not 128 hex digits")?;
+    if !ed25519::verify(&public, payload.as_bytes(), &signature) {
+        return Err(format!("signature does not match the payload and public key {}", public_key));
+    }
+    let payload = json::parse(payload).map_err(|e| format!("invalid payload: {}", e))?;
+    Ok((payload, public_key.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 8032 test 2: its one-byte message "r" is not JSON
+    const PUBLIC: &str = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+    const SIGNATURE: &str = "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+                             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00";
+
+    fn receipt_file(dir: &Path, name: &str, text: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(parse_hex(" 00ff7A\n"), Some(vec![0, 0xff, 0x7a]));
+        assert_eq!(parse_hex(""), Some(vec![]));
+        assert_eq!(parse_hex("abc"), None);
+        assert_eq!(parse_hex("zz"), None);
+        assert_eq!(parse_hex("+1"), None);
+        assert_eq!(parse_hex("éa"), None);
+    }
+
+    #[test]
+    fn keys_on_disk() {
+        let dir = crate::scratch_dir("receipt-keys");
+        let seed = receipt_file(&dir, "seed", &format!("{}\n", "01".repeat(32)));
+        assert_eq!(load_key(&seed).unwrap(), [1; 32]);
+        let short = receipt_file(&dir, "short", "0102");
+        assert_eq!(load_key(&short).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(load_key(&dir).is_err());
+        if ed25519::public_key(&[0; 32]).is_ok() {
+            let created = dir.join("created");
+            let first = load_key(&created).unwrap();
+            assert_eq!(load_key(&created).unwrap(), first);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(std::fs::metadata(&created).unwrap().permissions().mode() & 0o777, 0o600);
+            }
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn broken_receipts() {
+        let dir = crate::scratch_dir("receipt-broken");
+        let check = |name: &str, text: &str| verify(&receipt_file(&dir, name, text)).unwrap_err();
+        assert!(verify(&dir.join("missing")).unwrap_err().starts_with("failed to read"));
+        assert!(check("truncated", "{\"payload\": ").starts_with("invalid receipt"));
+        assert_eq!(check("empty", "{}"), "receipt has no payload");
+        assert_eq!(check("numeric", "{\"payload\": \"r\", \"public_key\": 7}"), "receipt has no public_key");
+        let key = |public: &str, signature: &str| {
+            format!("{{\"payload\": \"r\", \"public_key\": \"{}\", \"signature\": \"{}\"}}", public, signature)
+        };
+        assert_eq!(check("short-key", &key("3d40", SIGNATURE)), "public_key is not 64 hex digits");
+        assert_eq!(check("short-signature", &key(PUBLIC, &SIGNATURE[2..])), "signature is not 128 hex digits");
+        let tampered = format!("93{}", &SIGNATURE[2..]);
+        assert!(check("tampered", &key(PUBLIC, &tampered)).starts_with("signature does not match"));
+        assert!(check("rfc", &key(PUBLIC, SIGNATURE)).starts_with("invalid payload"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn signed_receipts_verify() {
+        if ed25519::public_key(&[0; 32]).is_err() {
+            return;
+        }
+        let parameters = json::parse(
+            "{\"address\": \"addr_test1qpzry9x8gf2tvdw0s3jn4syst5\", \"challenge_id\": \"**D07\", \
+             \"difficulty\": \"0fffffff\", \"no_pre_mine\": \"00112233\", \
+             \"latest_submission\": \"2099-12-31T23:59:59.000Z\", \"no_pre_mine_hour\": \"42\"}",
+        )
+        .unwrap();
+        let challenge = Challenge::from_json(&parameters).unwrap();
+        let solution = Solution::new(challenge.nonce_encoding, 6166, &[0, 0x0f], "**D07", "addr_test1");
+        let started = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_761_000_000);
+        let receipt = Receipt { challenge: &challenge, nonce: 6166, solution: &solution, started };
+        let dir = crate::scratch_dir("receipt-signed");
+        let seed = [7; 32];
+        let path = receipt.write(&dir, &seed).unwrap();
+        assert_eq!(path.file_name().unwrap(), "__D07-0000000000001816.receipt.json");
+        let (payload, public) = verify(&path).unwrap();
+        assert_eq!(public, to_hex(&ed25519::public_key(&seed).unwrap()));
+        let nonce = payload.get("solution").and_then(|s| s.get("nonce")).and_then(json::Json::as_str);
+        assert_eq!(nonce, Some("0000000000001816"));
+        assert_eq!(payload.get("started_unix").and_then(json::Json::as_f64), Some(1_761_000_000.0));
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replace("**D07", "**D08")).unwrap();
+        assert!(verify(&path).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,155 @@
+//! Just enough of the Redis protocol (RESP2) to claim nonce chunks and publish solutions.
+//! Array replies are not needed by any command portocripto sends and are rejected.
+
+use crate::{proxy, retry};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::net::TcpStream;
+
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    Status,
+    Integer(i64),
+    Bulk(Option<String>),
+}
+
+pub struct Redis {
+    reader: BufReader<TcpStream>,
+}
+
+/// `redis://[:password@]host[:port][/db]` as host:port, the userinfo and the database.
+fn parse_url(url: &str) -> std::io::Result<(String, Option<&str>, Option<&str>)> {
+    let rest = url
+        .strip_prefix("redis://")
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "only redis:// URLs are supported"))?;
+    let (auth, rest) = match rest.rsplit_once('@') {
+        Some((auth, rest)) => (Some(auth), rest),
+        None => (None, rest),
+    };
+    let (host, db) = match rest.split_once('/') {
+        Some((host, db)) => (host, Some(db).filter(|db| !db.is_empty())),
+        None => (rest, None),
+    };
+    let addr = if host.contains(':') { host.to_string() } else { format!("{}:6379", host) };
+    Ok((addr, auth, db))
+}
+
+fn encode(args: &[&str]) -> String {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    request
+}
+
+fn line(reader: &mut impl BufRead) -> std::io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "connection closed"));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn reply(reader: &mut impl BufRead) -> std::io::Result<Reply> {
+    let line = line(reader)?;
+    let invalid = || Error::new(ErrorKind::InvalidData, format!("unexpected reply {:?}", line));
+    let (kind, rest) = line.split_at_checked(1).ok_or_else(invalid)?;
+    match kind {
+        "+" => Ok(Reply::Status),
+        "-" => Err(Error::other(rest.to_string())),
+        ":" => rest.parse().map(Reply::Integer).map_err(|_| invalid()),
+        "$" => {
+            let len: i64 = rest.parse().map_err(|_| invalid())?;
+            if len < 0 {
+                return Ok(Reply::Bulk(None));
+            }
+            // Redis caps bulk strings at 512 MB; a longer length is not from Redis
+            if len > 512 << 20 {
+                return Err(invalid());
+            }
+            let mut data = vec![0u8; len as usize + 2];
+            reader.read_exact(&mut data)?;
+            data.truncate(len as usize);
+            Ok(Reply::Bulk(Some(String::from_utf8_lossy(&data).into_owned())))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+impl Redis {
+    /// Connect to `redis://[:password@]host[:port][/db]`.
+    pub fn connect(url: &str) -> std::io::Result<Redis> {
+        let (addr, auth, db) = parse_url(url)?;
+        let stream = retry::retry("redis", || proxy::connect(&addr))?;
+        let mut redis = Redis { reader: BufReader::new(stream) };
+
+        if let Some(auth) = auth {
+            match auth.split_once(':') {
+                Some(("", password)) => redis.command(&["AUTH", password])?,
+                Some((user, password)) => redis.command(&["AUTH", user, password])?,
+                None => redis.command(&["AUTH", auth])?,
+            };
+        }
+        if let Some(db) = db {
+            redis.command(&["SELECT", db])?;
+        }
+        Ok(redis)
+    }
+
+    /// Send one command and read its reply; Redis error replies become `Err`.
+    pub fn command(&mut self, args: &[&str]) -> std::io::Result<Reply> {
+        self.reader.get_mut().write_all(encode(args).as_bytes())?;
+        reply(&mut self.reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(bytes: &[u8]) -> std::io::Result<Reply> {
+        reply(&mut &bytes[..])
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(parse_url("redis://cache").unwrap(), ("cache:6379".to_string(), None, None));
+        assert_eq!(parse_url("redis://cache:7000/").unwrap(), ("cache:7000".to_string(), None, None));
+        assert_eq!(parse_url("redis://:pw@cache/2").unwrap(), ("cache:6379".to_string(), Some(":pw"), Some("2")));
+        assert_eq!(parse_url("redis://u:p@ss@10.0.0.1:6380/0").unwrap().1, Some("u:p@ss"));
+        assert_eq!(parse_url("rediss://cache").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn commands_are_length_prefixed() {
+        assert_eq!(encode(&["SET", "k", "ü v"]), "*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$4\r\nü v\r\n");
+        assert_eq!(encode(&[]), "*0\r\n");
+    }
+
+    #[test]
+    fn replies() {
+        assert_eq!(read(b"+OK\r\n").unwrap(), Reply::Status);
+        assert_eq!(read(b":-12\r\n").unwrap(), Reply::Integer(-12));
+        assert_eq!(read(b"$-1\r\n").unwrap(), Reply::Bulk(None));
+        assert_eq!(read(b"$0\r\n\r\n").unwrap(), Reply::Bulk(Some(String::new())));
+        assert_eq!(read(b"$4\r\na\r\nb\r\n").unwrap(), Reply::Bulk(Some("a\r\nb".into())));
+    }
+
+    #[test]
+    fn replies_one_at_a_time() {
+        let mut stream = &b"$2\r\nhi\r\n:1\r\n"[..];
+        assert_eq!(reply(&mut stream).unwrap(), Reply::Bulk(Some("hi".into())));
+        assert_eq!(reply(&mut stream).unwrap(), Reply::Integer(1));
+        assert_eq!(reply(&mut stream).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn bad_replies() {
+        let error = read(b"-WRONGPASS invalid password\r\n").unwrap_err();
+        assert_eq!((error.kind(), error.to_string()), (ErrorKind::Other, "WRONGPASS invalid password".into()));
+        for bytes in [&b"\r\n"[..], b"*1\r\n", b":x\r\n", b"$x\r\n", b"$99999999999\r\n", "é\r\n".as_bytes()] {
+            assert_eq!(read(bytes).unwrap_err().kind(), ErrorKind::InvalidData, "{:?}", bytes);
+        }
+        assert_eq!(read(b"").unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(read(b"$5\r\nab").unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,258 @@
+//! The periodic stats reporter: one thread samples the hash counters every
+//! --report-interval and hands the same sample to every sink configured, the telemetry
+//! POST, OTLP metrics, MQTT status, --log-progress and --progress-file alike, plus a final
+//! sample when the search stops. Sinks run one after another, so one that is slow to answer
+//! delays the others by as much as its network timeout. The --min-hashrate watchdog is a
+//! sink too, so it only sees the hashrate once per interval.
+
+use crate::{json_string, unix_now, wait_for_stop, Progress};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// The hash counters at the end of an interval.
+pub struct Sample {
+    pub hashes: u64,
+    /// Over the interval just ended
+    pub hashrate: f64,
+    pub uptime: Duration,
+    pub solutions: u64,
+    pub paused: bool,
+    /// Set on the sample taken once the search has stopped
+    pub last: bool,
+}
+
+pub trait Sink: Send {
+    fn report(&mut self, sample: &Sample);
+}
+
+/// Feed `sinks` a sample every `interval` until the search stops, and once more after it has.
+pub fn run(sinks: &mut [Box<dyn Sink + '_>], progress: &Progress, interval: Duration) {
+    let started = Instant::now();
+    let (mut last_uptime, mut last_hashes) = (Duration::ZERO, progress.hashes());
+    loop {
+        let stopped = wait_for_stop(&progress.stop, interval);
+        let (uptime, hashes) = (progress.awake(started), progress.hashes());
+        let sample = Sample {
+            hashes,
+            hashrate: (hashes - last_hashes) as f64 / uptime.saturating_sub(last_uptime).as_secs_f64(),
+            uptime,
+            solutions: progress.solutions.load(Ordering::Relaxed),
+            paused: progress.paused.load(Ordering::Relaxed),
+            last: stopped,
+        };
+        (last_uptime, last_hashes) = (uptime, hashes);
+        for sink in sinks.iter_mut() {
+            sink.report(&sample);
+        }
+        if stopped {
+            break;
+        }
+    }
+}
+
+/// `--log-progress`: a line on stderr.
+pub struct Stderr;
+
+impl Sink for Stderr {
+    fn report(&mut self, sample: &Sample) {
+        eprintln!(
+            "progress: {} hashes in {}, {:.0} hashes/s{}",
+            sample.hashes,
+            crate::format_duration(sample.uptime.as_secs_f64()),
+            sample.hashrate,
+            if sample.paused { " (paused)" } else { "" }
+        );
+    }
+}
+
+/// `--progress-file`: a JSON line appended to a file.
+pub struct JsonLines {
+    file: File,
+}
+
+impl JsonLines {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        Ok(JsonLines { file: std::fs::OpenOptions::new().create(true).append(true).open(path)? })
+    }
+}
+
+impl Sink for JsonLines {
+    fn report(&mut self, sample: &Sample) {
+        let line = format!(
+            concat!(
+                "{{\"unix\": {}, \"hashes\": {}, \"hashrate\": {:.1}, \"uptime_secs\": {}, \"solutions\": {}, ",
+                "\"paused\": {}, \"last\": {}, \"worker_id\": {}}}"
+            ),
+            unix_now(),
+            sample.hashes,
+            sample.hashrate,
+            sample.uptime.as_secs(),
+            sample.solutions,
+            sample.paused,
+            sample.last,
+            json_string(&crate::identity::current().id)
+        );
+        if let Err(e) = writeln!(self.file, "{}", line) {
+            eprintln!("progress-file: {}", e);
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloorAction {
+    /// Log a warning and keep mining
+    Warn,
+    /// Stop the search, writing the journal as usual, and exit with status 1
+    Abort,
+}
+
+/// `--min-hashrate`: act once the hashrate has stayed below `floor` for longer than `grace`,
+/// say a throttling CPU or a busy neighbour. Paused samples do not count.
+pub struct HashrateFloor<'a> {
+    floor: f64,
+    grace: Duration,
+    action: FloorAction,
+    progress: &'a Progress,
+    /// Set along with the stop flag when aborting
+    tripped: &'a AtomicBool,
+    /// Uptime of the last sample at or above the floor
+    last_ok: Duration,
+    warned: bool,
+}
+
+impl<'a> HashrateFloor<'a> {
+    pub fn new(
+        floor: f64,
+        grace: Duration,
+        action: FloorAction,
+        progress: &'a Progress,
+        tripped: &'a AtomicBool,
+    ) -> Self {
+        HashrateFloor { floor, grace, action, progress, tripped, last_ok: Duration::ZERO, warned: false }
+    }
+}
+
+impl Sink for HashrateFloor<'_> {
+    fn report(&mut self, sample: &Sample) {
+        if sample.last {
+            return;
+        }
+        if sample.paused || sample.hashrate >= self.floor {
+            if self.warned {
+                eprintln!("hashrate: back to {:.0} hashes/s", sample.hashrate);
+            }
+            (self.last_ok, self.warned) = (sample.uptime, false);
+            return;
+        }
+        let below = sample.uptime - self.last_ok;
+        if below <= self.grace || self.warned {
+            return;
+        }
+        self.warned = true;
+        eprintln!(
+            "hashrate: WARNING: {:.0} hashes/s, below the --min-hashrate of {:.0} for {}",
+            sample.hashrate,
+            self.floor,
+            crate::format_duration(below.as_secs_f64())
+        );
+        if self.action == FloorAction::Abort {
+            eprintln!("hashrate: stopping the search");
+            self.tripped.store(true, Ordering::Release);
+            self.progress.stop.store(true, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<(u64, bool)>>>);
+
+    impl Sink for Recorder {
+        fn report(&mut self, sample: &Sample) {
+            self.0.lock().unwrap().push((sample.hashes, sample.last));
+        }
+    }
+
+    fn sample(uptime_secs: u64, hashrate: f64) -> Sample {
+        Sample {
+            hashes: 0,
+            hashrate,
+            uptime: Duration::from_secs(uptime_secs),
+            solutions: 0,
+            paused: false,
+            last: false,
+        }
+    }
+
+    #[test]
+    fn every_sink_gets_every_sample() {
+        let progress = Progress::new(2);
+        let (a, b) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+        let mut sinks: Vec<Box<dyn Sink>> = vec![Box::new(Recorder(a.clone())), Box::new(Recorder(b.clone()))];
+        std::thread::scope(|scope| {
+            let reporting = scope.spawn(|| run(&mut sinks, &progress, Duration::from_millis(20)));
+            while a.lock().unwrap().len() < 2 {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            progress.workers[1].hashes.store(7, Ordering::Relaxed);
+            progress.stop.store(true, Ordering::Release);
+            reporting.join().unwrap();
+        });
+        let samples = a.lock().unwrap().clone();
+        assert_eq!(samples, *b.lock().unwrap());
+        assert_eq!(samples.last(), Some(&(7, true)));
+        assert!(samples[..samples.len() - 1].iter().all(|&(_, last)| !last));
+    }
+
+    #[test]
+    fn progress_files() {
+        let dir = crate::scratch_dir("reporter");
+        let path = dir.join("progress.jsonl");
+        let mut file = JsonLines::open(&path).unwrap();
+        file.report(&Sample { hashes: 4096, solutions: 1, paused: true, ..sample(90, 45.55) });
+        file.report(&Sample { last: true, ..sample(91, 0.0) });
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = text.lines().map(|line| crate::json::parse(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        let number = |i: usize, key: &str| lines[i].get(key).and_then(crate::json::Json::as_f64);
+        assert_eq!(
+            (number(0, "hashes"), number(0, "hashrate"), number(0, "uptime_secs")),
+            (Some(4096.0), Some(45.5), Some(90.0))
+        );
+        assert_eq!(lines[0].get("paused"), Some(&crate::json::Json::Bool(true)));
+        assert_eq!(lines[1].get("last"), Some(&crate::json::Json::Bool(true)));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn floors_trip_after_the_grace() {
+        let (progress, tripped) = (Progress::new(1), AtomicBool::new(false));
+        let mut floor = HashrateFloor::new(100.0, Duration::from_secs(30), FloorAction::Abort, &progress, &tripped);
+        floor.report(&sample(10, 150.0));
+        floor.report(&sample(30, 50.0));
+        // Paused samples reset the clock rather than counting as slow
+        floor.report(&Sample { paused: true, ..sample(35, 0.0) });
+        floor.report(&sample(60, 50.0));
+        floor.report(&Sample { last: true, ..sample(100, 0.0) });
+        assert!(!tripped.load(Ordering::Acquire) && !progress.stop.load(Ordering::Acquire));
+        floor.report(&sample(66, 50.0));
+        assert!(tripped.load(Ordering::Acquire) && progress.stop.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn warning_floors_keep_mining() {
+        let (progress, tripped) = (Progress::new(1), AtomicBool::new(false));
+        let mut floor = HashrateFloor::new(100.0, Duration::ZERO, FloorAction::Warn, &progress, &tripped);
+        floor.report(&sample(1, 10.0));
+        assert!(floor.warned && !tripped.load(Ordering::Acquire) && !progress.stop.load(Ordering::Acquire));
+        floor.report(&sample(2, 100.0));
+        assert!(!floor.warned);
+        assert_eq!(floor.last_ok, Duration::from_secs(2));
+    }
+}
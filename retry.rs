@@ -0,0 +1,153 @@
+//! Retries with exponential backoff and jitter for outgoing connections, so a brief
+//! outage of a range server, Redis, a broker or a collector does not end a run. The policy
+//! comes from the config file (`retry_attempts`, `retry_base_delay_ms`, `retry_max_delay_ms`
+//! and `network_timeout_secs`), with defaults that suit a LAN.
+
+use std::hash::{BuildHasher, RandomState};
+use std::io::{Error, ErrorKind};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+pub struct Policy {
+    /// Tries per operation, including the first
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Limit for connecting and for each read or write
+    pub timeout: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Replace the defaults; call before the first connection.
+pub fn set(policy: Policy) {
+    let _ = POLICY.set(policy);
+}
+
+pub fn policy() -> &'static Policy {
+    POLICY.get_or_init(Policy::default)
+}
+
+/// Errors that another try cannot fix: bad URLs, refused credentials, unsupported features
+/// and replies the server meant (HTTP 4xx).
+fn permanent(e: &Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::InvalidInput | ErrorKind::InvalidData | ErrorKind::Unsupported | ErrorKind::PermissionDenied
+    )
+}
+
+/// Between half and all of min(max_delay, base_delay * 2^attempt), so miners that lost the
+/// same server do not come back in lockstep.
+fn delay(policy: &Policy, attempt: u32) -> Duration {
+    let cap = policy.base_delay.saturating_mul(1 << attempt.min(20)).min(policy.max_delay);
+    let random = RandomState::new().hash_one(SystemTime::now());
+    cap.mul_f64(0.5 + 0.5 * (random as f64 / u64::MAX as f64))
+}
+
+/// Run `op` until it succeeds, fails permanently or runs out of attempts; `what` names it
+/// in the log lines between tries.
+pub fn retry<T>(what: &str, mut op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+    let policy = policy();
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(e) if attempt + 1 < policy.attempts && !permanent(&e) => {
+                let wait = delay(policy, attempt);
+                attempt += 1;
+                eprintln!("{}: {}; retry {}/{} in {:.1}s", what, e, attempt, policy.attempts - 1, wait.as_secs_f64());
+                std::thread::sleep(wait);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Connect to `addr` (host:port) within the policy's timeout, which also bounds every read
+/// and write on the returned stream.
+pub fn connect(addr: &str) -> std::io::Result<TcpStream> {
+    let timeout = policy().timeout;
+    let network = crate::offline::network()?;
+    let mut last = Error::new(ErrorKind::NotFound, format!("{} resolved to no address", addr));
+    for socket in addr.to_socket_addrs()? {
+        match network.connect(&socket, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(e) => last = e,
+        }
+    }
+    Err(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn which_errors_are_retried() {
+        for kind in
+            [ErrorKind::InvalidInput, ErrorKind::InvalidData, ErrorKind::Unsupported, ErrorKind::PermissionDenied]
+        {
+            assert!(permanent(&Error::from(kind)), "{:?}", kind);
+        }
+        for kind in [ErrorKind::ConnectionRefused, ErrorKind::TimedOut, ErrorKind::UnexpectedEof, ErrorKind::Other] {
+            assert!(!permanent(&Error::from(kind)), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn delays_back_off_within_the_cap() {
+        let policy =
+            Policy { base_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1), ..Policy::default() };
+        for (attempt, cap) in [(0, 100), (1, 200), (3, 800), (4, 1000), (40, 1000)] {
+            let wait = delay(&policy, attempt);
+            let cap = Duration::from_millis(cap);
+            assert!(wait >= cap / 2 && wait <= cap, "attempt {}: {:?}", attempt, wait);
+        }
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let mut calls = 0;
+        let result: std::io::Result<()> = retry("test", || {
+            calls += 1;
+            Err(Error::new(ErrorKind::InvalidData, "bad reply"))
+        });
+        assert_eq!((result.unwrap_err().kind(), calls), (ErrorKind::InvalidData, 1));
+    }
+
+    #[test]
+    fn transient_errors_are_retried() {
+        let mut calls = 0;
+        let result = retry("test", || {
+            calls += 1;
+            if calls < 2 {
+                Err(Error::from(ErrorKind::ConnectionRefused))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn unresolvable_addresses() {
+        assert!(connect("no-port").is_err());
+    }
+}
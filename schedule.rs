@@ -0,0 +1,205 @@
+//! `--schedule`: daily mining windows in local time, such as `22:00-07:00` for cheap
+//! night-time electricity. Several windows can be given separated by commas. Where the
+//! local clock cannot be read safely (see `local_minute_of_day`) they are in UTC.
+
+use std::str::FromStr;
+
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    /// (start, end) in minutes after midnight; `end < start` wraps past midnight
+    windows: Vec<(u32, u32)>,
+}
+
+fn parse_time(s: &str) -> Result<u32, String> {
+    let (h, m) = s.trim().split_once(':').ok_or_else(|| format!("expected HH:MM, got {:?}", s))?;
+    match (h.parse::<u32>(), m.parse::<u32>()) {
+        (Ok(h), Ok(m)) if h < 24 && m < 60 => Ok(h * 60 + m),
+        (Ok(24), Ok(0)) => Ok(24 * 60),
+        _ => Err(format!("{:?} is not a time between 00:00 and 24:00", s)),
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let windows = s
+            .split(',')
+            .map(|window| {
+                let (start, end) = window
+                    .split_once('-')
+                    .ok_or_else(|| format!("window {:?} should look like 22:00-07:00", window))?;
+                let (start, end) = (parse_time(start)?, parse_time(end)?);
+                if start == end {
+                    return Err(format!("window {:?} is empty", window));
+                }
+                Ok((start, end))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Schedule { windows })
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let windows: Vec<String> = self
+            .windows
+            .iter()
+            .map(|&(start, end)| format!("{:02}:{:02}-{:02}:{:02}", start / 60, start % 60, end / 60, end % 60))
+            .collect();
+        f.write_str(&windows.join(","))
+    }
+}
+
+impl Schedule {
+    /// Minutes after midnight at which each window opens.
+    pub fn starts(&self) -> impl Iterator<Item = u32> + '_ {
+        self.windows.iter().map(|&(start, _)| start)
+    }
+
+    /// Whether `minute` (after midnight) falls inside a window; starts are inclusive, ends exclusive.
+    pub fn contains(&self, minute: u32) -> bool {
+        self.windows.iter().any(|&(start, end)| {
+            if start < end {
+                (start..end).contains(&minute)
+            } else {
+                minute >= start || minute < end
+            }
+        })
+    }
+
+    pub fn active_now(&self) -> bool {
+        self.contains(local_minute_of_day())
+    }
+}
+
+/// Minutes since local midnight.
+///
+/// Only where the C types are known: a 64-bit `time_t` on 64-bit Linux, Android, macOS and
+/// the BSDs. Anywhere else, 32-bit targets included, the windows are read in UTC.
+#[cfg(all(
+    target_pointer_width = "64",
+    any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    )
+))]
+pub fn local_minute_of_day() -> u32 {
+    use std::ffi::{c_char, c_int, c_long};
+
+    // `struct tm` as glibc, musl, bionic and the BSDs lay it out, with spare room at the end
+    // so a libc that appends fields of its own still writes inside it
+    #[repr(C)]
+    struct Tm {
+        sec: c_int,
+        min: c_int,
+        hour: c_int,
+        mday: c_int,
+        mon: c_int,
+        year: c_int,
+        wday: c_int,
+        yday: c_int,
+        isdst: c_int,
+        gmtoff: c_long,
+        zone: *const c_char,
+        spare: [u64; 8],
+    }
+    // `time_t` is 64 bits on every target this is compiled for
+    unsafe extern "C" {
+        fn localtime_r(t: *const i64, tm: *mut Tm) -> *mut Tm;
+    }
+
+    let mut tm: Tm = unsafe { std::mem::zeroed() };
+    let now = crate::unix_now();
+    if unsafe { localtime_r(&now, &mut tm) }.is_null() {
+        return utc_minute_of_day();
+    }
+    (tm.hour * 60 + tm.min) as u32
+}
+
+#[cfg(not(all(
+    target_pointer_width = "64",
+    any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    )
+)))]
+pub fn local_minute_of_day() -> u32 {
+    utc_minute_of_day()
+}
+
+fn utc_minute_of_day() -> u32 {
+    (crate::unix_now().rem_euclid(86400) / 60) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(s: &str) -> Schedule {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn windows() {
+        let day = schedule("09:30-17:00");
+        assert!(!day.contains(9 * 60 + 29));
+        assert!(day.contains(9 * 60 + 30));
+        assert!(day.contains(16 * 60 + 59));
+        assert!(!day.contains(17 * 60));
+    }
+
+    #[test]
+    fn windows_past_midnight() {
+        let night = schedule("22:00-07:00");
+        assert!(night.contains(22 * 60));
+        assert!(night.contains(0));
+        assert!(night.contains(6 * 60 + 59));
+        assert!(!night.contains(7 * 60));
+        assert!(!night.contains(21 * 60 + 59));
+        let until_midnight = schedule("23:00-24:00");
+        assert!(until_midnight.contains(23 * 60 + 59));
+        assert!(!until_midnight.contains(0));
+        let from_midnight = schedule("00:00-01:00, 12:00 - 12:01");
+        assert!(from_midnight.contains(0) && from_midnight.contains(12 * 60) && !from_midnight.contains(12 * 60 + 1));
+        assert_eq!(from_midnight.starts().collect::<Vec<_>>(), [0, 12 * 60]);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        assert_eq!(schedule("9:05-17:00, 22:00-7:00").to_string(), "09:05-17:00,22:00-07:00");
+        assert_eq!(schedule("00:00-24:00").to_string(), "00:00-24:00");
+    }
+
+    #[test]
+    fn errors() {
+        for (s, error) in [
+            ("", "window \"\" should look like 22:00-07:00"),
+            ("22:00", "window \"22:00\" should look like 22:00-07:00"),
+            ("2200-0700", "expected HH:MM, got \"2200\""),
+            ("24:01-01:00", "\"24:01\" is not a time between 00:00 and 24:00"),
+            ("22:60-01:00", "\"22:60\" is not a time between 00:00 and 24:00"),
+            ("-1:00-02:00", "expected HH:MM, got \"\""),
+            ("08:00-08:00", "window \"08:00-08:00\" is empty"),
+            ("08:00-09:00,", "window \"\" should look like 22:00-07:00"),
+        ] {
+            assert_eq!(s.parse::<Schedule>().unwrap_err(), error, "{}", s);
+        }
+    }
+
+    #[test]
+    fn utc_is_a_minute_of_the_day() {
+        assert!(utc_minute_of_day() < 24 * 60);
+        assert!(local_minute_of_day() < 24 * 60);
+    }
+}
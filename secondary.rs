@@ -0,0 +1,132 @@
+//! `--secondary DIFFICULTY=FILE`: easier difficulties checked in the same pass over each
+//! hash as the real one, e.g. a pool's share difficulty or a near-miss log, each match
+//! written as a JSON line to its own FILE (`-` for stderr). A hash first has to pass the
+//! requirements every secondary difficulty shares, so the ones that pass none cost a single
+//! comparison however many are given. The solution counts too, as it meets them all; the
+//! matches are not recomputed by --verify, so a wrong hash from the backend can show up here.
+
+use crate::{format_mask, hash_structure_good, json_string, params, parse_mask, to_hex, unix_now, Mask};
+use std::fs::File;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, Debug)]
+pub struct Spec {
+    mask: Mask,
+    path: String,
+}
+
+pub fn parse_spec(s: &str) -> Result<Spec, String> {
+    let (difficulty, path) = s
+        .split_once('=')
+        .filter(|(_, path)| !path.is_empty())
+        .ok_or_else(|| format!("{:?} should look like DIFFICULTY=FILE", s))?;
+    let mask = parse_mask(params::hex_digits(difficulty)).map_err(|e| e.to_string())?;
+    Ok(Spec { mask, path: path.to_string() })
+}
+
+pub struct Secondary {
+    mask: Mask,
+    path: String,
+    sink: Mutex<Box<dyn Write + Send>>,
+    matches: AtomicU64,
+}
+
+impl Secondary {
+    pub fn open(spec: &Spec) -> Result<Secondary, String> {
+        let sink: Box<dyn Write + Send> = match spec.path.as_str() {
+            "-" => Box::new(std::io::stderr()),
+            path => {
+                Box::new(File::options().create(true).append(true).open(path).map_err(|e| format!("{}: {}", path, e))?)
+            }
+        };
+        Ok(Secondary { mask: spec.mask, path: spec.path.clone(), sink: Mutex::new(sink), matches: AtomicU64::new(0) })
+    }
+}
+
+/// The mask requiring only the zero bits every one of `secondaries` requires; a hash that
+/// fails it passes none of them.
+pub fn shared_mask(secondaries: &[Secondary]) -> Mask {
+    secondaries.iter().fold(0, |mask, s| mask | s.mask)
+}
+
+/// Write the hash of `nonce`, whose difficulty prefix is `prefix`, to the sink of every
+/// secondary difficulty it meets.
+pub fn check(secondaries: &[Secondary], prefix: &[u8], nonce: u64, hash: &[u8]) {
+    for secondary in secondaries.iter().filter(|s| hash_structure_good(prefix, s.mask)) {
+        secondary.matches.fetch_add(1, Ordering::Relaxed);
+        let line = format!(
+            "{{\"time\": {}, \"difficulty\": {}, \"nonce\": \"{:016x}\", \"hash\": \"{}\"}}\n",
+            unix_now(),
+            json_string(&format_mask(secondary.mask)),
+            nonce,
+            to_hex(hash)
+        );
+        if let Err(e) = secondary.sink.lock().unwrap().write_all(line.as_bytes()) {
+            eprintln!("secondary: failed to write to {}: {}", secondary.path, e);
+        }
+    }
+}
+
+/// How many hashes met each secondary difficulty, as printable lines.
+pub fn summary(secondaries: &[Secondary]) -> Vec<String> {
+    secondaries
+        .iter()
+        .map(|s| {
+            format!(
+                "secondary: {} hashes met {} (written to {})",
+                s.matches.load(Ordering::Relaxed),
+                format_mask(s.mask),
+                s.path
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn specs() {
+        let spec = parse_spec("0x00ffffff=shares.jsonl").unwrap();
+        assert_eq!((spec.mask, spec.path.as_str()), (parse_mask("00ffffff").unwrap(), "shares.jsonl"));
+        assert_eq!(parse_spec("00ffffff=a=b").unwrap().path, "a=b");
+        assert_eq!(parse_spec("00ffffff").unwrap_err(), "\"00ffffff\" should look like DIFFICULTY=FILE");
+        assert_eq!(parse_spec("00ffffff=").unwrap_err(), "\"00ffffff=\" should look like DIFFICULTY=FILE");
+        assert!(parse_spec("xyz=shares").is_err());
+    }
+
+    #[test]
+    fn matches_go_to_their_files() {
+        let dir = crate::scratch_dir("secondary");
+        let (near, far) = (dir.join("near"), dir.join("far"));
+        let specs = [format!("000fffff={}", near.display()), format!("0fffffff={}", far.display())];
+        let secondaries: Vec<Secondary> =
+            specs.iter().map(|s| Secondary::open(&parse_spec(s).unwrap()).unwrap()).collect();
+        assert_eq!(shared_mask(&secondaries), parse_mask("0fffffff").unwrap());
+        check(&secondaries, &[0x00, 0x01], 1, &[0x00, 0x01, 0xaa]);
+        check(&secondaries, &[0x01, 0xff], 2, &[0x01, 0xff]);
+        check(&secondaries, &[0x10], 3, &[0x10]);
+        let lines =
+            |path: &Path| std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect::<Vec<_>>();
+        let near_lines = lines(&near);
+        assert_eq!(near_lines.len(), 1);
+        let line = crate::json::parse(&near_lines[0]).unwrap();
+        assert_eq!(line.get("nonce").and_then(crate::json::Json::as_str), Some("0000000000000001"));
+        assert_eq!(line.get("hash").and_then(crate::json::Json::as_str), Some("0001aa"));
+        assert_eq!(line.get("difficulty").and_then(crate::json::Json::as_str), Some("000FFFFF"));
+        assert_eq!(lines(&far).len(), 2);
+        assert_eq!(
+            summary(&secondaries),
+            [
+                format!("secondary: 1 hashes met 000FFFFF (written to {})", near.display()),
+                format!("secondary: 2 hashes met 0FFFFFFF (written to {})", far.display()),
+            ]
+        );
+        assert!(Secondary::open(&parse_spec(&format!("0fffffff={}", dir.join("no/such").display())).unwrap()).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,232 @@
+//! `--stats-shm FILE`: live stats in a 64-byte file mapped into memory, for native GUI
+//! frontends that redraw at 60 fps and would rather read a few words than poll a socket.
+//! Put the file on a RAM-backed filesystem (/dev/shm on Linux) and map it read-only.
+//!
+//! Layout, native byte order, every field naturally aligned:
+//!
+//!   offset  type  field
+//!        0  u32   magic, "PCST" as bytes
+//!        4  u32   layout version, `VERSION`
+//!        8  u64   sequence: odd while the miner is writing
+//!       16  u64   hashes so far
+//!       24  f64   hashes per second over the last second
+//!       32  u64   milliseconds since the search started
+//!       40  u64   Unix time of this update, in milliseconds
+//!       48  u32   state: 0 starting, 1 mining, 2 paused, 3 solved, 4 stopped, 5 failed
+//!       52  u32   worker threads
+//!       56  u32   process id
+//!       60  u32   reserved, 0
+//!
+//! To read a consistent snapshot, load the sequence, copy the fields, and load the sequence
+//! again; retry if it was odd or changed. The miner rewrites the block every
+//! `UPDATE_INTERVAL` and leaves the final state behind when it exits.
+
+use crate::{wait_for_stop, Progress};
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{fence, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub const SIZE: usize = 64;
+pub const VERSION: u32 = 1;
+const MAGIC: [u8; 4] = *b"PCST";
+/// Faster than a 60 Hz frame
+pub const UPDATE_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Starting = 0,
+    Mining = 1,
+    Paused = 2,
+    Solved = 3,
+    Stopped = 4,
+    Failed = 5,
+}
+
+pub struct Stats {
+    base: *mut u8,
+    _file: File,
+}
+
+// Every access goes through atomics in the mapping, which lives as long as `Stats`
+unsafe impl Send for Stats {}
+unsafe impl Sync for Stats {}
+
+impl Stats {
+    pub fn create(path: &Path, threads: usize) -> std::io::Result<Stats> {
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        file.set_len(SIZE as u64)?;
+        let stats = Stats { base: map(&file)?, _file: file };
+        stats.u32(4).store(VERSION, Ordering::Relaxed);
+        stats.u32(52).store(threads as u32, Ordering::Relaxed);
+        stats.u32(56).store(std::process::id(), Ordering::Relaxed);
+        stats.u32(60).store(0, Ordering::Relaxed);
+        stats.update(0, 0.0, Duration::ZERO, State::Starting);
+        stats.u32(0).store(u32::from_ne_bytes(MAGIC), Ordering::Release);
+        Ok(stats)
+    }
+
+    fn u32(&self, offset: usize) -> &AtomicU32 {
+        unsafe { &*(self.base.add(offset) as *const AtomicU32) }
+    }
+
+    fn u64(&self, offset: usize) -> &AtomicU64 {
+        unsafe { &*(self.base.add(offset) as *const AtomicU64) }
+    }
+
+    pub fn update(&self, hashes: u64, hashrate: f64, elapsed: Duration, state: State) {
+        let sequence = self.u64(8);
+        let next = sequence.load(Ordering::Relaxed) | 1;
+        sequence.store(next, Ordering::Relaxed);
+        fence(Ordering::Release);
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        self.u64(16).store(hashes, Ordering::Relaxed);
+        self.u64(24).store(hashrate.to_bits(), Ordering::Relaxed);
+        self.u64(32).store(elapsed.as_millis() as u64, Ordering::Relaxed);
+        self.u64(40).store(now.as_millis() as u64, Ordering::Relaxed);
+        self.u32(48).store(state as u32, Ordering::Relaxed);
+        sequence.store(next.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Rewrite the block from `progress` every `UPDATE_INTERVAL` until the search stops.
+    pub fn follow(&self, progress: &Progress, started: Instant) {
+        let (mut sampled, mut sampled_hashes, mut hashrate) = (progress.awake(started), progress.hashes(), 0.0);
+        while !wait_for_stop(&progress.stop, UPDATE_INTERVAL) {
+            let (now, hashes) = (progress.awake(started), progress.hashes());
+            if now.saturating_sub(sampled) >= Duration::from_secs(1) {
+                hashrate = (hashes - sampled_hashes) as f64 / now.saturating_sub(sampled).as_secs_f64();
+                (sampled, sampled_hashes) = (now, hashes);
+            }
+            let state = if progress.paused.load(Ordering::Relaxed) { State::Paused } else { State::Mining };
+            self.update(hashes, hashrate, now, state);
+        }
+    }
+
+    /// The state the block is left in once the search is over.
+    pub fn finish(&self, progress: &Progress, started: Instant, state: State) {
+        self.update(progress.hashes(), 0.0, progress.awake(started), state);
+    }
+}
+
+#[cfg(unix)]
+fn map(file: &File) -> std::io::Result<*mut u8> {
+    use std::ffi::{c_int, c_long, c_void};
+    use std::os::unix::io::AsRawFd;
+
+    unsafe extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: c_long) -> *mut c_void;
+    }
+    const PROT_READ_WRITE: c_int = 3;
+    const MAP_SHARED: c_int = 1;
+
+    let base = unsafe { mmap(std::ptr::null_mut(), SIZE, PROT_READ_WRITE, MAP_SHARED, file.as_raw_fd(), 0) };
+    if base as isize == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(base as *mut u8)
+}
+
+#[cfg(windows)]
+fn map(file: &File) -> std::io::Result<*mut u8> {
+    use std::ffi::c_void;
+    use std::os::windows::io::AsRawHandle;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn CreateFileMappingW(
+            file: *mut c_void,
+            attributes: *mut c_void,
+            protect: u32,
+            size_high: u32,
+            size_low: u32,
+            name: *const u16,
+        ) -> *mut c_void;
+        fn MapViewOfFile(
+            mapping: *mut c_void,
+            access: u32,
+            offset_high: u32,
+            offset_low: u32,
+            len: usize,
+        ) -> *mut c_void;
+    }
+    const PAGE_READWRITE: u32 = 4;
+    const FILE_MAP_WRITE: u32 = 2;
+
+    let mapping = unsafe {
+        CreateFileMappingW(file.as_raw_handle(), std::ptr::null_mut(), PAGE_READWRITE, 0, SIZE as u32, std::ptr::null())
+    };
+    if mapping.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    // The view keeps the mapping alive for the rest of the process
+    let base = unsafe { MapViewOfFile(mapping, FILE_MAP_WRITE, 0, 0, SIZE) };
+    if base.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(base as *mut u8)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn map(_: &File) -> std::io::Result<*mut u8> {
+    Err(std::io::Error::other("shared memory stats are not supported on this system"))
+}
+
+#[cfg(all(test, any(unix, windows)))]
+mod tests {
+    use super::*;
+
+    fn field<const N: usize>(block: &[u8], offset: usize) -> [u8; N] {
+        block[offset..offset + N].try_into().unwrap()
+    }
+
+    #[test]
+    fn the_block_layout() {
+        let dir = crate::scratch_dir("shm");
+        let path = dir.join("stats");
+        std::fs::write(&path, [0xaa; 100]).unwrap();
+        let stats = Stats::create(&path, 8).unwrap();
+        let block = std::fs::read(&path).unwrap();
+        assert_eq!(block.len(), SIZE);
+        assert_eq!(block[..4], MAGIC);
+        assert_eq!(u32::from_ne_bytes(field(&block, 4)), VERSION);
+        assert_eq!(u64::from_ne_bytes(field(&block, 8)) % 2, 0);
+        assert_eq!(u32::from_ne_bytes(field(&block, 48)), State::Starting as u32);
+        assert_eq!(u32::from_ne_bytes(field(&block, 52)), 8);
+        assert_eq!(u32::from_ne_bytes(field(&block, 56)), std::process::id());
+        assert_eq!(block[60..], [0; 4]);
+
+        let sequence = u64::from_ne_bytes(field(&block, 8));
+        stats.update(1 << 40, 1234.5, Duration::from_millis(2500), State::Solved);
+        let block = std::fs::read(&path).unwrap();
+        assert_eq!(u64::from_ne_bytes(field(&block, 8)), sequence + 2);
+        assert_eq!(u64::from_ne_bytes(field(&block, 16)), 1 << 40);
+        assert_eq!(f64::from_ne_bytes(field(&block, 24)), 1234.5);
+        assert_eq!(u64::from_ne_bytes(field(&block, 32)), 2500);
+        assert!(u64::from_ne_bytes(field(&block, 40)) > 1_700_000_000_000);
+        assert_eq!(u32::from_ne_bytes(field(&block, 48)), State::Solved as u32);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn following_a_search() {
+        let dir = crate::scratch_dir("shm-follow");
+        let path = dir.join("stats");
+        let stats = Stats::create(&path, 1).unwrap();
+        let progress = Progress::new(1);
+        progress.workers[0].hashes.store(99, Ordering::Relaxed);
+        progress.paused.store(true, Ordering::Relaxed);
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| stats.follow(&progress, started));
+            while u32::from_ne_bytes(field(&std::fs::read(&path).unwrap(), 48)) != State::Paused as u32 {
+                std::thread::sleep(UPDATE_INTERVAL);
+            }
+            progress.stop.store(true, Ordering::Release);
+        });
+        stats.finish(&progress, started, State::Stopped);
+        let block = std::fs::read(&path).unwrap();
+        assert_eq!(u64::from_ne_bytes(field(&block, 16)), 99);
+        assert_eq!(u32::from_ne_bytes(field(&block, 48)), State::Stopped as u32);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,96 @@
+//! `--simulate HASHES` (hidden): a harness for testing the daemon's scheduling, cancellation
+//! and submission, and the same logic in a normal run, quickly and the same way every time.
+//! The hash backend is replaced by a fake that meets any difficulty for about one preimage in
+//! HASHES and misses it for the rest. Which ones comes from the preimage bytes alone, so a
+//! run with the same challenge and threads finds the same nonce again, and the verification
+//! pass agrees with the workers.
+//!
+//! The clock is virtual as well: every fake hash moves it on by 1/`--simulate-hashrate`
+//! seconds, starting from the real time the process started. Window checks read it through
+//! `unix_now`, and `wait_for_stop` waits for it (or for as long in real time, whichever comes
+//! first, so waits that no hashing moves along still end), so a one-hour `no_pre_mine_hour`
+//! window closes after a fixed number of hashes rather than after an hour.
+
+use crate::{splitmix64, Backend, HashFn};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_HASHRATE: f64 = 1e6;
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+pub const BACKEND: Backend = Backend { name: "simulated", hash, simd: None, supported: || true };
+
+/// (one solution in this many preimages, virtual hashes per second, real Unix time at start)
+static SIMULATION: OnceLock<(u64, f64, i64)> = OnceLock::new();
+static HASHES: AtomicU64 = AtomicU64::new(0);
+
+/// Simulate hashing and time for the rest of the process.
+pub fn set(one_in: u64, hashrate: f64) {
+    let _ = SIMULATION.set((one_in.max(1), hashrate, crate::unix_now()));
+}
+
+pub fn enabled() -> bool {
+    SIMULATION.get().is_some()
+}
+
+fn hash(preimage: &[u8], output: &mut [u8]) {
+    HASHES.fetch_add(1, Ordering::Relaxed);
+    let one_in = SIMULATION.get().map_or(1, |s| s.0);
+    let digest = preimage.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    output.fill(if splitmix64(digest).is_multiple_of(one_in) { 0 } else { 0xff });
+}
+
+/// The fake in place of `reference` while simulating, so a verification pass does not
+/// reject every fake solution as a false positive.
+pub fn reference(reference: HashFn) -> HashFn {
+    if enabled() {
+        hash
+    } else {
+        reference
+    }
+}
+
+fn virtual_secs(hashrate: f64) -> f64 {
+    HASHES.load(Ordering::Relaxed) as f64 / hashrate
+}
+
+/// The virtual Unix time, or `None` when not simulating.
+pub fn now() -> Option<i64> {
+    SIMULATION.get().map(|&(_, hashrate, start)| start + virtual_secs(hashrate) as i64)
+}
+
+/// `wait_for_stop` on the virtual clock, or `None` when not simulating.
+pub fn wait(stop: &AtomicBool, duration: Duration) -> Option<bool> {
+    let &(_, hashrate, _) = SIMULATION.get()?;
+    let (deadline, real_deadline) = (virtual_secs(hashrate) + duration.as_secs_f64(), Instant::now() + duration);
+    while !stop.load(Ordering::Acquire) {
+        if virtual_secs(hashrate) >= deadline || Instant::now() >= real_deadline {
+            return Some(false);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Some(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `set` is for the whole process and would fake every other test's hashing, so these
+    // only check that nothing changes without it
+    #[test]
+    fn off_unless_set() {
+        assert!(!enabled());
+        assert_eq!(now(), None);
+        assert_eq!(wait(&AtomicBool::new(false), Duration::from_secs(3600)), None);
+        let (mut real, mut passed) = ([0; 32], [0; 32]);
+        crate::hash_preimage(b"preimage", &mut real);
+        reference(crate::hash_preimage)(b"preimage", &mut passed);
+        assert_eq!(passed, real);
+        // Without a rate every preimage is a solution
+        let mut output = [0x55; 8];
+        hash(b"preimage", &mut output);
+        assert_eq!(output, [0; 8]);
+    }
+}
@@ -0,0 +1,148 @@
+//! `--solution-sink SINK`, or config `solution_sinks` (comma-separated): where a run's
+//! solution goes, as many places at once as are given:
+//!
+//!   stdout        the nonce on a line of its own, or the `solution` event with --machine
+//!   ws            the `solution` event to the --ws-listen clients
+//!   file:PATH     the `Solution` as a JSON line appended to PATH
+//!   webhook:URL   the `Solution` as JSON, POSTed to URL
+//!   submit:URL    the `encode-solution` payload, POSTed to URL, e.g. a pool's endpoint
+//!
+//! Without any, a run writes to stdout and ws as it always has. Each sink gets the solution
+//! on a thread of its own, so one that fails or is slow to answer only costs itself: its
+//! error is logged and the others deliver all the same. --on-solution stays apart from
+//! these, with the outbox and its retries behind it.
+
+use crate::solution::Solution;
+use crate::{http_post_json, machine, ws};
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sink {
+    Stdout,
+    Ws,
+    File(PathBuf),
+    Webhook(String),
+    Submit(String),
+}
+
+/// Where a solution goes without --solution-sink.
+pub const DEFAULT: &[Sink] = &[Sink::Stdout, Sink::Ws];
+
+pub fn parse(s: &str) -> Result<Sink, String> {
+    let (kind, target) = s.split_once(':').map_or((s, None), |(kind, target)| (kind, Some(target)));
+    match (kind, target.filter(|t| !t.is_empty())) {
+        ("stdout", None) => Ok(Sink::Stdout),
+        ("ws", None) => Ok(Sink::Ws),
+        ("file", Some(path)) => Ok(Sink::File(PathBuf::from(path))),
+        ("webhook", Some(url)) => Ok(Sink::Webhook(url.to_string())),
+        ("submit", Some(url)) => Ok(Sink::Submit(url.to_string())),
+        _ => Err(format!("{:?} should be stdout, ws, file:PATH, webhook:URL or submit:URL", s)),
+    }
+}
+
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sink::Stdout => write!(f, "stdout"),
+            Sink::Ws => write!(f, "ws"),
+            Sink::File(path) => write!(f, "file:{}", path.display()),
+            Sink::Webhook(url) => write!(f, "webhook:{}", url),
+            Sink::Submit(url) => write!(f, "submit:{}", url),
+        }
+    }
+}
+
+impl Sink {
+    pub fn needs_network(&self) -> bool {
+        matches!(self, Sink::Webhook(_) | Sink::Submit(_))
+    }
+
+    fn send(&self, solution: &Solution, event: &str, payload: Option<&str>) -> Result<(), String> {
+        match self {
+            Sink::Stdout if machine::enabled() => machine::print(event),
+            Sink::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                writeln!(stdout, "{}", solution.nonce).and_then(|_| stdout.flush()).map_err(|e| e.to_string())?;
+            }
+            Sink::Ws => ws::broadcast("solution", event),
+            Sink::File(path) => {
+                let file = std::fs::OpenOptions::new().create(true).append(true).open(path);
+                file.and_then(|mut f| writeln!(f, "{}", solution.to_json())).map_err(|e| e.to_string())?;
+            }
+            Sink::Webhook(url) => {
+                http_post_json(url, &solution.to_json()).map_err(|e| e.to_string())?;
+            }
+            Sink::Submit(url) => {
+                let payload = payload.ok_or("no submission payload for this solution")?;
+                http_post_json(url, payload).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Send `solution` to every one of `sinks` and wait until they are done; `payload` is what
+/// `submit` sinks POST.
+pub fn deliver(sinks: &[Sink], solution: &Solution, payload: Option<&str>) {
+    let event = machine::line("solution", &solution.fields());
+    std::thread::scope(|scope| {
+        let sending: Vec<_> =
+            sinks.iter().map(|sink| (sink, scope.spawn(|| sink.send(solution, &event, payload)))).collect();
+        for (sink, sent) in sending {
+            if let Err(e) = sent.join().unwrap_or_else(|_| Err("panicked".to_string())) {
+                eprintln!("sink: {}: {}", sink, e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sink_names() {
+        for name in [
+            "stdout",
+            "ws",
+            "file:/var/lib/portocripto/solutions.jsonl",
+            "webhook:http://hooks/x",
+            "submit:http://pool:8080/s",
+        ] {
+            assert_eq!(parse(name).unwrap().to_string(), name);
+        }
+        assert_eq!(parse("file:a:b"), Ok(Sink::File(PathBuf::from("a:b"))));
+        for bad in ["", "file", "file:", "stdout:x", "webhook", "email:me"] {
+            assert_eq!(
+                parse(bad).unwrap_err(),
+                format!("{:?} should be stdout, ws, file:PATH, webhook:URL or submit:URL", bad)
+            );
+        }
+        assert!(parse("submit:http://pool").unwrap().needs_network());
+        assert!(!DEFAULT.iter().any(Sink::needs_network));
+    }
+
+    #[test]
+    fn failing_sinks_do_not_stop_the_others() {
+        let dir = crate::scratch_dir("sink");
+        let (first, second) = (dir.join("first.jsonl"), dir.join("second.jsonl"));
+        let solution = Solution::new(crate::NonceEncoding::Decimal, 6166, &[0, 0x0f], "**D07", "addr_test1");
+        let sinks = [
+            Sink::File(first.clone()),
+            Sink::File(dir.join("no/such/dir")),
+            Sink::Submit("http://pool".into()),
+            Sink::File(second.clone()),
+        ];
+        assert_eq!(sinks[2].send(&solution, "", None).unwrap_err(), "no submission payload for this solution");
+        deliver(&sinks, &solution, None);
+        deliver(&sinks[..1], &solution, None);
+        for (path, lines) in [(first, 2), (second, 1)] {
+            let text = std::fs::read_to_string(path).unwrap();
+            assert_eq!(text.lines().count(), lines);
+            assert!(text.lines().all(|line| Solution::parse(line).is_ok_and(|s| s.nonce == "6166")));
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,155 @@
+//! The record of a found solution everything downstream of the search shares: the
+//! --on-solution placeholders, the outbox files, the `solution` machine event, receipts and
+//! the daemon's submissions all take a `Solution` rather than a bare nonce string. It goes
+//! to and from JSON through `to_json` and `from_json` on the crate's own `json` module, not
+//! serde: the tree ships no manifest, and the crate it is built in provides only blake2, clap
+//! and rayon, so a serde derive would not compile there. The keys are what serde would emit.
+//!
+//! The provenance fields (the `identity` of the miner, the worker thread and how many
+//! hashes that thread had done) are what a fleet splitting rewards by work needs. Outbox
+//! files saved before the timestamps, backend and provenance were recorded still load, with
+//! those left out.
+
+use crate::json::{self, Json};
+use crate::{json_string, to_hex, NonceEncoding};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    /// As submitted, in the challenge's nonce encoding
+    pub nonce: String,
+    pub hash: String,
+    pub challenge_id: String,
+    pub address: String,
+    pub found: Option<SystemTime>,
+    /// When --on-solution accepted it
+    pub submitted: Option<SystemTime>,
+    pub backend: Option<String>,
+    pub worker_id: Option<String>,
+    pub thread: Option<usize>,
+    /// Hashes the thread had computed when it found this one, including it
+    pub worker_hashes: Option<u64>,
+}
+
+pub fn unix_secs(t: SystemTime) -> f64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+impl Solution {
+    /// `nonce`, whose hash is `hash`, for `challenge_id` and `address`; the timestamps and
+    /// backend and provenance are left for the caller.
+    pub fn new(encoding: NonceEncoding, nonce: u64, hash: &[u8], challenge_id: &str, address: &str) -> Solution {
+        Solution {
+            nonce: encoding.text(nonce),
+            hash: to_hex(hash),
+            challenge_id: challenge_id.to_string(),
+            address: address.to_string(),
+            found: None,
+            submitted: None,
+            backend: None,
+            worker_id: None,
+            thread: None,
+            worker_hashes: None,
+        }
+    }
+
+    /// The values of the `--on-solution` placeholders.
+    pub fn placeholders(&self) -> [(&str, &str); 4] {
+        [("nonce", &self.nonce), ("hash", &self.hash), ("address", &self.address), ("challenge_id", &self.challenge_id)]
+    }
+
+    /// Keys and JSON values, in the form `machine::emit` takes.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let time = |t: Option<SystemTime>| t.map_or("null".to_string(), |t| format!("{:.3}", unix_secs(t)));
+        vec![
+            ("nonce", json_string(&self.nonce)),
+            ("hash", json_string(&self.hash)),
+            ("challenge_id", json_string(&self.challenge_id)),
+            ("address", json_string(&self.address)),
+            ("found_unix", time(self.found)),
+            ("submitted_unix", time(self.submitted)),
+            ("backend", self.backend.as_deref().map_or("null".to_string(), json_string)),
+            ("worker_id", self.worker_id.as_deref().map_or("null".to_string(), json_string)),
+            ("thread", self.thread.map_or("null".to_string(), |t| t.to_string())),
+            ("worker_hashes", self.worker_hashes.map_or("null".to_string(), |h| h.to_string())),
+        ]
+    }
+
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self.fields().iter().map(|(k, v)| format!("{}: {}", json_string(k), v)).collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    pub fn from_json(value: &Json) -> Result<Solution, String> {
+        let field = |key: &str| value.get(key).and_then(Json::as_str).map(str::to_string).ok_or(format!("no {}", key));
+        let time = |key: &str| {
+            // Written to the millisecond
+            let ms = value.get(key).and_then(Json::as_f64).filter(|t| *t >= 0.0).map(|t| (t * 1000.0).round());
+            ms.map(|ms| UNIX_EPOCH + Duration::from_millis(ms as u64))
+        };
+        Ok(Solution {
+            nonce: field("nonce")?,
+            hash: field("hash")?,
+            challenge_id: field("challenge_id")?,
+            address: field("address")?,
+            found: time("found_unix"),
+            submitted: time("submitted_unix"),
+            backend: value.get("backend").and_then(Json::as_str).map(str::to_string),
+            worker_id: value.get("worker_id").and_then(Json::as_str).map(str::to_string),
+            thread: value.get("thread").and_then(Json::as_f64).map(|t| t as usize),
+            worker_hashes: value.get("worker_hashes").and_then(Json::as_f64).map(|h| h as u64),
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Solution, String> {
+        Solution::from_json(&json::parse(text)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solution() -> Solution {
+        Solution::new(NonceEncoding::Decimal, 6166, &[0, 0x0f], "**D07", "addr_test1")
+    }
+
+    #[test]
+    fn placeholders_and_fields() {
+        let solution = solution();
+        assert_eq!(
+            solution.placeholders(),
+            [("nonce", "6166"), ("hash", "000f"), ("address", "addr_test1"), ("challenge_id", "**D07")]
+        );
+        assert_eq!(
+            solution.to_json(),
+            "{\"nonce\": \"6166\", \"hash\": \"000f\", \"challenge_id\": \"**D07\", \"address\": \"addr_test1\", \
+             \"found_unix\": null, \"submitted_unix\": null, \"backend\": null, \"worker_id\": null, \"thread\": null, \
+             \"worker_hashes\": null}"
+        );
+    }
+
+    #[test]
+    fn timestamps_keep_milliseconds() {
+        let found = UNIX_EPOCH + Duration::from_micros(1_761_000_000_123_400);
+        let solution = Solution { found: Some(found), ..solution() };
+        let parsed = Solution::parse(&solution.to_json()).unwrap();
+        assert_eq!(parsed.found, Some(UNIX_EPOCH + Duration::from_millis(1_761_000_000_123)));
+        assert_eq!(unix_secs(UNIX_EPOCH - Duration::from_secs(1)), 0.0);
+    }
+
+    #[test]
+    fn malformed_records() {
+        let base = r#""nonce": "1", "hash": "ab", "address": "a", "challenge_id": "b""#;
+        let parse = |extra: &str| Solution::parse(&format!("{{{}{}}}", base, extra)).unwrap();
+        assert_eq!(parse(r#", "found_unix": -5, "thread": "3""#).found, None);
+        assert_eq!(parse(r#", "thread": "3", "backend": 1"#).thread, None);
+        assert_eq!(parse(r#", "worker_hashes": 1e3"#).worker_hashes, Some(1000));
+        assert_eq!(
+            Solution::parse(r#"{"nonce": 1, "hash": "ab", "address": "a", "challenge_id": "b"}"#),
+            Err("no nonce".into())
+        );
+        assert_eq!(Solution::parse("[]"), Err("no nonce".into()));
+        assert!(Solution::parse("{").unwrap_err().contains("at byte 1"));
+    }
+}
@@ -0,0 +1,61 @@
+//! `--spec-version N`: the revisions of the challenge protocol this miner knows, each a set
+//! of layout flags (how the preimage is written and how the difficulty is read) filled in
+//! for whatever the command line leaves out. The challenge endpoint's `spec_version`, read
+//! through --args-file or --watch, selects one without a flag, so a protocol upgrade only
+//! needs a new row in `SPECS` rather than every miner hand-crafting the new layout.
+
+pub struct Spec {
+    pub version: u32,
+    pub description: &'static str,
+    /// Flags and the values the revision gives them
+    pub flags: &'static [(&'static str, &'static str)],
+}
+
+pub const SPECS: &[Spec] = &[Spec {
+    version: 1,
+    description: "hex16 nonce, fields as given, difficulty mask over the leading bytes of a 32-byte hash",
+    flags: &[
+        ("--nonce-encoding", "hex16"),
+        ("--hex-case", "as-is"),
+        ("--hash-len", "32"),
+        ("--prefix-offset", "0"),
+        ("--prefix-endian", "big"),
+    ],
+}];
+
+pub fn find(version: &str) -> Result<&'static Spec, String> {
+    let known: Vec<String> = SPECS.iter().map(|s| s.version.to_string()).collect();
+    let number = version.trim().parse::<u32>().ok();
+    SPECS.iter().find(|s| Some(s.version) == number).ok_or_else(|| {
+        format!(
+            "unknown spec version {:?} (this build knows {}); upgrade portocripto, or give the layout flags \
+             (--nonce-encoding, --hex-case, --salt, ...) yourself",
+            version,
+            known.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_versions() {
+        assert_eq!(find(" 1\n").unwrap().version, 1);
+        let error = find("2").err().unwrap();
+        assert!(error.starts_with("unknown spec version \"2\" (this build knows 1);"), "{}", error);
+        assert!(find("one").is_err() && find("").is_err());
+    }
+
+    #[test]
+    fn revisions_are_distinct_and_use_real_flags() {
+        let versions: Vec<u32> = SPECS.iter().map(|s| s.version).collect();
+        assert!(versions.windows(2).all(|w| w[0] < w[1]), "{:?}", versions);
+        for spec in SPECS {
+            for (flag, _) in spec.flags {
+                assert!(flag.starts_with("--") && !flag.contains('='), "spec {}: {}", spec.version, flag);
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Long-term run statistics rendered from `--report-json` files, which are the only history
+//! portocripto keeps. The export is one HTML file with inline SVG, so it opens offline.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// What one run report contributes to the charts.
+pub struct Run {
+    /// Unix seconds when the run ended
+    pub finished: i64,
+    pub challenge_id: String,
+    pub hashes: u64,
+    pub secs: f64,
+    pub hashrate: f64,
+    pub solved: bool,
+}
+
+const WIDTH: f64 = 760.0;
+const HEIGHT: f64 = 220.0;
+const MARGIN: f64 = 40.0;
+
+fn date(unix: i64) -> String {
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm), in UTC
+    let days = unix.div_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn svg_open(out: &mut String, title: &str, y_max: f64) {
+    let _ = write!(
+        out,
+        "<h2>{}</h2>\n<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#888\"/>\n\
+         <line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\" stroke=\"#888\"/>\n\
+         <text x=\"2\" y=\"{t}\" font-size=\"11\">{y_max:.0}</text>\n\
+         <text x=\"2\" y=\"{b}\" font-size=\"11\">0</text>\n",
+        title,
+        w = WIDTH,
+        h = HEIGHT + MARGIN,
+        m = MARGIN,
+        r = WIDTH - 10.0,
+        b = HEIGHT,
+        t = MARGIN + 4.0,
+    );
+}
+
+/// Hashrate of every run over time, solutions per (UTC) day, and what each challenge round
+/// (runs with the same challenge_id) cost.
+pub fn html(runs: &[Run]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>portocripto stats</title>\n\
+         <style>body{font-family:sans-serif;margin:2em}svg{background:#fafafa}</style></head><body>\n\
+         <h1>portocripto stats</h1>\n",
+    );
+    let solved = runs.iter().filter(|r| r.solved).count();
+    let _ = writeln!(out, "<p>{} runs, {} solutions</p>", runs.len(), solved);
+    if runs.is_empty() {
+        out.push_str("</body></html>\n");
+        return out;
+    }
+
+    let (first, last) = (runs[0].finished, runs[runs.len() - 1].finished);
+    let span = (last - first).max(1) as f64;
+    let max_rate = runs.iter().map(|r| r.hashrate).fold(0.0, f64::max).max(1.0);
+    let plot = WIDTH - MARGIN - 10.0;
+    let x = |t: i64| MARGIN + (t - first) as f64 / span * plot;
+    let y = |v: f64, max: f64| HEIGHT - v / max * (HEIGHT - MARGIN);
+
+    svg_open(&mut out, "Hashrate (hashes/s)", max_rate);
+    let points: Vec<String> =
+        runs.iter().map(|r| format!("{:.1},{:.1}", x(r.finished), y(r.hashrate, max_rate))).collect();
+    let _ =
+        writeln!(out, "<polyline fill=\"none\" stroke=\"#2a6\" stroke-width=\"1.5\" points=\"{}\"/>", points.join(" "));
+    for r in runs.iter().filter(|r| r.solved) {
+        let _ = writeln!(
+            out,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#c33\"><title>solved {}</title></circle>",
+            x(r.finished),
+            y(r.hashrate, max_rate),
+            date(r.finished)
+        );
+    }
+    let _ = writeln!(
+        out,
+        "<text x=\"{}\" y=\"{}\" font-size=\"11\">{}</text><text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{}</text>\n</svg>",
+        MARGIN,
+        HEIGHT + 16.0,
+        date(first),
+        WIDTH - 10.0,
+        HEIGHT + 16.0,
+        date(last)
+    );
+
+    let mut per_day: BTreeMap<String, u64> = BTreeMap::new();
+    for r in runs {
+        *per_day.entry(date(r.finished)).or_default() += r.solved as u64;
+    }
+    let max_day = per_day.values().copied().max().unwrap_or(0).max(1) as f64;
+    svg_open(&mut out, "Solutions per day (UTC)", max_day);
+    let bar = plot / per_day.len() as f64;
+    for (i, (day, &count)) in per_day.iter().enumerate() {
+        let top = y(count as f64, max_day);
+        let _ = writeln!(
+            out,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#36c\"><title>{}: {}</title></rect>",
+            MARGIN + i as f64 * bar + 1.0,
+            top,
+            (bar - 2.0).max(1.0),
+            HEIGHT - top,
+            day,
+            count
+        );
+    }
+    out.push_str("</svg>\n");
+
+    // Rounds in the order they were last mined
+    let mut rounds: BTreeMap<&str, (i64, u64, u64, f64, u64)> = BTreeMap::new();
+    for r in runs {
+        let round = rounds.entry(&r.challenge_id).or_default();
+        *round = (r.finished, round.1 + 1, round.2 + r.hashes, round.3 + r.secs, round.4 + r.solved as u64);
+    }
+    let mut rounds: Vec<_> = rounds.into_iter().collect();
+    rounds.sort_by_key(|(_, round)| round.0);
+    out.push_str(
+        "<h2>Rounds</h2>\n<table border=\"1\" cellpadding=\"4\" style=\"border-collapse:collapse\">\n\
+         <tr><th>challenge</th><th>last run</th><th>runs</th><th>hashes</th><th>time</th><th>solutions</th></tr>\n",
+    );
+    for (challenge_id, (last, count, hashes, secs, solved)) in rounds {
+        let name: String = challenge_id
+            .chars()
+            .map(|c| match c {
+                '<' => "&lt;".to_string(),
+                '>' => "&gt;".to_string(),
+                '&' => "&amp;".to_string(),
+                c => c.to_string(),
+            })
+            .collect();
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            name,
+            date(last),
+            count,
+            hashes,
+            crate::format_duration(secs),
+            solved
+        );
+    }
+    out.push_str("</table>\n</body></html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(finished: i64, challenge_id: &str, solved: bool) -> Run {
+        Run { finished, challenge_id: challenge_id.into(), hashes: 1000, secs: 10.0, hashrate: 100.0, solved }
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(-1), "1969-12-31");
+        assert_eq!(date(1_761_000_000), "2025-10-20");
+    }
+
+    #[test]
+    fn no_runs() {
+        let page = html(&[]);
+        assert!(page.contains("<p>0 runs, 0 solutions</p>"));
+        assert!(!page.contains("<svg"));
+        assert!(page.ends_with("</body></html>\n"));
+    }
+
+    #[test]
+    fn runs_by_day_and_round() {
+        let day = 86_400;
+        let runs = [run(day, "<a&b>", false), run(day + 60, "<a&b>", true), run(3 * day, "c2", true)];
+        let page = html(&runs);
+        assert!(page.contains("<p>3 runs, 2 solutions</p>"));
+        assert_eq!(page.matches("<circle").count(), 2);
+        assert!(page.contains("<title>1970-01-02: 1</title>"));
+        assert!(page.contains("<title>1970-01-04: 1</title>"));
+        assert!(page.contains("<tr><td>&lt;a&amp;b&gt;</td><td>1970-01-02</td><td>2</td><td>2000</td>"));
+        assert!(page.find("&lt;a&amp;b&gt;").unwrap() < page.find("<td>c2</td>").unwrap());
+        assert!(!page.contains("<a&b>"));
+    }
+
+    #[test]
+    fn one_run_has_a_width() {
+        let page = html(&[run(0, "c", false)]);
+        assert!(page.contains("points=\"40.0,40.0\""), "{}", page);
+        assert!(!page.contains("NaN"));
+    }
+}
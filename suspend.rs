@@ -0,0 +1,78 @@
+//! Sleep and wake: a laptop whose lid closes mid-search stops hashing until it opens again,
+//! and the round may well have ended by then. A watcher compares a clock that keeps counting
+//! while the machine sleeps (the boot time clock on Linux, the wall clock elsewhere, where a
+//! clock step reads as a sleep too) with `Instant` every `TICK`; a gap of `MIN_GAP` or more
+//! is a sleep. On wake it is logged, the part of it `Instant` counted (all of it on Windows,
+//! none on Linux and macOS) goes to `Progress::slept` so that hashrates and uptimes leave it
+//! out, and the caller checks whether the challenge is still worth mining.
+
+use crate::{format_duration, wait_for_stop, Progress};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const TICK: Duration = Duration::from_secs(1);
+/// Shorter stalls are a loaded machine rather than a sleep
+const MIN_GAP: Duration = Duration::from_secs(15);
+
+#[cfg(target_os = "linux")]
+fn sleeping_clock() -> Duration {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok();
+    let secs = uptime.as_deref().and_then(|u| u.split_whitespace().next()?.parse::<f64>().ok());
+    secs.map_or_else(wall_clock, Duration::from_secs_f64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sleeping_clock() -> Duration {
+    wall_clock()
+}
+
+fn wall_clock() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Watch for the machine sleeping until the search stops, calling `wake` with the length of
+/// each sleep once it is over.
+pub fn watch(progress: &Progress, mut wake: impl FnMut(Duration)) {
+    let (mut ticked, mut clock) = (Instant::now(), sleeping_clock());
+    while !wait_for_stop(&progress.stop, TICK) {
+        let (now, now_clock) = (Instant::now(), sleeping_clock());
+        let counted = now - ticked;
+        let gap = counted.max(now_clock.saturating_sub(clock)).saturating_sub(TICK);
+        (ticked, clock) = (now, now_clock);
+        if gap < MIN_GAP {
+            continue;
+        }
+        if counted.saturating_sub(TICK) >= MIN_GAP {
+            progress.slept.fetch_add(counted.saturating_sub(TICK).as_nanos() as u64, Ordering::Relaxed);
+        }
+        eprintln!("suspend: the machine slept for {}; hashrates leave the gap out", format_duration(gap.as_secs_f64()));
+        wake(gap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clocks_move_forward() {
+        let (before, wall) = (sleeping_clock(), wall_clock());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(sleeping_clock() > before);
+        assert!(wall > Duration::from_secs(1_700_000_000));
+    }
+
+    #[test]
+    fn no_sleep_without_a_gap() {
+        let progress = Progress::new(1);
+        let mut woken = Vec::new();
+        std::thread::scope(|scope| {
+            let watching = scope.spawn(|| watch(&progress, |gap| woken.push(gap)));
+            std::thread::sleep(TICK + Duration::from_millis(200));
+            progress.stop.store(true, Ordering::Release);
+            watching.join().unwrap();
+        });
+        assert!(woken.is_empty());
+        assert_eq!(progress.slept.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,172 @@
+//! The preimage construction and the difficulty check on their own, with no allocation and
+//! no I/O; a fixed buffer in, a verdict out. The rest of the miner uses these through the
+//! crate root, and `preimage` writes the nonce with `nonce_bytes`, so the two cannot drift
+//! apart. Nothing builds this module without `std`.
+
+/// Difficulty mask over the first 16 hash bytes, most significant bit first: a cleared bit
+/// must be zero in the hash. Bits past the digits the challenge wrote are set (don't care).
+pub type Mask = u128;
+
+/// A full 256-bit target, big-endian: a hash passes when its first 32 bytes are <= it.
+pub type Target = [u8; 32];
+
+/// Which hash bytes the difficulty mask is compared against, and in which order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefixLayout {
+    pub offset: usize,
+    pub width: usize,
+    pub little_endian: bool,
+}
+
+impl PrefixLayout {
+    /// The first four bytes, big-endian, as the challenge originally defined it.
+    pub const DEFAULT: PrefixLayout = PrefixLayout { offset: 0, width: 4, little_endian: false };
+
+    /// The hash bytes the mask applies to, most significant first, ready for
+    /// `hash_structure_good`; bytes past the prefix or the hash read as set.
+    pub fn prefix(&self, hash: &[u8]) -> [u8; 16] {
+        let mut prefix = [0xffu8; 16];
+        let window = hash.get(self.offset..).unwrap_or(&[]);
+        if self.little_endian {
+            let width = self.width.min(window.len()).min(16);
+            for (dst, src) in prefix.iter_mut().zip(window[..width].iter().rev()) {
+                *dst = *src;
+            }
+        } else {
+            let len = window.len().min(16);
+            prefix[..len].copy_from_slice(&window[..len]);
+        }
+        prefix
+    }
+}
+
+pub fn hash_structure_good(hash: &[u8], difficulty_mask: Mask) -> bool {
+    // Bytes a short hash lacks count as set, so they fail wherever the mask requires zeros
+    let mut prefix = [0xffu8; 16];
+    let len = hash.len().min(16);
+    prefix[..len].copy_from_slice(&hash[..len]);
+
+    let hash_prefix = u128::from_be_bytes(prefix);
+    (hash_prefix & !difficulty_mask) == 0
+}
+
+/// Whether `hash` is within `target`; bytes a short hash lacks count as set, as in
+/// `hash_structure_good`.
+pub fn meets_target(hash: &[u8], target: Option<&Target>) -> bool {
+    let Some(target) = target else { return true };
+    let mut padded = [0xffu8; 32];
+    let len = hash.len().min(32);
+    padded[..len].copy_from_slice(&hash[..len]);
+    padded <= *target
+}
+
+/// Whether `hash` is a solution: the prefix `layout` reads out of it passes `difficulty_mask`
+/// and it is within `target`.
+pub fn accepts(hash: &[u8], difficulty_mask: Mask, layout: &PrefixLayout, target: Option<&Target>) -> bool {
+    hash_structure_good(&layout.prefix(hash), difficulty_mask) && meets_target(hash, target)
+}
+
+/// How the nonce opens the preimage, as `preimage::NonceEncoding` names them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nonce {
+    Hex16,
+    HexNopad,
+    Decimal,
+    RawBe,
+    RawLe,
+}
+
+/// The bytes `nonce` opens the preimage with, written to the end of `buffer`.
+pub fn nonce_bytes(encoding: Nonce, nonce: u64, buffer: &mut [u8; 20]) -> &[u8] {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let (base, min_digits) = match encoding {
+        Nonce::RawBe => {
+            buffer[12..].copy_from_slice(&nonce.to_be_bytes());
+            return &buffer[12..];
+        }
+        Nonce::RawLe => {
+            buffer[12..].copy_from_slice(&nonce.to_le_bytes());
+            return &buffer[12..];
+        }
+        Nonce::Hex16 => (16, 16),
+        Nonce::HexNopad => (16, 1),
+        Nonce::Decimal => (10, 1),
+    };
+    let (mut rest, mut start) = (nonce, buffer.len());
+    while rest > 0 || buffer.len() - start < min_digits {
+        start -= 1;
+        buffer[start] = HEX[(rest % base) as usize];
+        rest /= base;
+    }
+    &buffer[start..]
+}
+
+/// Write the preimage of `nonce`, followed by `suffix` (the challenge fields), to the start
+/// of `out`; returns its length, or `None` if `out` is too short for it.
+pub fn write_preimage(out: &mut [u8], encoding: Nonce, nonce: u64, suffix: &[u8]) -> Option<usize> {
+    let mut buffer = [0; 20];
+    let nonce = nonce_bytes(encoding, nonce, &mut buffer);
+    let out = out.get_mut(..nonce.len() + suffix.len())?;
+    out[..nonce.len()].copy_from_slice(nonce);
+    out[nonce.len()..].copy_from_slice(suffix);
+    Some(out.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes() {
+        let hash: Vec<u8> = (1..=20).collect();
+        let shifted = PrefixLayout { offset: 2, width: 4, little_endian: false };
+        assert_eq!(shifted.prefix(&hash)[..3], [3, 4, 5]);
+        let reversed = PrefixLayout { offset: 2, width: 4, little_endian: true };
+        assert_eq!(reversed.prefix(&hash)[..5], [6, 5, 4, 3, 0xff]);
+        let past_the_end = PrefixLayout { offset: 30, width: 4, little_endian: true };
+        assert_eq!(past_the_end.prefix(&hash), [0xff; 16]);
+        assert_eq!(PrefixLayout::DEFAULT.prefix(&[0; 2])[..3], [0, 0, 0xff]);
+    }
+
+    #[test]
+    fn short_hashes_count_as_set() {
+        assert!(hash_structure_good(&[0, 0], Mask::MAX >> 16));
+        assert!(!hash_structure_good(&[0, 0], Mask::MAX >> 17));
+        assert!(hash_structure_good(&[], Mask::MAX));
+        let mut target = [0u8; 32];
+        target[0] = 1;
+        assert!(meets_target(&[0, 0xff], Some(&target)));
+        assert!(!meets_target(&[1], Some(&target)));
+        assert!(meets_target(&[0xff; 32], None));
+        assert!(meets_target(&[0xff; 40], Some(&[0xff; 32])));
+    }
+
+    #[test]
+    fn accepts_needs_both() {
+        let target = [0x7f; 32];
+        assert!(accepts(&[0x0f; 32], Mask::MAX >> 4, &PrefixLayout::DEFAULT, Some(&target)));
+        assert!(!accepts(&[0x1f; 32], Mask::MAX >> 4, &PrefixLayout::DEFAULT, Some(&target)));
+        assert!(!accepts(&[0x80; 32], Mask::MAX, &PrefixLayout::DEFAULT, Some(&target)));
+    }
+
+    #[test]
+    fn nonce_digits() {
+        let mut buffer = [0; 20];
+        assert_eq!(nonce_bytes(Nonce::Hex16, 0, &mut buffer), b"0000000000000000");
+        assert_eq!(nonce_bytes(Nonce::HexNopad, 0, &mut buffer), b"0");
+        assert_eq!(nonce_bytes(Nonce::Decimal, 0, &mut buffer), b"0");
+        assert_eq!(nonce_bytes(Nonce::HexNopad, u64::MAX, &mut buffer), b"ffffffffffffffff");
+        assert_eq!(nonce_bytes(Nonce::Decimal, u64::MAX, &mut buffer), b"18446744073709551615");
+        assert_eq!(nonce_bytes(Nonce::RawBe, 0x0102, &mut buffer), [0, 0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(nonce_bytes(Nonce::RawLe, 0x0102, &mut buffer), [2, 1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn preimage_buffers() {
+        let mut out = [0u8; 4];
+        assert_eq!(write_preimage(&mut out, Nonce::Decimal, 12, b"ab"), Some(4));
+        assert_eq!(&out, b"12ab");
+        assert_eq!(write_preimage(&mut out, Nonce::Decimal, 123, b"ab"), None);
+        assert_eq!(write_preimage(&mut [], Nonce::RawBe, 0, b""), None);
+    }
+}
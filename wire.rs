@@ -0,0 +1,351 @@
+//! Binary protocol between `range-server` and its miners, for farms where the JSON/HTTP
+//! round trips would dominate the lease traffic. One TCP connection carries every request
+//! of a miner; a renewal is a 3-byte frame instead of a few hundred bytes of HTTP.
+//!
+//! The client opens with `MAGIC` and its `VERSION`; the server answers with the version it
+//! speaks and closes the connection if it does not speak the client's. Version 2 adds
+//! `Solved`; the server still speaks version 1 to older miners. `Release` needs no new
+//! version: a server that predates it drops the connection, and the lease expires as it
+//! would have. Then each frame is a LEB128 payload length followed by the payload: a kind
+//! byte, LEB128 integers and strings (a LEB128 length and UTF-8 bytes).
+
+use crate::{proxy, retry};
+use std::io::{BufReader, Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+/// Starts every binary connection; 0xFC never starts an HTTP request line.
+pub const MAGIC: [u8; 3] = [0xFC, b'P', b'C'];
+pub const VERSION: u8 = 2;
+const MAX_FRAME: u64 = 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    /// Sent by miners with their `identity`; empty from miners that predate it
+    Lease {
+        worker_id: String,
+        worker: String,
+    },
+    /// Also a heartbeat: the miner's hashes per second and the nonces of the lease it has
+    /// covered; both 0 from miners that predate them
+    Renew {
+        id: u64,
+        hashrate: u64,
+        covered: u64,
+    },
+    Done {
+        id: u64,
+        nonce: Option<u64>,
+    },
+    /// The miner is going away having searched `covered` nonces of the lease
+    Release {
+        id: u64,
+        covered: u64,
+    },
+    Leased {
+        id: u64,
+        start: u64,
+        end: u64,
+        expires_in: u64,
+    },
+    Ok,
+    UnknownLease,
+    Exhausted,
+    /// Another miner's `nonce` solved the challenge: stop, and do not submit a solution of
+    /// your own (version 2)
+    Solved {
+        nonce: u64,
+    },
+}
+
+fn put(mut n: u64, out: &mut Vec<u8>) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn get(bytes: &mut &[u8]) -> std::io::Result<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or_else(|| Error::new(ErrorKind::InvalidData, "truncated frame"))?;
+        *bytes = rest;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(Error::new(ErrorKind::InvalidData, "integer longer than 64 bits"))
+}
+
+fn put_str(s: &str, out: &mut Vec<u8>) {
+    put(s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn get_str(bytes: &mut &[u8]) -> std::io::Result<String> {
+    let len = get(bytes)? as usize;
+    let text = bytes.get(..len).ok_or_else(|| Error::new(ErrorKind::InvalidData, "truncated string"))?;
+    let text = String::from_utf8(text.to_vec()).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    *bytes = &bytes[len..];
+    Ok(text)
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match *self {
+            Message::Lease { ref worker_id, ref worker } => {
+                payload.push(0x01);
+                put_str(worker_id, &mut payload);
+                put_str(worker, &mut payload);
+            }
+            Message::Renew { id, hashrate, covered } => {
+                payload.push(0x02);
+                for n in [id, hashrate, covered] {
+                    put(n, &mut payload);
+                }
+            }
+            Message::Done { id, nonce } => {
+                payload.push(if nonce.is_some() { 0x04 } else { 0x03 });
+                put(id, &mut payload);
+                if let Some(nonce) = nonce {
+                    put(nonce, &mut payload);
+                }
+            }
+            Message::Release { id, covered } => {
+                payload.push(0x05);
+                put(id, &mut payload);
+                put(covered, &mut payload);
+            }
+            Message::Leased { id, start, end, expires_in } => {
+                payload.push(0x81);
+                for n in [id, start, end, expires_in] {
+                    put(n, &mut payload);
+                }
+            }
+            Message::Ok => payload.push(0x82),
+            Message::UnknownLease => payload.push(0x83),
+            Message::Exhausted => payload.push(0x84),
+            Message::Solved { nonce } => {
+                payload.push(0x85);
+                put(nonce, &mut payload);
+            }
+        }
+        let mut frame = Vec::with_capacity(payload.len() + 1);
+        put(payload.len() as u64, &mut frame);
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    fn decode(mut bytes: &[u8]) -> std::io::Result<Message> {
+        let (&kind, rest) = bytes.split_first().ok_or_else(|| Error::new(ErrorKind::InvalidData, "empty frame"))?;
+        bytes = rest;
+        let bytes = &mut bytes;
+        let message = match kind {
+            0x01 if bytes.is_empty() => Message::Lease { worker_id: String::new(), worker: String::new() },
+            0x01 => Message::Lease { worker_id: get_str(bytes)?, worker: get_str(bytes)? },
+            0x02 => {
+                let id = get(bytes)?;
+                if bytes.is_empty() {
+                    Message::Renew { id, hashrate: 0, covered: 0 }
+                } else {
+                    Message::Renew { id, hashrate: get(bytes)?, covered: get(bytes)? }
+                }
+            }
+            0x03 => Message::Done { id: get(bytes)?, nonce: None },
+            0x04 => Message::Done { id: get(bytes)?, nonce: Some(get(bytes)?) },
+            0x05 => Message::Release { id: get(bytes)?, covered: get(bytes)? },
+            0x81 => Message::Leased { id: get(bytes)?, start: get(bytes)?, end: get(bytes)?, expires_in: get(bytes)? },
+            0x82 => Message::Ok,
+            0x83 => Message::UnknownLease,
+            0x84 => Message::Exhausted,
+            0x85 => Message::Solved { nonce: get(bytes)? },
+            _ => return Err(Error::new(ErrorKind::InvalidData, format!("unknown frame kind {:#04x}", kind))),
+        };
+        if !bytes.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "trailing bytes in frame"));
+        }
+        Ok(message)
+    }
+}
+
+pub fn write_message(stream: &mut impl Write, message: &Message) -> std::io::Result<()> {
+    stream.write_all(&message.encode())
+}
+
+/// The next message, or `None` if the peer closed the connection between frames.
+pub fn read_message(stream: &mut impl Read) -> std::io::Result<Option<Message>> {
+    let mut len = 0u64;
+    for shift in (0..14).step_by(7) {
+        let mut byte = [0u8];
+        if stream.read(&mut byte)? == 0 {
+            return match shift {
+                0 => Ok(None),
+                _ => Err(Error::new(ErrorKind::UnexpectedEof, "connection closed inside a frame")),
+            };
+        }
+        len |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    if len > MAX_FRAME {
+        return Err(Error::new(ErrorKind::InvalidData, format!("frame of {} bytes", len)));
+    }
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    Message::decode(&payload).map(Some)
+}
+
+/// Server side of the handshake, after `MAGIC` was peeked: the version the client speaks,
+/// or `None` if this server does not speak it and the connection should be dropped.
+pub fn accept<S: Read + Write>(stream: &mut BufReader<S>) -> std::io::Result<Option<u8>> {
+    let mut hello = [0u8; 4];
+    stream.read_exact(&mut hello)?;
+    let version = Some(hello[3]).filter(|v| hello[..3] == MAGIC && (1..=VERSION).contains(v));
+    stream.get_mut().write_all(&[version.unwrap_or(VERSION)])?;
+    Ok(version)
+}
+
+/// A miner's connection to `tcp://host:port`.
+pub struct Client {
+    addr: String,
+    stream: Option<TcpStream>,
+}
+
+impl Client {
+    pub fn new(url: &str) -> std::io::Result<Client> {
+        let addr = url
+            .strip_prefix("tcp://")
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "only tcp:// URLs speak the binary protocol"))?;
+        Ok(Client { addr: addr.trim_end_matches('/').to_string(), stream: None })
+    }
+
+    fn connect(&self) -> std::io::Result<TcpStream> {
+        let mut stream = proxy::connect(&self.addr)?;
+        let mut hello = MAGIC.to_vec();
+        hello.push(VERSION);
+        stream.write_all(&hello)?;
+        let mut version = [0u8];
+        stream.read_exact(&mut version)?;
+        if version[0] != VERSION {
+            return Err(Error::other(format!(
+                "range-server speaks wire version {}, this miner {}",
+                version[0], VERSION
+            )));
+        }
+        Ok(stream)
+    }
+
+    fn exchange(&mut self, message: &Message) -> std::io::Result<Message> {
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => self.connect()?,
+        };
+        write_message(&mut stream, message)?;
+        let reply = read_message(&mut stream)?.ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+        self.stream = Some(stream);
+        Ok(reply)
+    }
+
+    /// Send `message` and wait for the reply, reconnecting as the `retry` policy allows since
+    /// a server restart or an idle timeout drops the connection.
+    pub fn request(&mut self, message: &Message) -> std::io::Result<Message> {
+        let what = format!("range-server {}", self.addr);
+        retry::retry(&what, || self.exchange(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: Message) {
+        let frame = message.encode();
+        assert_eq!(read_message(&mut &frame[..]).unwrap(), Some(message));
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        round_trip(Message::Lease { worker_id: "id".into(), worker: "rig-1".into() });
+        round_trip(Message::Renew { id: 7, hashrate: 1_500_000, covered: u64::MAX });
+        round_trip(Message::Done { id: 1, nonce: None });
+        round_trip(Message::Done { id: 1, nonce: Some(0) });
+        round_trip(Message::Release { id: 300, covered: 128 });
+        round_trip(Message::Leased { id: 2, start: 1 << 40, end: (1 << 40) + 4096, expires_in: 60 });
+        round_trip(Message::Ok);
+        round_trip(Message::UnknownLease);
+        round_trip(Message::Exhausted);
+        round_trip(Message::Solved { nonce: 42 });
+    }
+
+    #[test]
+    fn renewal_is_three_bytes_from_old_miners() {
+        assert_eq!(Message::decode(&[0x02, 0x05]).unwrap(), Message::Renew { id: 5, hashrate: 0, covered: 0 });
+        assert_eq!(
+            Message::decode(&[0x01]).unwrap(),
+            Message::Lease { worker_id: String::new(), worker: String::new() }
+        );
+    }
+
+    #[test]
+    fn leb128_boundaries() {
+        for n in [0, 0x7f, 0x80, 0x3fff, 0x4000, u64::MAX] {
+            let mut out = Vec::new();
+            put(n, &mut out);
+            assert_eq!(get(&mut &out[..]).unwrap(), n);
+        }
+        let mut out = Vec::new();
+        put(u64::MAX, &mut out);
+        assert_eq!(out.len(), 10);
+        assert!(get(&mut &[0xff; 10][..]).is_err());
+        assert!(get(&mut &[0x80][..]).is_err());
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        for payload in [
+            &[][..],
+            &[0x00],
+            &[0x86],
+            &[0x03],
+            &[0x04, 0x01],
+            &[0x82, 0x00],
+            &[0x01, 0x05, b'a'],
+            &[0x01, 0x02, 0xff, 0xfe, 0x00],
+        ] {
+            let error = Message::decode(payload).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{:?}", payload);
+        }
+    }
+
+    #[test]
+    fn stream_edges() {
+        assert!(read_message(&mut &[][..]).unwrap().is_none());
+        assert_eq!(read_message(&mut &[0x80][..]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(read_message(&mut &[0x03, 0x82][..]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        let oversized = [0x81, 0x08];
+        assert_eq!(read_message(&mut &oversized[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn handshake_picks_a_version() {
+        let hello = |bytes: [u8; 4]| {
+            let mut stream = BufReader::new(std::io::Cursor::new(bytes.to_vec()));
+            let version = accept(&mut stream).unwrap();
+            (version, stream.into_inner().into_inner()[4..].to_vec())
+        };
+        assert_eq!(hello([0xFC, b'P', b'C', 1]), (Some(1), vec![1]));
+        assert_eq!(hello([0xFC, b'P', b'C', VERSION]), (Some(VERSION), vec![VERSION]));
+        assert_eq!(hello([0xFC, b'P', b'C', VERSION + 1]), (None, vec![VERSION]));
+        assert_eq!(hello([0xFC, b'P', b'C', 0]), (None, vec![VERSION]));
+        assert_eq!(hello([b'G', b'E', b'T', b' ']), (None, vec![VERSION]));
+    }
+
+    #[test]
+    fn only_tcp_urls() {
+        assert_eq!(Client::new("tcp://farm:7000/").unwrap().addr, "farm:7000");
+        assert_eq!(Client::new("http://farm:7000").err().unwrap().kind(), ErrorKind::InvalidInput);
+    }
+}
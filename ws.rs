@@ -0,0 +1,215 @@
+//! `--ws-listen ADDR`: the `machine` events (started, progress, solution, exhausted, error)
+//! as WebSocket text messages, one JSON object each, for browser dashboards and streaming
+//! overlays. Any path upgrades; a client that connects mid-run first gets the `started`
+//! event it missed. Nothing clients send is read, and a client that stops taking messages
+//! for `WRITE_TIMEOUT` is dropped rather than holding up the miner.
+
+use crate::proxy::base64;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// RFC 6455's fixed key suffix for `Sec-WebSocket-Accept`
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+static LISTENING: AtomicBool = AtomicBool::new(false);
+static CLIENTS: Mutex<Vec<TcpStream>> = Mutex::new(Vec::new());
+static STARTED: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn listening() -> bool {
+    LISTENING.load(Ordering::Relaxed)
+}
+
+/// Bind `listen` and accept clients on a background thread from now on.
+pub fn listen(listen: &str) -> std::io::Result<()> {
+    let listener = crate::offline::network()?.listen(listen)?;
+    eprintln!("ws: streaming events on ws://{}", listener.local_addr()?);
+    LISTENING.store(true, Ordering::Relaxed);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            // The handshake gets its own thread so a client that never finishes it blocks no one
+            let accepted = stream.and_then(|stream| std::thread::Builder::new().spawn(move || accept(stream)));
+            if let Err(e) = accepted {
+                eprintln!("ws: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn accept(mut stream: TcpStream) {
+    let peer = stream.peer_addr().map_or("?".to_string(), |a| a.to_string());
+    match handshake(&mut stream) {
+        Ok(true) => {
+            let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+            let mut clients = CLIENTS.lock().unwrap();
+            let started = STARTED.lock().unwrap().clone();
+            if started.is_none_or(|started| stream.write_all(&frame(&started)).is_ok()) {
+                clients.push(stream);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("ws: handshake with {} failed: {}", peer, e),
+    }
+}
+
+/// Answer the upgrade request; false after turning away a request that is not one.
+fn handshake(stream: &mut TcpStream) -> std::io::Result<bool> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut reader = BufReader::new(&*stream);
+    let mut key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        match line.split_once(':') {
+            Some((name, value)) if name.trim().eq_ignore_ascii_case("sec-websocket-key") => {
+                key = Some(value.trim().to_string())
+            }
+            _ => {}
+        }
+    }
+    let Some(key) = key else {
+        stream.write_all(
+            b"HTTP/1.1 426 Upgrade Required\r\nSec-WebSocket-Version: 13\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )?;
+        return Ok(false);
+    };
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+    Ok(true)
+}
+
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+/// An unmasked text frame, as a server sends them.
+fn frame(text: &str) -> Vec<u8> {
+    let mut frame = vec![0x81];
+    match text.len() {
+        len @ 0..126 => frame.push(len as u8),
+        len @ 126..65536 => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(text.as_bytes());
+    frame
+}
+
+/// Send `line`, the JSON of a `machine` event named `event`, to every client.
+pub fn broadcast(event: &str, line: &str) {
+    if !listening() {
+        return;
+    }
+    if event == "started" {
+        *STARTED.lock().unwrap() = Some(line.to_string());
+    }
+    let frame = frame(line);
+    CLIENTS.lock().unwrap().retain_mut(|client| client.write_all(&frame).is_ok());
+}
+
+pub fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&(message.len() as u64 * 8).to_be_bytes());
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A827999),
+                20..40 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut digest = [0; 20];
+    for (out, word) in digest.chunks_mut(4).zip(h) {
+        out.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    /// The server's reply to `request`, and whether it upgraded.
+    fn upgrade(request: &str) -> (String, bool) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let upgraded = handshake(&mut listener.accept().unwrap().0).unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        (reply, upgraded)
+    }
+
+    #[test]
+    fn handshake_answers_the_key() {
+        let (reply, upgraded) =
+            upgrade("GET /events HTTP/1.1\r\nHost: rig\r\nsec-websocket-key:  dGhlIHNhbXBsZSBub25jZQ== \r\n\r\n");
+        assert!(upgraded);
+        assert!(reply.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{}", reply);
+        assert!(reply.contains("\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"), "{}", reply);
+    }
+
+    #[test]
+    fn plain_requests_are_turned_away() {
+        let (reply, upgraded) = upgrade("GET / HTTP/1.1\r\nHost: rig\r\n\r\n");
+        assert!(!upgraded);
+        assert!(reply.starts_with("HTTP/1.1 426 Upgrade Required\r\n"), "{}", reply);
+        let (reply, upgraded) = upgrade("GET / HTTP/1.1\r\nSec-WebSocket-Key");
+        assert!(!upgraded);
+        assert!(reply.starts_with("HTTP/1.1 426"), "{}", reply);
+    }
+
+    #[test]
+    fn frame_lengths() {
+        for (len, header) in [
+            (0, &[0x81, 0][..]),
+            (125, &[0x81, 125]),
+            (126, &[0x81, 126, 0, 126]),
+            (65_535, &[0x81, 126, 0xff, 0xff]),
+            (65_536, &[0x81, 127, 0, 0, 0, 0, 0, 1, 0, 0]),
+        ] {
+            let frame = frame(&"x".repeat(len));
+            assert_eq!(&frame[..header.len()], header, "{}", len);
+            assert_eq!(frame.len(), header.len() + len);
+        }
+    }
+}